@@ -29,10 +29,30 @@ fn main() {
 
     print_metadata(&doc);
 
+    if output_kind == "png" {
+        let mut raster = RasterOutput::new(150.);
+        output_doc(&doc, &mut raster).unwrap();
+        // the first page goes in the output file and the others get numbered files
+        for (i, page) in raster.pages().iter().enumerate() {
+            if i == 0 {
+                page.write_png(&mut output_file).unwrap();
+            } else {
+                let mut name = PathBuf::from(filename);
+                name.set_extension(format!("{}.png", i + 1));
+                page.write_png(&mut File::create(name).expect("could not create output")).unwrap();
+            }
+        }
+        return;
+    }
+
     let mut output: Box<dyn OutputDev> = match output_kind.as_ref() {
         "txt" => Box::new(PlainTextOutput::new(&mut output_file as &mut dyn std::io::Write)),
+        "layout" => Box::new(LayoutTextOutput::new(&mut output_file as &mut dyn std::io::Write)),
         "html" => Box::new(HTMLOutput::new(&mut output_file)),
         "svg" => Box::new(SVGOutput::new(&mut output_file)),
+        "md" => Box::new(MarkdownOutput::new(&mut output_file as &mut dyn std::io::Write)),
+        "hocr" => Box::new(HocrOutput::new(&mut output_file as &mut dyn std::io::Write)),
+        "alto" => Box::new(AltoOutput::new(&mut output_file as &mut dyn std::io::Write)),
         _ => panic!(),
     };
 
@@ -0,0 +1,36 @@
+extern crate pdf_extract;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use pdf_extract::GlyphFingerprintDb;
+
+// Generates src/fingerprint_table.rs from the font files given as arguments, e.g.
+// cargo run --example glyph_fingerprints /usr/share/fonts/truetype/dejavu/DejaVuSans.ttf ... > src/fingerprint_table.rs
+// Only the printable characters of Basic Latin and Latin-1 Supplement are kept.
+fn main() {
+    let files: Vec<String> = env::args().skip(1).collect();
+    let mut db = GlyphFingerprintDb::new();
+    for file in &files {
+        let data = fs::read(&file).expect("could not read font");
+        let count = db.learn_from_font_file(&data);
+        eprintln!("{}: {} glyphs", file, count);
+    }
+    let mut entries: Vec<_> = db.iter().filter(|(_, s)| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => matches!(c, '\u{21}'..='\u{7e}' | '\u{a1}'..='\u{ff}') && c != '\u{ad}',
+            _ => false,
+        }
+    }).collect();
+    entries.sort();
+    let names: Vec<_> = files.iter().map(|file| Path::new(file).file_name().map_or(file.clone(), |name| name.to_string_lossy().into_owned())).collect();
+    println!("// Generated by examples/glyph_fingerprints.rs from {}", names.join(", "));
+    println!("// with the printable characters of Basic Latin and Latin-1 Supplement. Rebuild it with");
+    println!("// cargo run --example glyph_fingerprints {} > src/fingerprint_table.rs", files.join(" "));
+    println!("pub const FINGERPRINTS: &[(u64, &str)] = &[");
+    for (fingerprint, s) in entries {
+        println!("    (0x{:016x}, {:?}),", fingerprint, s);
+    }
+    println!("];");
+}
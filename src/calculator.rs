@@ -0,0 +1,275 @@
+// An interpreter for the PostScript calculator functions of type 4 functions
+// (7.10.5 "Type 4 (PostScript calculator) functions")
+
+use log::warn;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Value {
+    Num(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn num(self) -> f64 {
+        match self {
+            Value::Num(n) => n,
+            Value::Bool(b) => b as i64 as f64,
+        }
+    }
+    fn int(self) -> i64 {
+        self.num() as i64
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Op {
+    Push(Value),
+    Operator(String),
+    If(Vec<Op>),
+    IfElse(Vec<Op>, Vec<Op>),
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Program {
+    ops: Vec<Op>,
+}
+
+fn tokenize(source: &[u8]) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    for &c in source {
+        match c {
+            b'{' | b'}' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                tokens.push((c as char).to_string());
+            }
+            b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' | b'\0' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c as char),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// parse the contents of a procedure up to its closing brace
+fn parse_procedure(tokens: &mut std::slice::Iter<String>) -> Vec<Op> {
+    let mut ops = Vec::new();
+    // procedures that haven't been consumed by `if` or `ifelse` yet
+    let mut procedures: Vec<Vec<Op>> = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "{" => procedures.push(parse_procedure(tokens)),
+            "}" => break,
+            "if" => match procedures.pop() {
+                Some(p) => ops.push(Op::If(p)),
+                None => warn!("if without a procedure"),
+            },
+            "ifelse" => match (procedures.pop(), procedures.pop()) {
+                (Some(else_), Some(then)) => ops.push(Op::IfElse(then, else_)),
+                _ => warn!("ifelse without procedures"),
+            },
+            "true" => ops.push(Op::Push(Value::Bool(true))),
+            "false" => ops.push(Op::Push(Value::Bool(false))),
+            _ => match token.parse::<f64>() {
+                Ok(n) => ops.push(Op::Push(Value::Num(n))),
+                Err(_) => ops.push(Op::Operator(token.clone())),
+            },
+        }
+    }
+    ops
+}
+
+impl Program {
+    pub(crate) fn parse(source: &[u8]) -> Program {
+        let tokens = tokenize(source);
+        let mut tokens = tokens.iter();
+        // the whole program is enclosed in braces
+        let ops = match tokens.next().map(|t| t.as_str()) {
+            Some("{") => parse_procedure(&mut tokens),
+            _ => {
+                warn!("type 4 function doesn't start with a brace");
+                Vec::new()
+            }
+        };
+        Program { ops }
+    }
+
+    /// Run the program with `input` on the stack and return the resulting stack
+    pub(crate) fn run(&self, input: &[f64]) -> Vec<f64> {
+        let mut stack: Vec<Value> = input.iter().map(|x| Value::Num(*x)).collect();
+        if let Err(e) = execute(&self.ops, &mut stack) {
+            warn!("type 4 function failed: {}", e);
+        }
+        stack.iter().map(|v| v.num()).collect()
+    }
+}
+
+fn execute(ops: &[Op], stack: &mut Vec<Value>) -> Result<(), String> {
+    // a stack that's this deep is a runaway program
+    const MAX_STACK: usize = 1000;
+    for op in ops {
+        if stack.len() > MAX_STACK {
+            return Err("stack overflow".to_owned());
+        }
+        match op {
+            Op::Push(v) => stack.push(*v),
+            Op::If(then) => {
+                if pop(stack)? == Value::Bool(true) {
+                    execute(then, stack)?;
+                }
+            }
+            Op::IfElse(then, else_) => {
+                if pop(stack)? == Value::Bool(true) {
+                    execute(then, stack)?;
+                } else {
+                    execute(else_, stack)?;
+                }
+            }
+            Op::Operator(name) => operator(name, stack)?,
+        }
+    }
+    Ok(())
+}
+
+fn pop(stack: &mut Vec<Value>) -> Result<Value, String> {
+    stack.pop().ok_or_else(|| "stack underflow".to_owned())
+}
+
+fn operator(name: &str, stack: &mut Vec<Value>) -> Result<(), String> {
+    use Value::{Bool, Num};
+    let unary = |stack: &mut Vec<Value>, f: &dyn Fn(f64) -> f64| -> Result<(), String> {
+        let a = pop(stack)?.num();
+        stack.push(Num(f(a)));
+        Ok(())
+    };
+    let binary = |stack: &mut Vec<Value>, f: &dyn Fn(f64, f64) -> f64| -> Result<(), String> {
+        let b = pop(stack)?.num();
+        let a = pop(stack)?.num();
+        stack.push(Num(f(a, b)));
+        Ok(())
+    };
+    let compare = |stack: &mut Vec<Value>, f: &dyn Fn(f64, f64) -> bool| -> Result<(), String> {
+        let b = pop(stack)?.num();
+        let a = pop(stack)?.num();
+        stack.push(Bool(f(a, b)));
+        Ok(())
+    };
+    // the boolean operators are bitwise for integers
+    let logical = |stack: &mut Vec<Value>, f: &dyn Fn(i64, i64) -> i64, g: &dyn Fn(bool, bool) -> bool| -> Result<(), String> {
+        let b = pop(stack)?;
+        let a = pop(stack)?;
+        stack.push(match (a, b) {
+            (Bool(a), Bool(b)) => Bool(g(a, b)),
+            _ => Num(f(a.int(), b.int()) as f64),
+        });
+        Ok(())
+    };
+    match name {
+        "abs" => unary(stack, &f64::abs),
+        "add" => binary(stack, &|a, b| a + b),
+        "atan" => binary(stack, &|a, b| {
+            let angle = a.atan2(b).to_degrees();
+            if angle < 0. { angle + 360. } else { angle }
+        }),
+        "ceiling" => unary(stack, &f64::ceil),
+        "cos" => unary(stack, &|a| a.to_radians().cos()),
+        "cvi" | "truncate" => unary(stack, &f64::trunc),
+        "cvr" => Ok(()),
+        "div" => binary(stack, &|a, b| if b == 0. { 0. } else { a / b }),
+        "exp" => binary(stack, &f64::powf),
+        "floor" => unary(stack, &f64::floor),
+        "idiv" => binary(stack, &|a, b| {
+            let b = b as i64;
+            if b == 0 { 0. } else { (a as i64 / b) as f64 }
+        }),
+        "ln" => unary(stack, &f64::ln),
+        "log" => unary(stack, &f64::log10),
+        "mod" => binary(stack, &|a, b| {
+            let b = b as i64;
+            if b == 0 { 0. } else { (a as i64 % b) as f64 }
+        }),
+        "mul" => binary(stack, &|a, b| a * b),
+        "neg" => unary(stack, &|a| -a),
+        "round" => unary(stack, &|a| (a + 0.5).floor()),
+        "sin" => unary(stack, &|a| a.to_radians().sin()),
+        "sqrt" => unary(stack, &f64::sqrt),
+        "sub" => binary(stack, &|a, b| a - b),
+        "eq" => compare(stack, &|a, b| a == b),
+        "ne" => compare(stack, &|a, b| a != b),
+        "ge" => compare(stack, &|a, b| a >= b),
+        "gt" => compare(stack, &|a, b| a > b),
+        "le" => compare(stack, &|a, b| a <= b),
+        "lt" => compare(stack, &|a, b| a < b),
+        "and" => logical(stack, &|a, b| a & b, &|a, b| a && b),
+        "or" => logical(stack, &|a, b| a | b, &|a, b| a || b),
+        "xor" => logical(stack, &|a, b| a ^ b, &|a, b| a ^ b),
+        "not" => {
+            let v = match pop(stack)? {
+                Bool(b) => Bool(!b),
+                n => Num(!n.int() as f64),
+            };
+            stack.push(v);
+            Ok(())
+        }
+        "bitshift" => {
+            let shift = pop(stack)?.int();
+            let a = pop(stack)?.int();
+            let v = if shift >= 0 { a.checked_shl(shift as u32) } else { a.checked_shr(-shift as u32) };
+            stack.push(Num(v.unwrap_or(0) as f64));
+            Ok(())
+        }
+        "copy" => {
+            let n = pop(stack)?.int().max(0) as usize;
+            if n > stack.len() {
+                return Err("stack underflow".to_owned());
+            }
+            let start = stack.len() - n;
+            stack.extend_from_within(start..);
+            Ok(())
+        }
+        "dup" => {
+            let v = *stack.last().ok_or_else(|| "stack underflow".to_owned())?;
+            stack.push(v);
+            Ok(())
+        }
+        "exch" => {
+            let b = pop(stack)?;
+            let a = pop(stack)?;
+            stack.push(b);
+            stack.push(a);
+            Ok(())
+        }
+        "index" => {
+            let n = pop(stack)?.int().max(0) as usize;
+            let v = *stack.iter().rev().nth(n).ok_or_else(|| "stack underflow".to_owned())?;
+            stack.push(v);
+            Ok(())
+        }
+        "pop" => pop(stack).map(|_| ()),
+        "roll" => {
+            let j = pop(stack)?.int();
+            let n = pop(stack)?.int().max(0) as usize;
+            if n > stack.len() {
+                return Err("stack underflow".to_owned());
+            }
+            if n > 0 {
+                let start = stack.len() - n;
+                let j = j.rem_euclid(n as i64) as usize;
+                stack[start..].rotate_right(j);
+            }
+            Ok(())
+        }
+        _ => Err(format!("unknown operator {}", name)),
+    }
+}
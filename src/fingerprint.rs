@@ -0,0 +1,202 @@
+// Recovering Unicode for glyphs of fonts that don't say what they are (no ToUnicode map and
+// glyph names like /g23) by comparing the shapes of their outlines with glyphs that we've
+// seen mapped elsewhere. Subsetting keeps the glyph programs intact so the outline of a glyph
+// from a subset is the same as the one from the complete font.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+use lopdf::Document;
+use ttf_parser::Face;
+
+use crate::font_program::{FontProgram, FontProgramKind};
+use crate::{document_fonts, fingerprint_table, make_font, PathOp, NO_FONT_OVERRIDES};
+
+/// A hash of a glyph outline in glyph space (1000 units per em) with coordinates rounded to whole units
+pub type GlyphFingerprint = u64;
+
+// 64-bit FNV-1a so that fingerprints are stable across builds and platforms
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    fn write_coords(&mut self, coords: &[f64]) {
+        for c in coords {
+            self.write(&(c.round() as i32).to_le_bytes());
+        }
+    }
+}
+
+/// Returns `None` for empty outlines because all blank glyphs look the same
+pub(crate) fn fingerprint_outline(ops: &[PathOp]) -> Option<GlyphFingerprint> {
+    if !ops.iter().any(|op| matches!(op, PathOp::LineTo(..) | PathOp::CurveTo(..) | PathOp::Rect(..))) {
+        return None;
+    }
+    let mut hash = Fnv(0xcbf29ce484222325);
+    for op in ops {
+        match *op {
+            PathOp::MoveTo(x, y) => { hash.write(b"M"); hash.write_coords(&[x, y]) }
+            PathOp::LineTo(x, y) => { hash.write(b"L"); hash.write_coords(&[x, y]) }
+            PathOp::CurveTo(x1, y1, x2, y2, x, y) => { hash.write(b"C"); hash.write_coords(&[x1, y1, x2, y2, x, y]) }
+            PathOp::Rect(x, y, w, h) => { hash.write(b"R"); hash.write_coords(&[x, y, w, h]) }
+            PathOp::Close => hash.write(b"Z"),
+        }
+    }
+    Some(hash.0)
+}
+
+/// A mapping from glyph fingerprints to Unicode used for glyphs that fonts don't map themselves.
+///
+/// Lookups fall back to a table bundled with the crate that covers the printable Basic Latin
+/// and Latin-1 Supplement glyphs of DejaVu Sans, DejaVu Sans Bold, DejaVu Serif, DejaVu Serif
+/// Bold and DejaVu Sans Mono. The database can be extended by learning from documents whose
+/// fonts do have mappings and saved and loaded with `write` and `read`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphFingerprintDb {
+    entries: HashMap<GlyphFingerprint, String>,
+    // fingerprints that we've seen mapped to different text
+    conflicts: HashSet<GlyphFingerprint>,
+}
+
+impl GlyphFingerprintDb {
+    pub fn new() -> GlyphFingerprintDb {
+        GlyphFingerprintDb::default()
+    }
+
+    /// Record that glyphs with `fingerprint` represent `unicode`. A fingerprint that is
+    /// recorded with different text is considered ambiguous and won't be used.
+    pub fn insert(&mut self, fingerprint: GlyphFingerprint, unicode: &str) {
+        if self.conflicts.contains(&fingerprint) {
+            return;
+        }
+        match self.entries.get(&fingerprint) {
+            Some(existing) if existing != unicode => {
+                self.entries.remove(&fingerprint);
+                self.conflicts.insert(fingerprint);
+            }
+            Some(_) => {}
+            None => { self.entries.insert(fingerprint, unicode.to_owned()); }
+        }
+    }
+
+    pub fn lookup(&self, fingerprint: GlyphFingerprint) -> Option<&str> {
+        if let Some(s) = self.entries.get(&fingerprint) {
+            return Some(s);
+        }
+        if self.conflicts.contains(&fingerprint) {
+            return None;
+        }
+        lookup_bundled(fingerprint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The learned entries, not including the bundled table
+    pub fn iter(&self) -> impl Iterator<Item = (GlyphFingerprint, &str)> {
+        self.entries.iter().map(|(fp, s)| (*fp, s.as_str()))
+    }
+
+    /// Learn the glyphs of the embedded fonts in `doc` that have a `ToUnicode` entry or a
+    /// meaningful glyph name. Returns the number of glyphs that were fingerprinted.
+    pub fn learn_from_document(&mut self, doc: &Document) -> usize {
+        let mut count = 0;
+        for (font, _) in document_fonts(doc) {
+            let font = make_font(doc, font, &NO_FONT_OVERRIDES);
+            for glyph in font.glyph_table() {
+                if !font.has_unicode_mapping(glyph.code) || glyph.unicode.trim().is_empty() {
+                    continue;
+                }
+                if let Some(fingerprint) = font.glyph_outline(glyph.code).and_then(|o| fingerprint_outline(&o)) {
+                    self.insert(fingerprint, &glyph.unicode);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Learn the glyphs of a TrueType or OpenType font file through its Unicode `cmap`.
+    /// Returns the number of distinct glyph outlines that were fingerprinted.
+    pub fn learn_from_font_file(&mut self, data: &[u8]) -> usize {
+        let face = match Face::parse(data, 0) {
+            Ok(face) => face,
+            Err(_) => return 0,
+        };
+        let mut glyphs = HashMap::new();
+        for subtable in face.tables().cmap.iter().flat_map(|cmap| cmap.subtables) {
+            if !subtable.is_unicode() {
+                continue;
+            }
+            subtable.codepoints(|c| {
+                if let (Some(c), Some(gid)) = (char::from_u32(c), subtable.glyph_index(c)) {
+                    // prefer the lowest code point for glyphs that are mapped more than once
+                    let entry = glyphs.entry(gid.0).or_insert(c);
+                    *entry = (*entry).min(c);
+                }
+            });
+        }
+        let kind = if face.tables().cff.is_some() { FontProgramKind::OpenType } else { FontProgramKind::TrueType };
+        let program = FontProgram::new(kind, data.to_vec());
+        // fonts often share outlines between scripts, e.g. Latin A and Greek Alpha. Within a
+        // font we prefer the lowest code point instead of treating that as ambiguous.
+        let mut fingerprints: HashMap<GlyphFingerprint, char> = HashMap::new();
+        for (gid, c) in glyphs {
+            if let Some(fingerprint) = program.glyph_outline(gid).and_then(|o| fingerprint_outline(&o)) {
+                let entry = fingerprints.entry(fingerprint).or_insert(c);
+                *entry = (*entry).min(c);
+            }
+        }
+        for (fingerprint, c) in &fingerprints {
+            self.insert(*fingerprint, &c.to_string());
+        }
+        fingerprints.len()
+    }
+
+    /// Write the learned entries as lines of a hexadecimal fingerprint followed by a tab and
+    /// the hexadecimal code points of the text
+    pub fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort();
+        for (fingerprint, s) in entries {
+            let code_points: Vec<_> = s.chars().map(|c| format!("{:04x}", c as u32)).collect();
+            writeln!(w, "{:016x}\t{}", fingerprint, code_points.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Read entries in the format produced by `write`
+    pub fn read(r: &mut dyn BufRead) -> io::Result<GlyphFingerprintDb> {
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad fingerprint line {:?}", line));
+        let mut db = GlyphFingerprintDb::new();
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (fingerprint, code_points) = line.split_once('\t').ok_or_else(|| invalid(&line))?;
+            let fingerprint = u64::from_str_radix(fingerprint, 16).map_err(|_| invalid(&line))?;
+            let s = code_points.split(' ')
+                .map(|c| u32::from_str_radix(c, 16).ok().and_then(char::from_u32))
+                .collect::<Option<String>>()
+                .ok_or_else(|| invalid(&line))?;
+            db.insert(fingerprint, &s);
+        }
+        Ok(db)
+    }
+}
+
+pub(crate) fn lookup_bundled(fingerprint: GlyphFingerprint) -> Option<&'static str> {
+    let table = fingerprint_table::FINGERPRINTS;
+    table.binary_search_by_key(&fingerprint, |e| e.0).ok().map(|i| table[i].1)
+}
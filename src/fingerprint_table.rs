@@ -0,0 +1,940 @@
+// Generated by examples/glyph_fingerprints.rs from DejaVuSans.ttf, DejaVuSans-Bold.ttf, DejaVuSerif.ttf, DejaVuSerif-Bold.ttf, DejaVuSansMono.ttf
+// with the printable characters of Basic Latin and Latin-1 Supplement. Rebuild it with
+// cargo run --example glyph_fingerprints /usr/share/fonts/truetype/dejavu/DejaVuSans.ttf /usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf /usr/share/fonts/truetype/dejavu/DejaVuSerif.ttf /usr/share/fonts/truetype/dejavu/DejaVuSerif-Bold.ttf /usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf > src/fingerprint_table.rs
+pub const FINGERPRINTS: &[(u64, &str)] = &[
+    (0x00cbc897730d572b, "<"),
+    (0x00da467717f4d900, "Õ"),
+    (0x017bd858af46c977, "-"),
+    (0x0192404d3d1588af, "H"),
+    (0x0194ebf3d64b5de0, "e"),
+    (0x01beac31ba3ad3a4, "õ"),
+    (0x01d58ce53608a227, "U"),
+    (0x01f89dff4b3e9137, "^"),
+    (0x0203944a3e67526f, "¤"),
+    (0x02596e9bedafb4d4, "Ý"),
+    (0x0349e001762ee13b, "u"),
+    (0x0375c0aebd0aa545, "«"),
+    (0x03a1a7d651a0acd0, "5"),
+    (0x046ae30ae896fdf4, "*"),
+    (0x04a476bb838c8e4c, "ù"),
+    (0x04af01a8ca0f9742, "W"),
+    (0x04e0b470f2686ac1, "ê"),
+    (0x055349687788e9a4, "ù"),
+    (0x05635348e72fd19a, "Ò"),
+    (0x0631e4ccd275e50d, "F"),
+    (0x063f3953b581e50b, "("),
+    (0x0660478d81265437, "Ó"),
+    (0x06d27c32cd3a1c4d, "µ"),
+    (0x06f21e9ab648980b, "Ñ"),
+    (0x075a0e900f3f669c, "ø"),
+    (0x0806de06b1c51c32, "é"),
+    (0x082796dec4dc557f, "s"),
+    (0x08333451d9050687, "Í"),
+    (0x0834ffd57c6036e6, "ô"),
+    (0x08512d2d672de1f2, "j"),
+    (0x08728506b7bac33e, "Ï"),
+    (0x0894feab64d59dfb, "I"),
+    (0x08a97184bd200dfc, "+"),
+    (0x08b31f1944cc8eb1, "¼"),
+    (0x08b9b9e5d882ce50, "â"),
+    (0x093e69bba3255d53, "G"),
+    (0x09713d872890f22f, "ú"),
+    (0x097d48d812133e5d, "@"),
+    (0x09f7ad9f15dbb43d, "¥"),
+    (0x0a145b4ccde218d4, "#"),
+    (0x0a6f955661859b87, "X"),
+    (0x0ad2e8d193a77284, "ë"),
+    (0x0ad80ad9dee2e846, "î"),
+    (0x0aeed4b506b0fc5d, "æ"),
+    (0x0bd38004a17a7cdf, "2"),
+    (0x0bda8f9f8eba8661, "E"),
+    (0x0bde0688b55a8424, "x"),
+    (0x0c77277b215bccfb, "ï"),
+    (0x0d2286eb651da70c, ">"),
+    (0x0d620b3fdce8da93, "ä"),
+    (0x0d63f74464f70826, "J"),
+    (0x0dafc8079df811ee, "×"),
+    (0x0dbd1f0ac15d2ed5, "Ü"),
+    (0x0e8462d076b1cef5, "ê"),
+    (0x0e875c67ddf560fb, "N"),
+    (0x0e8a47f26b2ced05, "Ö"),
+    (0x0f336fe2035945f2, "ó"),
+    (0x0f5170c530c34945, "Ú"),
+    (0x0f6aae61fe664cd7, "·"),
+    (0x104fbe24d2835de3, "ï"),
+    (0x1093b280344d88fc, "ñ"),
+    (0x109778a9a6b10c21, "R"),
+    (0x10ab5254cd202a51, "Á"),
+    (0x10cb34fb100f8c1f, "I"),
+    (0x1108375237ac5486, "Ñ"),
+    (0x1115eb9b662c9b93, "k"),
+    (0x11183d9299ebff7f, "É"),
+    (0x11190661d96562eb, "m"),
+    (0x11589c7d6cc2046b, "4"),
+    (0x117e2ce0ea3f1d43, "ø"),
+    (0x11bc56b545205651, "q"),
+    (0x11f56b8d9dbd9da1, "c"),
+    (0x1217259ce6c58ac2, "¤"),
+    (0x127a162309ba9e92, "V"),
+    (0x142d1a1f9452f065, "Æ"),
+    (0x14338b04c3a698cc, "7"),
+    (0x1525e8a9dbefc1f9, "ª"),
+    (0x15440f1ef99ab9d2, "\""),
+    (0x155433dcfd599a12, "À"),
+    (0x15c4f1806097544c, "2"),
+    (0x15dc51556ad48ed3, "6"),
+    (0x1644d35196761abf, "ó"),
+    (0x165b290021f6bf31, "Ç"),
+    (0x16e4fb625445eced, "#"),
+    (0x17f3c27d9ae6d166, "ý"),
+    (0x17fae595c78350c9, "q"),
+    (0x18ac4cd0755b1803, "ý"),
+    (0x18cafa07269ff9cb, "í"),
+    (0x19ef3ced2aaa0c4a, "X"),
+    (0x19f79472350cec35, "+"),
+    (0x1a2d6dc48810723f, "Å"),
+    (0x1acfcfecc173322b, "¦"),
+    (0x1ae23fcf159f5841, "â"),
+    (0x1b96adb4f8c90aec, "Ã"),
+    (0x1bba3ac5e89cdf3d, "Ö"),
+    (0x1c28509b167d87c2, "'"),
+    (0x1c5ce3f336461ed1, "³"),
+    (0x1c7d1d6e49253ff0, "R"),
+    (0x1cc7220a3bd650cf, "æ"),
+    (0x1ce03b991d63717d, "À"),
+    (0x1cf89c64e761c82f, "ì"),
+    (0x1d09fdc0ce596898, "å"),
+    (0x1d32db3b57981956, "g"),
+    (0x1e3fd461bc6240da, "o"),
+    (0x1e6c5f747f3d6cfe, "¥"),
+    (0x1e97f635d9fefe75, "Ê"),
+    (0x1ea0a65fbb91740e, ","),
+    (0x1eee9960bb0acbfd, "£"),
+    (0x1f19ecb78a162d6e, "ê"),
+    (0x1f469a3df31d639e, "Ø"),
+    (0x1f693acb6ad7c61d, "®"),
+    (0x1fcfb600474ad8e1, "r"),
+    (0x20396aeaf7346e69, "¥"),
+    (0x2064aa06cff5bcd0, "ç"),
+    (0x20b135179cc3d7a7, "="),
+    (0x213d0714e0cbbce6, "½"),
+    (0x21a41121875ef5c9, "6"),
+    (0x21aaa6e6bbc88199, "q"),
+    (0x21f31ca5c1473a23, "O"),
+    (0x22193c2a69f55697, "O"),
+    (0x2287a41cb5f65347, "®"),
+    (0x22dfccfc12c2aafc, "¥"),
+    (0x22f35c487f27ee09, "5"),
+    (0x2438f0b9a32b7326, "0"),
+    (0x2465d565577b41c1, "&"),
+    (0x24723c66c8060f36, "0"),
+    (0x2474bca7e0f45a55, "}"),
+    (0x24b1ad5b17f52c26, "&"),
+    (0x25045adea065830c, "J"),
+    (0x254bd917d74affaf, "*"),
+    (0x25c6482819ea6b86, "$"),
+    (0x25cb4f0cd1989e5c, "á"),
+    (0x2614674a59665978, "w"),
+    (0x265945f9aee8e07e, "²"),
+    (0x26bd1ee5381cbbb1, "U"),
+    (0x26c3d53a24c982cc, "n"),
+    (0x26e06440b83a9a4b, "Ï"),
+    (0x270ad575235f5780, "T"),
+    (0x2710784c4096f9c4, "z"),
+    (0x27554c8bb614ddf5, "\""),
+    (0x276c27cb3516d9fa, "%"),
+    (0x27e803fc60c3a478, "á"),
+    (0x27f7b4a1755f5baa, "d"),
+    (0x28bdff72fd56a745, "Ò"),
+    (0x28c0c992ab0adcc7, "="),
+    (0x2911ecf56254a3d8, ")"),
+    (0x2a491e963d31fba1, "K"),
+    (0x2aa633ef2686bca9, "A"),
+    (0x2ac50dc7cb8b74f0, "©"),
+    (0x2b001da43580fadf, "×"),
+    (0x2b8eb3de6a3f1f2a, ","),
+    (0x2b90c8182f88a2b4, "È"),
+    (0x2ba1941386db15af, "Û"),
+    (0x2bec3e1d91adad01, "y"),
+    (0x2c5412dc1c0e147f, "ï"),
+    (0x2c7cba0cca99151c, "Ù"),
+    (0x2cc4e6b4255050e9, "]"),
+    (0x2cfa2ea149cdd2ef, "="),
+    (0x2d57d55410cb54dc, "?"),
+    (0x2d9c09684f1ec25c, "("),
+    (0x2d9d1af642f0f537, "@"),
+    (0x2dbdc50507dd0e2d, "i"),
+    (0x2e65e6daa357277d, "u"),
+    (0x2e7d70c44719178e, "$"),
+    (0x2f227b73cf7dcb49, "F"),
+    (0x2f7a8c72ce74b9b9, "R"),
+    (0x2f99faa42079c300, "Ì"),
+    (0x2fafa2142475beae, "Y"),
+    (0x300ecf43b99bf7d7, "r"),
+    (0x302a3e6d4cf58a43, "Y"),
+    (0x3054c3a3dff9b52e, "/"),
+    (0x30828477e9db148d, "Ë"),
+    (0x313602cce1ee0372, "¢"),
+    (0x3199193950f5d925, "q"),
+    (0x324d030203854a20, "î"),
+    (0x32600768a10d6dd9, "?"),
+    (0x3270ccc19dd06b14, "ð"),
+    (0x32b67084b4cba1ef, "N"),
+    (0x32e51271ebc168eb, "d"),
+    (0x334ff78b654b7ff3, "5"),
+    (0x3369102bf67441e6, "¹"),
+    (0x337e3977e1947a82, "m"),
+    (0x34d25555649e4138, "·"),
+    (0x354e378a9b74d281, "?"),
+    (0x3575ebbd7737cddf, "ª"),
+    (0x35ff4d68a5de0a41, "~"),
+    (0x367b4a372d03a9bf, "Ç"),
+    (0x36e8ff063cf4e013, "2"),
+    (0x370a23496fb33bed, "/"),
+    (0x370a5ba74baaf89d, "©"),
+    (0x374a8ec82f02b663, "Q"),
+    (0x37517d7c93b87885, "¨"),
+    (0x3779b6c8a9453b47, "Ì"),
+    (0x37a1aa2fc0ebd063, "÷"),
+    (0x37abe64254ba0ca1, "E"),
+    (0x37edf76733fb918f, "ù"),
+    (0x384afe9b3c8af688, "ò"),
+    (0x3865266ac8b484bb, "I"),
+    (0x38b4c458a9601119, "è"),
+    (0x38ec7e9cdb55c88a, "ë"),
+    (0x3942ab3b2dbbfe5d, "í"),
+    (0x3944cf176c9ff70c, "ó"),
+    (0x395a97e2003a20b6, "Ý"),
+    (0x3978dd4cc35d323e, "ô"),
+    (0x3aa6cbe2c5448284, "g"),
+    (0x3aaf8db491e7cf13, ">"),
+    (0x3b3182e8e6f4220f, "é"),
+    (0x3b72858bc9b0a575, "ì"),
+    (0x3bcfc68478be9373, "Î"),
+    (0x3bdcac53e265b9c7, "Â"),
+    (0x3be2d451d7d732bf, "Ì"),
+    (0x3c12eaf28d95877e, "Ï"),
+    (0x3c67baee8354cac2, "*"),
+    (0x3c9b984410a6f1ed, "ø"),
+    (0x3d0c7b66f765b9a7, "A"),
+    (0x3d3802113de2c5fc, "à"),
+    (0x3d38823b8f96b392, "e"),
+    (0x3d68195cb7f6a332, "Å"),
+    (0x3d721cdc8fcfdf6f, "H"),
+    (0x3d855811e4112dab, "h"),
+    (0x3daeb7b46a641407, "6"),
+    (0x3dc308ee88117a79, "Æ"),
+    (0x3e0c3dffd801e8dd, "Ì"),
+    (0x3e0de4137d73fea7, "D"),
+    (0x3ebae95936f3b4cc, "w"),
+    (0x3f17ff292889b343, "¿"),
+    (0x3f32b6864512239f, "¬"),
+    (0x3f4c817dac89ce64, "Y"),
+    (0x3f5533447bd6fded, "ÿ"),
+    (0x3f6afcd1c0c22708, "r"),
+    (0x3f9b0d61777a55a9, "T"),
+    (0x40832ca662589da8, "b"),
+    (0x40d7829f700e3df0, "G"),
+    (0x4104309428887bd9, "\\"),
+    (0x4105aba81847f9fb, "Ô"),
+    (0x4147252bcc549a97, "ð"),
+    (0x418d2db67e30d3a7, "õ"),
+    (0x41ecfb2dacf7f37d, "z"),
+    (0x4217d2d8496d717b, "Ä"),
+    (0x42a58a075d4d9b38, "$"),
+    (0x42ecf08a75361d95, "·"),
+    (0x42fc63bd391db3a8, "ä"),
+    (0x4320ce90f3c11896, "¾"),
+    (0x43752d2bd49be455, "M"),
+    (0x43aad7fd3162270e, "c"),
+    (0x43adc078dd391f25, "¡"),
+    (0x43d62511a3251f09, "Ü"),
+    (0x43e9d0b7a395d978, "Â"),
+    (0x450adf5cc71f5645, "="),
+    (0x454f09c4a5325a5a, "0"),
+    (0x45728a81240edbbb, "Z"),
+    (0x464bb89160b1ff7d, "1"),
+    (0x465fe545740f8472, "."),
+    (0x46849beef06b4ba6, "º"),
+    (0x4708120f34072b23, "¦"),
+    (0x470cdad3e4713bc4, "Ï"),
+    (0x4736aab685207d04, "Ç"),
+    (0x4854a2dbce832d73, "Ö"),
+    (0x496277c182972396, "î"),
+    (0x4971963b6f849198, "ª"),
+    (0x497d02e2e7953aaa, "Ó"),
+    (0x499ca2a92a117505, "Ç"),
+    (0x49f212f6003425d5, "³"),
+    (0x4a4857aa46c8026c, "á"),
+    (0x4a83049e89081f59, "Ê"),
+    (0x4aff1023d203e205, "\\"),
+    (0x4c1107c468adc469, ","),
+    (0x4c4ee6f59ed224b6, "t"),
+    (0x4d7c0055312b3a0b, "ñ"),
+    (0x4d8fdc9f9b18305d, "¶"),
+    (0x4d95c669834ac008, "×"),
+    (0x4d9d27a2fca201fe, "ô"),
+    (0x4dc17e036bb47702, "#"),
+    (0x4dc69558d7c4a38a, "7"),
+    (0x4e84e11e88fc687c, "d"),
+    (0x4eae3757d91a0aa3, "¿"),
+    (0x4ee7ea474a989bea, "o"),
+    (0x4f328193d3b71656, "Ã"),
+    (0x4f5c89eaf74501cc, "Ù"),
+    (0x4fde6a2b66876809, "ý"),
+    (0x501b5a63b9ad2e65, "å"),
+    (0x5035915a51fab75e, "<"),
+    (0x50474e9fce9e6c09, "_"),
+    (0x506e7dbb3af23550, "ì"),
+    (0x50e88170d79529ab, "l"),
+    (0x50eac4f5c4a7c19e, ">"),
+    (0x512fd272809e2d0a, "~"),
+    (0x51a0a9a676dacfe1, "ü"),
+    (0x51a4ad84e43d2ebc, "y"),
+    (0x521c6438f68b9679, "Ù"),
+    (0x524cc8bf08ff14c7, "{"),
+    (0x5280fd4698a43b33, "2"),
+    (0x52b85c7a85916c2c, "ò"),
+    (0x52e202dcde7f06bf, "O"),
+    (0x535ccf60ee81e13e, "à"),
+    (0x53e2fe69a82ef543, "s"),
+    (0x54418cf40a225ac6, "i"),
+    (0x544935c6f370bf5d, "j"),
+    (0x54e89add272d2740, "ô"),
+    (0x55209c77dc0c8849, "L"),
+    (0x552a075b9e21a24c, "x"),
+    (0x552e13e46581280a, ":"),
+    (0x5532c18e36fcef89, "¨"),
+    (0x55803bb569903ba3, "¼"),
+    (0x55c930c718063759, "»"),
+    (0x568fc56fc4f6151a, "."),
+    (0x56f5f2089eda0bde, "`"),
+    (0x583579c214a5ada4, "a"),
+    (0x5886f0f91a01710f, "d"),
+    (0x59f9ca9ac68e50ef, "F"),
+    (0x5a63a0a076eaa7ae, "À"),
+    (0x5a723727d4f91f4a, "Î"),
+    (0x5aaa9b45ebb52c8f, "´"),
+    (0x5b729f602f833080, "ÿ"),
+    (0x5ba3a2b0af0c4846, "È"),
+    (0x5bacac79efea973e, "û"),
+    (0x5bea86b6541f8e6c, "z"),
+    (0x5c67cc5aaf0646ba, "W"),
+    (0x5c99046016c19c6d, "]"),
+    (0x5cfe22b54a0605fe, "Ë"),
+    (0x5d237181c20f7243, "ã"),
+    (0x5d242bb137584c4c, "§"),
+    (0x5d4f91a6c3a34586, "U"),
+    (0x5d92a59c8689386f, "þ"),
+    (0x5e0d95c96d33224e, "a"),
+    (0x5e3cf77eb9758cad, ":"),
+    (0x5e8f644cb5b213f2, "´"),
+    (0x5ee85ce0ef38a79c, "¿"),
+    (0x5f08e92749eac85d, "{"),
+    (0x5fa135cdc30c0231, "Í"),
+    (0x605c7f7c05ba9e69, "û"),
+    (0x60686bb931d5cd53, "R"),
+    (0x6078de5442b5dedf, "È"),
+    (0x60e5909de6f43c26, "|"),
+    (0x60ff19ad9b2069a2, "÷"),
+    (0x6112bb1260bc0846, "t"),
+    (0x61a2630f82ae277d, "M"),
+    (0x61e7b0c5687c3d38, "4"),
+    (0x627b732c3129d14a, "1"),
+    (0x629813610a5a3a6c, "ü"),
+    (0x62a93d51db9b4024, "H"),
+    (0x636cf7faf54fdb4f, "'"),
+    (0x6373eec8b340b64b, "Z"),
+    (0x63ad5ca1b75032d3, "P"),
+    (0x63b88ee0c49f1286, "p"),
+    (0x63b9ce979278b658, "X"),
+    (0x63f808585d6264db, ";"),
+    (0x640cad0f0265ad28, "ö"),
+    (0x643958264c2749fa, "þ"),
+    (0x6447f1bbcf0aa5ad, "o"),
+    (0x646ef6255c6dfebe, "d"),
+    (0x649e03bab748e091, "ö"),
+    (0x64ea73229840f81a, "\\"),
+    (0x65c36ae46af883b2, "ö"),
+    (0x65e4edde618e9038, "3"),
+    (0x6685bdb414feff3f, "ð"),
+    (0x66ea5e72036998ab, "²"),
+    (0x6771b8f25d863f66, ";"),
+    (0x6785e0b630d298cd, "T"),
+    (0x67c55493d508fb67, "Ø"),
+    (0x67fcacc9446e086a, "Ü"),
+    (0x686ade5481058aff, "¨"),
+    (0x688bb38f6db167a0, "¬"),
+    (0x688ebc253d182240, "¬"),
+    (0x68b8364dd7dfa6e7, "w"),
+    (0x68bdb65fafaf58cc, "õ"),
+    (0x68ecc4b779851112, "Í"),
+    (0x69123286ce186c58, "0"),
+    (0x694328e3c169d0de, "é"),
+    (0x69d72d22cd650060, "ç"),
+    (0x69e0c599df292a3a, "¨"),
+    (0x69facca7ac7c2bca, "¹"),
+    (0x6a17b0894a490bbe, "Æ"),
+    (0x6a46d712929d358e, "ä"),
+    (0x6af3d4cd507f7767, "#"),
+    (0x6b3c3862fb8af90d, "ÿ"),
+    (0x6b819bf41aa7fcff, "¾"),
+    (0x6beacc85010713ba, "x"),
+    (0x6bfc6cf6f1a46cdf, "Á"),
+    (0x6c65905fb19be2c3, "ã"),
+    (0x6c9e265730cfc3a8, "°"),
+    (0x6ce15f8efcd64aea, "["),
+    (0x6d071f55cd187f07, "g"),
+    (0x6d4a0cec3ffa26c0, "i"),
+    (0x6dfa87176b6946f6, "µ"),
+    (0x6e0e158307a662b2, "["),
+    (0x6e2d34a687372ead, "º"),
+    (0x6e3214a6b8f8f738, "ü"),
+    (0x6e33d1800d662851, "®"),
+    (0x6e4a6e5f3b17194f, "y"),
+    (0x6e5e9988bd809186, "«"),
+    (0x6f3a7ac6018fde49, "º"),
+    (0x6f45a73fbacb836f, "º"),
+    (0x6f638d7748694ac1, "L"),
+    (0x6f74a7c9b63a77fc, "9"),
+    (0x6f75f843ccea6981, "t"),
+    (0x6f7f2cae2add4877, "ë"),
+    (0x6fc416c458beb267, "Ê"),
+    (0x6fc6ee72f6726906, "B"),
+    (0x6ff3561944b9825d, "¡"),
+    (0x7028c51f9f3ec152, "G"),
+    (0x713b9e1113c81292, "ò"),
+    (0x71aea8aebc3c2b4a, "O"),
+    (0x7202a6f680d360de, "Ý"),
+    (0x7223d60ea6947f3e, "S"),
+    (0x7243f281fe1b0aa9, "¢"),
+    (0x72475e58be7c8628, "'"),
+    (0x727dcfa9c333c1d9, "ó"),
+    (0x72cf41d31f61bf07, "È"),
+    (0x72d17975b5690da4, "±"),
+    (0x73364b735d8bc8b9, "\""),
+    (0x733bfeffb0da84c9, "Ô"),
+    (0x738bbfe67ee8dbc4, "3"),
+    (0x73a09598321e5e58, "k"),
+    (0x73fc853ed8e44420, "ý"),
+    (0x7433fb75802271a1, "ú"),
+    (0x747a23bad627fbd7, "0"),
+    (0x74899d67a004a27f, "`"),
+    (0x7522df73e39a0bef, "´"),
+    (0x763a9aba5c14dac1, "ë"),
+    (0x7664886b98361281, "ç"),
+    (0x767b85ed257b964d, "¼"),
+    (0x7690976b1d4559d3, "ß"),
+    (0x76fe724fa09bd6d0, "V"),
+    (0x771f8cde7dc6ee64, "É"),
+    (0x77a6f3324c616cd8, "¹"),
+    (0x77ac5883cc34b2da, "4"),
+    (0x77fca0373752721d, "y"),
+    (0x78ae8d9e251af8c5, "H"),
+    (0x792f8c78ff69759b, "Á"),
+    (0x79c197ccf6de7fcc, "v"),
+    (0x79d24d29ec0fd4f8, "v"),
+    (0x79f64200d0a8ddd8, "b"),
+    (0x7a1f25ff03f1a6f9, "!"),
+    (0x7a41d9d4fd21f346, "&"),
+    (0x7a4a775b13edeffe, "a"),
+    (0x7a60a21887854e4c, "K"),
+    (0x7a81a733bd5bc073, "à"),
+    (0x7adf4fa26cdbef9d, "!"),
+    (0x7b29a710fcc33a91, "ö"),
+    (0x7bb2ba736dd56fb2, "n"),
+    (0x7bc9218db243e8ac, "¾"),
+    (0x7bcf2344853259e2, "k"),
+    (0x7c055f89b6898ed5, "ù"),
+    (0x7c15cb1ca09af22d, "ª"),
+    (0x7c4a53cf9986b524, "Ó"),
+    (0x7c60234f55e27251, "»"),
+    (0x7c6599544ddaccbd, "g"),
+    (0x7c8263b0aa7879ed, "Y"),
+    (0x7c9d2a13203cf58c, "r"),
+    (0x7d830a5a841833ba, "è"),
+    (0x7e49fe69ad5bf3d7, "\\"),
+    (0x7e775c3bd0ce4f5c, "à"),
+    (0x7ec23ccfb4057a4c, "["),
+    (0x7ecb37efa427a86b, "µ"),
+    (0x7f0f5d02ff0d0b3c, "õ"),
+    (0x7f31618f3a34adb3, "c"),
+    (0x7f61852ddf84c66b, "î"),
+    (0x7fb6fe0004624745, "3"),
+    (0x803e1a119b2564c9, "G"),
+    (0x808041474dc6888e, "n"),
+    (0x80a9b23eac6b4dae, "¸"),
+    (0x80b208e541548d59, "û"),
+    (0x80c0140fffdd4e16, "l"),
+    (0x80ceab3957b9da8a, "ó"),
+    (0x81bec5de7abae2ac, "Ý"),
+    (0x81c774fd38340b8b, "<"),
+    (0x81f94926a67718f9, "Þ"),
+    (0x81f9e58d295fcd0e, "Â"),
+    (0x82005d1edf79f94d, "Ì"),
+    (0x82050c9cfe2c9229, "h"),
+    (0x820bca9032882e68, ">"),
+    (0x82104fcf4b9121f1, "¹"),
+    (0x82270ec12e3de677, ";"),
+    (0x824e641be1edb71c, "Í"),
+    (0x83109e495642d652, "9"),
+    (0x833c1dc8d5872709, "t"),
+    (0x8361630550b9b389, "³"),
+    (0x83799dc05fc9a471, "ÿ"),
+    (0x83bed15a0c505955, "!"),
+    (0x83e739fcf1f92492, "º"),
+    (0x841d19c0ed407444, "["),
+    (0x84362e124253dabd, "-"),
+    (0x844d1b72a9844ecb, "Ð"),
+    (0x84c3bc1874b3bc48, "v"),
+    (0x8535b45e1e3d4417, "ë"),
+    (0x8583e7a67f6f2217, "é"),
+    (0x85921d1c3cb5094b, "ì"),
+    (0x85ab3219cbf91cff, "Ô"),
+    (0x85d3fd8323d5f238, "A"),
+    (0x8623448e8769baab, "°"),
+    (0x865a37d64402e9d8, "£"),
+    (0x86a40b914d3e64be, "£"),
+    (0x86d970753e271c7d, "Ü"),
+    (0x887e4f1c44dfbfa5, "U"),
+    (0x88a1878207d78557, "I"),
+    (0x88b36e7203372d91, "»"),
+    (0x88bf2d596c77e441, "C"),
+    (0x891bba253452ef37, "¬"),
+    (0x89481aa6be80ad37, "S"),
+    (0x89d27fd2ff497df8, "Ö"),
+    (0x89d8955418a2ead3, "×"),
+    (0x8a6fd0895d3fa927, "7"),
+    (0x8a7dcc80df8ad70c, "ª"),
+    (0x8aff74229993375d, "Ã"),
+    (0x8b927c76c999bb0f, "1"),
+    (0x8b9d2089d3de46e0, "Ä"),
+    (0x8c4ec3e7cf54181f, "]"),
+    (0x8c8769955d034667, "&"),
+    (0x8c990aec4d935d77, "F"),
+    (0x8cfad1c386d2d08c, "h"),
+    (0x8d09d4c8b9b0172e, "Î"),
+    (0x8d2c314961be9d33, "à"),
+    (0x8d4fd4f32518c5f2, "¯"),
+    (0x8da2e332be46f0f8, "§"),
+    (0x8dec63557b943e60, "k"),
+    (0x8df747f2d243733e, "-"),
+    (0x8e3b4b531fefb2bb, "N"),
+    (0x8e3d13ef15199a8d, "¶"),
+    (0x8e71db145e4e19da, "£"),
+    (0x8ebbc8c77d448cf3, "»"),
+    (0x8fbe6853b7835d22, "5"),
+    (0x8fdebaa370640dfa, "m"),
+    (0x8fe3e12b989c9a96, "j"),
+    (0x900868a9d4c6eb4a, "{"),
+    (0x905c5be4dc139710, "°"),
+    (0x907c6741bd18c084, "u"),
+    (0x90f1b1ce3da49478, "ú"),
+    (0x90f3301be589aba7, "¤"),
+    (0x914753cee15a371b, "ê"),
+    (0x91a0cfbab775815f, "¦"),
+    (0x91e288fbe049b75f, "Q"),
+    (0x923e267228cc23e7, "8"),
+    (0x928792c076890fe9, "©"),
+    (0x92dd22067ec618ec, "å"),
+    (0x92fc4f823b7dfe3a, "Ð"),
+    (0x931c5fef2dcb5093, "/"),
+    (0x935994ae1ae92dd5, "="),
+    (0x939711285973858a, "|"),
+    (0x939b1fb796e11d98, "Õ"),
+    (0x941b3425676f3906, "É"),
+    (0x94b48957ce852f62, "|"),
+    (0x951533e8bc80c4ef, "."),
+    (0x954ed7be261c61d3, "C"),
+    (0x958c4cbed08ef496, "h"),
+    (0x95a66d394a7dc2f5, "h"),
+    (0x95f6421298728753, "ò"),
+    (0x96cb59dfff892d9c, "¸"),
+    (0x96d0a30a717f3ecf, "f"),
+    (0x96d4b4a71df8351e, "&"),
+    (0x974c0aefa92103e1, "²"),
+    (0x97914d2437e0c5d7, "j"),
+    (0x98c397267b747375, "%"),
+    (0x98ccbb4bc6697c9e, "v"),
+    (0x992c0f85fb46e04a, "L"),
+    (0x9949543e62e4d157, "ä"),
+    (0x996e46f52fff9e12, "v"),
+    (0x9988b72bbd705f51, "ß"),
+    (0x99d0e49485e304c5, "Q"),
+    (0x99d1bc747a88a441, "û"),
+    (0x99d1e8d7030692a4, "M"),
+    (0x9a0c98f262812ac8, "4"),
+    (0x9a5d23961e042bdc, "p"),
+    (0x9a745eb9beadb823, "Â"),
+    (0x9a9e18cda527045e, "A"),
+    (0x9b1ad189acd81d3b, "µ"),
+    (0x9b67b98f132389bb, "Y"),
+    (0x9b86274643fe42f0, "Z"),
+    (0x9b978f33aec45027, "C"),
+    (0x9c04b56bb6052290, "´"),
+    (0x9c299ce0c048b307, "*"),
+    (0x9c645aeee111e8d0, "Õ"),
+    (0x9c9a193007c40d64, "Þ"),
+    (0x9d5710368b8f65cb, "½"),
+    (0x9e11bd44a74a3363, "!"),
+    (0x9e23e06846a2112c, "¾"),
+    (0x9e5cde9015969aa3, "Ó"),
+    (0x9ec19d92c3ab4cea, "Ü"),
+    (0x9f3caacaa9e7ea24, "j"),
+    (0x9fa68b6124cc143e, "Õ"),
+    (0x9fb93389fef36bdb, ":"),
+    (0x9fc5bd213f5bff1d, "½"),
+    (0x9fea5f51f721772b, ")"),
+    (0xa013e6e36d75fba8, ";"),
+    (0xa02a8e5e1048a3c0, "Û"),
+    (0xa03154ab016f9d77, "\""),
+    (0xa03f1193241c5355, "ø"),
+    (0xa06fc718a7946db7, "x"),
+    (0xa0b33c22d97e712a, "m"),
+    (0xa10be9901a34f05e, "Ð"),
+    (0xa15a8df5e2a9157b, ","),
+    (0xa15e897b5504cf6b, "ã"),
+    (0xa17233a1637c5003, "`"),
+    (0xa19088f7535da5a4, "Å"),
+    (0xa23094ca05c1a91b, "U"),
+    (0xa250bdfdd0e0e94f, "~"),
+    (0xa256a06a0adb935a, "¿"),
+    (0xa26ff55b9d21248b, "F"),
+    (0xa2acbab49c3732a6, "D"),
+    (0xa2faefe25343d9b7, "L"),
+    (0xa3888cc47bf603fe, "c"),
+    (0xa421748be1e36ce5, "è"),
+    (0xa4444d0b8ad97da3, "!"),
+    (0xa4f49b7d944e6406, "t"),
+    (0xa5c7a31a893dbea9, "."),
+    (0xa5c88530b2c83d4a, "è"),
+    (0xa60351f58d38579f, "¢"),
+    (0xa619ef3d318b326f, "¸"),
+    (0xa62a2509c42f404c, "¿"),
+    (0xa64d0a1dd378d5c7, "1"),
+    (0xa75d8ca5c1e373d1, "Î"),
+    (0xa77ddfead9346208, "l"),
+    (0xa78fb2354b4c6055, "8"),
+    (0xa7bd5961dc9f64b2, "_"),
+    (0xa7ddb0d21beb50d9, "Ù"),
+    (0xa826cba1a03e23ea, "e"),
+    (0xa82f4298f39ba063, "Ë"),
+    (0xa8d072d3f2ca4c5e, "N"),
+    (0xa9150aba4d0bd513, "w"),
+    (0xa96ebe03a4b76912, "W"),
+    (0xa985ca9fda81f1ff, "@"),
+    (0xa9872db0366f622d, "À"),
+    (0xaa1c9c8b99c8745c, "á"),
+    (0xaa3e7ec5940af902, "@"),
+    (0xaa6addde0d9ffa96, "ú"),
+    (0xaac10a5d4a89cd05, "B"),
+    (0xaaecbb37f69627d7, "T"),
+    (0xab0aae837e4c6741, "K"),
+    (0xab130186c34a15db, "9"),
+    (0xaba5d3fa061d2a3a, "Z"),
+    (0xac14e7408ed60931, "õ"),
+    (0xacfa145cd2a55b71, "ã"),
+    (0xad6f55e53d0ef619, "7"),
+    (0xad7f6cec0e9aedb8, "¤"),
+    (0xadd8c386c6bceb11, ")"),
+    (0xae1ee3d9c53c3d9f, "["),
+    (0xae75bf2b30637805, "$"),
+    (0xae8b3aad81c2d379, "å"),
+    (0xae9a7004dbe64064, "ü"),
+    (0xaeaad5abeff46acc, "i"),
+    (0xaefa222b2c7776a2, "%"),
+    (0xaf129ddd03ad730a, "k"),
+    (0xaf555bf9d3747904, "J"),
+    (0xafa5f8447bd29997, "H"),
+    (0xafe372d90a107c89, "Ê"),
+    (0xb00f11a6d874d6eb, "°"),
+    (0xb075b9814bab2943, "ñ"),
+    (0xb07e3d90e179de19, "Ã"),
+    (0xb08057a28303f76a, "o"),
+    (0xb094368cd22dd621, "E"),
+    (0xb094fde6cc47c403, "?"),
+    (0xb0b0430b50e14392, "ñ"),
+    (0xb0bf5bb674e86981, "Û"),
+    (0xb12ccde783de53db, "?"),
+    (0xb16100188e76d0bb, "í"),
+    (0xb164a5112c123823, "»"),
+    (0xb1990ac29a5639ae, "8"),
+    (0xb1b439c3c1287be0, "È"),
+    (0xb2013c268581e93e, "ø"),
+    (0xb22f31fe7c02aa31, "W"),
+    (0xb250ffc6bce20bcd, "("),
+    (0xb2c9878a4f814ef1, "S"),
+    (0xb3044fdb740957ff, "â"),
+    (0xb324b9d9ee005f87, "ð"),
+    (0xb38d58196a3c81b5, "+"),
+    (0xb3e357818ceaa09f, "G"),
+    (0xb42bdec32dba68bb, "·"),
+    (0xb483a2b5bbf19367, "Ç"),
+    (0xb575db3745bdd366, "o"),
+    (0xb58d654168438bc6, "®"),
+    (0xb666492a76e43ff3, "_"),
+    (0xb6d322e58d7944bd, "X"),
+    (0xb6f3c0036f8dc889, "¡"),
+    (0xb7577c5d610f2e24, "Ó"),
+    (0xb847373e53c6d194, "y"),
+    (0xb888fadd46f5d6a5, "]"),
+    (0xb8a73d0305d58074, "{"),
+    (0xb8ec3981d81e06c5, "^"),
+    (0xb8f0a5c5ac0af538, "("),
+    (0xb9b55a86165d90e4, "÷"),
+    (0xb9dfecef2ee545f4, "ã"),
+    (0xba392954630c827e, "ÿ"),
+    (0xba41de063f10eb30, "7"),
+    (0xba7076a7132e0ee9, "±"),
+    (0xbabcf7c0400d8c69, "5"),
+    (0xbac7b3331e92f0ea, "¸"),
+    (0xbb394f90cd191a6e, "å"),
+    (0xbb6fd27fdae6636b, "«"),
+    (0xbb7210ae020f1a52, "â"),
+    (0xbb7d6777d575c76c, "b"),
+    (0xbbbae957a3f27458, "À"),
+    (0xbbbf22c5a05f34b5, "Î"),
+    (0xbbdb90eab719438d, "Ú"),
+    (0xbc2cd8bb65b53ba0, "ß"),
+    (0xbc82d7703e443476, "|"),
+    (0xbcf49a1a4a7545a3, "¼"),
+    (0xbd35d8ccc0cb8e91, "J"),
+    (0xbd877af034ac72fc, "D"),
+    (0xbdf8d5ff5ff3ede8, "("),
+    (0xbe9ee2dcf04536e4, "-"),
+    (0xbedaf4f77d54f2b6, "W"),
+    (0xbede285135f963d2, "Õ"),
+    (0xbf089f29aa593758, "Ö"),
+    (0xbf71495f6fac9ad5, "f"),
+    (0xbfa986a4e44fd556, "þ"),
+    (0xbfc0fb0f584b12de, "±"),
+    (0xc09f97323f06a726, "¯"),
+    (0xc0a538a4beb36566, "K"),
+    (0xc0cebbd1ebc2a6e8, "T"),
+    (0xc0e869e6fe554f32, "+"),
+    (0xc1c907f38f5aa4c4, "ñ"),
+    (0xc233499ca5c62f06, "É"),
+    (0xc256e2d9a299d258, "n"),
+    (0xc259c89198115516, "3"),
+    (0xc26b17e927db509b, "¦"),
+    (0xc27bf6c5f74dff2b, "÷"),
+    (0xc2dba9d348d1d2a9, "^"),
+    (0xc36b306b5df4ff09, "V"),
+    (0xc3c7c20fe703ead6, "Ê"),
+    (0xc3d9bd9ac1da150c, "¢"),
+    (0xc4cf47e962ff0f81, "]"),
+    (0xc4fcaef80151d666, "Æ"),
+    (0xc519a81d53e566a7, "¡"),
+    (0xc55f557c022c4e62, "Û"),
+    (0xc5759ab92b8a5b53, "¶"),
+    (0xc59306b7ea0620f5, "Ú"),
+    (0xc5e6f264f86d764b, "±"),
+    (0xc6110b51446a0228, "Ñ"),
+    (0xc696b2309aa19e76, "P"),
+    (0xc6eb0ea962abbcb7, "e"),
+    (0xc74d1a0f91a772bc, "þ"),
+    (0xc75b3fd6bc2ce851, "ß"),
+    (0xc7638fb399c23192, "`"),
+    (0xc774567b5bd15e74, "_"),
+    (0xc814a251249ba797, "~"),
+    (0xc81728cb990087a4, "÷"),
+    (0xc884c7d458690d4c, "ì"),
+    (0xc891937462af6764, "ä"),
+    (0xc8c1012e3d2cb57e, "^"),
+    (0xc8e3ca64138cb938, "½"),
+    (0xc8f4bb1716a3ef15, "Þ"),
+    (0xc919f8bf8d6e1012, "Á"),
+    (0xc947d26ca2f39a24, "|"),
+    (0xc9729c934b1b8a99, "Ú"),
+    (0xc9bf86c046cfa1d9, "~"),
+    (0xc9f1b6390c3ab8f0, "6"),
+    (0xca34b44ba71770e8, "<"),
+    (0xca5022fad47ea871, "b"),
+    (0xca5ab03c1d68e7b8, "a"),
+    (0xca864de13e368e96, "p"),
+    (0xcc54775cc81ec006, "s"),
+    (0xcc6a49cdaa22e5be, "$"),
+    (0xccccf69851531cbc, "P"),
+    (0xcd6be5f8de1113e7, "#"),
+    (0xcdb1fe626cb76e8a, "É"),
+    (0xce1b3f723269c910, "9"),
+    (0xce4054750b2c1e60, "4"),
+    (0xce9ce2dac84b84ac, "ï"),
+    (0xceb35af877c413c1, "í"),
+    (0xcfeac85094afcc5b, "¤"),
+    (0xd0859b783cb82f09, "Í"),
+    (0xd09beb5b1fc11aad, "Ø"),
+    (0xd0c4c713fe843906, "ü"),
+    (0xd0ed11b172346ec8, "é"),
+    (0xd105efd4ee7557c4, "q"),
+    (0xd14f9ffb44a05f09, ")"),
+    (0xd1c4a744a7f40338, "ß"),
+    (0xd231281cf77f4b97, "§"),
+    (0xd2551081f6c1763f, "B"),
+    (0xd26556c00e03a677, "\""),
+    (0xd2dded4b06e56536, "M"),
+    (0xd30619b18cdea4d7, "¨"),
+    (0xd311af7c9d73c09a, "`"),
+    (0xd31203c778c9c00a, "©"),
+    (0xd39b5ff8e3eec179, "á"),
+    (0xd433a89860ecd41f, "Á"),
+    (0xd484e96311e2c040, "Â"),
+    (0xd4bffa39eeaa5e94, "â"),
+    (0xd5b72112c782c422, "R"),
+    (0xd5cdd559d7032565, "}"),
+    (0xd5dbe791551258c8, "z"),
+    (0xd6a8b676ee408698, "Å"),
+    (0xd7108fb297824233, "b"),
+    (0xd720969872a26baa, "9"),
+    (0xd7b70995cd1b5137, "X"),
+    (0xd7d6f16ef87ecf20, "£"),
+    (0xd7ddec7e52370049, "n"),
+    (0xd8112ca0809e01a2, ">"),
+    (0xd84dc2b2196e0667, "}"),
+    (0xd859fefb39ae70c4, "%"),
+    (0xd8d8bf08c0408c37, "Ô"),
+    (0xd908c572ecef63c7, "°"),
+    (0xd90c71743c3150f2, "Ý"),
+    (0xd9b5b85a67b1c4fe, "B"),
+    (0xd9dd5ebcc5095527, ";"),
+    (0xd9fee00805d19126, "z"),
+    (0xda344215b7baf8df, "x"),
+    (0xda4fd6410522a5f9, "E"),
+    (0xda6957d0fd07a057, "%"),
+    (0xda6ce6848ef802ea, "þ"),
+    (0xdaac71beaf1e3e6e, "}"),
+    (0xdaea936126024e70, "½"),
+    (0xdb0120c0e1aa7b9f, "s"),
+    (0xdb17495d475471b8, "§"),
+    (0xdb3c8f03732fedf5, "V"),
+    (0xdb489fa32a428436, "æ"),
+    (0xdb659885b43153a8, "³"),
+    (0xdc1b9eb6714b958d, "+"),
+    (0xdc801cfaa1e766b3, "D"),
+    (0xdd643094012ffa59, "p"),
+    (0xdeaa3cfd7e3c9b7c, "Q"),
+    (0xded5221a29fff01c, "M"),
+    (0xdf0a8ba977240377, "L"),
+    (0xdf1f5f72f614fe7e, "²"),
+    (0xdf710f4dc5c4a9ec, "í"),
+    (0xdf870d8e7125e702, "Ï"),
+    (0xe01a062c2a657567, "a"),
+    (0xe0401ba3a4054b61, "Ñ"),
+    (0xe0d955e5a6659943, "r"),
+    (0xe195c25b5e988aa9, "Ä"),
+    (0xe1cca62365996dd8, "K"),
+    (0xe2167c1e89c84f26, "Ä"),
+    (0xe234bc1065242818, "Ä"),
+    (0xe23b7ea416432b24, "s"),
+    (0xe23f18b371c354d0, "m"),
+    (0xe26f48c22da44805, "Ú"),
+    (0xe27515112e949b87, "Ë"),
+    (0xe29da4daa611fe8f, "è"),
+    (0xe3114951bc6952d3, "¡"),
+    (0xe32564fffa243088, "Ð"),
+    (0xe35ce40664e42b81, "6"),
+    (0xe370d00e9c000aa3, "C"),
+    (0xe37f8fa52e2a23d4, "Þ"),
+    (0xe3ae43f164aef2e4, "¯"),
+    (0xe3b3424306b2d3cf, "E"),
+    (0xe3e0c46ebc7a8fd8, "e"),
+    (0xe47b48296c27940d, "-"),
+    (0xe4c0fc8334dee12a, "¾"),
+    (0xe4f22597dbd99eda, "·"),
+    (0xe52923bc93b29fed, "I"),
+    (0xe55e7013a4be276f, "f"),
+    (0xe567bdea3f792700, "³"),
+    (0xe58254bc880e01e1, "´"),
+    (0xe5b953b21c88729b, "."),
+    (0xe5d570bc63553e47, "¦"),
+    (0xe620494642ead1cb, "{"),
+    (0xe64b14a4182a88e9, "Ò"),
+    (0xe64e17ad4a99e233, "Ø"),
+    (0xe651692d85c794f3, "Å"),
+    (0xe680b7612ec069c6, "ö"),
+    (0xe6923a7d972ce65c, "@"),
+    (0xe6cb97e6cb49b006, "¶"),
+    (0xe749ed7c6ed9f050, "¥"),
+    (0xe7b251881ef871c4, "§"),
+    (0xe87613c31c5634d5, "Ð"),
+    (0xe89bf87363b4997e, "S"),
+    (0xe8c921c153f707d2, "p"),
+    (0xe8ca3b7b0eab6a04, "ð"),
+    (0xe9129126b93f2ee0, ":"),
+    (0xe919dc4e40a8d9e6, "V"),
+    (0xe95e434609f43c8d, "«"),
+    (0xe99007ee1ce933d4, "¼"),
+    (0xe9a4c19f6407606d, "ò"),
+    (0xeab466ebef4419a4, "B"),
+    (0xeadc62a7f83c9ae0, "ý"),
+    (0xeaf1347bff04a9dc, "P"),
+    (0xeb17ef1f5264fc35, "«"),
+    (0xeb1d00ff397153df, "l"),
+    (0xeb75d7d6a2dc5a78, "¬"),
+    (0xeb7a4ec3770ec039, "Ã"),
+    (0xeca125822cb46cd8, "ï"),
+    (0xecb7b944e58e905e, "Ò"),
+    (0xecbeffdbbfccb182, "µ"),
+    (0xeccb55f97bedc618, "u"),
+    (0xecce7841f440cca6, "ç"),
+    (0xed9c46bd1a8075a9, ":"),
+    (0xee8f3693730bf29f, "Ë"),
+    (0xeea618cbc8a85038, "±"),
+    (0xef775041bcf89c23, ","),
+    (0xef84ec08edac85a3, "8"),
+    (0xefb3cda1b4556ec8, "N"),
+    (0xefbba5178b9db318, "ç"),
+    (0xeff442e4762395ad, "'"),
+    (0xf02c5d4039b79a27, "f"),
+    (0xf03492059f36199e, "æ"),
+    (0xf067b2977ef582f8, "¶"),
+    (0xf07389568922a9cc, "J"),
+    (0xf0867f5c80e1efa7, "Q"),
+    (0xf094b9ca8f958c18, "f"),
+    (0xf0ee1e93ff530c34, "¸"),
+    (0xf1231df8a3366da0, "û"),
+    (0xf167fc759bd3ba62, "u"),
+    (0xf171658e61251077, "ô"),
+    (0xf19d49cea61bc64f, "}"),
+    (0xf1b920fb0e6ac224, "Ø"),
+    (0xf1cce6be999c2c29, "\\"),
+    (0xf1da1429cba7b952, "î"),
+    (0xf1f77e4497786fa9, "1"),
+    (0xf21a23f3322f15ca, "¢"),
+    (0xf290b2b4e171e35b, "Þ"),
+    (0xf2ba19deb0a8c847, "l"),
+    (0xf2d932890b1191a3, "®"),
+    (0xf36d53294bde6e1f, "Ò"),
+    (0xf38067ca1a60d0f3, "×"),
+    (0xf3a3782681167b5f, "Ñ"),
+    (0xf3b771cd51f2c04f, "8"),
+    (0xf3de4ab3e38d8b4b, "Ô"),
+    (0xf3e02d374743f3e0, "*"),
+    (0xf3fd5adb06de2e85, "¹"),
+    (0xf53f2b6119e80f5a, "æ"),
+    (0xf562cafa30d9cfa3, "A"),
+    (0xf56871f7920f5894, "©"),
+    (0xf59f6045755bd4fc, "g"),
+    (0xf637c5861ea6f7a5, "^"),
+    (0xf6b8d442348149cc, "ê"),
+    (0xf70738a17232eff5, "ù"),
+    (0xf7588aaf576140d6, "Z"),
+    (0xf7bb8c11897e11b4, ")"),
+    (0xf7d6a75af1fb7115, "Û"),
+    (0xf7de6f24ab243892, "<"),
+    (0xf87db55f92dec9cd, "O"),
+    (0xf94abc5ecf2d494a, "Æ"),
+    (0xf9b89ace98db9015, "¯"),
+    (0xfa2a005b4a6547c0, "w"),
+    (0xfa8c6c2c88c93f9b, "/"),
+    (0xfb2f8f7c1cd94e71, "P"),
+    (0xfb360114fabadf32, "²"),
+    (0xfbb2438ce1ffedf1, "2"),
+    (0xfc259b3be75a5ee9, "c"),
+    (0xfdc140b745b092a9, "D"),
+    (0xfea297da637203c6, "ú"),
+    (0xfecc3e2a47ad74ad, "Ù"),
+    (0xff093b7433b9f81a, "S"),
+    (0xff0f3899ff7e7996, "C"),
+    (0xfffd6ffb19cd2e93, "3"),
+];
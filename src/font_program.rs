@@ -0,0 +1,603 @@
+// Access to the font programs embedded through the FontFile, FontFile2 and FontFile3 entries of
+// font descriptors.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use lopdf::{Dictionary, Document, Object, Stream};
+use ttf_parser::{cff, Face, GlyphId, OutlineBuilder, PlatformId};
+
+use crate::{get_contents, get_name_string, glyphnames, maybe_get_obj, PathOp};
+use crate::type1::Type1Program;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontProgramKind {
+    /// `FontFile`: a Type 1 font program
+    Type1,
+    /// `FontFile2`: a TrueType font
+    TrueType,
+    /// `FontFile3` with subtype `Type1C`: a bare CFF font
+    Type1C,
+    /// `FontFile3` with subtype `CIDFontType0C`: a bare CID-keyed CFF font
+    CIDFontType0C,
+    /// `FontFile3` with subtype `OpenType`
+    OpenType,
+}
+
+/// Find the font program embedded in a font descriptor without decoding it
+pub(crate) fn embedded_stream<'a>(doc: &'a Document, descriptor: &'a Dictionary) -> Option<(FontProgramKind, &'a Stream)> {
+    if let Some(Object::Stream(s)) = maybe_get_obj(doc, descriptor, b"FontFile") {
+        Some((FontProgramKind::Type1, s))
+    } else if let Some(Object::Stream(s)) = maybe_get_obj(doc, descriptor, b"FontFile2") {
+        Some((FontProgramKind::TrueType, s))
+    } else if let Some(Object::Stream(s)) = maybe_get_obj(doc, descriptor, b"FontFile3") {
+        let kind = match get_name_string(doc, &s.dict, b"Subtype").as_ref() {
+            "Type1C" => FontProgramKind::Type1C,
+            "CIDFontType0C" => FontProgramKind::CIDFontType0C,
+            "OpenType" => FontProgramKind::OpenType,
+            subtype => {
+                dlog!("unknown FontFile3 subtype {}", subtype);
+                return None;
+            }
+        };
+        Some((kind, s))
+    } else {
+        None
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct FontProgram {
+    pub kind: FontProgramKind,
+    pub data: Vec<u8>,
+    // Type 1 programs are decrypted once instead of for every glyph
+    type1: Option<Rc<Type1Program>>,
+}
+
+impl FontProgram {
+    pub(crate) fn new(kind: FontProgramKind, data: Vec<u8>) -> FontProgram {
+        let type1 = if kind == FontProgramKind::Type1 { Type1Program::parse(&data).map(Rc::new) } else { None };
+        FontProgram { kind, data, type1 }
+    }
+
+    pub(crate) fn from_descriptor(doc: &Document, descriptor: &Dictionary) -> Option<FontProgram> {
+        let (kind, stream) = embedded_stream(doc, descriptor)?;
+        Some(FontProgram::new(kind, get_contents(stream)))
+    }
+
+    /// Find the glyph that a simple font selects for `code`. This follows
+    /// 9.6.6.4 "Encodings for TrueType Fonts" for TrueType programs and uses the
+    /// built-in encoding for CFF programs. Type 1 programs don't have glyph ids.
+    pub(crate) fn simple_glyph_id(&self, code: u8, glyph_name: Option<&str>) -> Option<u16> {
+        match self.kind {
+            FontProgramKind::Type1C => {
+                let table = cff::Table::parse(&self.data)?;
+                glyph_name.and_then(|name| table.glyph_index_by_name(name))
+                    .or_else(|| table.glyph_index(code))
+                    .map(|gid| gid.0)
+            }
+            FontProgramKind::TrueType | FontProgramKind::OpenType => {
+                let face = Face::parse(&self.data, 0).ok()?;
+                let cmap = face.tables().cmap;
+                for subtable in cmap.iter().flat_map(|cmap| cmap.subtables) {
+                    let gid = match (subtable.platform_id, subtable.encoding_id) {
+                        (PlatformId::Windows, 1) => glyph_name
+                            .and_then(glyphnames::name_to_unicode)
+                            .and_then(|unicode| subtable.glyph_index(unicode as u32)),
+                        (PlatformId::Windows, 0) => [0xf000, 0xf100, 0xf200, 0]
+                            .iter()
+                            .find_map(|high| subtable.glyph_index(high | code as u32)),
+                        (PlatformId::Macintosh, 0) => subtable.glyph_index(code as u32),
+                        _ => None,
+                    };
+                    if let Some(gid) = gid {
+                        return Some(gid.0);
+                    }
+                }
+                glyph_name.and_then(|name| face.glyph_index_by_name(name)).map(|gid| gid.0)
+            }
+            FontProgramKind::Type1 | FontProgramKind::CIDFontType0C => None,
+        }
+    }
+
+    /// The glyph of each CID in a CID-keyed CFF program, which maps glyphs to CIDs through its charset
+    pub(crate) fn cid_glyph_ids(&self) -> HashMap<u32, u16> {
+        let table = match (self.kind, cff::Table::parse(&self.data)) {
+            (FontProgramKind::CIDFontType0C, Some(table)) => table,
+            _ => return HashMap::new(),
+        };
+        (0..table.number_of_glyphs())
+            .filter_map(|gid| table.glyph_cid(GlyphId(gid)).map(|cid| (cid as u32, gid)))
+            .collect()
+    }
+
+    /// The outline of glyph `gid` in glyph space, i.e. 1000 units per em
+    pub(crate) fn glyph_outline(&self, gid: u16) -> Option<Vec<PathOp>> {
+        match self.kind {
+            FontProgramKind::Type1C | FontProgramKind::CIDFontType0C => {
+                let table = cff::Table::parse(&self.data)?;
+                let m = table.matrix();
+                let mut builder = OutlineCollector::new([m.sx, m.ky, m.kx, m.sy, m.tx, m.ty].map(|x| x as f64 * 1000.));
+                table.outline(GlyphId(gid), &mut builder).ok()?;
+                Some(builder.ops)
+            }
+            FontProgramKind::TrueType | FontProgramKind::OpenType => {
+                let face = Face::parse(&self.data, 0).ok()?;
+                let scale = 1000. / face.units_per_em() as f64;
+                let mut builder = OutlineCollector::new([scale, 0., 0., scale, 0., 0.]);
+                face.outline_glyph(GlyphId(gid), &mut builder)?;
+                Some(builder.ops)
+            }
+            FontProgramKind::Type1 => None,
+        }
+    }
+
+    /// The outline of the glyph named `name` in a Type 1 program, which doesn't have glyph ids
+    pub(crate) fn named_glyph_outline(&self, name: &str) -> Option<Vec<PathOp>> {
+        self.type1.as_ref()?.glyph_outline(name)
+    }
+}
+
+// Collects an outline as `PathOp`s after applying `matrix`. Quadratic curves are raised to cubics.
+struct OutlineCollector {
+    matrix: [f64; 6],
+    current: (f64, f64),
+    ops: Vec<PathOp>,
+}
+
+impl OutlineCollector {
+    fn new(matrix: [f64; 6]) -> OutlineCollector {
+        OutlineCollector { matrix, current: (0., 0.), ops: Vec::new() }
+    }
+    fn transform(&self, x: f32, y: f32) -> (f64, f64) {
+        let m = &self.matrix;
+        let (x, y) = (x as f64, y as f64);
+        (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5])
+    }
+}
+
+impl OutlineBuilder for OutlineCollector {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.transform(x, y);
+        self.ops.push(PathOp::MoveTo(x, y));
+        self.current = (x, y);
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.transform(x, y);
+        self.ops.push(PathOp::LineTo(x, y));
+        self.current = (x, y);
+    }
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (qx, qy) = self.transform(x1, y1);
+        let (x, y) = self.transform(x, y);
+        let (x0, y0) = self.current;
+        self.ops.push(PathOp::CurveTo(x0 + 2. / 3. * (qx - x0), y0 + 2. / 3. * (qy - y0),
+                                      x + 2. / 3. * (qx - x), y + 2. / 3. * (qy - y),
+                                      x, y));
+        self.current = (x, y);
+    }
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.transform(x1, y1);
+        let (x2, y2) = self.transform(x2, y2);
+        let (x, y) = self.transform(x, y);
+        self.ops.push(PathOp::CurveTo(x1, y1, x2, y2, x, y));
+        self.current = (x, y);
+    }
+    fn close(&mut self) {
+        self.ops.push(PathOp::Close);
+    }
+}
+
+/// A font program embedded in a document as returned by `embedded_fonts`
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    pub base_font: String,
+    pub kind: FontProgramKind,
+    /// The decoded font program as it's stored in the document
+    pub data: Vec<u8>,
+    // Length1 and Length2 of Type 1 programs: the sizes of the clear text and the encrypted portion
+    pub(crate) type1_lengths: Option<(usize, usize)>,
+    // the glyph id, advance width (in thousandths of an em) and Unicode of the glyphs that the
+    // document selects. Used for the metrics and cmap when wrapping CFF into OpenType.
+    pub(crate) glyphs: Vec<(u16, f64, Option<char>)>,
+    // from the font descriptor, in thousandths of an em
+    pub(crate) ascent: f64,
+    pub(crate) descent: f64,
+}
+
+impl EmbeddedFont {
+    /// A file name made from the base font and the kind of program, e.g. `ABCDEF+Helvetica.cff`
+    pub fn file_name(&self) -> String {
+        let extension = match self.kind {
+            FontProgramKind::Type1 if self.data.iter().all(|b| b.is_ascii()) => "pfa",
+            FontProgramKind::Type1 => "pfb",
+            FontProgramKind::TrueType => "ttf",
+            FontProgramKind::Type1C | FontProgramKind::CIDFontType0C => "cff",
+            FontProgramKind::OpenType => "otf",
+        };
+        format!("{}.{}", self.file_stem(), extension)
+    }
+
+    /// Like `file_name` but for the result of `to_opentype`
+    pub fn opentype_file_name(&self) -> String {
+        format!("{}.otf", self.file_stem())
+    }
+
+    fn file_stem(&self) -> String {
+        if self.base_font.is_empty() {
+            return "font".to_owned();
+        }
+        self.base_font.chars().map(|c| if c.is_ascii_alphanumeric() || "+-_".contains(c) { c } else { '_' }).collect()
+    }
+
+    /// The contents of the file named by `file_name`. Type 1 programs are stored in PDF
+    /// without the segment headers of PFB files so those get added back.
+    pub fn file_contents(&self) -> Vec<u8> {
+        if self.kind != FontProgramKind::Type1 || self.data.iter().all(|b| b.is_ascii()) {
+            return self.data.clone();
+        }
+        let (clear_text, binary) = self.type1_lengths
+            .filter(|(clear_text, binary)| clear_text + binary <= self.data.len())
+            .unwrap_or_else(|| {
+                // without usable lengths, the binary portion starts after "eexec" and its line end
+                let eexec = self.data.windows(5).position(|w| w == b"eexec").map_or(0, |i| i + 5);
+                let clear_text = eexec + self.data[eexec..].iter().take_while(|b| b.is_ascii_whitespace()).count();
+                (clear_text, self.data.len() - clear_text)
+            });
+        let mut pfb = Vec::new();
+        for (segment_type, segment) in [(1, &self.data[..clear_text]),
+                                        (2, &self.data[clear_text..clear_text + binary]),
+                                        (1, &self.data[clear_text + binary..])] {
+            pfb.extend_from_slice(&[0x80, segment_type]);
+            pfb.extend_from_slice(&(segment.len() as u32).to_le_bytes());
+            pfb.extend_from_slice(segment);
+        }
+        pfb.extend_from_slice(&[0x80, 3]);
+        pfb
+    }
+
+    /// Wrap a bare CFF program into an OpenType font that can be used with font loaders that
+    /// don't accept CFF, like browsers. The cmap maps the Unicode of the glyphs used by the
+    /// document. OpenType programs are returned as they are and other kinds give `None`.
+    pub fn to_opentype(&self) -> Option<Vec<u8>> {
+        match self.kind {
+            FontProgramKind::Type1C | FontProgramKind::CIDFontType0C => cff_to_opentype(self),
+            FontProgramKind::OpenType => Some(self.data.clone()),
+            FontProgramKind::Type1 | FontProgramKind::TrueType => None,
+        }
+    }
+
+    /// A font that browsers can load for showing the text of the document with the font, e.g.
+    /// through `@font-face`. This is `to_opentype` for CFF and OpenType programs. TrueType
+    /// programs get a cmap that maps the Unicode of the glyphs used by the document, as the
+    /// document selects glyphs by character code instead. Type 1 programs give `None`.
+    pub fn to_web_font(&self) -> Option<Vec<u8>> {
+        match self.kind {
+            FontProgramKind::TrueType => truetype_with_unicode_cmap(self),
+            _ => self.to_opentype(),
+        }
+    }
+}
+
+fn push_u16(v: &mut Vec<u8>, x: u16) {
+    v.extend_from_slice(&x.to_be_bytes());
+}
+
+fn push_i16(v: &mut Vec<u8>, x: i16) {
+    v.extend_from_slice(&x.to_be_bytes());
+}
+
+fn push_u32(v: &mut Vec<u8>, x: u32) {
+    v.extend_from_slice(&x.to_be_bytes());
+}
+
+fn table_checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn cff_to_opentype(font: &EmbeddedFont) -> Option<Vec<u8>> {
+    let table = cff::Table::parse(&font.data)?;
+    let num_glyphs = table.number_of_glyphs();
+    let matrix = table.matrix();
+    let units_per_em = if matrix.sx > 0. { (1. / matrix.sx as f64).round().clamp(16., 16384.) } else { 1000. };
+    let scale = units_per_em / 1000.;
+
+    // glyph bounding boxes in font units, which give the left side bearings and the font bbox
+    let mut lsbs = vec![0i16; num_glyphs as usize];
+    let mut bbox: Option<ttf_parser::Rect> = None;
+    for gid in 0..num_glyphs {
+        struct NoOutline;
+        impl OutlineBuilder for NoOutline {
+            fn move_to(&mut self, _: f32, _: f32) {}
+            fn line_to(&mut self, _: f32, _: f32) {}
+            fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+            fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+            fn close(&mut self) {}
+        }
+        if let Ok(rect) = table.outline(GlyphId(gid), &mut NoOutline) {
+            lsbs[gid as usize] = rect.x_min;
+            bbox = Some(match bbox {
+                Some(b) => ttf_parser::Rect { x_min: b.x_min.min(rect.x_min), y_min: b.y_min.min(rect.y_min),
+                                              x_max: b.x_max.max(rect.x_max), y_max: b.y_max.max(rect.y_max) },
+                None => rect,
+            });
+        }
+    }
+    let bbox = bbox.unwrap_or(ttf_parser::Rect { x_min: 0, y_min: 0, x_max: 0, y_max: 0 });
+
+    let mut advances = vec![0u16; num_glyphs as usize];
+    let mut unicode = std::collections::BTreeMap::new();
+    for &(gid, width, c) in &font.glyphs {
+        if let Some(advance) = advances.get_mut(gid as usize) {
+            *advance = (width * scale).round().clamp(0., u16::MAX as f64) as u16;
+        }
+        if let Some(c) = c.and_then(|c| u16::try_from(c as u32).ok()) {
+            if c != 0xffff {
+                unicode.entry(c).or_insert(gid);
+            }
+        }
+    }
+    let ascent = if font.ascent != 0. { (font.ascent * scale).round() as i16 } else { bbox.y_max };
+    let descent = if font.descent != 0. { (font.descent * scale).round() as i16 } else { bbox.y_min };
+    let name = font.base_font.split_once('+').map_or(font.base_font.as_str(), |(_, name)| name);
+    let name = if name.is_empty() { "Font" } else { name };
+
+    let mut head = Vec::new();
+    push_u32(&mut head, 0x00010000); // version
+    push_u32(&mut head, 0x00010000); // fontRevision
+    push_u32(&mut head, 0); // checkSumAdjustment, filled in at the end
+    push_u32(&mut head, 0x5F0F3CF5); // magicNumber
+    push_u16(&mut head, 3); // flags: baseline and left side bearing at 0
+    push_u16(&mut head, units_per_em as u16);
+    head.extend_from_slice(&[0; 16]); // created and modified
+    for v in [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max] {
+        push_i16(&mut head, v);
+    }
+    push_u16(&mut head, 0); // macStyle
+    push_u16(&mut head, 8); // lowestRecPPEM
+    push_i16(&mut head, 2); // fontDirectionHint
+    push_i16(&mut head, 0); // indexToLocFormat
+    push_i16(&mut head, 0); // glyphDataFormat
+
+    let mut hhea = Vec::new();
+    push_u32(&mut hhea, 0x00010000);
+    push_i16(&mut hhea, ascent);
+    push_i16(&mut hhea, descent);
+    push_i16(&mut hhea, 0); // lineGap
+    push_u16(&mut hhea, advances.iter().copied().max().unwrap_or(0));
+    push_i16(&mut hhea, bbox.x_min); // minLeftSideBearing
+    push_i16(&mut hhea, 0); // minRightSideBearing
+    push_i16(&mut hhea, bbox.x_max); // xMaxExtent
+    push_i16(&mut hhea, 1); // caretSlopeRise
+    push_i16(&mut hhea, 0); // caretSlopeRun
+    hhea.extend_from_slice(&[0; 10]); // caretOffset and reserved
+    push_i16(&mut hhea, 0); // metricDataFormat
+    push_u16(&mut hhea, num_glyphs);
+
+    let mut hmtx = Vec::new();
+    for (advance, lsb) in advances.iter().zip(&lsbs) {
+        push_u16(&mut hmtx, *advance);
+        push_i16(&mut hmtx, *lsb);
+    }
+
+    let mut maxp = Vec::new();
+    push_u32(&mut maxp, 0x00005000);
+    push_u16(&mut maxp, num_glyphs);
+
+    let (cmap, first, last) = unicode_cmap(unicode);
+    let average = advances.iter().filter(|a| **a > 0).map(|a| *a as u32).sum::<u32>() / advances.iter().filter(|a| **a > 0).count().max(1) as u32;
+    let os2 = os2_table(average as i16, first, last, ascent, descent);
+    let tables: [(&[u8; 4], &[u8]); 9] = [(b"CFF ", &font.data), (b"OS/2", &os2), (b"cmap", &cmap), (b"head", &head),
+                                          (b"hhea", &hhea), (b"hmtx", &hmtx), (b"maxp", &maxp), (b"name", &name_table(name)),
+                                          (b"post", &post_table())];
+    Some(write_sfnt(b"OTTO", &tables))
+}
+
+// A cmap that maps the characters of `unicode` to their glyphs, with a format 4 subtable, or a
+// format 12 subtable when the characters need more segments than fit in a format 4 one. Also
+// returns the first and last characters.
+fn unicode_cmap(unicode: std::collections::BTreeMap<u16, u16>) -> (Vec<u8>, u16, u16) {
+    // runs of consecutive characters mapped to consecutive glyphs, as (start, end, first glyph)
+    let mut segments: Vec<(u16, u16, u16)> = Vec::new();
+    for (c, gid) in unicode {
+        // 0xffff is kept for the last segment of format 4 subtables
+        if c == 0xffff {
+            continue;
+        }
+        match segments.last_mut() {
+            Some((start, end, first_gid)) if *end as u32 + 1 == c as u32 && *first_gid as u32 + (c - *start) as u32 == gid as u32 => *end = c,
+            _ => segments.push((c, c, gid)),
+        }
+    }
+    let first = segments.first().map_or(0xffff, |s| s.0);
+    let last = segments.last().map_or(0, |s| s.1);
+    // with the segment that maps 0xffff to glyph 0
+    let seg_count = segments.len() as u32 + 1;
+    let mut subtable = Vec::new();
+    let encoding_id = if 16 + 8 * seg_count <= 0xffff {
+        segments.push((0xffff, 0xffff, 0));
+        let entry_selector = 31 - seg_count.leading_zeros();
+        let search_range = 2 << entry_selector;
+        push_u16(&mut subtable, 4);
+        push_u16(&mut subtable, (16 + 8 * seg_count) as u16);
+        push_u16(&mut subtable, 0); // language
+        push_u16(&mut subtable, (seg_count * 2) as u16);
+        push_u16(&mut subtable, search_range as u16);
+        push_u16(&mut subtable, entry_selector as u16);
+        push_u16(&mut subtable, (seg_count * 2 - search_range) as u16);
+        for (_, end, _) in &segments {
+            push_u16(&mut subtable, *end); // endCode
+        }
+        push_u16(&mut subtable, 0); // reservedPad
+        for (start, _, _) in &segments {
+            push_u16(&mut subtable, *start); // startCode
+        }
+        for (start, _, gid) in &segments {
+            // the last segment maps 0xffff to glyph 0
+            let delta = if *start == 0xffff { 1 } else { gid.wrapping_sub(*start) };
+            push_u16(&mut subtable, delta);
+        }
+        for _ in &segments {
+            push_u16(&mut subtable, 0); // idRangeOffset
+        }
+        1 // Unicode BMP
+    } else {
+        push_u16(&mut subtable, 12);
+        push_u16(&mut subtable, 0); // reserved
+        push_u32(&mut subtable, 16 + 12 * segments.len() as u32);
+        push_u32(&mut subtable, 0); // language
+        push_u32(&mut subtable, segments.len() as u32);
+        for (start, end, gid) in &segments {
+            push_u32(&mut subtable, *start as u32);
+            push_u32(&mut subtable, *end as u32);
+            push_u32(&mut subtable, *gid as u32);
+        }
+        10 // Unicode full repertoire
+    };
+    let mut cmap = Vec::new();
+    push_u16(&mut cmap, 0);
+    push_u16(&mut cmap, 1);
+    push_u16(&mut cmap, 3); // Windows
+    push_u16(&mut cmap, encoding_id);
+    push_u32(&mut cmap, 12);
+    cmap.extend_from_slice(&subtable);
+    (cmap, first, last)
+}
+
+fn name_table(name: &str) -> Vec<u8> {
+    let postscript_name: String = name.chars().filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c)).take(63).collect();
+    let names = [(1, name), (2, "Regular"), (3, name), (4, name), (6, postscript_name.as_str())];
+    let mut name_table = Vec::new();
+    let mut strings = Vec::new();
+    push_u16(&mut name_table, 0);
+    push_u16(&mut name_table, names.len() as u16);
+    push_u16(&mut name_table, 6 + 12 * names.len() as u16);
+    for (name_id, s) in names {
+        let utf16: Vec<u8> = s.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+        for v in [3, 1, 0x409, name_id, utf16.len() as u16, strings.len() as u16] {
+            push_u16(&mut name_table, v);
+        }
+        strings.extend_from_slice(&utf16);
+    }
+    name_table.extend_from_slice(&strings);
+    name_table
+}
+
+fn os2_table(average_width: i16, first: u16, last: u16, ascent: i16, descent: i16) -> Vec<u8> {
+    let mut os2 = Vec::new();
+    push_u16(&mut os2, 4); // version
+    push_i16(&mut os2, average_width); // xAvgCharWidth
+    push_u16(&mut os2, 400); // usWeightClass
+    push_u16(&mut os2, 5); // usWidthClass
+    push_u16(&mut os2, 0); // fsType: installable
+    os2.extend_from_slice(&[0; 22]); // subscript, superscript and strikeout metrics and sFamilyClass
+    os2.extend_from_slice(&[0; 10]); // panose
+    os2.extend_from_slice(&[0; 16]); // ulUnicodeRange
+    os2.extend_from_slice(b"NONE"); // achVendID
+    push_u16(&mut os2, 0x40); // fsSelection: regular
+    push_u16(&mut os2, first);
+    push_u16(&mut os2, last);
+    push_i16(&mut os2, ascent); // sTypoAscender
+    push_i16(&mut os2, descent); // sTypoDescender
+    push_i16(&mut os2, 0); // sTypoLineGap
+    push_u16(&mut os2, ascent.max(0) as u16); // usWinAscent
+    push_u16(&mut os2, descent.min(0).unsigned_abs()); // usWinDescent
+    os2.extend_from_slice(&[0; 8]); // ulCodePageRange
+    os2.extend_from_slice(&[0; 10]); // sxHeight, sCapHeight, usDefaultChar, usBreakChar, usMaxContext
+    os2
+}
+
+fn post_table() -> Vec<u8> {
+    let mut post = Vec::new();
+    push_u32(&mut post, 0x00030000);
+    post.extend_from_slice(&[0; 28]);
+    post
+}
+
+// Write an sfnt font from its tables, which have to be sorted by tag
+fn write_sfnt(version: &[u8; 4], tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut otf = Vec::new();
+    otf.extend_from_slice(version);
+    push_u16(&mut otf, tables.len() as u16);
+    let entry_selector = 15 - (tables.len() as u16).leading_zeros() as u16;
+    push_u16(&mut otf, (1 << entry_selector) * 16); // searchRange
+    push_u16(&mut otf, entry_selector);
+    push_u16(&mut otf, (tables.len() as u16 - (1 << entry_selector)) * 16); // rangeShift
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if *tag == b"head" {
+            head_offset = Some(offset);
+        }
+        otf.extend_from_slice(*tag);
+        push_u32(&mut otf, table_checksum(data));
+        push_u32(&mut otf, offset as u32);
+        push_u32(&mut otf, data.len() as u32);
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in tables {
+        otf.extend_from_slice(data);
+        otf.resize((otf.len() + 3) & !3, 0);
+    }
+    if let Some(head_offset) = head_offset.filter(|o| o + 12 <= otf.len()) {
+        // the checksum adjustment is computed with the field set to 0
+        otf[head_offset + 8..head_offset + 12].copy_from_slice(&[0; 4]);
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(table_checksum(&otf));
+        otf[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    otf
+}
+
+// Replace the cmap of a TrueType program with one for the glyphs used by the document and add the
+// tables that embedded programs tend to leave out but that font loaders require
+fn truetype_with_unicode_cmap(font: &EmbeddedFont) -> Option<Vec<u8>> {
+    let face = Face::parse(&font.data, 0).ok()?;
+    let data = &font.data;
+    let num_tables = u16::from_be_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+    let mut tables: Vec<([u8; 4], &[u8])> = Vec::new();
+    for i in 0..num_tables {
+        let entry = data.get(12 + 16 * i..12 + 16 * (i + 1))?;
+        let tag = <[u8; 4]>::try_from(&entry[0..4]).ok()?;
+        let offset = u32::from_be_bytes(<[u8; 4]>::try_from(&entry[8..12]).ok()?) as usize;
+        let length = u32::from_be_bytes(<[u8; 4]>::try_from(&entry[12..16]).ok()?) as usize;
+        if &tag != b"cmap" {
+            tables.push((tag, data.get(offset..offset.checked_add(length)?)?));
+        }
+    }
+
+    let scale = face.units_per_em() as f64 / 1000.;
+    let mut unicode = std::collections::BTreeMap::new();
+    let mut widths = Vec::new();
+    for &(gid, width, c) in &font.glyphs {
+        if width > 0. {
+            widths.push(width * scale);
+        }
+        if let Some(c) = c.and_then(|c| u16::try_from(c as u32).ok()) {
+            if c != 0xffff {
+                unicode.entry(c).or_insert(gid);
+            }
+        }
+    }
+    let (cmap, first, last) = unicode_cmap(unicode);
+    let name = font.base_font.split_once('+').map_or(font.base_font.as_str(), |(_, name)| name);
+    let name = name_table(if name.is_empty() { "Font" } else { name });
+    let average = widths.iter().sum::<f64>() / widths.len().max(1) as f64;
+    let os2 = os2_table(average.round() as i16, first, last, face.ascender(), face.descender());
+    let post = post_table();
+
+    let mut added: Vec<([u8; 4], &[u8])> = vec![(*b"cmap", &cmap)];
+    for (tag, table) in [(b"name", &name), (b"OS/2", &os2), (b"post", &post)] {
+        if !tables.iter().any(|(t, _)| t == tag) {
+            added.push((*tag, table));
+        }
+    }
+    tables.extend(added);
+    tables.sort_by_key(|(tag, _)| *tag);
+    let tables: Vec<(&[u8; 4], &[u8])> = tables.iter().map(|(tag, data)| (tag, *data)).collect();
+    Some(write_sfnt(&[0, 1, 0, 0], &tables))
+}
@@ -0,0 +1,992 @@
+// Layout analysis for the output devices that reflow the text. The characters of each page are
+// grouped into lines and then into blocks, which are classified as headings, paragraphs, list
+// items, tables and figures from their font sizes, styles and positions.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{is_word_gap, BoundingBox, MediaBox, Path, PathOp, TextGlyph, TextRenderMode, Transform, ASCENT, DESCENT};
+
+type Rect = (f64, f64, f64, f64);
+
+/// A character as it's drawn, in the default user space of the page
+#[derive(Clone, Debug)]
+pub(crate) struct LayoutChar {
+    pub text: String,
+    pub x: f64,
+    pub end: f64,
+    // the baseline
+    pub y: f64,
+    pub size: f64,
+    // the base font without the subset tag
+    pub font: Rc<str>,
+    pub bold: bool,
+    pub italic: bool,
+    // the fill color as RGB, or the stroke color of glyphs that are only stroked, and none for
+    // invisible glyphs
+    pub color: Option<[f64; 3]>,
+    link: Option<String>,
+    // whether a gap in a TJ array before the character separates words, if there was one
+    word_gap: Option<bool>,
+}
+
+impl LayoutChar {
+    fn is_space(&self) -> bool {
+        self.text.chars().all(char::is_whitespace)
+    }
+
+    pub fn bbox(&self) -> Rect {
+        (self.x, self.y - DESCENT * self.size, self.end, self.y + ASCENT * self.size)
+    }
+}
+
+// The style of a glyph which `character` gives the following character
+struct GlyphStyle {
+    font: Rc<str>,
+    bold: bool,
+    italic: bool,
+    color: Option<[f64; 3]>,
+}
+
+enum PageItem {
+    Char(LayoutChar),
+    Figure(Figure),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Figure {
+    pub bbox: Rect,
+    pub data_uri: Option<String>,
+    pub caption: Option<Vec<Span>>,
+}
+
+/// A run of text with the same style
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub link: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Block {
+    Heading { level: u8, spans: Vec<Span> },
+    Paragraph(Vec<Span>),
+    List { ordered: bool, start: u32, items: Vec<Vec<Span>> },
+    // rows of cells
+    Table(Vec<Vec<Vec<Span>>>),
+    Figure(Figure),
+}
+
+pub(crate) struct LayoutPage {
+    pub page_num: u32,
+    pub blocks: Vec<Block>,
+}
+
+/// A word with its bounding box in the default user space of the page
+pub(crate) struct TextWord {
+    pub text: String,
+    pub bbox: Rect,
+    pub size: f64,
+    pub font: Rc<str>,
+    pub bold: bool,
+    pub italic: bool,
+    pub chars: Vec<LayoutChar>,
+}
+
+pub(crate) struct TextLine {
+    pub words: Vec<TextWord>,
+    pub bbox: Rect,
+    pub baseline: f64,
+    pub size: f64,
+}
+
+/// The regions of a page as found by the layout analysis, without classifying the text
+pub(crate) enum Region {
+    // the lines of a paragraph or other block of text
+    Text(Vec<TextLine>),
+    // the lines are the rows
+    Table(Vec<TextLine>),
+    Figure(Rect),
+}
+
+pub(crate) struct PageRegions {
+    pub page_num: u32,
+    pub media_box: MediaBox,
+    pub regions: Vec<Region>,
+    // the bounding boxes of the filled and stroked paths
+    pub graphics: Vec<Rect>,
+}
+
+struct Page {
+    page_num: u32,
+    media_box: MediaBox,
+    items: Vec<PageItem>,
+    links: Vec<(Rect, String)>,
+    graphics: Vec<Rect>,
+}
+
+/// Collects the characters, images and links of the pages for `finish` to analyze
+#[derive(Default)]
+pub(crate) struct LayoutBuilder {
+    pages: Vec<Page>,
+    // the style of the glyph passed to `glyph` for the following `character`
+    style: Option<GlyphStyle>,
+    // the font dictionary and base font of the last glyph
+    font: Option<(usize, Rc<str>)>,
+    // the gaps from TJ arrays since the last character, with the font size and space width
+    gap: Option<(f64, f64, Option<f64>)>,
+}
+
+// Images smaller than this in both directions are left out as decorations
+const MIN_FIGURE_SIZE: f64 = 16.;
+
+impl LayoutBuilder {
+    pub fn begin_page(&mut self, page_num: u32, media_box: &MediaBox) {
+        self.pages.push(Page { page_num, media_box: *media_box, items: Vec::new(), links: Vec::new(), graphics: Vec::new() });
+    }
+
+    pub fn glyph(&mut self, glyph: &TextGlyph) {
+        let font = match &self.font {
+            Some((key, font)) if *key == glyph.key().0 => font.clone(),
+            _ => {
+                let font: Rc<str> = glyph.base_font().unwrap_or_default().into();
+                self.font = Some((glyph.key().0, font.clone()));
+                font
+            }
+        };
+        let color = match glyph.render_mode {
+            TextRenderMode::Fill | TextRenderMode::FillStroke | TextRenderMode::FillClip | TextRenderMode::FillStrokeClip => {
+                glyph.fill_colorspace.to_rgb(glyph.fill_color)
+            }
+            TextRenderMode::Stroke | TextRenderMode::StrokeClip => glyph.stroke_colorspace.to_rgb(glyph.stroke_color),
+            TextRenderMode::Invisible | TextRenderMode::Clip => None,
+        };
+        self.style = Some(GlyphStyle { font, bold: glyph.is_bold(), italic: glyph.is_italic(), color });
+    }
+
+    pub fn character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) {
+        let style = self.style.take().unwrap_or_else(|| GlyphStyle { font: "".into(), bold: false, italic: false, color: None });
+        let page = match self.pages.last_mut() {
+            Some(page) => page,
+            None => return,
+        };
+        let size = font_size * (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        let end = trm.pre_transform(&Transform::create_translation(width * font_size + spacing, 0.)).m31;
+        page.items.push(PageItem::Char(LayoutChar {
+            text: char.to_owned(), x: trm.m31, end: end.max(trm.m31), y: trm.m32, size, font: style.font,
+            bold: style.bold, italic: style.italic, color: style.color, link: None,
+            word_gap: self.gap.take().map(|(gap, font_size, space_width)| is_word_gap(gap, font_size, space_width)),
+        }));
+    }
+
+    /// `ctm` maps the unit square to the image
+    pub fn image(&mut self, ctm: &Transform, data_uri: Option<String>) {
+        let page = match self.pages.last_mut() {
+            Some(page) => page,
+            None => return,
+        };
+        let corners = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)].map(|(x, y)| ctm.transform_point(euclid::point2(x, y)));
+        let bbox = corners.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, p| (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y)));
+        if bbox.2 - bbox.0 < MIN_FIGURE_SIZE && bbox.3 - bbox.1 < MIN_FIGURE_SIZE {
+            return;
+        }
+        page.items.push(PageItem::Figure(Figure { bbox, data_uri, caption: None }));
+    }
+
+    /// A filled or stroked path
+    pub fn graphic(&mut self, ctm: &Transform, path: &Path) {
+        let page = match self.pages.last_mut() {
+            Some(page) => page,
+            None => return,
+        };
+        // the control points of curves contain them
+        let mut points = Vec::new();
+        for op in &path.ops {
+            match *op {
+                PathOp::MoveTo(x, y) | PathOp::LineTo(x, y) => points.push((x, y)),
+                PathOp::CurveTo(x1, y1, x2, y2, x, y) => points.extend([(x1, y1), (x2, y2), (x, y)]),
+                PathOp::Rect(x, y, w, h) => points.extend([(x, y), (x + w, y + h), (x + w, y), (x, y + h)]),
+                PathOp::Close => {}
+            }
+        }
+        let bbox = points.iter().map(|&(x, y)| ctm.transform_point(euclid::point2(x, y)))
+            .fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, p| (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y)));
+        if bbox.0 <= bbox.2 && (bbox.2 - bbox.0 >= 1. || bbox.3 - bbox.1 >= 1.) {
+            page.graphics.push(bbox);
+        }
+    }
+
+    /// A gap from a TJ array, see `OutputDev::text_gap`
+    pub fn gap(&mut self, gap: f64, font_size: f64, space_width: Option<f64>) {
+        let total = self.gap.map_or(0., |(total, ..)| total);
+        self.gap = Some((total + gap, font_size, space_width));
+    }
+
+    pub fn link(&mut self, rect: &Rect, uri: &str) {
+        if let Some(page) = self.pages.last_mut() {
+            page.links.push((*rect, uri.to_owned()));
+        }
+    }
+
+    /// The lines and words of the blocks of the pages collected so far. Unlike `finish`, this
+    /// doesn't depend on the other pages, so it can be used a page at a time.
+    pub fn finish_regions(&mut self) -> Vec<PageRegions> {
+        self.pages.drain(..).map(|page| {
+            let regions = raw_blocks(lines(page.items)).into_iter().map(|block| match block {
+                RawBlock::Text { lines, .. } => Region::Text(lines.iter().map(Line::text_line).collect()),
+                RawBlock::Table(rows) => Region::Table(rows.iter().map(Line::text_line).collect()),
+                RawBlock::Figure(figure) => Region::Figure(figure.bbox),
+            }).collect();
+            PageRegions { page_num: page.page_num, media_box: page.media_box, regions, graphics: page.graphics }
+        }).collect()
+    }
+
+    /// The text of each page as paragraphs in reading order. The blocks found by `xy_cut` are
+    /// split into paragraphs.
+    pub fn finish_reading_order(&mut self) -> Vec<Vec<Vec<TextLine>>> {
+        self.pages.drain(..).map(|page| {
+            // the pieces of the lines that could be in different columns
+            let pieces: Vec<Line> = lines(page.items).into_iter().flat_map(|item| match item {
+                LineItem::Line(line) => line.cells().into_iter().filter_map(Line::new).collect(),
+                LineItem::Figure(_) => Vec::new(),
+            }).collect();
+            let mut sizes: Vec<f64> = pieces.iter().map(|piece| piece.size).collect();
+            sizes.sort_by(f64::total_cmp);
+            let size = sizes.get(sizes.len() / 2).copied().unwrap_or(0.);
+            let mut blocks = Vec::new();
+            xy_cut(pieces, size, &mut blocks);
+            blocks.into_iter().flat_map(|block| paragraphs(join_pieces(block)))
+                .map(|paragraph| paragraph.iter().map(Line::text_line).collect()).collect()
+        }).collect()
+    }
+
+    /// Analyze the pages collected so far. The font sizes of all of them are used to tell
+    /// headings from the body text.
+    pub fn finish(&mut self) -> Vec<LayoutPage> {
+        let pages: Vec<(u32, Vec<RawBlock>)> = self.pages.drain(..).map(|mut page| {
+            resolve_links(&mut page);
+            (page.page_num, raw_blocks(lines(page.items)))
+        }).collect();
+
+        // the body text has the size of most of the characters
+        let mut sizes: HashMap<i64, usize> = HashMap::new();
+        for (_, blocks) in &pages {
+            for line in blocks.iter().flat_map(RawBlock::lines) {
+                *sizes.entry(size_key(line.size)).or_default() += line.chars.len();
+            }
+        }
+        let body_size = sizes.iter().max_by_key(|(size, count)| (**count, -**size)).map_or(0., |(size, _)| *size as f64 / 2.);
+
+        // heading levels go by size, followed by bold headings at the body size
+        let mut heading_sizes: Vec<i64> = pages.iter().flat_map(|(_, blocks)| blocks.iter())
+            .filter(|block| heading_kind(block, body_size) == Some(true))
+            .map(|block| size_key(block.size()))
+            .collect();
+        heading_sizes.sort_unstable_by(|a, b| b.cmp(a));
+        heading_sizes.dedup();
+
+        pages.into_iter().map(|(page_num, raw)| {
+            let mut blocks = Vec::new();
+            let mut raw = raw.into_iter().peekable();
+            while let Some(block) = raw.next() {
+                match block {
+                    RawBlock::Figure(mut figure) => {
+                        let caption = raw.next_if(|next| match next {
+                            RawBlock::Text { marker: None, lines } => is_caption(lines),
+                            _ => false,
+                        });
+                        if let Some(RawBlock::Text { lines, .. }) = caption {
+                            figure.caption = Some(spans(&lines, 0));
+                        }
+                        blocks.push(Block::Figure(figure));
+                    }
+                    RawBlock::Table(rows) => {
+                        blocks.push(Block::Table(rows.iter().map(|row| row.cells().iter().map(|cell| spans_of_chars(cell)).collect()).collect()));
+                    }
+                    RawBlock::Text { .. } if heading_kind(&block, body_size).is_some() => {
+                        let level = match heading_sizes.iter().position(|size| *size == size_key(block.size())) {
+                            Some(index) if heading_kind(&block, body_size) == Some(true) => index + 1,
+                            _ => heading_sizes.len() + 1,
+                        };
+                        // the heading itself shows that it's bold
+                        let mut heading = spans(block.lines(), 0);
+                        heading.iter_mut().for_each(|span| span.bold = false);
+                        blocks.push(Block::Heading { level: level.min(6) as u8, spans: merge_spans(heading) });
+                    }
+                    RawBlock::Text { marker: Some(marker), lines } => {
+                        let mut items = vec![spans(&lines, marker.len)];
+                        // the following items of the same kind of list
+                        while let Some(RawBlock::Text { lines, marker: Some(next) }) =
+                                raw.next_if(|next| continues_list(next, marker.ordered, body_size)) {
+                            items.push(spans(&lines, next.len));
+                        }
+                        blocks.push(Block::List { ordered: marker.ordered, start: marker.number, items });
+                    }
+                    RawBlock::Text { lines, .. } => blocks.push(Block::Paragraph(spans(&lines, 0))),
+                }
+            }
+            LayoutPage { page_num, blocks }
+        }).collect()
+    }
+}
+
+// Characters further apart than this many ems are in different cells of a table
+const CELL_GAP: f64 = 1.5;
+
+struct Line {
+    // without spaces at the ends
+    chars: Vec<LayoutChar>,
+    y: f64,
+    size: f64,
+    x0: f64,
+    x1: f64,
+}
+
+impl Line {
+    fn new(mut chars: Vec<LayoutChar>) -> Option<Line> {
+        while chars.last().is_some_and(LayoutChar::is_space) {
+            chars.pop();
+        }
+        let start = chars.iter().position(|c| !c.is_space())?;
+        chars.drain(..start);
+        let largest = chars.iter().max_by(|a, b| a.size.total_cmp(&b.size))?;
+        let (y, size) = (largest.y, largest.size);
+        let x0 = chars.iter().map(|c| c.x).fold(f64::MAX, f64::min);
+        let x1 = chars.iter().map(|c| c.end).fold(f64::MIN, f64::max);
+        Some(Line { chars, y, size, x0, x1 })
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    fn is_bold(&self) -> bool {
+        self.chars.iter().filter(|c| !c.is_space()).all(|c| c.bold)
+    }
+
+    fn text_line(&self) -> TextLine {
+        let words: Vec<TextWord> = self.chars.split(LayoutChar::is_space).filter(|word| !word.is_empty()).map(|chars| {
+            let bbox = chars.iter().map(LayoutChar::bbox).fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, c| {
+                (b.0.min(c.0), b.1.min(c.1), b.2.max(c.2), b.3.max(c.3))
+            });
+            TextWord {
+                text: chars.iter().map(|c| c.text.as_str()).collect(),
+                bbox,
+                size: chars.iter().map(|c| c.size).fold(0., f64::max),
+                font: chars[0].font.clone(),
+                bold: chars.iter().all(|c| c.bold),
+                italic: chars.iter().all(|c| c.italic),
+                chars: chars.to_vec(),
+            }
+        }).collect();
+        let bbox = words.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, w| {
+            (b.0.min(w.bbox.0), b.1.min(w.bbox.1), b.2.max(w.bbox.2), b.3.max(w.bbox.3))
+        });
+        TextLine { words, bbox, baseline: self.y, size: self.size }
+    }
+
+    // The characters split where they're far enough apart to be in different columns
+    fn cells(&self) -> Vec<Vec<LayoutChar>> {
+        let mut cells = vec![Vec::new()];
+        let mut last_end: Option<f64> = None;
+        for c in &self.chars {
+            if !c.is_space() {
+                if last_end.is_some_and(|end| c.x - end > CELL_GAP * self.size) {
+                    cells.push(Vec::new());
+                }
+                last_end = Some(c.end);
+            }
+            if let Some(cell) = cells.last_mut() {
+                cell.push(c.clone());
+            }
+        }
+        cells
+    }
+}
+
+// Columns are separated by gutters at least this many ems wide and are at least MIN_COLUMN_WIDTH
+// ems wide, so that the columns of tables aren't read one after the other
+const MIN_GUTTER: f64 = 1.;
+const MIN_COLUMN_WIDTH: f64 = 5.;
+// Blocks are separated vertically by at least this many ems
+const MIN_BLOCK_GAP: f64 = 0.5;
+
+// The extents of `ranges` split where there are gaps of at least `gap` between them, as the
+// indices of the ranges in each part sorted by their start
+fn split_at_gaps(ranges: &[(f64, f64)], gap: f64) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..ranges.len()).collect();
+    order.sort_by(|&a, &b| ranges[a].0.total_cmp(&ranges[b].0));
+    let mut parts: Vec<Vec<usize>> = Vec::new();
+    let mut end = f64::MIN;
+    for i in order {
+        match parts.last_mut() {
+            Some(part) if ranges[i].0 - end < gap => part.push(i),
+            _ => parts.push(vec![i]),
+        }
+        end = end.max(ranges[i].1);
+    }
+    parts
+}
+
+fn take_parts(mut pieces: Vec<Line>, parts: &[Vec<usize>]) -> Vec<Vec<Line>> {
+    let mut taken: Vec<Option<Line>> = pieces.drain(..).map(Some).collect();
+    parts.iter().map(|part| part.iter().filter_map(|&i| taken[i].take()).collect()).collect()
+}
+
+// Order the pieces of lines for reading with a recursive XY-cut: they're split into columns at
+// vertical gutters that run through all of them, read from left to right, or else into bands
+// at horizontal gaps, read from top to bottom, until neither is possible and the pieces form a
+// block
+fn xy_cut(pieces: Vec<Line>, size: f64, blocks: &mut Vec<Vec<Line>>) {
+    if pieces.len() <= 1 {
+        blocks.extend(Some(pieces).filter(|pieces| !pieces.is_empty()));
+        return;
+    }
+    let columns = split_at_gaps(&pieces.iter().map(|piece| (piece.x0, piece.x1)).collect::<Vec<_>>(), MIN_GUTTER * size);
+    let wide_enough = columns.iter().all(|column| {
+        let x0 = column.iter().map(|&i| pieces[i].x0).fold(f64::MAX, f64::min);
+        let x1 = column.iter().map(|&i| pieces[i].x1).fold(f64::MIN, f64::max);
+        x1 - x0 >= MIN_COLUMN_WIDTH * size
+    });
+    if columns.len() > 1 && wide_enough {
+        for column in take_parts(pieces, &columns) {
+            xy_cut(column, size, blocks);
+        }
+        return;
+    }
+    // from the top, so y is negated
+    let extents: Vec<(f64, f64)> = pieces.iter().map(|piece| (-piece.y - ASCENT * piece.size, -piece.y + DESCENT * piece.size)).collect();
+    let bands = split_at_gaps(&extents, MIN_BLOCK_GAP * size);
+    if bands.len() > 1 {
+        for band in take_parts(pieces, &bands) {
+            xy_cut(band, size, blocks);
+        }
+        return;
+    }
+    blocks.push(pieces);
+}
+
+// Put the pieces of a block that share a baseline back together into lines, from the top
+fn join_pieces(mut pieces: Vec<Line>) -> Vec<Line> {
+    pieces.sort_by(|a, b| b.y.total_cmp(&a.y));
+    let mut rows: Vec<Vec<Line>> = Vec::new();
+    for piece in pieces {
+        match rows.last_mut() {
+            Some(row) if (row[0].y - piece.y).abs() < 0.5 * row[0].size.max(piece.size) => row.push(piece),
+            _ => rows.push(vec![piece]),
+        }
+    }
+    rows.into_iter().filter_map(join_row).collect()
+}
+
+// Join pieces of a line from left to right, with spaces where they're apart
+fn join_row(mut row: Vec<Line>) -> Option<Line> {
+    row.sort_by(|a, b| a.x0.total_cmp(&b.x0));
+    let mut chars: Vec<LayoutChar> = Vec::new();
+    for piece in row {
+        if let Some(last) = chars.last() {
+            if piece.x0 - last.end > 0.15 * last.size.max(piece.size) {
+                let space = LayoutChar { text: " ".to_owned(), x: last.end, end: piece.x0, ..last.clone() };
+                chars.push(space);
+            }
+        }
+        chars.extend(piece.chars);
+    }
+    Line::new(chars)
+}
+
+// Lines that are drawn in pieces out of order, e.g. when a word is drawn after the ones to the
+// right of it, are put back together
+fn merge_baselines(lines: Vec<Line>) -> Vec<Line> {
+    let mut merged: Vec<Line> = Vec::new();
+    for line in lines {
+        if let Some(prev) = merged.last() {
+            let same_baseline = (prev.y - line.y).abs() < 0.5 * prev.size.max(line.size);
+            if same_baseline && (line.x1 <= prev.x0 || line.x0 >= prev.x1) {
+                let prev = merged.pop().expect("the previous line");
+                merged.extend(join_row(vec![prev, line]));
+                continue;
+            }
+        }
+        merged.push(line);
+    }
+    merged
+}
+
+// Lines are aligned when their edges are within this many ems
+const ALIGNMENT_TOLERANCE: f64 = 0.5;
+
+fn spread(values: impl Iterator<Item = f64>) -> f64 {
+    let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v), max.max(v)));
+    max - min
+}
+
+fn alignment(lines: &[Line]) -> Alignment {
+    let n = lines.len();
+    if n < 2 {
+        return Alignment::Left;
+    }
+    let tolerance = ALIGNMENT_TOLERANCE * lines.iter().map(|line| line.size).fold(0., f64::max);
+    let aligned = |lines: &[Line], edge: fn(&Line) -> f64| spread(lines.iter().map(edge)) < tolerance;
+    // the first line can be indented and the last one can be short
+    let left = aligned(lines, |line| line.x0) || (n >= 3 && aligned(&lines[1..], |line| line.x0));
+    let right = aligned(lines, |line| line.x1);
+    let justified = right || (n >= 3 && aligned(&lines[..n - 1], |line| line.x1));
+    if left && justified {
+        Alignment::Justified
+    } else if left {
+        Alignment::Left
+    } else if right {
+        Alignment::Right
+    } else if aligned(lines, |line| (line.x0 + line.x1) / 2.) {
+        Alignment::Centered
+    } else {
+        Alignment::Left
+    }
+}
+
+// Group lines that continue each other into paragraphs, which also end where the spacing grows,
+// before an indented first line and after a short last line
+fn paragraphs(lines: Vec<Line>) -> Vec<Vec<Line>> {
+    let mut paragraphs: Vec<Vec<Line>> = Vec::new();
+    for line in lines {
+        if let Some(paragraph) = paragraphs.last_mut() {
+            let prev = &paragraph[paragraph.len() - 1];
+            let size = prev.size.max(line.size);
+            let pitch = if paragraph.len() >= 2 { paragraph[0].y - paragraph[1].y } else { f64::MAX };
+            let spaced = prev.y - line.y > 1.3 * pitch;
+            // the lines after the first set the left edge of paragraphs that are aligned on the left
+            let rest = &paragraph[1..];
+            let edges = (!rest.is_empty() && spread(rest.iter().map(|l| l.x0)) < ALIGNMENT_TOLERANCE * size).then(|| {
+                (rest.iter().map(|l| l.x0).fold(f64::MAX, f64::min), paragraph.iter().map(|l| l.x1).fold(f64::MIN, f64::max))
+            });
+            let indented = edges.is_some_and(|(left, _)| line.x0 > left + ALIGNMENT_TOLERANCE * size);
+            let after_short_line = edges.is_some_and(|(left, right)| {
+                prev.x1 < right - 3. * size && (line.x0 - left).abs() < ALIGNMENT_TOLERANCE * size
+            });
+            if continues(prev, &line) && !spaced && !indented && !after_short_line {
+                paragraph.push(line);
+                continue;
+            }
+        }
+        paragraphs.push(vec![line]);
+    }
+    paragraphs
+}
+
+/// How the lines of a `Paragraph` are aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    Left,
+    Right,
+    Centered,
+    /// Aligned on both sides, except for the last line
+    Justified,
+}
+
+/// A line of text found by `LineBuilder`, with spaces where there are gaps between the characters
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VisualLine {
+    pub text: String,
+    pub bbox: BoundingBox,
+    /// The y coordinate of the baseline
+    pub baseline: f64,
+    /// The size of the largest characters of the line
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Paragraph {
+    pub lines: Vec<VisualLine>,
+    pub bbox: BoundingBox,
+    /// How much further right the first line starts than the others, which is negative for
+    /// hanging indents. It's 0 for paragraphs that aren't aligned on the left.
+    pub indent: f64,
+    pub alignment: Alignment,
+}
+
+impl Paragraph {
+    fn new(lines: Vec<Line>) -> Paragraph {
+        let alignment = alignment(&lines);
+        let indent = match alignment {
+            Alignment::Left | Alignment::Justified if lines.len() >= 2 => {
+                lines[0].x0 - lines[1..].iter().map(|line| line.x0).fold(f64::MAX, f64::min)
+            }
+            _ => 0.,
+        };
+        let lines: Vec<VisualLine> = lines.iter().map(|line| {
+            let text_line = line.text_line();
+            VisualLine { text: line.text(), bbox: BoundingBox::new(text_line.bbox), baseline: line.y, size: line.size }
+        }).collect();
+        let bbox = lines.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, l| {
+            (b.0.min(l.bbox.llx), b.1.min(l.bbox.lly), b.2.max(l.bbox.urx), b.3.max(l.bbox.ury))
+        });
+        Paragraph { lines, bbox: BoundingBox::new(bbox), indent, alignment }
+    }
+}
+
+/// Where a character is drawn relative to the character before it, see `LineBuilder::line_break`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineBreak {
+    /// The character continues the line
+    None,
+    /// The character starts the next line, having moved back to the left and up or down
+    Line,
+    /// The character is further up or down than the lines of a paragraph are apart, or it's the
+    /// first character. `moved_left` is whether it moved back to the left as well.
+    Paragraph { moved_left: bool },
+}
+
+/// Groups the characters of the pages into lines and the lines into paragraphs for output
+/// devices that want the text as it's laid out instead of as it's drawn. Characters belong to
+/// the same line while their baselines are within half of their size, so superscripts and text
+/// with a rise stay in their line, and a line continues when parts of it are drawn out of order.
+/// Paragraphs end where the spacing between lines grows or the size or weight of the text
+/// changes, before an indented first line and after a short last line.
+///
+/// Call `begin_page` and `character` from the `OutputDev` methods of the same names, and
+/// `finish` from `end_page`.
+///
+/// Output devices that write the text as it's drawn can call `line_break` for each character
+/// instead, to find where the lines and paragraphs on the page break. This takes the place of
+/// `OutputDev::end_line`, which doesn't correspond to the lines on the page.
+pub struct LineBuilder {
+    layout: LayoutBuilder,
+    line_threshold: f64,
+    paragraph_threshold: f64,
+    // where the last character passed to `line_break` ended and its baseline
+    last: Option<(f64, f64)>,
+}
+
+impl Default for LineBuilder {
+    fn default() -> LineBuilder {
+        LineBuilder::with_thresholds(0.5, 1.5)
+    }
+}
+
+impl LineBuilder {
+    pub fn new() -> LineBuilder {
+        LineBuilder::default()
+    }
+
+    /// A `LineBuilder` whose `line_break` starts a line where the baseline moves up or down by
+    /// more than `line_threshold` and a paragraph where it moves by more than
+    /// `paragraph_threshold`, as fractions of the font size. They're 0.5 and 1.5 by default.
+    pub fn with_thresholds(line_threshold: f64, paragraph_threshold: f64) -> LineBuilder {
+        LineBuilder { layout: LayoutBuilder::default(), line_threshold, paragraph_threshold, last: None }
+    }
+
+    /// Where a character, with the arguments of `OutputDev::output_character`, breaks from the
+    /// character that was passed before it. A line breaks where the text moves back to the left
+    /// of the end of the last character and its baseline moves by more than the line threshold.
+    pub fn line_break(&mut self, trm: &Transform, width: f64, font_size: f64) -> LineBreak {
+        let transformed_font_size_vec = trm.transform_vector(euclid::vec2(font_size, font_size));
+        // get the length of one sized of the square with the same area with a rectangle of size (x, y)
+        let size = (transformed_font_size_vec.x * transformed_font_size_vec.y).sqrt();
+        let (x, y) = (trm.m31, trm.m32);
+        let last = self.last.replace((x + width * size, y));
+        let (last_end, last_y) = match last {
+            Some(last) => last,
+            None => return LineBreak::Paragraph { moved_left: true },
+        };
+        let moved_left = x < last_end;
+        if (y - last_y).abs() > size * self.paragraph_threshold {
+            LineBreak::Paragraph { moved_left }
+        } else if moved_left && (y - last_y).abs() > size * self.line_threshold {
+            LineBreak::Line
+        } else {
+            LineBreak::None
+        }
+    }
+
+    pub fn begin_page(&mut self, page_num: u32, media_box: &MediaBox) {
+        self.layout.begin_page(page_num, media_box);
+    }
+
+    /// A character, with the arguments of `OutputDev::output_character`
+    pub fn character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) {
+        self.layout.character(trm, width, spacing, font_size, char);
+    }
+
+    /// A gap in a `TJ` array, with the arguments of `OutputDev::text_gap`, which decides whether
+    /// the next character starts a new word
+    pub fn text_gap(&mut self, gap: f64, font_size: f64, space_width: Option<f64>) {
+        self.layout.gap(gap, font_size, space_width);
+    }
+
+    /// The paragraphs of the pages since the last call, in the order that they're drawn
+    pub fn finish(&mut self) -> Vec<Paragraph> {
+        self.layout.pages.drain(..).flat_map(|page| {
+            let lines = lines(page.items).into_iter().filter_map(|item| match item {
+                LineItem::Line(line) => Some(line),
+                LineItem::Figure(_) => None,
+            }).collect();
+            paragraphs(merge_baselines(lines)).into_iter().map(Paragraph::new)
+        }).collect()
+    }
+
+    /// The paragraphs of each page in reading order, see `LayoutBuilder::finish_reading_order`
+    pub(crate) fn finish_reading_order(&mut self) -> Vec<Vec<Vec<TextLine>>> {
+        self.layout.finish_reading_order()
+    }
+}
+
+// The marker at the start of a list item
+struct Marker {
+    ordered: bool,
+    number: u32,
+    // the number of characters of the marker and the following spaces
+    len: usize,
+}
+
+fn list_marker(text: &str) -> Option<Marker> {
+    const BULLETS: &[char] = &['•', '◦', '▪', '▫', '‣', '⁃', '∙', '·', '○', '●', '■', '□', '►', '➢', '✓', '-', '*', '–',
+                               '\u{f0b7}'];
+    let first = text.chars().next()?;
+    let (ordered, number, marker_len) = if BULLETS.contains(&first) {
+        (false, 1, 1)
+    } else {
+        let digits = text.chars().take_while(char::is_ascii_digit).count();
+        let rest = &text[digits..];
+        if digits == 0 || digits > 3 || !(rest.starts_with('.') || rest.starts_with(')')) {
+            return None;
+        }
+        (true, text[..digits].parse().ok()?, digits + 1)
+    };
+    let spaces = text.chars().skip(marker_len).take_while(|c| c.is_whitespace()).count();
+    // the marker is followed by a space, except for the bullets that don't start words
+    if spaces == 0 && (ordered || "-*–".contains(first)) {
+        return None;
+    }
+    if text.chars().count() <= marker_len + spaces {
+        return None;
+    }
+    Some(Marker { ordered, number, len: marker_len + spaces })
+}
+
+enum LineItem {
+    Line(Line),
+    Figure(Figure),
+}
+
+// Group the characters into lines in the order that they're drawn. Spaces are added where there
+// are gaps between characters.
+fn lines(items: Vec<PageItem>) -> Vec<LineItem> {
+    let mut result = Vec::new();
+    let mut line: Vec<LayoutChar> = Vec::new();
+    for item in items {
+        let c = match item {
+            PageItem::Figure(figure) => {
+                result.extend(Line::new(std::mem::take(&mut line)).map(LineItem::Line));
+                result.push(LineItem::Figure(figure));
+                continue;
+            }
+            PageItem::Char(c) => c,
+        };
+        if c.is_space() {
+            if line.last().is_some_and(|last| !last.is_space()) {
+                line.push(c);
+            }
+            continue;
+        }
+        if let Some(last) = line.iter().rev().find(|last| !last.is_space()) {
+            let size = last.size.max(c.size);
+            // bold is sometimes faked by drawing the text twice with a small offset
+            if last.text == c.text && (last.x - c.x).abs() < 0.1 * size && (last.y - c.y).abs() < 0.1 * size {
+                continue;
+            }
+            if (c.y - last.y).abs() > 0.5 * size || c.x < last.x - 0.5 * size {
+                result.extend(Line::new(std::mem::take(&mut line)).map(LineItem::Line));
+            } else if c.word_gap.unwrap_or(c.x - last.end > 0.15 * size) && line.last().is_some_and(|last| !last.is_space()) {
+                // gaps in TJ arrays decide whether there's a space, where there are any
+                let space = LayoutChar { text: " ".to_owned(), x: last.end, end: c.x.max(last.end), ..last.clone() };
+                line.push(space);
+            }
+        }
+        line.push(c);
+    }
+    result.extend(Line::new(line).map(LineItem::Line));
+    result
+}
+
+enum RawBlock {
+    Text { lines: Vec<Line>, marker: Option<Marker> },
+    Table(Vec<Line>),
+    Figure(Figure),
+}
+
+impl RawBlock {
+    fn lines(&self) -> &[Line] {
+        match self {
+            RawBlock::Text { lines, .. } | RawBlock::Table(lines) => lines,
+            RawBlock::Figure(_) => &[],
+        }
+    }
+
+    fn size(&self) -> f64 {
+        self.lines().iter().map(|line| line.size).fold(0., f64::max)
+    }
+}
+
+// Whether `line` continues the paragraph that ends with `prev`
+fn continues(prev: &Line, line: &Line) -> bool {
+    let size = prev.size.max(line.size);
+    let dy = prev.y - line.y;
+    (0.85..1.18).contains(&(line.size / prev.size)) && dy > 0. && dy < 1.8 * size &&
+        prev.is_bold() == line.is_bold() && line.x0 < prev.x1 + size && line.x1 > prev.x0 - size
+}
+
+// Group the lines into blocks of text, tables of at least two rows and figures
+fn raw_blocks(items: Vec<LineItem>) -> Vec<RawBlock> {
+    let mut blocks = Vec::new();
+    let mut items = items.into_iter().peekable();
+    while let Some(item) = items.next() {
+        let mut line = match item {
+            LineItem::Figure(figure) => {
+                blocks.push(RawBlock::Figure(figure));
+                continue;
+            }
+            LineItem::Line(line) => line,
+        };
+        let columns = line.cells().len();
+        if columns >= 2 {
+            let mut rows = vec![line];
+            loop {
+                let last = &rows[rows.len() - 1];
+                let row = items.next_if(|next| match next {
+                    LineItem::Line(next) => next.cells().len() == columns && last.y > next.y && last.y - next.y < 3. * last.size,
+                    LineItem::Figure(_) => false,
+                });
+                match row {
+                    Some(LineItem::Line(row)) => rows.push(row),
+                    _ => break,
+                }
+            }
+            if rows.len() >= 2 {
+                blocks.push(RawBlock::Table(rows));
+                continue;
+            }
+            line = rows.remove(0);
+        }
+        let marker = list_marker(&line.text());
+        if marker.is_none() {
+            if let Some(RawBlock::Text { lines, marker }) = blocks.last_mut() {
+                // the lines of list items are indented past the marker
+                let indented = marker.is_none() || line.x0 > lines[0].x0 + 0.5 * line.size;
+                if indented && lines.last().is_some_and(|prev| continues(prev, &line)) {
+                    lines.push(line);
+                    continue;
+                }
+            }
+        }
+        blocks.push(RawBlock::Text { lines: vec![line], marker });
+    }
+    blocks
+}
+
+// Sizes are compared in half points
+fn size_key(size: f64) -> i64 {
+    (size * 2.).round() as i64
+}
+
+// Some(true) for headings set in a larger size than the body text, Some(false) for short bold
+// lines at the body size and None for other blocks
+fn heading_kind(block: &RawBlock, body_size: f64) -> Option<bool> {
+    let (lines, marker) = match block {
+        RawBlock::Text { lines, marker } => (lines, marker),
+        _ => return None,
+    };
+    let len: usize = lines.iter().map(|line| line.chars.len()).sum();
+    if lines.len() > 3 || len > 200 {
+        return None;
+    }
+    if block.size() >= 1.15 * body_size {
+        return Some(true);
+    }
+    let sentence = lines.last().is_some_and(|line| line.text().ends_with('.'));
+    let bold = lines.len() == 1 && lines[0].is_bold() && len < 100 && block.size() >= 0.95 * body_size;
+    if bold && marker.is_none() && !sentence {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn continues_list(block: &RawBlock, ordered: bool, body_size: f64) -> bool {
+    matches!(block, RawBlock::Text { marker: Some(marker), .. } if marker.ordered == ordered) && heading_kind(block, body_size).is_none()
+}
+
+fn is_caption(lines: &[Line]) -> bool {
+    let text = lines.first().map(Line::text).unwrap_or_default().to_lowercase();
+    text.starts_with("figure") || text.starts_with("fig.") || text.starts_with("fig ")
+}
+
+// Attach the links to the characters under them
+fn resolve_links(page: &mut Page) {
+    if page.links.is_empty() {
+        return;
+    }
+    for item in &mut page.items {
+        if let PageItem::Char(c) = item {
+            let (x, y) = ((c.x + c.end) / 2., c.y + 0.3 * c.size);
+            c.link = page.links.iter()
+                .find(|((x0, y0, x1, y1), _)| (*x0..=*x1).contains(&x) && (*y0..=*y1).contains(&y))
+                .map(|(_, uri)| uri.clone());
+        }
+    }
+}
+
+// The spans of lines that are joined with spaces, leaving out the first `skip` characters
+fn spans(lines: &[Line], skip: usize) -> Vec<Span> {
+    let mut chars: Vec<LayoutChar> = Vec::new();
+    for line in lines {
+        if let Some(last) = chars.last() {
+            chars.push(LayoutChar { text: " ".to_owned(), ..last.clone() });
+        }
+        chars.extend(line.chars.iter().cloned());
+    }
+    let mut skipped = 0;
+    let start = chars.iter().take_while(|c| {
+        skipped += c.text.chars().count();
+        skipped <= skip
+    }).count();
+    spans_of_chars(&chars[start..])
+}
+
+// Spaces are collapsed and trimmed, and attached to the span before them
+fn spans_of_chars(chars: &[LayoutChar]) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for c in chars {
+        if c.is_space() {
+            match spans.last_mut() {
+                Some(span) if !span.text.ends_with(' ') => span.text.push(' '),
+                _ => {}
+            }
+            continue;
+        }
+        match spans.last_mut() {
+            Some(span) if span.bold == c.bold && span.italic == c.italic && span.link == c.link => span.text += &c.text,
+            _ => spans.push(Span { text: c.text.clone(), bold: c.bold, italic: c.italic, link: c.link.clone() }),
+        }
+    }
+    if let Some(span) = spans.last_mut() {
+        span.text.truncate(span.text.trim_end().len());
+    }
+    spans
+}
+
+fn merge_spans(spans: Vec<Span>) -> Vec<Span> {
+    let mut merged: Vec<Span> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if last.bold == span.bold && last.italic == span.italic && last.link == span.link => last.text += &span.text,
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
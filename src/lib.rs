@@ -17,10 +17,13 @@ use unicode_normalization::UnicodeNormalization;
 use std::fmt;
 use std::str;
 use std::fs::File;
+use std::io::Write as _;
+use std::fmt::Write as _;
 use std::slice::Iter;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry;
 use std::rc::Rc;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::result::Result;
 use log::{warn, error, debug};
@@ -76,6 +79,26 @@ macro_rules! dlog {
     //($($t:tt)*) => { println!($($t)*) }
 }
 
+mod font_program;
+use font_program::FontProgram;
+pub use font_program::{EmbeddedFont, FontProgramKind};
+mod fingerprint;
+mod fingerprint_table;
+mod calculator;
+mod png;
+mod type1;
+mod raster;
+mod layout;
+use layout::{Block, LayoutBuilder, Region, Span};
+pub use layout::{Alignment, LineBreak, LineBuilder, Paragraph, VisualLine};
+mod model;
+pub use model::{BlockKind, BlockModel, BoundingBox, DocumentModel, FontInfo, GlyphModel, LineModel, PageModel, WordModel};
+pub use raster::{Pixmap, RasterOutput};
+mod shading;
+use shading::make_shading;
+pub use shading::{Shading, ShadingGeometry, ShadedPolygon};
+pub use fingerprint::{GlyphFingerprint, GlyphFingerprintDb};
+
 fn get_info(doc: &Document) -> Option<&Dictionary> {
     match doc.trailer.get(b"Info") {
         Ok(&Object::Reference(ref id)) => {
@@ -307,6 +330,9 @@ struct PdfSimpleFont<'a> {
     unicode_map: Option<HashMap<u32, String>>,
     widths: HashMap<CharCode, f64>, // should probably just use i32 here
     missing_width: f64,
+    glyph_names: HashMap<CharCode, String>,
+    overrides: HashMap<CharCode, String>,
+    program: Option<FontProgram>,
 }
 
 #[derive(Clone)]
@@ -315,22 +341,203 @@ struct PdfType3Font<'a> {
     doc: &'a Document,
     encoding: Option<Vec<u16>>,
     unicode_map: Option<HashMap<CharCode, String>>,
-    widths: HashMap<CharCode, f64>, // should probably just use i32 here
+    widths: HashMap<CharCode, f64>, // in glyph space
+    glyph_names: HashMap<CharCode, String>,
+    overrides: HashMap<CharCode, String>,
+    char_procs: HashMap<CharCode, &'a Stream>,
+    font_matrix: Transform,
+    resources: Option<&'a Dictionary>,
 }
 
 
-fn make_font<'a>(doc: &'a Document, font: &'a Dictionary) -> Rc<dyn PdfFont + 'a> {
+fn make_font<'a>(doc: &'a Document, font: &'a Dictionary, font_overrides: &FontOverrides) -> Rc<dyn PdfFont + 'a> {
     let subtype = get_name_string(doc, font, b"Subtype");
     dlog!("MakeFont({})", subtype);
     if subtype == "Type0" {
-        Rc::new(PdfCIDFont::new(doc, font))
+        Rc::new(PdfCIDFont::new(doc, font, font_overrides))
     } else if subtype == "Type3" {
-        Rc::new(PdfType3Font::new(doc, font))
+        Rc::new(PdfType3Font::new(doc, font, font_overrides))
     } else {
-        Rc::new(PdfSimpleFont::new(doc, font))
+        Rc::new(PdfSimpleFont::new(doc, font, font_overrides))
+    }
+}
+
+/// Selects a glyph of a font for `FontOverrides`
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlyphKey {
+    /// A character code, or a CID for composite fonts
+    CharCode(u32),
+    /// A glyph name from the font's encoding
+    GlyphName(String),
+    /// A glyph index in the embedded font program
+    GlyphId(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FontOverride {
+    base_font_pattern: String,
+    glyph: GlyphKey,
+    unicode: String,
+}
+
+/// Replacement Unicode mappings for fonts with broken or missing `ToUnicode` maps.
+///
+/// Fonts are matched by their base font name using a pattern where `*` matches any sequence
+/// of characters and `?` matches a single character. A pattern without a `+` also matches
+/// subset fonts (e.g. `ABCDEF+Foo`) by the name after the subset tag. Overrides are consulted
+/// before any of the mappings in the font and later overrides win over earlier ones.
+///
+/// Glyphs of embedded fonts that have no Unicode mapping at all can be looked up by the shape of
+/// their outline with `recover_unmapped_glyphs` or `set_glyph_fingerprints`. This parses the
+/// font programs of all the fonts, so it's off by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontOverrides {
+    overrides: Vec<FontOverride>,
+    fingerprints: Option<GlyphFingerprintDb>,
+    recover_glyphs: bool,
+}
+
+impl FontOverrides {
+    pub const fn new() -> FontOverrides {
+        FontOverrides { overrides: Vec::new(), fingerprints: None, recover_glyphs: false }
+    }
+
+    /// Look up unmapped glyphs in the fingerprints bundled with the crate, see `GlyphFingerprintDb`
+    pub fn recover_unmapped_glyphs(&mut self) -> &mut FontOverrides {
+        self.recover_glyphs = true;
+        self
+    }
+
+    /// Look up unmapped glyphs in `fingerprints` and then in the bundled ones
+    pub fn set_glyph_fingerprints(&mut self, fingerprints: GlyphFingerprintDb) -> &mut FontOverrides {
+        self.fingerprints = Some(fingerprints);
+        self.recover_glyphs = true;
+        self
+    }
+
+    pub fn add(&mut self, base_font_pattern: &str, glyph: GlyphKey, unicode: &str) -> &mut FontOverrides {
+        self.overrides.push(FontOverride { base_font_pattern: base_font_pattern.to_owned(), glyph, unicode: unicode.to_owned() });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty() && !self.recover_glyphs
+    }
+
+    // `glyph_table` is only computed if some override applies to the font
+    fn resolve<F: FnOnce() -> Vec<GlyphInfo>>(&self, base_font: &str, glyph_table: F) -> HashMap<CharCode, String> {
+        let matching: Vec<_> = self.overrides.iter().filter(|o| base_font_matches(&o.base_font_pattern, base_font)).collect();
+        let mut result = HashMap::new();
+        if matching.is_empty() {
+            return result;
+        }
+        let glyph_table = glyph_table();
+        for o in matching {
+            for glyph in &glyph_table {
+                let applies = match &o.glyph {
+                    GlyphKey::CharCode(code) => glyph.code == *code,
+                    GlyphKey::GlyphName(name) => glyph.glyph_name.as_ref() == Some(name),
+                    GlyphKey::GlyphId(gid) => glyph.glyph_id == Some(*gid),
+                };
+                if applies {
+                    result.insert(glyph.code, o.unicode.clone());
+                }
+            }
+            // codes that the font doesn't otherwise know about can still be overridden
+            if let GlyphKey::CharCode(code) = o.glyph {
+                result.insert(code, o.unicode.clone());
+            }
+        }
+        result
+    }
+
+    // Look up the outlines of the glyphs for `codes` in the fingerprint databases
+    fn recover_unmapped<F: Fn(CharCode) -> Option<Vec<PathOp>>>(&self, codes: impl Iterator<Item = CharCode>, glyph_outline: F) -> HashMap<CharCode, String> {
+        let mut result = HashMap::new();
+        for code in codes {
+            let fingerprint = match glyph_outline(code).and_then(|outline| fingerprint::fingerprint_outline(&outline)) {
+                Some(fingerprint) => fingerprint,
+                None => continue,
+            };
+            let unicode = match &self.fingerprints {
+                Some(db) => db.lookup(fingerprint),
+                None => fingerprint::lookup_bundled(fingerprint),
+            };
+            if let Some(unicode) = unicode {
+                dlog!("recovered {} -> {:?} from glyph outline", code, unicode);
+                result.insert(code, unicode.to_owned());
+            }
+        }
+        result
+    }
+}
+
+static NO_FONT_OVERRIDES: FontOverrides = FontOverrides::new();
+
+fn base_font_matches(pattern: &str, base_font: &str) -> bool {
+    fn glob(pattern: &[char], s: &[char]) -> bool {
+        match pattern.split_first() {
+            None => s.is_empty(),
+            Some(('*', rest)) => (0..=s.len()).any(|i| glob(rest, &s[i..])),
+            Some((&p, rest)) => s.split_first().is_some_and(|(&c, s)| (p == '?' || p == c) && glob(rest, s)),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = base_font.chars().collect();
+    if glob(&pattern, &name) {
+        return true;
+    }
+    match base_font.split_once('+') {
+        Some((_, unsubsetted)) if !pattern.contains(&'+') => {
+            glob(&pattern, &unsubsetted.chars().collect::<Vec<_>>())
+        }
+        _ => false
     }
 }
 
+/// A glyph of a font as listed by `font_glyph_tables`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphInfo {
+    /// The character code, or the CID for composite fonts
+    pub code: u32,
+    pub glyph_name: Option<String>,
+    /// The glyph index in the embedded font program
+    pub glyph_id: Option<u32>,
+    /// The text that extraction currently produces for this code
+    pub unicode: String,
+}
+
+/// The glyphs of one of the fonts used in a document
+#[derive(Debug, Clone)]
+pub struct FontGlyphTable {
+    pub base_font: String,
+    pub subtype: String,
+    pub glyphs: Vec<GlyphInfo>,
+}
+
+/// A summary of one of the fonts used in a document as produced by `list_fonts`
+#[derive(Debug, Clone)]
+pub struct FontReport {
+    /// The pages that use the font, directly or through form XObjects
+    pub pages: Vec<u32>,
+    pub subtype: String,
+    /// The subtype of the descendant font of Type0 fonts
+    pub cid_subtype: Option<String>,
+    pub base_font: String,
+    /// The tag of subset fonts, e.g. `ABCDEF` for `ABCDEF+Helvetica`
+    pub subset_tag: Option<String>,
+    /// The kind of embedded font program, if any
+    pub embedded: Option<FontProgramKind>,
+    /// The name of the encoding, `Custom` for encodings with Differences and the CMap name for
+    /// embedded CMaps
+    pub encoding: Option<String>,
+    pub has_to_unicode: bool,
+    /// The char codes shown during extraction that couldn't be mapped to Unicode
+    pub unmapped_codes: Vec<u32>,
+    /// The number of characters shown during extraction that couldn't be mapped to Unicode
+    pub unmapped_count: usize,
+}
+
 fn is_core_font(name: &str) -> bool {
     match name {
         "Courier-Bold" |
@@ -351,13 +558,23 @@ fn is_core_font(name: &str) -> bool {
     }
 }
 
-fn encoding_to_unicode_table(name: &[u8]) -> Vec<u16> {
-    let encoding = match &name[..] {
+fn encoding_by_name(name: &[u8]) -> [Option<&'static str>; 256] {
+    match &name[..] {
         b"MacRomanEncoding" => encodings::MAC_ROMAN_ENCODING,
         b"MacExpertEncoding" => encodings::MAC_EXPERT_ENCODING,
         b"WinAnsiEncoding" => encodings::WIN_ANSI_ENCODING,
         _ => panic!("unexpected encoding {:?}", pdf_to_utf8(name))
-    };
+    }
+}
+
+fn encoding_to_glyph_names(encoding: &[Option<&str>]) -> HashMap<CharCode, String> {
+    encoding.iter().enumerate()
+        .filter_map(|(code, name)| name.map(|name| (code as CharCode, name.to_owned())))
+        .collect()
+}
+
+fn encoding_to_unicode_table(name: &[u8]) -> Vec<u16> {
+    let encoding = encoding_by_name(name);
     let encoding_table = encoding.iter()
         .map(|x| if let &Some(x) = x { glyphnames::name_to_unicode(x).unwrap() } else { 0 })
         .collect();
@@ -371,7 +588,7 @@ fn encoding_to_unicode_table(name: &[u8]) -> Vec<u16> {
     described in Section 5.5.5, “Character Encoding.”
 */
 impl<'a> PdfSimpleFont<'a> {
-    fn new(doc: &'a Document, font: &'a Dictionary) -> PdfSimpleFont<'a> {
+    fn new(doc: &'a Document, font: &'a Dictionary, font_overrides: &FontOverrides) -> PdfSimpleFont<'a> {
         let base_name = get_name_string(doc, font, b"BaseFont");
         let subtype = get_name_string(doc, font, b"Subtype");
 
@@ -380,6 +597,8 @@ impl<'a> PdfSimpleFont<'a> {
         let descriptor: Option<&Dictionary> = get(doc, font, b"FontDescriptor");
         let mut type1_encoding = None;
         let mut unicode_map = None;
+        // the names of the glyphs selected by the font program's built-in encoding
+        let mut builtin_glyph_names = HashMap::new();
         if let Some(descriptor) = descriptor {
             dlog!("descriptor {:?}", descriptor);
             if subtype == "Type1" {
@@ -392,15 +611,6 @@ impl<'a> PdfSimpleFont<'a> {
                     }
                     _ => { dlog!("font file {:?}", file) }
                 }
-            } else if subtype == "TrueType" {
-                let file = maybe_get_obj(doc, descriptor, b"FontFile2");
-                match file {
-                    Some(&Object::Stream(ref s)) => {
-                        let _s = get_contents(s);
-                        //File::create(format!("/tmp/{}", base_name)).unwrap().write_all(&s);
-                    }
-                    _ => { dlog!("font file {:?}", file) }
-                }
             }
 
             let font_file3 = get::<Option<&Object>>(doc, descriptor, b"FontFile3");
@@ -411,10 +621,12 @@ impl<'a> PdfSimpleFont<'a> {
                     let s = get_contents(s);
                     if subtype == "Type1C" {
                         let table = cff_parser::Table::parse(&s).unwrap();
-                        //use std::io::Write;
-                        //File::create(format!("/tmp/{}", base_name)).unwrap().write_all(&s);
-                        
                         let encoding = table.encoding.get_code_to_sid_table(&table.charset);
+                        for (&code, &sid) in &encoding {
+                            if let Some(name) = cff_parser::string_by_id(&table, sid).filter(|name| *name != ".notdef") {
+                                builtin_glyph_names.insert(code as CharCode, name.to_string());
+                            }
+                        }
 
                         let mapping: HashMap<u32, String> = encoding.into_iter().filter_map(|(cid, sid)| {
                             let name = cff_parser::string_by_id(&table, sid).unwrap();
@@ -433,9 +645,6 @@ impl<'a> PdfSimpleFont<'a> {
                         }).collect();
                         unicode_map = Some(mapping);
                     }
-
-                    //
-                    //File::create(format!("/tmp/{}", base_name)).unwrap().write_all(&s);
                 }
                 None => {}
                 _ => { dlog!("unexpected") }
@@ -460,18 +669,28 @@ impl<'a> PdfSimpleFont<'a> {
         };
 
 
+        if let Some(ref type1_encoding) = type1_encoding {
+            for (&code, name) in type1_encoding {
+                builtin_glyph_names.insert(code as CharCode, pdf_to_utf8(name));
+            }
+        }
+
         let mut encoding_table = None;
+        let mut glyph_names = HashMap::new();
         match encoding {
             Some(&Object::Name(ref encoding_name)) => {
                 dlog!("encoding {:?}", pdf_to_utf8(encoding_name));
                 encoding_table = Some(encoding_to_unicode_table(encoding_name));
+                glyph_names = encoding_to_glyph_names(&encoding_by_name(encoding_name));
             }
             Some(&Object::Dictionary(ref encoding)) => {
                 //dlog!("Encoding {:?}", encoding);
                 let mut table = if let Some(base_encoding) = maybe_get_name(doc, encoding, b"BaseEncoding") {
                     dlog!("BaseEncoding {:?}", base_encoding);
+                    glyph_names = encoding_to_glyph_names(&encoding_by_name(base_encoding));
                     encoding_to_unicode_table(base_encoding)
                 } else {
+                    glyph_names = builtin_glyph_names.clone();
                     Vec::from(PDFDocEncoding)
                 };
                 let differences = maybe_get_array(doc, encoding, b"Differences");
@@ -484,6 +703,7 @@ impl<'a> PdfSimpleFont<'a> {
                             &Object::Integer(i) => { code = i; },
                             &Object::Name(ref n) => {
                                 let name = pdf_to_utf8(&n);
+                                glyph_names.insert(code as CharCode, name.clone());
                                 // XXX: names of Type1 fonts can map to arbitrary strings instead of real
                                 // unicode names, so we should probably handle this differently
                                 let unicode = glyphnames::name_to_unicode(&name);
@@ -558,6 +778,10 @@ impl<'a> PdfSimpleFont<'a> {
                     encoding_table = Some(encodings::WIN_ANSI_ENCODING.iter()
                         .map(|x| if let &Some(x) = x { glyphnames::name_to_unicode(x).unwrap() } else { 0 })
                         .collect());
+                    glyph_names = encoding_to_glyph_names(&encodings::WIN_ANSI_ENCODING);
+                }
+                if glyph_names.is_empty() {
+                    glyph_names = builtin_glyph_names;
                 }
             }
             _ => { panic!() }
@@ -638,6 +862,9 @@ impl<'a> PdfSimpleFont<'a> {
                         for w in font_metrics.2 {
                             width_map.insert(w.0 as CharCode, w.1 as f64);
                             // -1 is "not encoded"
+                            if w.0 != -1 {
+                                glyph_names.insert(w.0 as CharCode, w.2.to_owned());
+                            }
                         }
                         encoding_table = Some(encoding.to_vec());
                     }
@@ -655,7 +882,33 @@ impl<'a> PdfSimpleFont<'a> {
         }
 
         let missing_width = get::<Option<f64>>(doc, font, b"MissingWidth").unwrap_or(0.);
-        PdfSimpleFont {doc, font, widths: width_map, encoding: encoding_table, missing_width, unicode_map}
+        let program = maybe_get_obj(doc, font, b"FontDescriptor")
+            .and_then(|desc| desc.as_dict().ok())
+            .and_then(|desc| FontProgram::from_descriptor(doc, desc));
+        let mut simple_font = PdfSimpleFont {doc, font, widths: width_map, encoding: encoding_table, missing_width, unicode_map, glyph_names, overrides: HashMap::new(), program};
+        simple_font.overrides = font_overrides.resolve(&base_name, || simple_font.glyph_table());
+        if simple_font.program.is_some() && font_overrides.recover_glyphs {
+            let unmapped = (0..256).filter(|code| !simple_font.has_unicode_mapping(*code));
+            let recovered = font_overrides.recover_unmapped(unmapped, |code| simple_font.glyph_outline(code));
+            simple_font.overrides.extend(recovered);
+        }
+        simple_font
+    }
+
+    fn glyph_id(&self, code: CharCode) -> Option<u16> {
+        let code = u8::try_from(code).ok()?;
+        self.program.as_ref()?.simple_glyph_id(code, self.glyph_names.get(&(code as CharCode)).map(|name| name.as_str()))
+    }
+
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        (0..256).filter_map(|code| {
+            let glyph_name = self.glyph_names.get(&code).cloned();
+            let glyph_id = self.glyph_id(code).map(|gid| gid as u32);
+            if glyph_name.is_none() && glyph_id.is_none() && !self.widths.contains_key(&code) {
+                return None;
+            }
+            Some(GlyphInfo { code, glyph_name, glyph_id, unicode: self.decode_char(code) })
+        }).collect()
     }
 
     #[allow(dead_code)]
@@ -690,12 +943,13 @@ impl<'a> PdfSimpleFont<'a> {
 
 
 impl<'a> PdfType3Font<'a> {
-    fn new(doc: &'a Document, font: &'a Dictionary) -> PdfType3Font<'a> {
+    fn new(doc: &'a Document, font: &'a Dictionary, font_overrides: &FontOverrides) -> PdfType3Font<'a> {
 
         let unicode_map = get_unicode_map(doc, font);
         let encoding: Option<&Object> = get(doc, font, b"Encoding");
 
         let encoding_table;
+        let mut glyph_names = HashMap::new();
         match encoding {
             Some(&Object::Name(ref encoding_name)) => {
                 dlog!("encoding {:?}", pdf_to_utf8(encoding_name));
@@ -728,6 +982,7 @@ impl<'a> PdfType3Font<'a> {
                                 if let Some(ref unicode_map) = unicode_map {
                                     dlog!("{} {:?}", code, unicode_map.get(&(code as u32)));
                                 }
+                                glyph_names.insert(code as CharCode, name.clone());
                                 code += 1;
                             }
                             _ => { panic!("wrong type"); }
@@ -746,24 +1001,134 @@ impl<'a> PdfType3Font<'a> {
             _ => { panic!() }
         }
 
-        let first_char: i64 = get(doc, font, b"FirstChar");
-        let last_char: i64 = get(doc, font, b"LastChar");
-        let widths: Vec<f64> = get(doc, font, b"Widths");
+        // "The glyph descriptions in a Type 3 font are executed in glyph space, which is mapped
+        // to text space by FontMatrix"
+        let font_matrix = get::<Option<Vec<f64>>>(doc, font, b"FontMatrix")
+            .filter(|m| m.len() == 6)
+            .map(|m| Transform2D::row_major(m[0], m[1], m[2], m[3], m[4], m[5]))
+            .unwrap_or_else(|| Transform2D::create_scale(0.001, 0.001));
+        let resources = maybe_get::<&Dictionary>(doc, font, b"Resources");
+
+        let mut char_procs = HashMap::new();
+        if let Some(procs) = maybe_get::<&Dictionary>(doc, font, b"CharProcs") {
+            for (&code, name) in &glyph_names {
+                if let Some(stream) = maybe_get::<&Stream>(doc, procs, name.as_bytes()) {
+                    char_procs.insert(code, stream);
+                } else {
+                    dlog!("missing CharProc for {} {}", code, name);
+                }
+            }
+        }
 
         let mut width_map = HashMap::new();
+        // Widths are required but some producers leave them out or make them too short
+        if let (Some(first_char), Some(widths)) = (maybe_get::<i64>(doc, font, b"FirstChar"), maybe_get::<Vec<f64>>(doc, font, b"Widths")) {
+            dlog!("first_char {:?}, widths: {} {:?}", first_char, widths.len(), widths);
+            for (i, w) in widths.into_iter().enumerate() {
+                width_map.insert((first_char + i as i64) as CharCode, w);
+            }
+            if let Some(last_char) = maybe_get::<i64>(doc, font, b"LastChar") {
+                if first_char + width_map.len() as i64 - 1 != last_char {
+                    warn!("Type3 font has {} widths for FirstChar {} LastChar {}", width_map.len(), first_char, last_char);
+                }
+            }
+        } else {
+            warn!("Type3 font without Widths {:?}", font);
+        }
+        // fall back to the width given by the d0/d1 operator of the glyph description
+        for (code, stream) in &char_procs {
+            if let Entry::Vacant(v) = width_map.entry(*code) {
+                if let Some(w) = type3_glyph_width(doc, stream) {
+                    v.insert(w);
+                }
+            }
+        }
 
-        let mut i = 0;
-        dlog!("first_char {:?}, last_char: {:?}, widths: {} {:?}", first_char, last_char, widths.len(), widths);
+        let mut type3_font = PdfType3Font {doc, font, widths: width_map, encoding: encoding_table, unicode_map, glyph_names, overrides: HashMap::new(), char_procs, font_matrix, resources};
+        // Type3 fonts don't need a BaseFont so we match against the optional Name
+        let name = maybe_get_name_string(doc, font, b"Name").unwrap_or_default();
+        type3_font.overrides = font_overrides.resolve(&name, || type3_font.glyph_table());
+        type3_font
+    }
 
-        for w in widths {
-            width_map.insert((first_char + i) as CharCode, w);
-            i += 1;
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        let mut codes: Vec<_> = self.glyph_names.keys().chain(self.widths.keys()).copied().collect();
+        codes.sort();
+        codes.dedup();
+        codes.into_iter().map(|code| {
+            GlyphInfo { code, glyph_name: self.glyph_names.get(&code).cloned(), glyph_id: None, unicode: self.decode_char(code) }
+        }).collect()
+    }
+}
+
+impl<'a> PdfType3Font<'a> {
+    /// Run the glyph description for `char` with glyph space mapped through `trm`
+    #[allow(clippy::too_many_arguments)]
+    fn render_glyph(&self, p: &mut Processor<'a>, char: CharCode, trm: &Transform, gs: &GraphicsState<'a>, page_resources: &'a Dictionary, media_box: &MediaBox, output: &mut dyn OutputDev) -> Result<(), OutputError> {
+        let char_proc = match self.char_procs.get(&char) {
+            Some(char_proc) => *char_proc,
+            None => { dlog!("no CharProc for {}", char); return Ok(()) }
+        };
+        // a glyph description that shows itself, directly or through other glyphs, would never end
+        if p.type3_glyphs.iter().any(|running| std::ptr::eq(*running, char_proc)) {
+            warn!("CharProc for {} shows itself", char);
+            return Ok(());
+        }
+        // "If the Resources entry is absent, the resources of the page on which the font is used are used"
+        let resources = self.resources.unwrap_or(page_resources);
+        // The glyph description inherits the graphics state (e.g. the fill color for d1 glyphs) but
+        // has its own text state
+        let mut glyph_gs = GraphicsState { ctm: self.font_matrix.post_transform(trm), ..GraphicsState::new() };
+        glyph_gs.fill_colorspace = gs.fill_colorspace.clone();
+        glyph_gs.fill_color = gs.fill_color.clone();
+        glyph_gs.fill_pattern = gs.fill_pattern.clone();
+        glyph_gs.fill_pattern_base = gs.fill_pattern_base.clone();
+        glyph_gs.stroke_colorspace = gs.stroke_colorspace.clone();
+        glyph_gs.stroke_color = gs.stroke_color.clone();
+        glyph_gs.transparency = gs.transparency.clone();
+        p.type3_glyphs.push(char_proc);
+        let result = p.process_stream(self.doc, get_contents(char_proc), resources, media_box, output, glyph_gs);
+        p.type3_glyphs.pop();
+        result
+    }
+}
+
+// lopdf doesn't allow digits in operator names so `d0` and `d1` come through as a `d` operator
+// followed by an operation with an extra leading 0 or 1 operand. Unlike the dash operator, `d`
+// will have numbers instead of an array as its first operand. When nothing follows, the 0 or 1
+// is lost and the operators are told apart by their number of operands.
+fn fix_glyph_metrics_operators(operations: &mut [lopdf::content::Operation]) {
+    if let Some(d) = operations.last_mut().filter(|d| d.operator == "d" && d.operands.iter().all(|o| o.as_float().is_ok())) {
+        match d.operands.len() {
+            2 => d.operator = "d0".to_owned(),
+            6 => d.operator = "d1".to_owned(),
+            _ => {}
+        }
+    }
+    for i in 1..operations.len() {
+        let (prev, rest) = operations.split_at_mut(i);
+        let (d, next) = (&mut prev[i - 1], &mut rest[0]);
+        if d.operator != "d" || matches!(d.operands.first(), Some(Object::Array(_)) | None) {
+            continue;
         }
-        assert_eq!(first_char + i - 1, last_char);
-        PdfType3Font {doc, font, widths: width_map, encoding: encoding_table, unicode_map}
+        match next.operands.first() {
+            Some(Object::Integer(0)) => d.operator = "d0".to_owned(),
+            Some(Object::Integer(1)) => d.operator = "d1".to_owned(),
+            _ => continue,
+        }
+        next.operands.remove(0);
     }
 }
 
+// The first operator of a glyph description must be d0 or d1 and its first operand is the
+// horizontal displacement in glyph space
+fn type3_glyph_width(doc: &Document, stream: &Stream) -> Option<f64> {
+    let mut content = Content::decode(&get_contents(stream)).ok()?;
+    fix_glyph_metrics_operators(&mut content.operations);
+    let op = content.operations.iter().find(|op| op.operator == "d0" || op.operator == "d1")?;
+    op.operands.first().and_then(|o| f64::from_obj(doc, o))
+}
+
 type CharCode = u32;
 
 struct PdfFontIter<'a>
@@ -783,6 +1148,16 @@ trait PdfFont : Debug {
     fn get_width(&self, id: CharCode) -> f64;
     fn next_char(&self, iter: &mut Iter<u8>) -> Option<(CharCode, u8)>;
     fn decode_char(&self, char: CharCode) -> String;
+    fn dict(&self) -> &Dictionary;
+    fn glyph_table(&self) -> Vec<GlyphInfo>;
+    /// Whether we know what `char` is, through ToUnicode, a known glyph name or an override
+    fn has_unicode_mapping(&self, char: CharCode) -> bool;
+    /// The outline of the glyph for `char` from the embedded font program in glyph space
+    fn glyph_outline(&self, _char: CharCode) -> Option<Vec<PathOp>> { None }
+    /// The embedded font program
+    fn program(&self) -> Option<&FontProgram> { None }
+    /// The font descriptor of the font, or of the descendant font of Type0 fonts
+    fn descriptor(&self) -> Option<&Dictionary> { None }
 
         /*fn char_codes<'a>(&'a self, chars: &'a [u8]) -> PdfFontIter {
             let p = self;
@@ -804,6 +1179,30 @@ impl<'a> dyn PdfFont + 'a {
 
 
 impl<'a> PdfFont for PdfSimpleFont<'a> {
+    fn dict(&self) -> &Dictionary {
+        self.font
+    }
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        PdfSimpleFont::glyph_table(self)
+    }
+    fn has_unicode_mapping(&self, char: CharCode) -> bool {
+        self.overrides.contains_key(&char) ||
+            self.unicode_map.as_ref().is_some_and(|m| m.contains_key(&char)) ||
+            self.glyph_names.get(&char).is_some_and(|name| glyphnames::name_to_unicode(name).is_some())
+    }
+    fn program(&self) -> Option<&FontProgram> {
+        self.program.as_ref()
+    }
+    fn descriptor(&self) -> Option<&Dictionary> {
+        maybe_get(self.doc, self.font, b"FontDescriptor")
+    }
+    fn glyph_outline(&self, char: CharCode) -> Option<Vec<PathOp>> {
+        let program = self.program.as_ref()?;
+        if program.kind == FontProgramKind::Type1 {
+            return program.named_glyph_outline(self.glyph_names.get(&char)?);
+        }
+        program.glyph_outline(self.glyph_id(char)?)
+    }
     fn get_width(&self, id: CharCode) -> f64 {
         let width = self.widths.get(&id);
         if let Some(width) = width {
@@ -824,6 +1223,9 @@ impl<'a> PdfFont for PdfSimpleFont<'a> {
         iter.next().map(|x| (*x as CharCode, 1))
     }
     fn decode_char(&self, char: CharCode) -> String {
+        if let Some(s) = self.overrides.get(&char) {
+            return s.clone();
+        }
         let slice = [char as u8];
         if let Some(ref unicode_map) = self.unicode_map {
             let s = unicode_map.get(&char);
@@ -832,7 +1234,7 @@ impl<'a> PdfFont for PdfSimpleFont<'a> {
                     debug!("missing char {:?} in unicode map {:?} for {:?}", char, unicode_map, self.font);
                     // some pdf's like http://arxiv.org/pdf/2312.00064v1 are missing entries in their unicode map but do have
                     // entries in the encoding.
-                    let encoding = self.encoding.as_ref().map(|x| &x[..]).expect("missing unicode map and encoding");
+                    let encoding = self.encoding.as_ref().map(|x| &x[..]).unwrap_or(PDFDocEncoding);
                     let s = to_utf8(encoding, &slice);
                     debug!("falling back to encoding {} -> {:?}", char, s);
                     s
@@ -857,12 +1259,25 @@ impl<'a> fmt::Debug for PdfSimpleFont<'a> {
 }
 
 impl<'a> PdfFont for PdfType3Font<'a> {
+    fn dict(&self) -> &Dictionary {
+        self.font
+    }
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        PdfType3Font::glyph_table(self)
+    }
+    fn has_unicode_mapping(&self, char: CharCode) -> bool {
+        self.overrides.contains_key(&char) ||
+            self.unicode_map.as_ref().is_some_and(|m| m.contains_key(&char)) ||
+            self.glyph_names.get(&char).is_some_and(|name| glyphnames::name_to_unicode(name).is_some())
+    }
     fn get_width(&self, id: CharCode) -> f64 {
         let width = self.widths.get(&id);
         if let Some(width) = width {
-            return *width;
+            // widths are in glyph space but callers expect thousandths of text space
+            self.font_matrix.transform_vector(vec2(*width, 0.)).x * 1000.
         } else {
-            panic!("missing width for {} {:?}", id, self.font);
+            dlog!("missing width for {} {:?}", id, self.font);
+            0.
         }
     }
     /*fn decode(&self, chars: &[u8]) -> String {
@@ -874,6 +1289,9 @@ impl<'a> PdfFont for PdfType3Font<'a> {
         iter.next().map(|x| (*x as CharCode, 1))
     }
     fn decode_char(&self, char: CharCode) -> String {
+        if let Some(s) = self.overrides.get(&char) {
+            return s.clone();
+        }
         let slice = [char as u8];
         if let Some(ref unicode_map) = self.unicode_map {
             let s = unicode_map.get(&char);
@@ -882,7 +1300,7 @@ impl<'a> PdfFont for PdfType3Font<'a> {
                     debug!("missing char {:?} in unicode map {:?} for {:?}", char, unicode_map, self.font);
                     // some pdf's like http://arxiv.org/pdf/2312.00577v1 are missing entries in their unicode map but do have
                     // entries in the encoding.
-                    let encoding = self.encoding.as_ref().map(|x| &x[..]).expect("missing unicode map and encoding");
+                    let encoding = self.encoding.as_ref().map(|x| &x[..]).unwrap_or(PDFDocEncoding);
                     let s = to_utf8(encoding, &slice);
                     debug!("falling back to encoding {} -> {:?}", char, s);
                     s
@@ -908,13 +1326,38 @@ impl<'a> fmt::Debug for PdfType3Font<'a> {
 
 struct PdfCIDFont<'a> {
     font: &'a Dictionary,
-    #[allow(dead_code)]
     doc: &'a Document,
     #[allow(dead_code)]
     encoding: ByteMapping,
     to_unicode: Option<HashMap<u32, String>>,
     widths: HashMap<CharCode, f64>, // should probably just use i32 here
     default_width: Option<f64>, // only used for CID fonts and we should probably brake out the different font types
+    cid_to_gid: CIDToGIDMap,
+    overrides: HashMap<CharCode, String>,
+    program: Option<FontProgram>,
+}
+
+enum CIDToGIDMap {
+    Identity,
+    // big-endian glyph ids indexed by CID
+    Stream(Vec<u8>),
+    // CIDFontType0 fonts select glyphs through the charset of the CFF program
+    Charset(HashMap<u32, u16>),
+    None,
+}
+
+impl CIDToGIDMap {
+    fn glyph_id(&self, cid: CharCode) -> Option<u32> {
+        match self {
+            CIDToGIDMap::Identity => Some(cid),
+            CIDToGIDMap::Stream(map) => {
+                let i = cid as usize * 2;
+                map.get(i..i + 2).map(|gid| (gid[0] as u32) << 8 | gid[1] as u32)
+            }
+            CIDToGIDMap::Charset(map) => map.get(&cid).map(|gid| *gid as u32),
+            CIDToGIDMap::None => None,
+        }
+    }
 }
 
 fn get_unicode_map<'a>(doc: &'a Document, font: &'a Dictionary) -> Option<HashMap<u32, String>> {
@@ -969,7 +1412,7 @@ fn get_unicode_map<'a>(doc: &'a Document, font: &'a Dictionary) -> Option<HashMa
 
 
 impl<'a> PdfCIDFont<'a> {
-    fn new(doc: &'a Document, font: &'a Dictionary) -> PdfCIDFont<'a> {
+    fn new(doc: &'a Document, font: &'a Dictionary, font_overrides: &FontOverrides) -> PdfCIDFont<'a> {
         let base_name = get_name_string(doc, font, b"BaseFont");
         let descendants = maybe_get_array(doc, font, b"DescendantFonts").expect("Descendant fonts required");
         let ciddict = maybe_deref(doc, &descendants[0]).as_dict().expect("should be CID dict");
@@ -1033,11 +1476,68 @@ impl<'a> PdfCIDFont<'a> {
                 }
             }
         }
-        PdfCIDFont{doc, font, widths, to_unicode: unicode_map, encoding, default_width: Some(default_width as f64) }
+        let program = maybe_get_obj(doc, ciddict, b"FontDescriptor")
+            .and_then(|desc| desc.as_dict().ok())
+            .and_then(|desc| FontProgram::from_descriptor(doc, desc));
+        let cid_to_gid = if get_name_string(doc, ciddict, b"Subtype") != "CIDFontType2" {
+            match &program {
+                Some(program) => CIDToGIDMap::Charset(program.cid_glyph_ids()),
+                None => CIDToGIDMap::None,
+            }
+        } else {
+            match maybe_get_obj(doc, ciddict, b"CIDToGIDMap") {
+                Some(Object::Stream(stream)) => CIDToGIDMap::Stream(get_contents(stream)),
+                _ => CIDToGIDMap::Identity,
+            }
+        };
+        let mut cid_font = PdfCIDFont{doc, font, widths, to_unicode: unicode_map, encoding, default_width: Some(default_width as f64), cid_to_gid, overrides: HashMap::new(), program };
+        cid_font.overrides = font_overrides.resolve(&base_name, || cid_font.glyph_table());
+        if cid_font.program.is_some() && font_overrides.recover_glyphs {
+            let mut cids: Vec<_> = cid_font.widths.keys().copied().collect();
+            if let CIDToGIDMap::Charset(map) = &cid_font.cid_to_gid {
+                cids.extend(map.keys());
+            }
+            cids.sort();
+            cids.dedup();
+            let unmapped = cids.into_iter().filter(|cid| !cid_font.has_unicode_mapping(*cid));
+            let recovered = font_overrides.recover_unmapped(unmapped, |cid| cid_font.glyph_outline(cid));
+            cid_font.overrides.extend(recovered);
+        }
+        cid_font
+    }
+
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        let mut cids: Vec<_> = self.widths.keys().chain(self.to_unicode.iter().flat_map(|m| m.keys())).copied().collect();
+        cids.sort();
+        cids.dedup();
+        cids.into_iter().map(|code| {
+            GlyphInfo { code, glyph_name: None, glyph_id: self.cid_to_gid.glyph_id(code), unicode: self.decode_char(code) }
+        }).collect()
     }
 }
 
 impl<'a> PdfFont for PdfCIDFont<'a> {
+    fn dict(&self) -> &Dictionary {
+        self.font
+    }
+    fn glyph_table(&self) -> Vec<GlyphInfo> {
+        PdfCIDFont::glyph_table(self)
+    }
+    fn has_unicode_mapping(&self, char: CharCode) -> bool {
+        self.overrides.contains_key(&char) ||
+            self.to_unicode.as_ref().is_some_and(|m| m.contains_key(&char))
+    }
+    fn program(&self) -> Option<&FontProgram> {
+        self.program.as_ref()
+    }
+    fn descriptor(&self) -> Option<&Dictionary> {
+        let descendant = maybe_get_array(self.doc, self.font, b"DescendantFonts")?.first()?;
+        maybe_get(self.doc, maybe_deref(self.doc, descendant).as_dict().ok()?, b"FontDescriptor")
+    }
+    fn glyph_outline(&self, char: CharCode) -> Option<Vec<PathOp>> {
+        let gid = u16::try_from(self.cid_to_gid.glyph_id(char)?).ok()?;
+        self.program.as_ref()?.glyph_outline(gid)
+    }
     fn get_width(&self, id: CharCode) -> f64 {
         let width = self.widths.get(&id);
         if let Some(width) = width {
@@ -1079,6 +1579,9 @@ impl<'a> PdfFont for PdfCIDFont<'a> {
         None
     }
     fn decode_char(&self, char: CharCode) -> String {
+        if let Some(s) = self.overrides.get(&char) {
+            return s.clone();
+        }
         let s = self.to_unicode.as_ref().and_then(|x| x.get(&char));
         if let Some(s) = s {
             s.clone()
@@ -1117,6 +1620,7 @@ impl<'a> fmt::Debug for PdfFontDescriptor<'a> {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Type0Func {
     domain: Vec<f64>,
     range: Vec<f64>,
@@ -1127,9 +1631,8 @@ struct Type0Func {
     decode: Vec<f64>,
 }
 
-#[allow(dead_code)]
-fn interpolate(x: f64, x_min: f64, _x_max: f64, y_min: f64, y_max: f64) -> f64 {
-    let divisor = x - x_min;
+fn interpolate(x: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64) -> f64 {
+    let divisor = x_max - x_min;
     if divisor != 0. {
         y_min + (x - x_min) * ((y_max - y_min) / divisor)
     } else {
@@ -1139,30 +1642,131 @@ fn interpolate(x: f64, x_min: f64, _x_max: f64, y_min: f64, y_max: f64) -> f64 {
     }
 }
 
+// clip each value to the corresponding [min max] pair of `bounds`
+fn clip_to(values: &mut [f64], bounds: &[f64]) {
+    for (v, b) in values.iter_mut().zip(bounds.chunks_exact(2)) {
+        *v = v.max(b[0]).min(b[1]);
+    }
+}
+
 impl Type0Func {
-    #[allow(dead_code)]
-    fn eval(&self, _input: &[f64], _output: &mut [f64]) {
-        let _n_inputs = self.domain.len() / 2;
-        let _n_ouputs = self.range.len() / 2;
+    // the number of samples in a dimension, which is 1 where the size is missing
+    fn size(&self, i: usize) -> usize {
+        self.size.get(i).copied().unwrap_or(1).max(1) as usize
+    }
+
+    fn sample(&self, index: usize) -> f64 {
+        let bits = self.bits_per_sample as usize;
+        let start = index * bits;
+        let mut value: u64 = 0;
+        for bit in start..start + bits {
+            let byte = self.contents.get(bit / 8).copied().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u64;
+        }
+        value as f64
+    }
 
+    fn eval(&self, input: &[f64], output: &mut [f64]) {
+        let n_inputs = self.domain.len() / 2;
+        let n_outputs = self.range.len() / 2;
+        let max_sample = ((1u64 << self.bits_per_sample) - 1) as f64;
+        // the position of the input in the sample table and the offset between the
+        // neighbouring samples in each dimension
+        let mut low = Vec::with_capacity(n_inputs);
+        let mut fraction = Vec::with_capacity(n_inputs);
+        for i in 0..n_inputs {
+            let x = input.get(i).copied().unwrap_or(0.).max(self.domain[2 * i]).min(self.domain[2 * i + 1]);
+            let size = self.size(i);
+            let (e0, e1) = match self.encode.get(2 * i..2 * i + 2) {
+                Some(&[e0, e1]) => (e0, e1),
+                _ => (0., (size - 1) as f64),
+            };
+            let e = interpolate(x, self.domain[2 * i], self.domain[2 * i + 1], e0, e1)
+                .max(0.).min((size - 1) as f64);
+            let l = (e.floor() as usize).min(size.saturating_sub(2));
+            low.push(l);
+            fraction.push(if size > 1 { e - l as f64 } else { 0. });
+        }
+        for (j, out) in output.iter_mut().enumerate().take(n_outputs) {
+            // multilinear interpolation between the 2^m surrounding samples
+            let mut value = 0.;
+            for corner in 0..1usize << n_inputs {
+                let mut weight = 1.;
+                let mut index = 0;
+                let mut stride = 1;
+                for i in 0..n_inputs {
+                    let size = self.size(i);
+                    let high = corner >> i & 1 == 1;
+                    weight *= if high { fraction[i] } else { 1. - fraction[i] };
+                    index += (low[i] + (high && size > 1) as usize) * stride;
+                    stride *= size;
+                }
+                if weight != 0. {
+                    value += weight * self.sample(index * n_outputs + j);
+                }
+            }
+            let decode = self.decode.get(2 * j..2 * j + 2).unwrap_or(&self.range[2 * j..2 * j + 2]);
+            *out = interpolate(value, 0., max_sample, decode[0], decode[1])
+                .max(self.range[2 * j]).min(self.range[2 * j + 1]);
+        }
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Type2Func {
+    domain: Vec<f64>,
     c0: Option<Vec<f64>>,
     c1: Option<Vec<f64>>,
     n: f64,
 }
 
+impl Type2Func {
+    fn eval(&self, input: &[f64], output: &mut [f64]) {
+        let mut x = input.first().copied().unwrap_or(0.);
+        clip_to(std::slice::from_mut(&mut x), &self.domain);
+        let c0 = self.c0.as_deref().unwrap_or(&[0.]);
+        let c1 = self.c1.as_deref().unwrap_or(&[1.]);
+        for (out, (c0, c1)) in output.iter_mut().zip(c0.iter().zip(c1)) {
+            *out = c0 + x.powf(self.n) * (c1 - c0);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Type3Func {
+    domain: Vec<f64>,
+    functions: Vec<Function>,
+    bounds: Vec<f64>,
+    encode: Vec<f64>,
+}
+
+impl Type3Func {
+    fn eval(&self, input: &[f64], output: &mut [f64]) {
+        let mut x = input.first().copied().unwrap_or(0.);
+        clip_to(std::slice::from_mut(&mut x), &self.domain);
+        let k = self.bounds.iter().take_while(|b| x >= **b).count().min(self.functions.len().saturating_sub(1));
+        let low = if k == 0 { self.domain[0] } else { self.bounds[k - 1] };
+        let high = self.bounds.get(k).copied().unwrap_or(self.domain[1]);
+        let (e0, e1) = match self.encode.get(2 * k..2 * k + 2) {
+            Some(&[e0, e1]) => (e0, e1),
+            _ => (0., 1.),
+        };
+        let x = interpolate(x, low, high, e0, e1);
+        if let Some(f) = self.functions.get(k) {
+            f.eval(&[x], output);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum Function {
     Type0(Type0Func),
     Type2(Type2Func),
-    #[allow(dead_code)]
-    Type3,
-    #[allow(dead_code)]
-    Type4(Vec<u8>)
+    Type3(Type3Func),
+    Type4 { domain: Vec<f64>, range: Vec<f64>, program: calculator::Program },
 }
 
 impl Function {
@@ -1173,6 +1777,15 @@ impl Function {
             _ => panic!()
         };
         let function_type: i64 = get(doc, dict, b"FunctionType");
+        // missing keys don't stop the colors from being drawn: Domain defaults to [0 1] and the
+        // other arrays to empty ones, which leave the function without outputs
+        let domain = match maybe_get::<Vec<f64>>(doc, dict, b"Domain") {
+            Some(domain) if domain.len() >= 2 => domain,
+            domain => {
+                warn!("unexpected function domain {:?}", domain);
+                vec![0., 1.]
+            }
+        };
         let f = match function_type {
             0 => {
                 // Sampled function
@@ -1180,11 +1793,10 @@ impl Function {
                     &Object::Stream(ref stream) => stream,
                     _ => panic!()
                 };
-                let range: Vec<f64> = get(doc, dict, b"Range");
-                let domain: Vec<f64> = get(doc, dict, b"Domain");
+                let range: Vec<f64> = maybe_get(doc, dict, b"Range").unwrap_or_default();
                 let contents = get_contents(stream);
-                let size: Vec<i64> = get(doc, dict, b"Size");
-                let bits_per_sample = get(doc, dict, b"BitsPerSample");
+                let size: Vec<i64> = maybe_get(doc, dict, b"Size").unwrap_or_default();
+                let bits_per_sample = maybe_get(doc, dict, b"BitsPerSample").unwrap_or(8);
                 // We ignore 'Order' like pdfium, poppler and pdf.js
 
                 let encode = get::<Option<Vec<f64>>>(doc, dict, b"Encode");
@@ -1204,30 +1816,64 @@ impl Function {
                 // Exponential interpolation function
                 let c0 = get::<Option<Vec<f64>>>(doc, dict, b"C0");
                 let c1 = get::<Option<Vec<f64>>>(doc, dict, b"C1");
-                let n = get::<f64>(doc, dict, b"N");
-                Function::Type2(Type2Func { c0, c1, n})
+                let n = maybe_get::<f64>(doc, dict, b"N").unwrap_or(1.);
+                Function::Type2(Type2Func { domain, c0, c1, n})
             }
             3 => {
                 // Stitching function
-                Function::Type3
+                let functions = maybe_get_array(doc, dict, b"Functions")
+                    .map_or(Vec::new(), |functions| functions.iter().map(|f| Function::new(doc, maybe_deref(doc, f))).collect());
+                let bounds: Vec<f64> = maybe_get(doc, dict, b"Bounds").unwrap_or_default();
+                let encode: Vec<f64> = maybe_get(doc, dict, b"Encode").unwrap_or_default();
+                Function::Type3(Type3Func { domain, functions, bounds, encode })
             }
             4 => {
                 // PostScript calculator function
                 let contents = match obj {
-                    &Object::Stream(ref stream) => {
-                        let contents = get_contents(stream);
-                        warn!("unhandled type-4 function");
-                        warn!("Stream: {}", String::from_utf8(contents.clone()).unwrap());
-                        contents
-                    }
+                    &Object::Stream(ref stream) => get_contents(stream),
                     _ => { panic!("type 4 functions should be streams") }
                 };
-                Function::Type4(contents)
+                let range: Vec<f64> = maybe_get(doc, dict, b"Range").unwrap_or_default();
+                let program = calculator::Program::parse(&contents);
+                Function::Type4 { domain, range, program }
             }
             _ => { panic!("unhandled function type {}", function_type) }
         };
         f
     }
+
+    fn output_count(&self) -> usize {
+        match self {
+            Function::Type0(f) => f.range.len() / 2,
+            Function::Type2(f) => f.c0.as_ref().or(f.c1.as_ref()).map_or(1, |c| c.len()),
+            Function::Type3(f) => f.functions.first().map_or(0, |f| f.output_count()),
+            Function::Type4 { range, .. } => range.len() / 2,
+        }
+    }
+
+    /// Evaluate the function, writing as many outputs as fit in `output`
+    fn eval(&self, input: &[f64], output: &mut [f64]) {
+        match self {
+            Function::Type0(f) => f.eval(input, output),
+            Function::Type2(f) => f.eval(input, output),
+            Function::Type3(f) => f.eval(input, output),
+            Function::Type4 { domain, range, program } => {
+                let mut input = input.to_vec();
+                clip_to(&mut input, domain);
+                let result = program.run(&input);
+                for (out, r) in output.iter_mut().zip(&result) {
+                    *out = *r;
+                }
+                clip_to(output, range);
+            }
+        }
+    }
+
+    fn call(&self, input: &[f64]) -> Vec<f64> {
+        let mut output = vec![0.; self.output_count()];
+        self.eval(input, &mut output);
+        output
+    }
 }
 
 fn as_num(o: &Object) -> f64 {
@@ -1238,6 +1884,15 @@ fn as_num(o: &Object) -> f64 {
     }
 }
 
+// The number in an operand that may be missing or not be a number
+fn maybe_num(o: Option<&Object>) -> Option<f64> {
+    match o {
+        Some(&Object::Integer(i)) => Some(i as f64),
+        Some(&Object::Real(f)) => Some(f.into()),
+        _ => None,
+    }
+}
+
 #[derive(Clone)]
 struct TextState<'a>
 {
@@ -1248,9 +1903,38 @@ struct TextState<'a>
     horizontal_scaling: f64,
     leading: f64,
     rise: f64,
+    render_mode: TextRenderMode,
     tm: Transform,
 }
 
+/// The text rendering modes set by `Tr`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextRenderMode {
+    Fill,
+    Stroke,
+    FillStroke,
+    Invisible,
+    FillClip,
+    StrokeClip,
+    FillStrokeClip,
+    Clip,
+}
+
+impl TextRenderMode {
+    fn from_pdf(mode: f64) -> TextRenderMode {
+        match mode as i64 {
+            1 => TextRenderMode::Stroke,
+            2 => TextRenderMode::FillStroke,
+            3 => TextRenderMode::Invisible,
+            4 => TextRenderMode::FillClip,
+            5 => TextRenderMode::StrokeClip,
+            6 => TextRenderMode::FillStrokeClip,
+            7 => TextRenderMode::Clip,
+            _ => TextRenderMode::Fill,
+        }
+    }
+}
+
 // XXX: We'd ideally implement this without having to copy the uncompressed data
 fn get_contents(contents: &Stream) -> Vec<u8> {
     if contents.filters().is_ok() {
@@ -1265,27 +1949,149 @@ struct GraphicsState<'a>
 {
     ctm: Transform,
     ts: TextState<'a>,
-    smask: Option<Dictionary>,
+    transparency: Transparency,
+    // only affects the conversion of colors to device colors so we just keep track of it
+    #[allow(dead_code)]
+    rendering_intent: String,
     fill_colorspace: ColorSpace,
     fill_color: Vec<f64>,
+    fill_pattern: Option<Pattern<'a>>,
+    // the colorspace of the color components of uncolored tiling patterns
+    fill_pattern_base: Option<ColorSpace>,
     stroke_colorspace: ColorSpace,
     stroke_color: Vec<f64>,
-    line_width: f64,
+    stroke_pattern: Option<Pattern<'a>>,
+    stroke_style: StrokeStyle,
+}
+
+// the pattern that a Pattern color refers to
+#[derive(Clone)]
+enum Pattern<'a> {
+    // `matrix` maps pattern space to device space
+    Shading { shading: Rc<Shading>, matrix: Transform },
+    Tiling { stream: &'a Stream, matrix: Transform },
+}
+
+/// A tiling pattern as used by a fill. Tiles are `bbox` repeated every `x_step` and `y_step`
+/// in pattern space.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TilingPattern {
+    /// Identifies the pattern in `OutputDev::fill_path_with_pattern`. Patterns used with different
+    /// matrices or colors get different ids.
+    pub id: usize,
+    /// 1 for colored patterns, whose cells specify their own colors, and 2 for uncolored
+    /// patterns, whose cells are painted with the color given with the pattern
+    pub paint_type: i64,
+    pub tiling_type: i64,
+    pub bbox: [f64; 4],
+    pub x_step: f64,
+    pub y_step: f64,
+    /// Maps pattern space to device space
+    pub matrix: Transform,
+}
+
+// The pattern called `name` in the resources. Its matrix is relative to `base_ctm`, the CTM at
+// the start of the content stream that uses the pattern.
+fn make_pattern<'a>(doc: &'a Document, resources: &'a Dictionary, name: &[u8], base_ctm: &Transform) -> Option<Pattern<'a>> {
+    let patterns: &Dictionary = get(doc, resources, b"Pattern");
+    let dict = match maybe_get_obj(doc, patterns, name) {
+        Some(Object::Dictionary(dict)) => dict,
+        Some(Object::Stream(stream)) => &stream.dict,
+        _ => {
+            warn!("missing pattern {:?}", pdf_to_utf8(name));
+            return None;
+        }
+    };
+    let matrix = match maybe_get::<Vec<f64>>(doc, dict, b"Matrix").as_deref() {
+        Some([a, b, c, d, e, f]) => Transform2D::row_major(*a, *b, *c, *d, *e, *f),
+        _ => Transform2D::identity(),
+    };
+    let matrix = matrix.post_transform(base_ctm);
+    match get::<i64>(doc, dict, b"PatternType") {
+        1 => match maybe_get_obj(doc, patterns, name) {
+            Some(Object::Stream(stream)) => Some(Pattern::Tiling { stream, matrix }),
+            _ => {
+                warn!("tiling pattern {:?} should be a stream", pdf_to_utf8(name));
+                None
+            }
+        },
+        2 => {
+            let shading = make_shading(doc, get(doc, dict, b"Shading"), resources)?;
+            Some(Pattern::Shading { shading: Rc::new(shading), matrix })
+        }
+        pattern_type => {
+            dlog!("unhandled pattern type {} {:?}", pattern_type, dict);
+            None
+        }
+    }
+}
+
+impl<'a> GraphicsState<'a> {
+    fn new() -> GraphicsState<'a> {
+        GraphicsState {
+            ts: TextState {
+                font: None,
+                font_size: std::f64::NAN,
+                character_spacing: 0.,
+                word_spacing: 0.,
+                horizontal_scaling: 100. / 100.,
+                leading: 0.,
+                rise: 0.,
+                render_mode: TextRenderMode::Fill,
+                tm: Transform2D::identity(),
+            },
+            fill_color: vec![0.],
+            fill_colorspace: ColorSpace::DeviceGray,
+            fill_pattern: None,
+            fill_pattern_base: None,
+            stroke_color: vec![0.],
+            stroke_pattern: None,
+            stroke_colorspace: ColorSpace::DeviceGray,
+            stroke_style: StrokeStyle::new(),
+            ctm: Transform2D::identity(),
+            transparency: Transparency::new(),
+            rendering_intent: "RelativeColorimetric".to_owned(),
+        }
+    }
+}
+
+// The number of times each char code without a Unicode mapping was shown, per font dictionary
+type UnmappedCodes = HashMap<*const Dictionary, HashMap<CharCode, usize>>;
+
+// The width of the font's space character in ems, if code 32 is a space
+fn space_width(font: &dyn PdfFont) -> Option<f64> {
+    let width = font.get_width(32) / 1000.;
+    (width > 0. && font.decode_char(32) == " ").then_some(width)
+}
+
+// Whether a gap between glyphs is wide enough to be a space between words, in text space units
+pub(crate) fn is_word_gap(gap: f64, font_size: f64, space_width: Option<f64>) -> bool {
+    match space_width {
+        Some(space_width) => gap > 0.5 * space_width,
+        None => gap > 0.2 * font_size,
+    }
 }
 
-fn show_text(gs: &mut GraphicsState, s: &[u8],
-             _tlm: &Transform,
-             _flip_ctm: &Transform,
+fn show_text<'a>(p: &mut Processor<'a>, gs: &mut GraphicsState<'a>, s: &[u8],
+             resources: &'a Dictionary,
+             media_box: &MediaBox,
              output: &mut dyn OutputDev) -> Result<(), OutputError> {
-    let ts = &mut gs.ts;
-    let font = ts.font.as_ref().unwrap();
+    let font = gs.ts.font.clone().unwrap();
+    let type3 = p.type3_fonts.get(&(font.dict() as *const Dictionary)).cloned();
     //let encoding = font.encoding.as_ref().map(|x| &x[..]).unwrap_or(&PDFDocEncoding);
     dlog!("{:?}", font.decode(s));
     dlog!("{:?}", font.decode(s).as_bytes());
     dlog!("{:?}", s);
-    output.begin_word()?;
+    // the text that CharProcs show is drawn as part of their glyph, which is the character
+    // that's output, so only its glyphs are drawn
+    let text_output = p.type3_glyphs.is_empty();
+    if text_output {
+        output.begin_word()?;
+    }
 
     for (c, length) in font.char_codes(s) {
+        let ts = &gs.ts;
         // 5.3.3 Text Space Details
         let tsm = Transform2D::row_major(ts.horizontal_scaling,
                                                  0.,
@@ -1311,7 +2117,36 @@ fn show_text(gs: &mut GraphicsState, s: &[u8],
         let is_space = c == 32 && length == 1;
         if is_space { spacing += ts.word_spacing }
 
-        output.output_character(&trm, w0, spacing, ts.font_size, &font.decode_char(c))?;
+        if !font.has_unicode_mapping(c) {
+            *p.unmapped_codes.entry(font.dict()).or_default().entry(c).or_default() += 1;
+        }
+        let text = font.decode_char(c);
+        if let Some(type3) = &type3 {
+            let glyph_trm = Transform2D::create_scale(ts.font_size, ts.font_size).post_transform(&trm);
+            output.save_state()?;
+            type3.render_glyph(p, c, &glyph_trm, gs, resources, media_box, output)?;
+            output.restore_state()?;
+        } else {
+            output.draw_glyph(&TextGlyph {
+                trm,
+                font_size: ts.font_size,
+                code: c,
+                text: &text,
+                render_mode: ts.render_mode,
+                fill_colorspace: &gs.fill_colorspace,
+                fill_color: &gs.fill_color,
+                stroke_colorspace: &gs.stroke_colorspace,
+                stroke_color: &gs.stroke_color,
+                line_width: gs.stroke_style.line_width,
+                character_spacing: ts.character_spacing,
+                word_spacing: ts.word_spacing,
+                font: &*font,
+            })?;
+        }
+        if text_output {
+            output.output_character(&trm, w0, spacing, ts.font_size, &text)?;
+        }
+        let ts = &mut gs.ts;
         let tj = 0.;
         let ty = 0.;
         let tx = ts.horizontal_scaling * ((w0 - tj/1000.)* ts.font_size + spacing);
@@ -1322,11 +2157,14 @@ fn show_text(gs: &mut GraphicsState, s: &[u8],
         //dlog!("post pos: {:?}", trm);
 
     }
-    output.end_word()?;
+    if text_output {
+        output.end_word()?;
+    }
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MediaBox {
     pub llx: f64,
     pub lly: f64,
@@ -1334,24 +2172,185 @@ pub struct MediaBox {
     pub ury: f64
 }
 
-fn apply_state(doc: &Document, gs: &mut GraphicsState, state: &Dictionary) {
-    for (k, v) in state.iter() {
-        let k : &[u8] = k.as_ref();
-        match k {
-            b"SMask" => { match maybe_deref(doc, v)  {
-                &Object::Name(ref name) => {
-                    if name == b"None" {
-                        gs.smask = None;
-                    } else {
-                        panic!("unexpected smask name")
-                    }
-                }
-                &Object::Dictionary(ref dict) => {
-                    gs.smask = Some(dict.clone());
-                }
-                _ => { panic!("unexpected smask type {:?}", v) }
-            }}
-            b"Type" => { match v {
+/// The blend modes of 11.3.5 "Blend mode"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    fn from_name(name: &[u8]) -> Option<BlendMode> {
+        Some(match name {
+            b"Normal" | b"Compatible" => BlendMode::Normal,
+            b"Multiply" => BlendMode::Multiply,
+            b"Screen" => BlendMode::Screen,
+            b"Overlay" => BlendMode::Overlay,
+            b"Darken" => BlendMode::Darken,
+            b"Lighten" => BlendMode::Lighten,
+            b"ColorDodge" => BlendMode::ColorDodge,
+            b"ColorBurn" => BlendMode::ColorBurn,
+            b"HardLight" => BlendMode::HardLight,
+            b"SoftLight" => BlendMode::SoftLight,
+            b"Difference" => BlendMode::Difference,
+            b"Exclusion" => BlendMode::Exclusion,
+            b"Hue" => BlendMode::Hue,
+            b"Saturation" => BlendMode::Saturation,
+            b"Color" => BlendMode::Color,
+            b"Luminosity" => BlendMode::Luminosity,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftMaskType {
+    Alpha,
+    Luminosity,
+}
+
+/// A soft mask from the `SMask` entry of an ExtGState (11.6.5.2 "Soft-mask dictionaries")
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct SoftMask {
+    pub subtype: SoftMaskType,
+    /// The transparency group XObject that defines the mask
+    pub group: Option<ObjectId>,
+    /// The backdrop color for luminosity masks, in the colorspace of the group
+    pub backdrop: Option<Vec<f64>>,
+}
+
+/// The transparency parameters of the graphics state
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Transparency {
+    /// `ca`: the constant alpha for filling, including text
+    pub fill_alpha: f64,
+    /// `CA`: the constant alpha for stroking
+    pub stroke_alpha: f64,
+    pub blend_mode: BlendMode,
+    pub soft_mask: Option<SoftMask>,
+    pub alpha_is_shape: bool,
+    pub text_knockout: bool,
+}
+
+impl Transparency {
+    fn new() -> Transparency {
+        Transparency { fill_alpha: 1., stroke_alpha: 1., blend_mode: BlendMode::Normal, soft_mask: None, alpha_is_shape: false, text_knockout: true }
+    }
+}
+
+fn make_soft_mask(doc: &Document, dict: &Dictionary) -> Option<SoftMask> {
+    let subtype = match maybe_get_name(doc, dict, b"S") {
+        Some(b"Alpha") => SoftMaskType::Alpha,
+        Some(b"Luminosity") => SoftMaskType::Luminosity,
+        s => {
+            warn!("unexpected soft mask type {:?}", s.map(pdf_to_utf8));
+            return None;
+        }
+    };
+    let group = dict.get(b"G").ok().and_then(|g| g.as_reference().ok());
+    let backdrop = maybe_get::<Vec<f64>>(doc, dict, b"BC");
+    Some(SoftMask { subtype, group, backdrop })
+}
+
+fn apply_state<'a>(doc: &'a Document, gs: &mut GraphicsState<'a>, state: &'a Dictionary, p: &mut Processor<'a>) {
+    for (k, v) in state.iter() {
+        let k : &[u8] = k.as_ref();
+        let v = maybe_deref(doc, v);
+        match k {
+            b"LW" => match maybe_num(Some(v)) {
+                Some(width) => gs.stroke_style.line_width = width,
+                None => warn!("unexpected line width {:?}", v),
+            },
+            b"LC" => match maybe_num(Some(v)) {
+                Some(cap) => gs.stroke_style.line_cap = LineCap::from_pdf(cap),
+                None => warn!("unexpected line cap {:?}", v),
+            },
+            b"LJ" => match maybe_num(Some(v)) {
+                Some(join) => gs.stroke_style.line_join = LineJoin::from_pdf(join),
+                None => warn!("unexpected line join {:?}", v),
+            },
+            b"ML" => match maybe_num(Some(v)) {
+                Some(limit) => gs.stroke_style.miter_limit = limit,
+                None => warn!("unexpected miter limit {:?}", v),
+            },
+            b"D" => { match v.as_array().map(|d| d.as_slice()) {
+                // [dashArray dashPhase]
+                Ok([array, phase]) => match (maybe_deref(doc, array).as_array(), maybe_num(Some(maybe_deref(doc, phase)))) {
+                    (Ok(array), Some(phase)) => {
+                        gs.stroke_style.dash_array = array.iter().filter_map(|x| maybe_num(Some(maybe_deref(doc, x)))).collect();
+                        gs.stroke_style.dash_phase = phase;
+                    }
+                    _ => { warn!("unexpected dash pattern {:?}", v) }
+                },
+                _ => { warn!("unexpected dash pattern {:?}", v) }
+            }}
+            b"RI" => { match v.as_name() {
+                Ok(name) => gs.rendering_intent = pdf_to_utf8(name),
+                Err(_) => warn!("unexpected rendering intent {:?}", v),
+            }}
+            b"Font" => { match v.as_array().map(|f| f.as_slice()) {
+                // [font size]
+                Ok([font, size]) => match (maybe_deref(doc, font).as_dict(), maybe_num(Some(maybe_deref(doc, size)))) {
+                    (Ok(font), Some(size)) => {
+                        gs.ts.font = Some(p.font(doc, font));
+                        gs.ts.font_size = size;
+                    }
+                    _ => warn!("unexpected font entry {:?}", v),
+                },
+                _ => { warn!("unexpected font entry {:?}", v) }
+            }}
+            b"CA" => match maybe_num(Some(v)) {
+                Some(alpha) => gs.transparency.stroke_alpha = alpha,
+                None => warn!("unexpected stroke alpha {:?}", v),
+            },
+            b"ca" => match maybe_num(Some(v)) {
+                Some(alpha) => gs.transparency.fill_alpha = alpha,
+                None => warn!("unexpected fill alpha {:?}", v),
+            },
+            b"BM" => {
+                // an array gives a list of blend modes to choose from in order of preference
+                let blend_mode = match v {
+                    Object::Name(name) => BlendMode::from_name(name),
+                    Object::Array(names) => names.iter().find_map(|name| maybe_deref(doc, name).as_name().ok().and_then(BlendMode::from_name)),
+                    _ => None,
+                };
+                match blend_mode {
+                    Some(blend_mode) => gs.transparency.blend_mode = blend_mode,
+                    // "the blend mode shall be Normal if none of the listed modes are recognised"
+                    None => { warn!("unknown blend mode {:?}", v); gs.transparency.blend_mode = BlendMode::Normal }
+                }
+            }
+            b"SMask" => { match v {
+                Object::Name(name) => {
+                    if name != b"None" {
+                        warn!("unexpected smask name {:?}", pdf_to_utf8(name));
+                    }
+                    gs.transparency.soft_mask = None;
+                }
+                Object::Dictionary(dict) => {
+                    gs.transparency.soft_mask = make_soft_mask(doc, dict);
+                }
+                _ => { warn!("unexpected smask type {:?}", v) }
+            }}
+            b"AIS" => { gs.transparency.alpha_is_shape = v.as_bool().unwrap_or(false); }
+            b"TK" => { gs.transparency.text_knockout = v.as_bool().unwrap_or(true); }
+            b"Type" => { match v {
                 &Object::Name(ref name) => {
                     assert_eq!(name, b"ExtGState")
                 }
@@ -1364,6 +2363,7 @@ fn apply_state(doc: &Document, gs: &mut GraphicsState, state: &Dictionary) {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PathOp {
     MoveTo(f64, f64),
     LineTo(f64, f64),
@@ -1374,10 +2374,78 @@ pub enum PathOp {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Path {
     pub ops: Vec<PathOp>
 }
 
+/// How the inside of a path is determined when filling (8.5.3.3 "Filling")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    ProjectingSquare,
+}
+
+impl LineCap {
+    fn from_pdf(style: f64) -> LineCap {
+        match style as i64 {
+            1 => LineCap::Round,
+            2 => LineCap::ProjectingSquare,
+            _ => LineCap::Butt,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl LineJoin {
+    fn from_pdf(style: f64) -> LineJoin {
+        match style as i64 {
+            1 => LineJoin::Round,
+            2 => LineJoin::Bevel,
+            _ => LineJoin::Miter,
+        }
+    }
+}
+
+/// The parameters of the graphics state that apply to stroking, in user space units
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct StrokeStyle {
+    pub line_width: f64,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub miter_limit: f64,
+    /// The lengths of alternating dashes and gaps, empty for solid lines
+    pub dash_array: Vec<f64>,
+    pub dash_phase: f64,
+}
+
+impl StrokeStyle {
+    fn new() -> StrokeStyle {
+        StrokeStyle {
+            line_width: 1.,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.,
+            dash_array: Vec::new(),
+            dash_phase: 0.,
+        }
+    }
+}
+
 impl Path {
     fn new() -> Path {
         Path { ops: Vec::new() }
@@ -1393,6 +2461,7 @@ impl Path {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CalGray {
     white_point: [f64; 3],
     black_point: Option<[f64; 3]>,
@@ -1400,6 +2469,7 @@ pub struct CalGray {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CalRGB {
     white_point: [f64; 3],
     black_point: Option<[f64; 3]>,
@@ -1408,6 +2478,7 @@ pub struct CalRGB {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lab {
     white_point: [f64; 3],
     black_point: Option<[f64; 3]>,
@@ -1415,6 +2486,7 @@ pub struct Lab {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AlternateColorSpace {
     DeviceGray,
     DeviceRGB,
@@ -1426,6 +2498,7 @@ pub enum AlternateColorSpace {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Separation {
     name: String,
     alternate_space: AlternateColorSpace,
@@ -1433,6 +2506,15 @@ pub struct Separation {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Indexed {
+    base: Box<ColorSpace>,
+    hival: i64,
+    lookup: Vec<u8>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorSpace {
     DeviceGray,
     DeviceRGB,
@@ -1443,9 +2525,103 @@ pub enum ColorSpace {
     CalGray(CalGray),
     Lab(Lab),
     Separation(Separation),
+    Indexed(Indexed),
     ICCBased(Vec<u8>)
 }
 
+// the number of components of the color space in the header of an ICC profile
+fn icc_components(profile: &[u8]) -> usize {
+    match profile.get(16..20) {
+        Some(b"GRAY") => 1,
+        Some(b"CMYK") => 4,
+        _ => 3,
+    }
+}
+
+fn cmyk_to_rgb(c: f64, m: f64, y: f64, k: f64) -> [f64; 3] {
+    [(1. - c) * (1. - k), (1. - m) * (1. - k), (1. - y) * (1. - k)]
+}
+
+fn lab_to_rgb(lab: &Lab, color: &[f64]) -> [f64; 3] {
+    // CIE L*a*b* to XYZ relative to the white point and then to sRGB, ignoring chromatic adaptation
+    let l = color.first().copied().unwrap_or(0.);
+    let (a_min, a_max, b_min, b_max) = match lab.range { Some(r) => (r[0], r[1], r[2], r[3]), None => (-100., 100., -100., 100.) };
+    let a = color.get(1).copied().unwrap_or(0.).max(a_min).min(a_max);
+    let b = color.get(2).copied().unwrap_or(0.).max(b_min).min(b_max);
+    let g = |x: f64| if x >= 6. / 29. { x * x * x } else { 108. / 841. * (x - 4. / 29.) };
+    let m = (l + 16.) / 116.;
+    let x = lab.white_point[0] * g(m + a / 500.);
+    let y = lab.white_point[1] * g(m);
+    let z = lab.white_point[2] * g(m - b / 200.);
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    let gamma = |c: f64| if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1. / 2.4) - 0.055 };
+    [gamma(r), gamma(g), gamma(b)]
+}
+
+impl AlternateColorSpace {
+    fn to_rgb(&self, color: &[f64]) -> Option<[f64; 3]> {
+        match self {
+            AlternateColorSpace::DeviceGray | AlternateColorSpace::CalGray(_) => ColorSpace::DeviceGray.to_rgb(color),
+            AlternateColorSpace::DeviceRGB | AlternateColorSpace::CalRGB(_) => ColorSpace::DeviceRGB.to_rgb(color),
+            AlternateColorSpace::DeviceCMYK => ColorSpace::DeviceCMYK.to_rgb(color),
+            AlternateColorSpace::Lab(lab) => Some(lab_to_rgb(lab, color)),
+            AlternateColorSpace::ICCBased(profile) => ColorSpace::ICCBased(profile.clone()).to_rgb(color),
+        }
+    }
+}
+
+impl ColorSpace {
+    /// The number of color components, which is 1 for `Pattern` and unknown for `DeviceN`
+    pub fn components(&self) -> Option<usize> {
+        Some(match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) | ColorSpace::Separation(_) | ColorSpace::Indexed(_) | ColorSpace::Pattern => 1,
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) | ColorSpace::Lab(_) => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::ICCBased(profile) => icc_components(profile),
+            ColorSpace::DeviceN => return None,
+        })
+    }
+
+    /// An approximate sRGB equivalent of `color` with components in [0, 1]. Calibrated and ICC
+    /// based colors are treated as the corresponding device colors and CMYK is converted naively.
+    /// Returns `None` for colors that can't be converted, like patterns and DeviceN colors.
+    pub fn to_rgb(&self, color: &[f64]) -> Option<[f64; 3]> {
+        let c = |i: usize| color.get(i).copied().unwrap_or(0.).clamp(0., 1.);
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => Some([c(0), c(0), c(0)]),
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => Some([c(0), c(1), c(2)]),
+            ColorSpace::DeviceCMYK => Some(cmyk_to_rgb(c(0), c(1), c(2), c(3))),
+            ColorSpace::Lab(lab) => Some(lab_to_rgb(lab, color)),
+            ColorSpace::ICCBased(profile) => match icc_components(profile) {
+                1 => ColorSpace::DeviceGray.to_rgb(color),
+                4 => ColorSpace::DeviceCMYK.to_rgb(color),
+                _ => ColorSpace::DeviceRGB.to_rgb(color),
+            },
+            ColorSpace::Separation(separation) => {
+                let alternate = separation.tint_transform.call(&[c(0)]);
+                separation.alternate_space.to_rgb(&alternate)
+            }
+            ColorSpace::Indexed(indexed) => {
+                let n = indexed.base.components()?;
+                let index = color.first().copied().unwrap_or(0.).max(0.).min(indexed.hival as f64) as usize;
+                let entry = indexed.lookup.get(index * n..(index + 1) * n)?;
+                let base_color: Vec<f64> = entry.iter().map(|x| *x as f64 / 255.).collect();
+                match *indexed.base {
+                    // the lookup table holds the actual values of Lab colors
+                    ColorSpace::Lab(ref lab) => {
+                        let (a_min, a_max, b_min, b_max) = match lab.range { Some(r) => (r[0], r[1], r[2], r[3]), None => (-100., 100., -100., 100.) };
+                        Some(lab_to_rgb(lab, &[base_color[0] * 100., a_min + base_color[1] * (a_max - a_min), b_min + base_color[2] * (b_max - b_min)]))
+                    }
+                    ref base => base.to_rgb(&base_color),
+                }
+            }
+            ColorSpace::DeviceN | ColorSpace::Pattern => None,
+        }
+    }
+}
+
 fn make_colorspace<'a>(doc: &'a Document, name: &[u8], resources: &'a Dictionary) -> ColorSpace {
     match name {
         b"DeviceGray" => ColorSpace::DeviceGray,
@@ -1455,160 +2631,435 @@ fn make_colorspace<'a>(doc: &'a Document, name: &[u8], resources: &'a Dictionary
         _ => {
             let colorspaces: &Dictionary = get(&doc, resources, b"ColorSpace");
             let cs: &Object = maybe_get_obj(doc, colorspaces, &name[..]).unwrap_or_else(|| panic!("missing colorspace {:?}", &name[..]));
-            if let Ok(cs) = cs.as_array() {
-                let cs_name = pdf_to_utf8(cs[0].as_name().expect("first arg must be a name"));
-                match cs_name.as_ref() {
-                    "Separation" => {
-                        let name = pdf_to_utf8(cs[1].as_name().expect("second arg must be a name"));
-                        let alternate_space = match &maybe_deref(doc, &cs[2]) {
-                            Object::Name(name) => {
-                                match &name[..] {
-                                    b"DeviceGray" => AlternateColorSpace::DeviceGray,
-                                    b"DeviceRGB" => AlternateColorSpace::DeviceRGB,
-                                    b"DeviceCMYK" => AlternateColorSpace::DeviceCMYK,
-                                    _ => panic!("unexpected color space name")
-                                }
-                            }
-                            Object::Array(cs) => {
-                                let cs_name = pdf_to_utf8(cs[0].as_name().expect("first arg must be a name"));
-                                match cs_name.as_ref() {
-                                    "ICCBased" => {
-                                        let stream = maybe_deref(doc, &cs[1]).as_stream().unwrap();
-                                        dlog!("ICCBased {:?}", stream);
-                                        // XXX: we're going to be continually decompressing everytime this object is referenced
-                                        AlternateColorSpace::ICCBased(get_contents(stream))
-                                    }
-                                    "CalGray" => {
-                                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                                        AlternateColorSpace::CalGray(CalGray {
-                                            white_point: get(&doc, dict, b"WhitePoint"),
-                                            black_point: get(&doc, dict, b"BackPoint"),
-                                            gamma: get(&doc, dict, b"Gamma"),
-                                        })
-                                    }
-                                    "CalRGB" => {
-                                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                                        AlternateColorSpace::CalRGB(CalRGB {
-                                            white_point: get(&doc, dict, b"WhitePoint"),
-                                            black_point: get(&doc, dict, b"BackPoint"),
-                                            gamma: get(&doc, dict, b"Gamma"),
-                                            matrix: get(&doc, dict, b"Matrix"),
-                                        })
-                                    }
-                                    "Lab" => {
-                                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                                        AlternateColorSpace::Lab(Lab {
-                                            white_point: get(&doc, dict, b"WhitePoint"),
-                                            black_point: get(&doc, dict, b"BackPoint"),
-                                            range: get(&doc, dict, b"Range"),
-                                        })
-                                    }
-                                    _ => panic!("Unexpected color space name")
-                                }
-                            }
-                            _ => panic!("Alternate space should be name or array {:?}", cs[2])
-                        };
-                        let tint_transform = Box::new(Function::new(doc, maybe_deref(doc, &cs[3])));
+            make_colorspace_from_obj(doc, cs, resources)
+        }
+    }
+}
 
-                        dlog!("{:?} {:?} {:?}", name, alternate_space, tint_transform);
-                        ColorSpace::Separation(Separation{ name, alternate_space, tint_transform})
-                    }
-                    "ICCBased" => {
-                        let stream = maybe_deref(doc, &cs[1]).as_stream().unwrap();
-                        dlog!("ICCBased {:?}", stream);
-                        // XXX: we're going to be continually decompressing everytime this object is referenced
-                        ColorSpace::ICCBased(get_contents(stream))
-                    }
-                    "CalGray" => {
-                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                        ColorSpace::CalGray(CalGray {
-                            white_point: get(&doc, dict, b"WhitePoint"),
-                            black_point: get(&doc, dict, b"BackPoint"),
-                            gamma: get(&doc, dict, b"Gamma"),
-                        })
-                    }
-                    "CalRGB" => {
-                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                        ColorSpace::CalRGB(CalRGB {
-                            white_point: get(&doc, dict, b"WhitePoint"),
-                            black_point: get(&doc, dict, b"BackPoint"),
-                            gamma: get(&doc, dict, b"Gamma"),
-                            matrix: get(&doc, dict, b"Matrix"),
-                        })
-                    }
-                    "Lab" => {
-                        let dict = cs[1].as_dict().expect("second arg must be a dict");
-                        ColorSpace::Lab(Lab {
-                            white_point: get(&doc, dict, b"WhitePoint"),
-                            black_point: get(&doc, dict, b"BackPoint"),
-                            range: get(&doc, dict, b"Range"),
-                        })
+// "The initial color value for all colour spaces, except Indexed, DeviceCMYK and
+// Pattern, shall be 0.0 for each component" (CMYK is black)
+fn initial_color(colorspace: &ColorSpace) -> Vec<f64> {
+    match colorspace {
+        ColorSpace::DeviceCMYK => vec![0., 0., 0., 1.],
+        ColorSpace::Pattern => Vec::new(),
+        cs => vec![0.; cs.components().unwrap_or(1)],
+    }
+}
+
+// The underlying colorspace of an uncolored pattern colorspace, e.g. [/Pattern /DeviceRGB]
+fn pattern_base_colorspace(doc: &Document, name: &[u8], resources: &Dictionary) -> Option<ColorSpace> {
+    let colorspaces: &Dictionary = maybe_get(doc, resources, b"ColorSpace")?;
+    let cs = maybe_get_array(doc, colorspaces, name)?;
+    match cs.as_slice() {
+        [Object::Name(pattern), base] if pattern == b"Pattern" => Some(match maybe_deref(doc, base) {
+            Object::Name(base) => make_colorspace(doc, base, resources),
+            base => make_colorspace_from_obj(doc, base, resources),
+        }),
+        _ => None,
+    }
+}
+
+fn make_colorspace_from_obj<'a>(doc: &'a Document, cs: &'a Object, resources: &'a Dictionary) -> ColorSpace {
+    if let Ok(cs) = cs.as_array() {
+        let cs_name = pdf_to_utf8(cs[0].as_name().expect("first arg must be a name"));
+        match cs_name.as_ref() {
+            "Separation" => {
+                let name = pdf_to_utf8(cs[1].as_name().expect("second arg must be a name"));
+                let alternate_space = match &maybe_deref(doc, &cs[2]) {
+                    Object::Name(name) => {
+                        match &name[..] {
+                            b"DeviceGray" => AlternateColorSpace::DeviceGray,
+                            b"DeviceRGB" => AlternateColorSpace::DeviceRGB,
+                            b"DeviceCMYK" => AlternateColorSpace::DeviceCMYK,
+                            _ => panic!("unexpected color space name")
+                        }
                     }
-                    "Pattern" => {
-                        ColorSpace::Pattern
-                    },
-                    "DeviceGray" => ColorSpace::DeviceGray,
-                    "DeviceRGB" => ColorSpace::DeviceRGB,
-                    "DeviceCMYK" => ColorSpace::DeviceCMYK,
-                    "DeviceN" => ColorSpace::DeviceN,
-                    _ => {
-                        panic!("color_space {:?} {:?} {:?}", name, cs_name, cs)
+                    Object::Array(cs) => {
+                        let cs_name = pdf_to_utf8(cs[0].as_name().expect("first arg must be a name"));
+                        match cs_name.as_ref() {
+                            "ICCBased" => {
+                                let stream = maybe_deref(doc, &cs[1]).as_stream().unwrap();
+                                dlog!("ICCBased {:?}", stream);
+                                // XXX: we're going to be continually decompressing everytime this object is referenced
+                                AlternateColorSpace::ICCBased(get_contents(stream))
+                            }
+                            "CalGray" => {
+                                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                                AlternateColorSpace::CalGray(CalGray {
+                                    white_point: get(&doc, dict, b"WhitePoint"),
+                                    black_point: get(&doc, dict, b"BackPoint"),
+                                    gamma: get(&doc, dict, b"Gamma"),
+                                })
+                            }
+                            "CalRGB" => {
+                                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                                AlternateColorSpace::CalRGB(CalRGB {
+                                    white_point: get(&doc, dict, b"WhitePoint"),
+                                    black_point: get(&doc, dict, b"BackPoint"),
+                                    gamma: get(&doc, dict, b"Gamma"),
+                                    matrix: get(&doc, dict, b"Matrix"),
+                                })
+                            }
+                            "Lab" => {
+                                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                                AlternateColorSpace::Lab(Lab {
+                                    white_point: get(&doc, dict, b"WhitePoint"),
+                                    black_point: get(&doc, dict, b"BackPoint"),
+                                    range: get(&doc, dict, b"Range"),
+                                })
+                            }
+                            _ => panic!("Unexpected color space name")
+                        }
                     }
+                    _ => panic!("Alternate space should be name or array {:?}", cs[2])
+                };
+                let tint_transform = Box::new(Function::new(doc, maybe_deref(doc, &cs[3])));
+
+                dlog!("{:?} {:?} {:?}", name, alternate_space, tint_transform);
+                ColorSpace::Separation(Separation{ name, alternate_space, tint_transform})
+            }
+            "ICCBased" => {
+                let stream = maybe_deref(doc, &cs[1]).as_stream().unwrap();
+                dlog!("ICCBased {:?}", stream);
+                // XXX: we're going to be continually decompressing everytime this object is referenced
+                ColorSpace::ICCBased(get_contents(stream))
+            }
+            "CalGray" => {
+                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                ColorSpace::CalGray(CalGray {
+                    white_point: get(&doc, dict, b"WhitePoint"),
+                    black_point: get(&doc, dict, b"BackPoint"),
+                    gamma: get(&doc, dict, b"Gamma"),
+                })
+            }
+            "CalRGB" => {
+                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                ColorSpace::CalRGB(CalRGB {
+                    white_point: get(&doc, dict, b"WhitePoint"),
+                    black_point: get(&doc, dict, b"BackPoint"),
+                    gamma: get(&doc, dict, b"Gamma"),
+                    matrix: get(&doc, dict, b"Matrix"),
+                })
+            }
+            "Lab" => {
+                let dict = cs[1].as_dict().expect("second arg must be a dict");
+                ColorSpace::Lab(Lab {
+                    white_point: get(&doc, dict, b"WhitePoint"),
+                    black_point: get(&doc, dict, b"BackPoint"),
+                    range: get(&doc, dict, b"Range"),
+                })
+            }
+            "Pattern" => {
+                ColorSpace::Pattern
+            },
+            "Indexed" | "I" => {
+                let base = match maybe_deref(doc, &cs[1]) {
+                    Object::Name(name) => make_colorspace(doc, expand_colorspace_abbreviation(name), resources),
+                    base => make_colorspace_from_obj(doc, base, resources)
+                };
+                let hival = as_num(maybe_deref(doc, &cs[2])) as i64;
+                let lookup = match maybe_deref(doc, &cs[3]) {
+                    Object::String(s, _) => s.clone(),
+                    Object::Stream(s) => get_contents(s),
+                    lookup => panic!("unexpected Indexed lookup {:?}", lookup)
+                };
+                ColorSpace::Indexed(Indexed { base: Box::new(base), hival, lookup })
+            }
+            "DeviceGray" => ColorSpace::DeviceGray,
+            "DeviceRGB" => ColorSpace::DeviceRGB,
+            "DeviceCMYK" => ColorSpace::DeviceCMYK,
+            "DeviceN" => ColorSpace::DeviceN,
+            _ => {
+                panic!("color_space {:?} {:?}", cs_name, cs)
+            }
+        }
+    } else if let Ok(cs) = cs.as_name() {
+        match pdf_to_utf8(cs).as_ref() {
+            "DeviceRGB" => ColorSpace::DeviceRGB,
+            "DeviceGray" => ColorSpace::DeviceGray,
+            _ => panic!()
+        }
+    } else {
+        panic!();
+    }
+}
+
+// Inline images are allowed to use abbreviated colorspace names
+fn expand_colorspace_abbreviation(name: &[u8]) -> &[u8] {
+    match name {
+        b"G" => b"DeviceGray",
+        b"RGB" => b"DeviceRGB",
+        b"CMYK" => b"DeviceCMYK",
+        _ => name
+    }
+}
+
+/// How the samples of an `Image` are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    /// Decoded samples, `bits_per_component` bits per component with each row starting on a byte boundary
+    Raw,
+    /// A JPEG file (DCTDecode)
+    Jpeg,
+    /// A JPEG 2000 file (JPXDecode)
+    Jpeg2000,
+    /// Data in a filter that we don't decode (e.g. CCITTFaxDecode or JBIG2Decode)
+    Other,
+}
+
+#[derive(Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_component: u32,
+    /// `None` for stencil masks, which are painted with the fill color
+    pub colorspace: Option<ColorSpace>,
+    pub image_mask: bool,
+    pub decode: Option<Vec<f64>>,
+    pub format: ImageFormat,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    // read the sample at `index` of a row
+    fn sample(&self, row: &[u8], index: usize) -> u32 {
+        let bits = self.bits_per_component as usize;
+        match bits {
+            8 => row.get(index).copied().unwrap_or(0) as u32,
+            16 => row.get(index * 2..index * 2 + 2).map_or(0, |s| u16::from_be_bytes([s[0], s[1]]) as u32),
+            _ => {
+                let bit = index * bits;
+                let byte = row.get(bit / 8).copied().unwrap_or(0) as u32;
+                (byte >> (8 - bits - bit % 8)) & ((1 << bits) - 1)
+            }
+        }
+    }
+
+    /// Convert `Raw` images to 8-bit RGBA pixels, with stencil masks painted in `fill_rgb`.
+    /// Returns `None` for the other formats, for colorspaces that can't be converted and for
+    /// images that are empty or have less data than their size needs.
+    pub fn to_rgba(&self, fill_rgb: [f64; 3]) -> Option<Vec<u8>> {
+        if self.format != ImageFormat::Raw || !matches!(self.bits_per_component, 1 | 2 | 4 | 8 | 16) {
+            return None;
+        }
+        let to_byte = |x: f64| (x * 255.).round().clamp(0., 255.) as u8;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let bits = self.bits_per_component as usize;
+        let max = ((1u32 << bits) - 1) as f64;
+        // the bytes of each row, which has to be there for every row
+        let has_rows = |stride: Option<usize>| -> Option<usize> {
+            let stride = stride?;
+            (width > 0 && height > 0 && stride.checked_mul(height)? <= self.data.len()).then_some(stride)
+        };
+        if self.image_mask || self.colorspace.is_none() {
+            // samples of 0 are painted unless the decode array is [1 0]
+            let inverted = matches!(self.decode.as_deref(), Some([d0, _]) if *d0 == 1.);
+            let stride = has_rows(Some(width.div_ceil(8)))?;
+            let mut rgba = Vec::with_capacity(width * height * 4);
+            for y in 0..height {
+                let row = self.data.get(y * stride..).unwrap_or(&[]);
+                for x in 0..width {
+                    let bit = row.get(x / 8).map_or(1, |b| (b >> (7 - x % 8)) & 1);
+                    let painted = (bit == 0) != inverted;
+                    let [r, g, b] = fill_rgb;
+                    rgba.extend_from_slice(&[to_byte(r), to_byte(g), to_byte(b), if painted { 255 } else { 0 }]);
                 }
-            } else if let Ok(cs) = cs.as_name() {
-                match pdf_to_utf8(cs).as_ref() {
-                    "DeviceRGB" => ColorSpace::DeviceRGB,
-                    "DeviceGray" => ColorSpace::DeviceGray,
-                    _ => panic!()
-                }
-            } else {
-                panic!();
+            }
+            return Some(rgba);
+        }
+        let colorspace = self.colorspace.as_ref()?;
+        let n = colorspace.components()?;
+        let decode: Vec<f64> = match (&self.decode, colorspace) {
+            (Some(decode), _) if decode.len() >= 2 * n => decode.clone(),
+            (_, ColorSpace::Indexed(_)) => vec![0., max],
+            (_, ColorSpace::Lab(lab)) => {
+                let r = lab.range.unwrap_or([-100., 100., -100., 100.]);
+                vec![0., 100., r[0], r[1], r[2], r[3]]
+            }
+            _ => [0., 1.].repeat(n),
+        };
+        let stride = has_rows(width.checked_mul(n * bits).map(|bits| bits.div_ceil(8)))?;
+        // single component images are converted through a table of all of their values
+        let table: Option<Vec<[u8; 3]>> = if n == 1 && bits <= 8 {
+            Some((0..=max as u32).map(|v| {
+                let rgb = colorspace.to_rgb(&[interpolate(v as f64, 0., max, decode[0], decode[1])]).unwrap_or([0., 0., 0.]);
+                [to_byte(rgb[0]), to_byte(rgb[1]), to_byte(rgb[2])]
+            }).collect())
+        } else {
+            None
+        };
+        let mut color = vec![0.; n];
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let row = self.data.get(y * stride..).unwrap_or(&[]);
+            for x in 0..width {
+                let rgb = match &table {
+                    Some(table) => table[self.sample(row, x) as usize],
+                    None => {
+                        for (i, c) in color.iter_mut().enumerate() {
+                            *c = interpolate(self.sample(row, x * n + i) as f64, 0., max, decode[2 * i], decode[2 * i + 1]);
+                        }
+                        let rgb = colorspace.to_rgb(&color)?;
+                        [to_byte(rgb[0]), to_byte(rgb[1]), to_byte(rgb[2])]
+                    }
+                };
+                rgba.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
             }
         }
+        Some(rgba)
     }
 }
 
+fn make_image<'a>(doc: &'a Document, stream: &'a Stream, resources: &'a Dictionary, inline: bool) -> Image {
+    let dict = &stream.dict;
+    // inline images use abbreviated keys
+    let key = |full: &'static [u8], abbreviation: &'static [u8]| -> &'static [u8] {
+        if inline && !dict.has(full) { abbreviation } else { full }
+    };
+    // sizes that aren't positive are taken as 0
+    let size = |full, abbreviation| {
+        get::<Option<i64>>(doc, dict, key(full, abbreviation)).and_then(|size| u32::try_from(size).ok()).unwrap_or(0)
+    };
+    let (width, height) = (size(b"Width", b"W"), size(b"Height", b"H"));
+    let image_mask = matches!(maybe_get_obj(doc, dict, key(b"ImageMask", b"IM")), Some(Object::Boolean(true)));
+    let bits_per_component = if image_mask { 1 } else {
+        get::<Option<i64>>(doc, dict, key(b"BitsPerComponent", b"BPC")).unwrap_or(8) as u32
+    };
+    let colorspace = if image_mask { None } else {
+        maybe_get_obj(doc, dict, key(b"ColorSpace", b"CS")).map(|cs| match cs {
+            Object::Name(name) => make_colorspace(doc, expand_colorspace_abbreviation(name), resources),
+            cs => make_colorspace_from_obj(doc, cs, resources)
+        })
+    };
+    let decode = get::<Option<Vec<f64>>>(doc, dict, key(b"Decode", b"D"));
+    let filters = stream.filters().unwrap_or_default();
+    let format = match filters.last().copied() {
+        Some(b"DCTDecode") | Some(b"DCT") => ImageFormat::Jpeg,
+        Some(b"JPXDecode") => ImageFormat::Jpeg2000,
+        Some(b"CCITTFaxDecode") | Some(b"CCF") | Some(b"JBIG2Decode") => ImageFormat::Other,
+        _ => ImageFormat::Raw,
+    };
+    let data = if format == ImageFormat::Raw { get_contents(stream) } else { stream.content.clone() };
+    Image { width, height, bits_per_component, colorspace, image_mask, decode, format, data }
+}
+
 struct Processor<'a> {
-    font_table: HashMap<Vec<u8>, Rc<dyn PdfFont + 'a>>,
+    // keyed by the font dictionary because forms and pages can use the same name for different fonts
+    font_table: HashMap<*const Dictionary, Rc<dyn PdfFont + 'a>>,
+    font_overrides: &'a FontOverrides,
+    unmapped_codes: UnmappedCodes,
+    // the last transparency given to `OutputDev::set_transparency`
+    transparency: Transparency,
+    // the tiling patterns that have been given to `OutputDev::begin_pattern` on this page along
+    // with the color that uncolored patterns were defined with
+    tiling_patterns: Vec<(*const Stream, Vec<f64>, TilingPattern)>,
+    // the id of the next tiling pattern, which are numbered through the document so that
+    // output devices that put the pages together don't get the same id twice
+    next_pattern_id: usize,
+    // the Type3 fonts in `font_table`, whose glyphs are drawn by running their CharProcs
+    type3_fonts: HashMap<*const Dictionary, Rc<PdfType3Font<'a>>>,
+    // the CharProcs that are running, innermost last
+    type3_glyphs: Vec<&'a Stream>,
     _none: PhantomData<&'a ()>,
 }
 
 impl<'a> Processor<'a> {
-    fn new() -> Processor<'a> {
-        Processor { font_table: HashMap::new(), _none: PhantomData }
+    fn new(font_overrides: &'a FontOverrides) -> Processor<'a> {
+        Processor { font_table: HashMap::new(), font_overrides, unmapped_codes: HashMap::new(), transparency: Transparency::new(), tiling_patterns: Vec::new(),
+                    next_pattern_id: 0, type3_fonts: HashMap::new(), type3_glyphs: Vec::new(), _none: PhantomData }
     }
 
-    fn process_stream(&mut self, doc: &'a Document, content: Vec<u8>, resources: &'a Dictionary, media_box: &MediaBox, output: &mut dyn OutputDev, page_num: u32) -> Result<(), OutputError> {
-        let content = Content::decode(&content).unwrap();
-        let mut gs: GraphicsState = GraphicsState {
-            ts: TextState {
-                font: None,
-                font_size: std::f64::NAN,
-                character_spacing: 0.,
-                word_spacing: 0.,
-                horizontal_scaling: 100. / 100.,
-                leading: 0.,
-                rise: 0.,
-                tm: Transform2D::identity(),
-            },
-            fill_color: Vec::new(),
-            fill_colorspace: ColorSpace::DeviceGray,
-            stroke_color: Vec::new(),
-            stroke_colorspace: ColorSpace::DeviceGray,
-            line_width: 1.,
-            ctm: Transform2D::identity(),
-            smask: None
+    // The font of a font dictionary, which is made the first time that it's used
+    fn font(&mut self, doc: &'a Document, dict: &'a Dictionary) -> Rc<dyn PdfFont + 'a> {
+        let key = dict as *const Dictionary;
+        if let Some(font) = self.font_table.get(&key) {
+            return font.clone();
+        }
+        let font: Rc<dyn PdfFont + 'a> = if get_name_string(doc, dict, b"Subtype") == "Type3" {
+            let font = Rc::new(PdfType3Font::new(doc, dict, self.font_overrides));
+            self.type3_fonts.insert(key, font.clone());
+            font
+        } else {
+            make_font(doc, dict, self.font_overrides)
         };
+        self.font_table.insert(key, font.clone());
+        font
+    }
+
+    // Run the content stream of a tiling pattern's cell the first time that it's used with a
+    // particular matrix and color
+    #[allow(clippy::too_many_arguments)]
+    fn define_tiling_pattern(&mut self, doc: &'a Document, stream: &'a Stream, matrix: &Transform, gs: &GraphicsState<'a>,
+                             resources: &'a Dictionary, media_box: &MediaBox, output: &mut dyn OutputDev) -> Result<TilingPattern, OutputError> {
+        let dict = &stream.dict;
+        let paint_type = maybe_get::<i64>(doc, dict, b"PaintType").unwrap_or(1);
+        // colored patterns don't depend on the current color
+        let color = if paint_type == 2 { gs.fill_color.clone() } else { Vec::new() };
+        if let Some((_, _, pattern)) = self.tiling_patterns.iter().find(|(s, c, p)| std::ptr::eq(*s, stream) && *c == color && p.matrix == *matrix) {
+            return Ok(pattern.clone());
+        }
+        let bbox = match maybe_get::<Vec<f64>>(doc, dict, b"BBox").as_deref() {
+            Some([llx, lly, urx, ury]) => [*llx, *lly, *urx, *ury],
+            _ => [0., 0., 0., 0.],
+        };
+        let pattern = TilingPattern {
+            id: self.next_pattern_id,
+            paint_type,
+            tiling_type: maybe_get::<i64>(doc, dict, b"TilingType").unwrap_or(1),
+            bbox,
+            x_step: maybe_get(doc, dict, b"XStep").unwrap_or(bbox[2] - bbox[0]),
+            y_step: maybe_get(doc, dict, b"YStep").unwrap_or(bbox[3] - bbox[1]),
+            matrix: *matrix,
+        };
+        self.next_pattern_id += 1;
+        // recorded before the cell is run so that patterns that use themselves don't recurse forever
+        self.tiling_patterns.push((stream, color.clone(), pattern.clone()));
+        output.begin_pattern(&pattern)?;
+        let mut cell_gs = GraphicsState { ctm: *matrix, transparency: gs.transparency.clone(), ..GraphicsState::new() };
+        if paint_type == 2 {
+            if let Some(base) = &gs.fill_pattern_base {
+                cell_gs.fill_colorspace = base.clone();
+                cell_gs.stroke_colorspace = base.clone();
+            }
+            cell_gs.fill_color = color.clone();
+            cell_gs.stroke_color = color;
+        }
+        let resources = maybe_get_obj(doc, dict, b"Resources").and_then(|n| n.as_dict().ok()).unwrap_or(resources);
+        output.save_state()?;
+        self.process_stream(doc, get_contents(stream), resources, media_box, output, cell_gs)?;
+        output.restore_state()?;
+        output.end_pattern()?;
+        // the cell's graphics state doesn't leak out
+        self.update_transparency(gs, output)?;
+        Ok(pattern)
+    }
+
+    fn update_transparency(&mut self, gs: &GraphicsState, output: &mut dyn OutputDev) -> Result<(), OutputError> {
+        if gs.transparency != self.transparency {
+            self.transparency = gs.transparency.clone();
+            output.set_transparency(&self.transparency)?;
+        }
+        Ok(())
+    }
+
+    fn process_stream(&mut self, doc: &'a Document, content: Vec<u8>, resources: &'a Dictionary, media_box: &MediaBox, output: &mut dyn OutputDev, mut gs: GraphicsState<'a>) -> Result<(), OutputError> {
+        let mut content = Content::decode(&content).unwrap();
+        fix_glyph_metrics_operators(&mut content.operations);
+        // patterns are defined relative to the coordinate space of the page or form that uses them
+        let base_ctm = gs.ctm;
         //let mut ts = &mut gs.ts;
         let mut gs_stack = Vec::new();
         let mut mc_stack = Vec::new();
         // XXX: replace tlm with a point for text start
         let mut tlm = Transform2D::identity();
         let mut path = Path::new();
-        let flip_ctm = Transform2D::row_major(1., 0., 0., -1., 0., media_box.ury - media_box.lly);
+        // set by W and W* for the next painting operator
+        let mut clip_rule = None;
         dlog!("MediaBox {:?}", media_box);
         for operation in &content.operations {
             //dlog!("op: {:?}", operation);
+            // report the transparency changes made by `gs` and `Q` (and forms) before anything is drawn with them
+            self.update_transparency(&gs, output)?;
 
             match operation.operator.as_ref() {
                 "BT" => {
@@ -1633,51 +3084,84 @@ impl<'a> Processor<'a> {
                 "CS" => {
                     let name = operation.operands[0].as_name().unwrap();
                     gs.stroke_colorspace = make_colorspace(doc, name, resources);
+                    gs.stroke_color = initial_color(&gs.stroke_colorspace);
+                    gs.stroke_pattern = None;
                 }
                 "cs" => {
                     let name = operation.operands[0].as_name().unwrap();
                     gs.fill_colorspace = make_colorspace(doc, name, resources);
+                    gs.fill_color = initial_color(&gs.fill_colorspace);
+                    gs.fill_pattern = None;
+                    gs.fill_pattern_base = pattern_base_colorspace(doc, name, resources);
                 }
                 "SC" | "SCN" => {
-                    gs.stroke_color = match gs.stroke_colorspace {
-                        ColorSpace::Pattern => { dlog!("unhandled pattern color"); Vec::new() }
-                        _ => { operation.operands.iter().map(|x| as_num(x)).collect() }
+                    // pattern colors end with the name of the pattern which is preceded by the
+                    // color components of uncolored patterns
+                    gs.stroke_pattern = match operation.operands.last() {
+                        Some(Object::Name(name)) => make_pattern(doc, resources, name, &base_ctm),
+                        _ => None
                     };
+                    gs.stroke_color = operation.operands.iter().filter(|x| !matches!(x, Object::Name(_))).map(|x| as_num(x)).collect();
                 }
                 "sc" | "scn" => {
-                    gs.fill_color = match gs.fill_colorspace {
-                        ColorSpace::Pattern => { dlog!("unhandled pattern color"); Vec::new() }
-                        _ => { operation.operands.iter().map(|x| as_num(x)).collect() }
+                    gs.fill_pattern = match operation.operands.last() {
+                        Some(Object::Name(name)) => make_pattern(doc, resources, name, &base_ctm),
+                        _ => None
                     };
+                    gs.fill_color = operation.operands.iter().filter(|x| !matches!(x, Object::Name(_))).map(|x| as_num(x)).collect();
+                }
+                "sh" => {
+                    let shadings: &Dictionary = get(doc, resources, b"Shading");
+                    let name = operation.operands[0].as_name().unwrap();
+                    match maybe_get_obj(doc, shadings, name).and_then(|shading| make_shading(doc, shading, resources)) {
+                        Some(shading) => output.shade(&gs.ctm, &shading, None)?,
+                        None => warn!("missing shading {:?}", pdf_to_utf8(name)),
+                    }
                 }
                 "G" | "g" | "RG" | "rg" | "K" | "k" => {
-                    dlog!("unhandled color operation {:?}", operation);
+                    let colorspace = match operation.operator.as_ref() {
+                        "G" | "g" => ColorSpace::DeviceGray,
+                        "RG" | "rg" => ColorSpace::DeviceRGB,
+                        _ => ColorSpace::DeviceCMYK,
+                    };
+                    let color = operation.operands.iter().map(as_num).collect();
+                    if operation.operator.chars().all(|c| c.is_ascii_uppercase()) {
+                        gs.stroke_colorspace = colorspace;
+                        gs.stroke_color = color;
+                        gs.stroke_pattern = None;
+                    } else {
+                        gs.fill_colorspace = colorspace;
+                        gs.fill_color = color;
+                        gs.fill_pattern = None;
+                    }
                 }
                 "TJ" => {
                     match operation.operands[0] {
                         Object::Array(ref array) => {
-                            for e in array {
+                            let is_string = |e: &Object| matches!(e, Object::String(..));
+                            for (i, e) in array.iter().enumerate() {
                                 match e {
                                     &Object::String(ref s, _) => {
-                                        show_text(&mut gs, s, &tlm, &flip_ctm, output)?;
-                                    }
-                                    &Object::Integer(i) => {
-                                        let ts = &mut gs.ts;
-                                        let w0 = 0.;
-                                        let tj = i as f64;
-                                        let ty = 0.;
-                                        let tx = ts.horizontal_scaling * ((w0 - tj / 1000.) * ts.font_size);
-                                        ts.tm = ts.tm.pre_transform(&Transform2D::create_translation(tx, ty));
-                                        dlog!("adjust text by: {} {:?}", i, ts.tm);
+                                        show_text(self, &mut gs, s, resources, media_box, output)?;
                                     }
-                                    &Object::Real(i) => {
+                                    &Object::Integer(_) | &Object::Real(_) => {
                                         let ts = &mut gs.ts;
                                         let w0 = 0.;
-                                        let tj = i as f64;
+                                        let tj = as_num(e);
                                         let ty = 0.;
                                         let tx = ts.horizontal_scaling * ((w0 - tj / 1000.) * ts.font_size);
+                                        // the gap is reported before the text moves, and only between strings of the
+                                        // array, so it isn't applied to text that's positioned by other operators, and not
+                                        // for the text of CharProcs
+                                        if self.type3_glyphs.is_empty() && array[..i].iter().any(is_string) && array[i + 1..].iter().any(is_string) {
+                                            let tsm = Transform2D::row_major(ts.horizontal_scaling, 0., 0., 1., 0., ts.rise);
+                                            let trm = tsm.post_transform(&ts.tm.post_transform(&gs.ctm));
+                                            let space_width = ts.font.as_deref().and_then(space_width)
+                                                .map(|w| w * ts.font_size * ts.horizontal_scaling);
+                                            output.text_gap(&trm, tx, ts.font_size, space_width)?;
+                                        }
                                         ts.tm = ts.tm.pre_transform(&Transform2D::create_translation(tx, ty));
-                                        dlog!("adjust text by: {} {:?}", i, ts.tm);
+                                        dlog!("adjust text by: {} {:?}", tj, ts.tm);
                                     }
                                     _ => { dlog!("kind of {:?}", e); }
                                 }
@@ -1689,7 +3173,7 @@ impl<'a> Processor<'a> {
                 "Tj" => {
                     match operation.operands[0] {
                         Object::String(ref s, _) => {
-                            show_text(&mut gs, s, &tlm, &flip_ctm, output)?;
+                            show_text(self, &mut gs, s, resources, media_box, output)?;
                         }
                         _ => { panic!("unexpected Tj operand {:?}", operation) }
                     }
@@ -1700,6 +3184,9 @@ impl<'a> Processor<'a> {
                 "Tw" => {
                     gs.ts.word_spacing = as_num(&operation.operands[0]);
                 }
+                "Tr" => {
+                    gs.ts.render_mode = TextRenderMode::from_pdf(as_num(&operation.operands[0]));
+                }
                 "Tz" => {
                     gs.ts.horizontal_scaling = as_num(&operation.operands[0]) / 100.;
                 }
@@ -1709,7 +3196,7 @@ impl<'a> Processor<'a> {
                 "Tf" => {
                     let fonts: &Dictionary = get(&doc, resources, b"Font");
                     let name = operation.operands[0].as_name().unwrap();
-                    let font = self.font_table.entry(name.to_owned()).or_insert_with(|| make_font(doc, get::<&Dictionary>(doc, fonts, name))).clone();
+                    let font = self.font(doc, get::<&Dictionary>(doc, fonts, name));
                     {
                         /*let file = font.get_descriptor().and_then(|desc| desc.get_file());
                     if let Some(file) = file {
@@ -1737,7 +3224,9 @@ impl<'a> Processor<'a> {
                                                  as_num(&operation.operands[5]));
                     gs.ts.tm = tlm;
                     dlog!("Tm: matrix {:?}", gs.ts.tm);
-                    output.end_line()?;
+                    if self.type3_glyphs.is_empty() {
+                        output.end_line()?;
+                    }
                 }
                 "Td" => {
                     /* Move to the start of the next line, offset from the start of the current line by (tx , ty ).
@@ -1752,7 +3241,9 @@ impl<'a> Processor<'a> {
                     tlm = tlm.pre_transform(&Transform2D::create_translation(tx, ty));
                     gs.ts.tm = tlm;
                     dlog!("Td matrix {:?}", gs.ts.tm);
-                    output.end_line()?;
+                    if self.type3_glyphs.is_empty() {
+                        output.end_line()?;
+                    }
                 }
 
                 "TD" => {
@@ -1768,7 +3259,9 @@ impl<'a> Processor<'a> {
                     tlm = tlm.pre_transform(&Transform2D::create_translation(tx, ty));
                     gs.ts.tm = tlm;
                     dlog!("TD matrix {:?}", gs.ts.tm);
-                    output.end_line()?;
+                    if self.type3_glyphs.is_empty() {
+                        output.end_line()?;
+                    }
                 }
 
                 "T*" => {
@@ -1778,13 +3271,19 @@ impl<'a> Processor<'a> {
                     tlm = tlm.pre_transform(&Transform2D::create_translation(tx, ty));
                     gs.ts.tm = tlm;
                     dlog!("T* matrix {:?}", gs.ts.tm);
-                    output.end_line()?;
+                    if self.type3_glyphs.is_empty() {
+                        output.end_line()?;
+                    }
+                }
+                "q" => {
+                    gs_stack.push(gs.clone());
+                    output.save_state()?;
                 }
-                "q" => { gs_stack.push(gs.clone()); }
                 "Q" => {
                     let s = gs_stack.pop();
                     if let Some(s) = s {
                         gs = s;
+                        output.restore_state()?;
                     } else {
                         warn!("No state to pop");
                     }
@@ -1793,11 +3292,37 @@ impl<'a> Processor<'a> {
                     let ext_gstate: &Dictionary = get(doc, resources, b"ExtGState");
                     let name = operation.operands[0].as_name().unwrap();
                     let state: &Dictionary = get(doc, ext_gstate, name);
-                    apply_state(doc, &mut gs, state);
+                    apply_state(doc, &mut gs, state, self);
                 }
                 "i" => { dlog!("unhandled graphics state flattness operator {:?}", operation); }
-                "w" => { gs.line_width = as_num(&operation.operands[0]); }
-                "J" | "j" | "M" | "d" | "ri"  => { dlog!("unknown graphics state operator {:?}", operation); }
+                "w" => match maybe_num(operation.operands.first()) {
+                    Some(width) => gs.stroke_style.line_width = width,
+                    None => warn!("unexpected line width {:?}", operation.operands),
+                },
+                "J" => match maybe_num(operation.operands.first()) {
+                    Some(cap) => gs.stroke_style.line_cap = LineCap::from_pdf(cap),
+                    None => warn!("unexpected line cap {:?}", operation.operands),
+                },
+                "j" => match maybe_num(operation.operands.first()) {
+                    Some(join) => gs.stroke_style.line_join = LineJoin::from_pdf(join),
+                    None => warn!("unexpected line join {:?}", operation.operands),
+                },
+                "M" => match maybe_num(operation.operands.first()) {
+                    Some(limit) => gs.stroke_style.miter_limit = limit,
+                    None => warn!("unexpected miter limit {:?}", operation.operands),
+                },
+                "d" => match (operation.operands.first(), maybe_num(operation.operands.get(1))) {
+                    (Some(Object::Array(array)), Some(phase)) => {
+                        gs.stroke_style.dash_array = array.iter().filter_map(|x| maybe_num(Some(x))).collect();
+                        gs.stroke_style.dash_phase = phase;
+                    }
+                    _ => warn!("unexpected dash pattern {:?}", operation.operands),
+                },
+                "ri" => {
+                    if let Ok(name) = operation.operands[0].as_name() {
+                        gs.rendering_intent = pdf_to_utf8(name);
+                    }
+                }
                 "m" => { path.ops.push(PathOp::MoveTo(as_num(&operation.operands[0]), as_num(&operation.operands[1]))) }
                 "l" => { path.ops.push(PathOp::LineTo(as_num(&operation.operands[0]), as_num(&operation.operands[1]))) }
                 "c" => {
@@ -1835,20 +3360,46 @@ impl<'a> Processor<'a> {
                                                as_num(&operation.operands[2]),
                                                as_num(&operation.operands[3])))
                 }
-                "s" | "f*" | "B" | "B*" | "b" => {
-                    dlog!("unhandled path op {:?}", operation);
-                }
-                "S" => {
-                    output.stroke(&gs.ctm, &gs.stroke_colorspace, &gs.stroke_color, &path)?;
+                "S" | "s" | "F" | "f" | "f*" | "B" | "B*" | "b" | "b*" => {
+                    // the lower case variants close the path first
+                    if matches!(operation.operator.as_ref(), "s" | "b" | "b*") {
+                        path.ops.push(PathOp::Close);
+                    }
+                    let fill_rule = match operation.operator.as_ref() {
+                        "F" | "f" | "B" | "b" => Some(FillRule::NonZero),
+                        "f*" | "B*" | "b*" => Some(FillRule::EvenOdd),
+                        _ => None,
+                    };
+                    match (fill_rule, &gs.fill_pattern) {
+                        (Some(fill_rule), Some(Pattern::Shading { shading, matrix })) => {
+                            output.shade(matrix, shading, Some((&gs.ctm, &path, fill_rule)))?;
+                        }
+                        (Some(fill_rule), Some(Pattern::Tiling { stream, matrix })) => {
+                            let pattern = self.define_tiling_pattern(doc, stream, matrix, &gs, resources, media_box, output)?;
+                            output.fill_path_with_pattern(&gs.ctm, &path, fill_rule, &pattern)?;
+                        }
+                        (Some(fill_rule), _) => {
+                            output.fill_path(&gs.ctm, &gs.fill_colorspace, &gs.fill_color, &path, fill_rule)?;
+                        }
+                        (None, _) => {}
+                    }
+                    if !matches!(operation.operator.as_ref(), "F" | "f" | "f*") {
+                        output.stroke_path(&gs.ctm, &gs.stroke_colorspace, &gs.stroke_color, &path, &gs.stroke_style)?;
+                    }
+                    if let Some(fill_rule) = clip_rule.take() {
+                        output.clip(&gs.ctm, &path, fill_rule)?;
+                    }
                     path.ops.clear();
                 }
-                "F" | "f" => {
-                    output.fill(&gs.ctm, &gs.fill_colorspace, &gs.fill_color, &path)?;
-                    path.ops.clear();
+                "W" | "W*" => {
+                    // the clip takes effect after the path is painted
+                    clip_rule = Some(if operation.operator == "W" { FillRule::NonZero } else { FillRule::EvenOdd });
                 }
-                "W" | "w*" => { dlog!("unhandled clipping operation {:?}", operation); }
                 "n" => {
                     dlog!("discard {:?}", path);
+                    if let Some(fill_rule) = clip_rule.take() {
+                        output.clip(&gs.ctm, &path, fill_rule)?;
+                    }
                     path.ops.clear();
                 }
                 "BMC" | "BDC" => {
@@ -1857,20 +3408,51 @@ impl<'a> Processor<'a> {
                 "EMC" => {
                     mc_stack.pop();
                 }
+                "d0" | "d1" => {
+                    // glyph widths are read by PdfType3Font and d1 glyphs just use the inherited colors
+                    dlog!("glyph metrics {:?}", operation);
+                }
+                "BI" => {
+                    if let Some(&Object::Stream(ref stream)) = operation.operands.first() {
+                        let image = make_image(doc, stream, resources, true);
+                        output.image(&gs.ctm, &gs.fill_colorspace, &gs.fill_color, &image)?;
+                    }
+                }
                 "Do" => {
-                    // `Do` process an entire subdocument, so we do a recursive call to `process_stream`
-                    // with the subdocument content and resources
                     let xobject: &Dictionary = get(&doc, resources, b"XObject");
                     let name = operation.operands[0].as_name().unwrap();
                     let xf: &Stream = get(&doc, xobject, name);
+                    if maybe_get_name(doc, &xf.dict, b"Subtype") == Some(b"Image") {
+                        let image = make_image(doc, xf, resources, false);
+                        output.image(&gs.ctm, &gs.fill_colorspace, &gs.fill_color, &image)?;
+                        continue;
+                    }
+                    // `Do` process an entire subdocument, so we do a recursive call to `process_stream`
+                    // with the subdocument content and resources
                     let resources = maybe_get_obj(&doc, &xf.dict, b"Resources").and_then(|n| n.as_dict().ok()).unwrap_or(resources);
                     let contents = get_contents(xf);
-                    self.process_stream(&doc, contents, resources, &media_box, output, page_num)?;
+                    // forms inherit the graphics state, with the form matrix added to the CTM, and
+                    // are clipped to their bounding box
+                    let mut form_gs = gs.clone();
+                    if let Some([a, b, c, d, e, f]) = maybe_get::<Vec<f64>>(doc, &xf.dict, b"Matrix").as_deref() {
+                        form_gs.ctm = Transform2D::row_major(*a, *b, *c, *d, *e, *f).post_transform(&gs.ctm);
+                    }
+                    output.save_state()?;
+                    if let Some([llx, lly, urx, ury]) = maybe_get::<Vec<f64>>(doc, &xf.dict, b"BBox").as_deref() {
+                        let bbox = Path { ops: vec![PathOp::Rect(*llx, *lly, urx - llx, ury - lly)] };
+                        output.clip(&form_gs.ctm, &bbox, FillRule::NonZero)?;
+                    }
+                    self.process_stream(&doc, contents, resources, &media_box, output, form_gs)?;
+                    output.restore_state()?;
                 }
                 _ => { dlog!("unknown operation {:?}", operation); }
 
             }
         }
+        // streams that don't restore all of their states
+        for _ in gs_stack {
+            output.restore_state()?;
+        }
         Ok(())
     }
 }
@@ -1882,323 +3464,2298 @@ pub trait OutputDev {
     fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError>;
     fn begin_word(&mut self)-> Result<(), OutputError>;
     fn end_word(&mut self)-> Result<(), OutputError>;
+    /// Called for the operators that move to the next line of text, `Td`, `TD`, `Tm` and `T*`,
+    /// which don't necessarily start a new line on the page, since some producers move to each
+    /// word with `Td`. The built-in outputs ignore it and use `LineBuilder` to find the lines
+    /// from the positions of the characters instead.
     fn end_line(&mut self)-> Result<(), OutputError>;
+    /// Called for the numbers between the strings of `TJ` arrays, before the text is moved by
+    /// them. Numbers at the start or end of an array aren't reported, since the text next to them
+    /// may be positioned by other operators. `gap` is how far the text moves to the right in text
+    /// space units, i.e. the negated number in thousandths of an em times the font size and the
+    /// horizontal scaling, so it's positive for gaps and negative for kerning that moves the
+    /// glyphs together. Producers often leave out the spaces between words and move the
+    /// words apart instead. `space_width` is the width of the font's space character in the same
+    /// units, if it has one, and `trm` is the text rendering matrix, as for `output_character`.
+    fn text_gap(&mut self, _trm: &Transform, _gap: f64, _font_size: f64, _space_width: Option<f64>)-> Result<(), OutputError> {Ok(())}
     fn stroke(&mut self, _ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _path: &Path)-> Result<(), OutputError> {Ok(())}
     fn fill(&mut self, _ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _path: &Path)-> Result<(), OutputError> {Ok(())}
+    /// Called for all the path stroking operators. Defaults to `stroke` for output devices that
+    /// don't care about the line style.
+    fn stroke_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, _style: &StrokeStyle)-> Result<(), OutputError> {
+        self.stroke(ctm, colorspace, color, path)
+    }
+    /// Called for all the path filling operators, including the fill of fill-and-stroke
+    /// operators which is followed by a call to `stroke_path`. Defaults to `fill`.
+    fn fill_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, _fill_rule: FillRule)-> Result<(), OutputError> {
+        self.fill(ctm, colorspace, color, path)
+    }
+    /// `ctm` maps the unit square to the image. The colorspace and color are the current fill
+    /// color which is what stencil masks are painted with.
+    fn image(&mut self, _ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _image: &Image)-> Result<(), OutputError> {Ok(())}
+    /// Called when the transparency parameters change, before the drawing that they apply to.
+    /// All drawing uses `Transparency`'s defaults (opaque, Normal blend mode) until then.
+    fn set_transparency(&mut self, _transparency: &Transparency)-> Result<(), OutputError> {Ok(())}
+    /// Called for the `sh` operator, which paints `shading` everywhere inside the current clip,
+    /// with a `path` of `None`, and instead of `fill_path` for paths filled with a shading pattern.
+    /// `ctm` maps the shading's coordinates to device space while the path has its own transform.
+    fn shade(&mut self, _ctm: &Transform, _shading: &Shading, _path: Option<(&Transform, &Path, FillRule)>)-> Result<(), OutputError> {Ok(())}
+    /// Called the first time that a tiling pattern is used on a page. The contents of the
+    /// pattern's cell are drawn as usual, with the pattern matrix (i.e. as the tile at the origin
+    /// of pattern space), between this and `end_pattern`.
+    fn begin_pattern(&mut self, _pattern: &TilingPattern)-> Result<(), OutputError> {Ok(())}
+    fn end_pattern(&mut self)-> Result<(), OutputError> {Ok(())}
+    /// Called instead of `fill_path` for paths filled with a tiling pattern, after the pattern
+    /// has been defined with `begin_pattern`
+    fn fill_path_with_pattern(&mut self, _ctm: &Transform, _path: &Path, _fill_rule: FillRule, _pattern: &TilingPattern)-> Result<(), OutputError> {Ok(())}
+    /// Called for `q` and before forms, pattern cells and Type3 glyphs are drawn
+    fn save_state(&mut self)-> Result<(), OutputError> {Ok(())}
+    /// Called for `Q` and after forms, pattern cells and Type3 glyphs are drawn. Undoes the
+    /// clips since the matching `save_state`.
+    fn restore_state(&mut self)-> Result<(), OutputError> {Ok(())}
+    /// Intersect the clip with `path`, until the next `restore_state`
+    fn clip(&mut self, _ctm: &Transform, _path: &Path, _fill_rule: FillRule)-> Result<(), OutputError> {Ok(())}
+    /// Called for each glyph of fonts other than Type3 fonts, whose glyphs are drawn with the
+    /// other callbacks, before `output_character`
+    fn draw_glyph(&mut self, _glyph: &TextGlyph)-> Result<(), OutputError> {Ok(())}
+    /// Called for each link annotation of the page that goes to a URI, after the page's contents.
+    /// `rect` is (llx, lly, urx, ury) in the default user space of the page.
+    fn link(&mut self, _rect: &(f64, f64, f64, f64), _uri: &str)-> Result<(), OutputError> {Ok(())}
+    /// Called after all the pages of a document have been output
+    fn end_document(&mut self)-> Result<(), OutputError> {Ok(())}
 }
 
-
-pub struct HTMLOutput<'a>  {
-    file: &'a mut dyn std::io::Write,
-    flip_ctm: Transform,
-    last_ctm: Transform,
-    buf_ctm: Transform,
-    buf_font_size: f64,
-    buf: String
+/// A glyph as it's drawn
+#[non_exhaustive]
+pub struct TextGlyph<'g> {
+    /// The text rendering matrix. Glyph space is scaled by `font_size` / 1000 relative to it.
+    pub trm: Transform,
+    pub font_size: f64,
+    pub code: u32,
+    pub text: &'g str,
+    pub render_mode: TextRenderMode,
+    pub fill_colorspace: &'g ColorSpace,
+    pub fill_color: &'g [f64],
+    pub stroke_colorspace: &'g ColorSpace,
+    pub stroke_color: &'g [f64],
+    pub line_width: f64,
+    /// The character spacing (Tc) in unscaled text space units
+    pub character_spacing: f64,
+    /// The word spacing (Tw) in unscaled text space units
+    pub word_spacing: f64,
+    font: &'g dyn PdfFont,
 }
 
-fn insert_nbsp(input: &str) -> String {
-    let mut result = String::new();
-    let mut word_end = false;
-    let mut chars = input.chars().peekable();
-    while let Some(c) = chars.next() {
-        if c == ' ' {
-            if !word_end || chars.peek().filter(|x| **x != ' ').is_none() {
-                result += "&nbsp;";
-            } else {
-                result += " ";
-            }
-            word_end = false;
-        } else {
-            word_end = true;
-            result.push(c);
-        }
+impl<'g> TextGlyph<'g> {
+    /// The BaseFont of the font without the subset tag
+    pub fn base_font(&self) -> Option<String> {
+        let name = self.font.dict().get(b"BaseFont").ok()?.as_name().ok().map(pdf_to_utf8)?;
+        Some(match subset_tag(&name) {
+            Some(_) => name[7..].to_owned(),
+            None => name,
+        })
     }
-    result
+
+    /// Whether the font is bold, from its name, the ForceBold flag or the weight in its font
+    /// descriptor, or because the glyph is filled and stroked
+    pub fn is_bold(&self) -> bool {
+        let (_, bold, _) = css_font(self.base_font().as_deref());
+        let descriptor = self.font.descriptor();
+        let flags = descriptor.and_then(|d| d.get(b"Flags").ok()).and_then(|f| f.as_i64().ok()).unwrap_or(0);
+        let weight = descriptor.and_then(|d| d.get(b"FontWeight").ok()).map_or(0., as_num);
+        bold || flags & (1 << 18) != 0 || weight >= 600. ||
+            matches!(self.render_mode, TextRenderMode::FillStroke | TextRenderMode::FillStrokeClip)
+    }
+
+    /// Whether the font is italic, from its name or the Italic flag in its font descriptor
+    pub fn is_italic(&self) -> bool {
+        let (_, _, italic) = css_font(self.base_font().as_deref());
+        let flags = self.font.descriptor().and_then(|d| d.get(b"Flags").ok()).and_then(|f| f.as_i64().ok()).unwrap_or(0);
+        italic || flags & (1 << 6) != 0
+    }
+
+    /// The width of the font's space character in unscaled text space units, if code 32 is a
+    /// space. It doesn't include the character and word spacing.
+    pub fn space_width(&self) -> Option<f64> {
+        space_width(self.font).map(|width| width * self.font_size)
+    }
+
+    /// The outline of the glyph from the embedded font program, in glyph space where an em is
+    /// 1000 units. `None` when the font program isn't embedded or doesn't have the glyph.
+    pub fn outline(&self) -> Option<Path> {
+        self.font.glyph_outline(self.code).map(|ops| Path { ops })
+    }
+
+    // The font program as a font that browsers can load, see `EmbeddedFont::to_web_font`
+    fn web_font(&self) -> Option<Vec<u8>> {
+        let program = self.font.program()?;
+        let font = EmbeddedFont {
+            base_font: self.base_font().unwrap_or_default(),
+            kind: program.kind,
+            data: program.data.clone(),
+            type1_lengths: None,
+            glyphs: used_glyphs(self.font),
+            ascent: 0.,
+            descent: 0.,
+        };
+        font.to_web_font()
+    }
+
+    // identifies the glyph for reusing its outline
+    fn key(&self) -> (usize, u32) {
+        (self.font.dict() as *const Dictionary as usize, self.code)
+    }
+}
+
+
+/// Options for `HTMLOutput`
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct HTMLOptions {
+    /// Render the paths, images and shadings of the pages as inline SVG behind the text
+    pub background: bool,
+    /// Embed the font programs that browsers can load with `@font-face` and use them for the text
+    pub embed_fonts: bool,
+    /// Mark each character in red at its position and outline the pages, for checking text positions
+    pub debug_overlay: bool,
+    /// The title of the document
+    pub title: Option<String>,
+    /// Instead of positioning the text as it's drawn, write flowing HTML with the headings,
+    /// paragraphs, lists, tables, figures and links found by analyzing the layout of the pages.
+    /// The other options besides the title don't apply.
+    pub reflow: bool,
+}
+
+impl Default for HTMLOptions {
+    fn default() -> HTMLOptions {
+        HTMLOptions { background: false, embed_fonts: true, debug_overlay: false, title: None, reflow: false }
+    }
+}
+
+// The style of a text run. Runs are split where it changes.
+#[derive(Clone, Default, PartialEq)]
+struct HTMLTextStyle {
+    family: String,
+    bold: bool,
+    italic: bool,
+    color: Option<String>,
+    invisible: bool,
+}
+
+/// Renders the pages as a single HTML document with a positioned `<div>` per page and an
+/// absolutely positioned `<span>` per run of text, so that the text can be selected and searched
+/// where it's drawn. The document is written by `end_document`.
+pub struct HTMLOutput<'a>  {
+    file: &'a mut dyn std::io::Write,
+    options: HTMLOptions,
+    flip_ctm: Transform,
+    last_ctm: Transform,
+    buf_ctm: Transform,
+    buf_font_size: f64,
+    buf: String,
+    buf_style: HTMLTextStyle,
+    // the style of the glyph passed to `draw_glyph` for the following `output_character`
+    glyph_style: Option<HTMLTextStyle>,
+    // the pages are buffered because the font faces go in the head
+    body: String,
+    page_text: String,
+    background: Option<SVGOutput<'static>>,
+    // the family of the web font of each font dictionary, or None for fonts without one
+    fonts: HashMap<usize, Option<String>>,
+    font_faces: String,
+    // collects the pages for reflowing
+    layout: Option<LayoutBuilder>,
 }
 
 impl<'a> HTMLOutput<'a> {
     pub fn new(file: &mut dyn std::io::Write) -> HTMLOutput {
+        HTMLOutput::with_options(file, HTMLOptions::default())
+    }
+
+    /// Like `new` but with `options` instead of the defaults
+    pub fn with_options(file: &mut dyn std::io::Write, options: HTMLOptions) -> HTMLOutput<'_> {
+        let background = if options.background && !options.reflow { Some(SVGOutput::without_file()) } else { None };
+        let layout = if options.reflow { Some(LayoutBuilder::default()) } else { None };
         HTMLOutput {
             file,
+            options,
             flip_ctm: Transform2D::identity(),
             last_ctm: Transform2D::identity(),
             buf_ctm: Transform2D::identity(),
             buf: String::new(),
             buf_font_size: 0.,
+            buf_style: HTMLTextStyle::default(),
+            glyph_style: None,
+            body: String::new(),
+            page_text: String::new(),
+            background,
+            fonts: HashMap::new(),
+            font_faces: String::new(),
+            layout,
         }
     }
-    fn flush_string(&mut self) -> Result<(), OutputError>{
-        if self.buf.len() != 0 {
 
-            let position = self.buf_ctm.post_transform(&self.flip_ctm);
-            let transformed_font_size_vec = self.buf_ctm.transform_vector(vec2(self.buf_font_size, self.buf_font_size));
-            // get the length of one sized of the square with the same area with a rectangle of size (x, y)
-            let transformed_font_size = (transformed_font_size_vec.x * transformed_font_size_vec.y).sqrt();
-            let (x, y) = (position.m31, position.m32);
-            warn!("flush {} {:?}", self.buf, (x,y));
+    fn write_reflowed(&mut self) -> Result<(), OutputError> {
+        let pages = match &mut self.layout {
+            Some(layout) => layout.finish(),
+            None => return Ok(()),
+        };
+        let title = self.options.title.as_deref().unwrap_or("Document");
+        writeln!(self.file, "<!DOCTYPE html>")?;
+        writeln!(self.file, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(self.file, "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">")?;
+        writeln!(self.file, "<title>{}</title>", xml_escape(title))?;
+        writeln!(self.file, "<style>")?;
+        writeln!(self.file, "body {{ max-width: 40em; margin: 0 auto; padding: 1em; line-height: 1.5; }}")?;
+        writeln!(self.file, "figure img {{ max-width: 100%; height: auto; }}")?;
+        writeln!(self.file, "table {{ border-collapse: collapse; }}")?;
+        writeln!(self.file, "td, th {{ border: 1px solid #ccc; padding: 0.2em 0.5em; }}")?;
+        writeln!(self.file, "</style>\n</head>\n<body>")?;
+        for page in pages {
+            writeln!(self.file, "<!-- page {} -->", page.page_num)?;
+            for block in page.blocks {
+                let mut html = String::new();
+                match block {
+                    Block::Heading { level, spans } => writeln!(html, "<h{}>{}</h{}>", level, html_spans(&spans), level)?,
+                    Block::Paragraph(spans) => writeln!(html, "<p>{}</p>", html_spans(&spans))?,
+                    Block::List { ordered, start, items } => {
+                        let tag = if ordered { "ol" } else { "ul" };
+                        let start = if ordered && start != 1 { format!(" start=\"{}\"", start) } else { String::new() };
+                        writeln!(html, "<{}{}>", tag, start)?;
+                        for item in items {
+                            writeln!(html, "<li>{}</li>", html_spans(&item))?;
+                        }
+                        writeln!(html, "</{}>", tag)?;
+                    }
+                    Block::Table(rows) => {
+                        // a bold first row is the header
+                        let header = rows.first().is_some_and(|row| row.iter().flatten().all(|span| span.bold));
+                        writeln!(html, "<table>")?;
+                        for (i, row) in rows.iter().enumerate() {
+                            html += "<tr>";
+                            for cell in row {
+                                if i == 0 && header {
+                                    let cell: Vec<Span> = cell.iter().map(|span| Span { bold: false, ..span.clone() }).collect();
+                                    write!(html, "<th>{}</th>", html_spans(&cell))?;
+                                } else {
+                                    write!(html, "<td>{}</td>", html_spans(cell))?;
+                                }
+                            }
+                            html += "</tr>\n";
+                        }
+                        writeln!(html, "</table>")?;
+                    }
+                    Block::Figure(figure) => {
+                        let alt = figure.caption.as_deref().map_or("Figure".to_owned(), plain_text);
+                        let (x0, y0, x1, y1) = figure.bbox;
+                        html += "<figure>";
+                        if let Some(data_uri) = &figure.data_uri {
+                            write!(html, "<img src=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\">",
+                                   data_uri, (x1 - x0).round(), (y1 - y0).round(), xml_escape(&alt))?;
+                        }
+                        if let Some(caption) = &figure.caption {
+                            write!(html, "<figcaption>{}</figcaption>", html_spans(caption))?;
+                        }
+                        html += "</figure>\n";
+                    }
+                }
+                self.file.write_all(html.as_bytes())?;
+            }
+        }
+        writeln!(self.file, "</body>\n</html>")?;
+        Ok(())
+    }
 
-            write!(self.file, "<div style='position: absolute; left: {}px; top: {}px; font-size: {}px'>{}</div>\n",
-                   x, y, transformed_font_size, insert_nbsp(&self.buf))?;
+    // The family of the glyph's font program as a web font, which is added at the first use
+    fn web_font_family(&mut self, glyph: &TextGlyph) -> Option<String> {
+        let key = glyph.key().0;
+        if let Some(family) = self.fonts.get(&key) {
+            return family.clone();
         }
+        let family = glyph.web_font().map(|font| {
+            let family = format!("f{}", self.fonts.values().flatten().count() + 1);
+            let format = if font.starts_with(b"OTTO") { "opentype" } else { "truetype" };
+            let mime = if font.starts_with(b"OTTO") { "font/otf" } else { "font/ttf" };
+            self.font_faces += &format!("@font-face {{ font-family: '{}'; src: url(data:{};base64,{}) format('{}'); }}\n",
+                                        family, mime, base64_encode(&font), format);
+            family
+        });
+        self.fonts.insert(key, family.clone());
+        family
+    }
+
+    fn flush_string(&mut self) -> Result<(), OutputError>{
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let position = self.buf_ctm.post_transform(&self.flip_ctm);
+        // the scale of the text is the side of the square with the same area as a unit square in text space
+        let scale = (position.m11 * position.m22 - position.m12 * position.m21).abs().sqrt();
+        let font_size = self.buf_font_size * scale;
+        if font_size <= 0. {
+            return Ok(());
+        }
+        // the top of the line box is roughly an ascent above the baseline
+        let mut style = format!("left: {}px; top: {}px; font-size: {}px", position.m31, position.m32 - ASCENT * font_size, font_size);
+        // the y axis of text space points up while CSS's points down
+        let (a, b, c, d) = (position.m11 / scale, position.m12 / scale, -position.m21 / scale, -position.m22 / scale);
+        if !Transform::row_major(a, b, c, d, 0., 0.).approx_eq(&Transform::identity()) {
+            style += &format!("; transform: matrix({}, {}, {}, {}, 0, 0); transform-origin: 0 {}em", a, b, c, d, ASCENT);
+        }
+        let text_style = &self.buf_style;
+        if !text_style.family.is_empty() {
+            style += &format!("; font-family: {}", text_style.family);
+        }
+        if text_style.bold {
+            style += "; font-weight: bold";
+        }
+        if text_style.italic {
+            style += "; font-style: italic";
+        }
+        if text_style.invisible {
+            style += "; color: transparent";
+        } else if let Some(color) = &text_style.color {
+            style += &format!("; color: {}", color);
+        }
+        writeln!(self.page_text, "<span style=\"{}\">{}</span>", xml_escape(&style), xml_escape(&self.buf))?;
+        self.buf.clear();
         Ok(())
     }
 }
 
 type ArtBox = (f64, f64, f64, f64);
 
+// Spans as HTML with <strong> for bold, <em> for italic and links
+fn html_spans(spans: &[Span]) -> String {
+    let mut html = String::new();
+    let mut i = 0;
+    while i < spans.len() {
+        // the spans under the same link share it
+        let link = &spans[i].link;
+        let end = i + spans[i..].iter().take_while(|span| span.link == *link).count();
+        let mut text = String::new();
+        for span in &spans[i..end] {
+            let mut styled = xml_escape(&span.text);
+            if span.italic {
+                styled = format!("<em>{}</em>", styled);
+            }
+            if span.bold {
+                styled = format!("<strong>{}</strong>", styled);
+            }
+            text += &styled;
+        }
+        match link {
+            Some(uri) => html += &format!("<a href=\"{}\">{}</a>", xml_escape(uri), text),
+            None => html += &text,
+        }
+        i = end;
+    }
+    html
+}
+
+fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+// The ascent and descent of fonts as fractions of the font size, which are used for the
+// extent of the text because the actual ones aren't known
+const ASCENT: f64 = 0.85;
+const DESCENT: f64 = 0.15;
+
 impl<'a> OutputDev for HTMLOutput<'a> {
     fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
-        write!(self.file, "<meta charset='utf-8' /> ")?;
-        write!(self.file, "<!-- page {} -->", page_num)?;
-        write!(self.file, "<div id='page{}' style='position: relative; height: {}px; width: {}px; border: 1px black solid'>", page_num, media_box.ury - media_box.lly, media_box.urx - media_box.llx)?;
-        self.flip_ctm = Transform::row_major(1., 0., 0., -1., 0., media_box.ury - media_box.lly);
+        if let Some(layout) = &mut self.layout {
+            layout.begin_page(page_num, media_box);
+            return Ok(());
+        }
+        let (width, height) = (media_box.urx - media_box.llx, media_box.ury - media_box.lly);
+        let class = if self.options.debug_overlay { "page debug" } else { "page" };
+        writeln!(self.body, "<div class=\"{}\" id=\"page{}\" style=\"width: {}px; height: {}px\">", class, page_num, width, height)?;
+        self.flip_ctm = Transform::row_major(1., 0., 0., -1., -media_box.llx, media_box.ury);
+        if let Some(background) = &mut self.background {
+            background.begin_page(page_num, media_box, None)?;
+        }
         Ok(())
     }
     fn end_page(&mut self) -> Result<(), OutputError> {
+        if self.layout.is_some() {
+            return Ok(());
+        }
         self.flush_string()?;
-        self.buf = String::new();
         self.last_ctm = Transform::identity();
-        write!(self.file, "</div>")?;
+        if let Some(background) = &mut self.background {
+            background.end_page()?;
+            self.body += &String::from_utf8_lossy(&background.take_pages());
+        }
+        self.body += &self.page_text;
+        self.page_text.clear();
+        writeln!(self.body, "</div>")?;
+        Ok(())
+    }
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        if self.layout.is_some() {
+            return self.write_reflowed();
+        }
+        let title = self.options.title.as_deref().unwrap_or("Document");
+        writeln!(self.file, "<!DOCTYPE html>")?;
+        writeln!(self.file, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", xml_escape(title))?;
+        writeln!(self.file, "<style>")?;
+        writeln!(self.file, ".page {{ position: relative; overflow: hidden; margin: 8px auto; background: white; }}")?;
+        writeln!(self.file, ".page > svg {{ position: absolute; left: 0; top: 0; }}")?;
+        writeln!(self.file, ".page span {{ position: absolute; white-space: pre; line-height: 1; }}")?;
+        if self.options.debug_overlay {
+            writeln!(self.file, ".debug {{ outline: 1px solid black; }}")?;
+            writeln!(self.file, ".page .debug {{ color: red; outline: none; }}")?;
+        }
+        self.file.write_all(self.font_faces.as_bytes())?;
+        writeln!(self.file, "</style>\n</head>\n<body>")?;
+        self.file.write_all(self.body.as_bytes())?;
+        writeln!(self.file, "</body>\n</html>")?;
+        self.body.clear();
+        self.fonts.clear();
+        self.font_faces.clear();
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        if let Some(layout) = &mut self.layout {
+            layout.glyph(glyph);
+            return Ok(());
+        }
+        let (family, _, _) = css_font(glyph.base_font().as_deref());
+        let (bold, italic) = (glyph.is_bold(), glyph.is_italic());
+        let family = match self.options.embed_fonts.then(|| self.web_font_family(glyph)).flatten() {
+            // the generic families are still there for characters missing from the font
+            Some(web_font) => format!("'{}', {}", web_font, family),
+            None => family,
+        };
+        let color = match glyph.render_mode {
+            TextRenderMode::Stroke | TextRenderMode::StrokeClip => Some(svg_color(glyph.stroke_colorspace, glyph.stroke_color)),
+            _ => Some(svg_color(glyph.fill_colorspace, glyph.fill_color)),
+        };
+        let invisible = matches!(glyph.render_mode, TextRenderMode::Invisible | TextRenderMode::Clip);
+        self.glyph_style = Some(HTMLTextStyle { family, bold, italic, color, invisible });
         Ok(())
     }
     fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError>{
-        if trm.approx_eq(&self.last_ctm) {
-            let position = trm.post_transform(&self.flip_ctm);
-            let (x, y) = (position.m31, position.m32);
-
-            warn!("accum {} {:?}", char, (x,y));
+        if let Some(layout) = &mut self.layout {
+            layout.character(trm, width, spacing, font_size, char);
+            return Ok(());
+        }
+        // Type3 glyphs aren't passed to draw_glyph. They're already drawn when there's a background.
+        let style = self.glyph_style.take()
+            .unwrap_or_else(|| HTMLTextStyle { invisible: self.background.is_some(), ..HTMLTextStyle::default() });
+        if trm.approx_eq(&self.last_ctm) && style == self.buf_style {
             self.buf += char;
         } else {
-            warn!("flush {} {:?} {:?} {} {} {}", char, trm, self.last_ctm, width, font_size, spacing);
             self.flush_string()?;
             self.buf = char.to_owned();
             self.buf_font_size = font_size;
             self.buf_ctm = *trm;
+            self.buf_style = style;
+        }
+        if self.options.debug_overlay {
+            let position = trm.post_transform(&self.flip_ctm);
+            let font_size = font_size * (position.m11 * position.m22 - position.m12 * position.m21).abs().sqrt();
+            writeln!(self.page_text, "<span class=\"debug\" style=\"left: {}px; top: {}px; font-size: {}px\">{}</span>",
+                     position.m31, position.m32 - ASCENT * font_size, font_size, xml_escape(char))?;
         }
-        let position = trm.post_transform(&self.flip_ctm);
-        let transformed_font_size_vec = trm.transform_vector(vec2(font_size, font_size));
-        // get the length of one sized of the square with the same area with a rectangle of size (x, y)
-        let transformed_font_size = (transformed_font_size_vec.x * transformed_font_size_vec.y).sqrt();
-        let (x, y) = (position.m31, position.m32);
-        write!(self.file, "<div style='position: absolute; color: red; left: {}px; top: {}px; font-size: {}px'>{}</div>",
-               x, y, transformed_font_size, char)?;
         self.last_ctm = trm.pre_transform(&Transform2D::create_translation(width * font_size + spacing, 0.));
-
         Ok(())
     }
     fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
     fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
     fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn fill(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.fill_path(ctm, colorspace, color, path, FillRule::NonZero)
+    }
+    fn fill_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.fill_path(ctm, colorspace, color, path, fill_rule),
+            None => Ok(()),
+        }
+    }
+    fn stroke(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.stroke_path(ctm, colorspace, color, path, &StrokeStyle::new())
+    }
+    fn stroke_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, style: &StrokeStyle) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.stroke_path(ctm, colorspace, color, path, style),
+            None => Ok(()),
+        }
+    }
+    fn image(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], image: &Image) -> Result<(), OutputError> {
+        if let Some(layout) = &mut self.layout {
+            layout.image(ctm, image_data_uri(colorspace, color, image));
+        }
+        match &mut self.background {
+            Some(background) => background.image(ctm, colorspace, color, image),
+            None => Ok(()),
+        }
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        if let Some(layout) = &mut self.layout {
+            layout.gap(gap, font_size, space_width);
+        }
+        Ok(())
+    }
+    fn link(&mut self, rect: &(f64, f64, f64, f64), uri: &str) -> Result<(), OutputError> {
+        if let Some(layout) = &mut self.layout {
+            layout.link(rect, uri);
+        }
+        Ok(())
+    }
+    fn set_transparency(&mut self, transparency: &Transparency) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.set_transparency(transparency),
+            None => Ok(()),
+        }
+    }
+    fn shade(&mut self, ctm: &Transform, shading: &Shading, path: Option<(&Transform, &Path, FillRule)>) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.shade(ctm, shading, path),
+            None => Ok(()),
+        }
+    }
+    fn begin_pattern(&mut self, pattern: &TilingPattern) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.begin_pattern(pattern),
+            None => Ok(()),
+        }
+    }
+    fn end_pattern(&mut self) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.end_pattern(),
+            None => Ok(()),
+        }
+    }
+    fn fill_path_with_pattern(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule, pattern: &TilingPattern) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.fill_path_with_pattern(ctm, path, fill_rule, pattern),
+            None => Ok(()),
+        }
+    }
+    fn save_state(&mut self) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.save_state(),
+            None => Ok(()),
+        }
+    }
+    fn restore_state(&mut self) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.restore_state(),
+            None => Ok(()),
+        }
+    }
+    fn clip(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        match &mut self.background {
+            Some(background) => background.clip(ctm, path, fill_rule),
+            None => Ok(()),
+        }
+    }
 }
 
+/// Renders the pages as SVG. The pages are stacked vertically in a single document, each in its
+/// own nested `<svg>` element, and the document is written by `end_document`.
 pub struct SVGOutput<'a>  {
-    file: &'a mut dyn std::io::Write
+    // None when the pages are taken with `take_pages` instead of written as a document
+    file: Option<&'a mut dyn std::io::Write>,
+    // the pages are buffered because the size of the document depends on all of them
+    body: Vec<u8>,
+    width: f64,
+    height: f64,
+    media_box: MediaBox,
+    // used to give gradients and clip paths unique ids
+    next_id: usize,
+    transparency: Transparency,
+    // the number of groups opened by clips in the current graphics state and in the saved ones
+    clip_groups: usize,
+    saved_clip_groups: Vec<usize>,
+    glyph_outlines: bool,
+    // the ids of the glyph outlines that have been defined, or None for glyphs without an outline
+    glyphs: HashMap<(usize, u32), Option<String>>,
 }
 impl<'a> SVGOutput<'a> {
     pub fn new(file: &mut dyn std::io::Write) -> SVGOutput {
-        SVGOutput{file}
+        SVGOutput { file: Some(file), ..SVGOutput::without_file() }
     }
-}
 
-impl<'a> OutputDev for SVGOutput<'a> {
-    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
-        let ver = 1.1;
-        write!(self.file, "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n")?;
-        if ver == 1.1 {
-            write!(self.file, r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">"#)?;
-        } else {
-            write!(self.file, r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.0//EN" "http://www.w3.org/TR/2001/REC-SVG-20010904/DTD/svg10.dtd">"#)?;
+    fn without_file() -> SVGOutput<'static> {
+        SVGOutput {
+            file: None,
+            body: Vec::new(),
+            width: 0.,
+            height: 0.,
+            media_box: MediaBox { llx: 0., lly: 0., urx: 0., ury: 0. },
+            next_id: 0,
+            transparency: Transparency::new(),
+            clip_groups: 0,
+            saved_clip_groups: Vec::new(),
+            glyph_outlines: false,
+            glyphs: HashMap::new(),
+        }
+    }
+
+    /// Like `new` but text is drawn with the outlines of the glyphs from the embedded font
+    /// programs, so it looks the same without the fonts. Each glyph is defined once and reused.
+    /// Glyphs of fonts that aren't embedded are still drawn as text.
+    pub fn with_glyph_outlines(file: &mut dyn std::io::Write) -> SVGOutput<'_> {
+        SVGOutput { glyph_outlines: true, ..SVGOutput::new(file) }
+    }
+
+    // The pages output since the last call, each as an `<svg>` element at the origin
+    fn take_pages(&mut self) -> Vec<u8> {
+        self.width = 0.;
+        self.height = 0.;
+        std::mem::take(&mut self.body)
+    }
+
+    // The id of the definition of the glyph's outline, which is written at the glyph's first use
+    fn glyph_outline_id(&mut self, glyph: &TextGlyph) -> Result<Option<String>, OutputError> {
+        if let Some(id) = self.glyphs.get(&glyph.key()) {
+            return Ok(id.clone());
+        }
+        let id = match glyph.outline() {
+            Some(outline) => {
+                let id = self.new_id("glyph");
+                writeln!(self.body, "<defs><path id='{}' d='{}' /></defs>", id, svg_path_data(&outline))?;
+                Some(id)
+            }
+            None => None,
+        };
+        self.glyphs.insert(glyph.key(), id.clone());
+        Ok(id)
+    }
+
+    // The paint attributes of a glyph, or None when it's invisible. `line_width` is in the space
+    // that the glyph is drawn in.
+    fn glyph_paint(&self, glyph: &TextGlyph, line_width: f64) -> Option<String> {
+        let style = StrokeStyle { line_width, ..StrokeStyle::new() };
+        Some(match glyph.render_mode {
+            TextRenderMode::Invisible | TextRenderMode::Clip => return None,
+            TextRenderMode::Fill | TextRenderMode::FillClip => self.fill_attributes(glyph.fill_colorspace, glyph.fill_color, FillRule::NonZero),
+            TextRenderMode::Stroke | TextRenderMode::StrokeClip => self.stroke_attributes(glyph.stroke_colorspace, glyph.stroke_color, &style),
+            TextRenderMode::FillStroke | TextRenderMode::FillStrokeClip => {
+                let stroke = self.stroke_attributes(glyph.stroke_colorspace, glyph.stroke_color, &style);
+                // the stroke attributes start with fill='none' which is replaced by the fill
+                let stroke = stroke.trim_start_matches("fill='none' ").to_owned();
+                let fill = self.fill_attributes(glyph.fill_colorspace, glyph.fill_color, FillRule::NonZero);
+                let fill = fill.split(" style=").next().unwrap_or("").to_owned();
+                format!("{} {}", fill, stroke)
+            }
+        })
+    }
+
+    fn new_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}{}", prefix, self.next_id)
+    }
+
+    fn blend_style(&self) -> String {
+        match svg_blend_mode(self.transparency.blend_mode) {
+            Some(mode) => format!(" style='mix-blend-mode:{}'", mode),
+            None => String::new(),
+        }
+    }
+
+    fn fill_attributes(&self, colorspace: &ColorSpace, color: &[f64], fill_rule: FillRule) -> String {
+        let mut attributes = format!("fill='{}'", svg_color(colorspace, color));
+        if fill_rule == FillRule::EvenOdd {
+            attributes += " fill-rule='evenodd'";
+        }
+        if self.transparency.fill_alpha < 1. {
+            attributes += &format!(" fill-opacity='{}'", self.transparency.fill_alpha);
         }
-        if let Some(art_box) = art_box {
-            let width = art_box.2 - art_box.0;
-            let height = art_box.3 - art_box.1;
-            let y = media_box.ury - art_box.1 - height;
-            write!(self.file, "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" version=\"{}\" viewBox='{} {} {} {}'>", width, height, ver, art_box.0, y, width, height)?;
+        attributes + &self.blend_style()
+    }
+
+    fn stroke_attributes(&self, colorspace: &ColorSpace, color: &[f64], style: &StrokeStyle) -> String {
+        let mut attributes = format!("fill='none' stroke='{}'", svg_color(colorspace, color));
+        if style.line_width > 0. {
+            attributes += &format!(" stroke-width='{}'", style.line_width);
         } else {
-            let width = media_box.urx - media_box.llx;
-            let height = media_box.ury - media_box.lly;
-            write!(self.file, "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" version=\"{}\" viewBox='{} {} {} {}'>", width, height, ver, media_box.llx, media_box.lly, width, height)?;
+            // a width of 0 is the thinnest line that can be drawn
+            attributes += " stroke-width='1' vector-effect='non-scaling-stroke'";
+        }
+        match style.line_cap {
+            LineCap::Butt => {}
+            LineCap::Round => attributes += " stroke-linecap='round'",
+            LineCap::ProjectingSquare => attributes += " stroke-linecap='square'",
+        }
+        match style.line_join {
+            LineJoin::Miter => {}
+            LineJoin::Round => attributes += " stroke-linejoin='round'",
+            LineJoin::Bevel => attributes += " stroke-linejoin='bevel'",
+        }
+        if style.miter_limit != 4. {
+            attributes += &format!(" stroke-miterlimit='{}'", style.miter_limit.max(1.));
+        }
+        // an array of all zeros would be invisible in PDF but isn't allowed by SVG
+        if style.dash_array.iter().any(|d| *d > 0.) {
+            let dashes: Vec<_> = style.dash_array.iter().map(|d| d.to_string()).collect();
+            attributes += &format!(" stroke-dasharray='{}' stroke-dashoffset='{}'", dashes.join(" "), style.dash_phase);
+        }
+        if self.transparency.stroke_alpha < 1. {
+            attributes += &format!(" stroke-opacity='{}'", self.transparency.stroke_alpha);
+        }
+        attributes + &self.blend_style()
+    }
+}
+
+fn svg_matrix(m: &Transform) -> String {
+    format!("matrix({}, {}, {}, {}, {}, {})", m.m11, m.m12, m.m21, m.m22, m.m31, m.m32)
+}
+
+// path data in device space, for paths that use a paint server that's defined in device space
+fn svg_device_path_data(ctm: &Transform, path: &Path) -> String {
+    let p = |x: f64, y: f64| {
+        let p = ctm.transform_point(point2(x, y));
+        format!("{} {}", p.x, p.y)
+    };
+    let mut d = Vec::new();
+    for op in &path.ops {
+        match *op {
+            PathOp::MoveTo(x, y) => d.push(format!("M{}", p(x, y))),
+            PathOp::LineTo(x, y) => d.push(format!("L{}", p(x, y))),
+            PathOp::CurveTo(x1, y1, x2, y2, x, y) => d.push(format!("C{} {} {}", p(x1, y1), p(x2, y2), p(x, y))),
+            PathOp::Close => d.push("Z".to_owned()),
+            PathOp::Rect(x, y, width, height) => {
+                d.push(format!("M{} L{} L{} L{} Z", p(x, y), p(x + width, y), p(x + width, y + height), p(x, y + height)));
+            }
+        }
+    }
+    d.join(" ")
+}
+
+fn svg_path_data(path: &Path) -> String {
+    let mut d = Vec::new();
+    for op in &path.ops {
+        match op {
+            &PathOp::MoveTo(x, y) => { d.push(format!("M{} {}", x, y))}
+            &PathOp::LineTo(x, y) => { d.push(format!("L{} {}", x, y))},
+            &PathOp::CurveTo(x1, y1, x2, y2, x, y) => { d.push(format!("C{} {} {} {} {} {}", x1, y1, x2, y2, x, y))},
+            &PathOp::Close => { d.push(format!("Z"))},
+            &PathOp::Rect(x, y, width, height) => {
+                d.push(format!("M{} {}", x, y));
+                d.push(format!("L{} {}", x + width, y));
+                d.push(format!("L{} {}", x + width, y + height));
+                d.push(format!("L{} {}", x, y + height));
+                d.push(format!("Z"));
+            }
+
+        }
+    }
+    d.join(" ")
+}
+
+// colors that can't be converted are drawn in black
+fn svg_color(colorspace: &ColorSpace, color: &[f64]) -> String {
+    let [r, g, b] = colorspace.to_rgb(color).unwrap_or([0., 0., 0.]);
+    format!("#{:02x}{:02x}{:02x}", (r * 255.).round() as u8, (g * 255.).round() as u8, (b * 255.).round() as u8)
+}
+
+fn svg_fill_rule(fill_rule: FillRule) -> &'static str {
+    match fill_rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+fn svg_blend_mode(blend_mode: BlendMode) -> Option<&'static str> {
+    Some(match blend_mode {
+        BlendMode::Normal => return None,
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Overlay => "overlay",
+        BlendMode::Darken => "darken",
+        BlendMode::Lighten => "lighten",
+        BlendMode::ColorDodge => "color-dodge",
+        BlendMode::ColorBurn => "color-burn",
+        BlendMode::HardLight => "hard-light",
+        BlendMode::SoftLight => "soft-light",
+        BlendMode::Difference => "difference",
+        BlendMode::Exclusion => "exclusion",
+        BlendMode::Hue => "hue",
+        BlendMode::Saturation => "saturation",
+        BlendMode::Color => "color",
+        BlendMode::Luminosity => "luminosity",
+    })
+}
+
+// escape text for use in XML content and attributes, dropping the characters that XML doesn't allow
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
         }
-        write!(self.file, "\n")?;
+    }
+    encoded
+}
+
+// The CSS font properties for a font. The core fonts are mapped to their common equivalents.
+// A data URI with the image as JPEG, JPEG 2000 or PNG. Stencil masks are painted with the color.
+fn image_data_uri(colorspace: &ColorSpace, color: &[f64], image: &Image) -> Option<String> {
+    let (mime_type, data) = match image.format {
+        ImageFormat::Jpeg => ("image/jpeg", image.data.clone()),
+        ImageFormat::Jpeg2000 => ("image/jp2", image.data.clone()),
+        ImageFormat::Raw => {
+            let fill = colorspace.to_rgb(color).unwrap_or([0., 0., 0.]);
+            ("image/png", png::encode_rgba(image.width, image.height, &image.to_rgba(fill)?))
+        }
+        ImageFormat::Other => return None,
+    };
+    Some(format!("data:{};base64,{}", mime_type, base64_encode(&data)))
+}
+
+fn svg_font(base_font: Option<&str>) -> String {
+    let (family, bold, italic) = css_font(base_font);
+    let mut font = format!("font-family='{}'", xml_escape(&family));
+    if bold {
+        font += " font-weight='bold'";
+    }
+    if italic {
+        font += " font-style='italic'";
+    }
+    font
+}
+
+// The CSS font family list for a base font, and whether it's bold and italic
+fn css_font(base_font: Option<&str>) -> (String, bool, bool) {
+    let name = base_font.unwrap_or("");
+    let family = if name.starts_with("Helvetica") || name.starts_with("Arial") {
+        "Helvetica, Arial, sans-serif".to_owned()
+    } else if name.starts_with("Times") {
+        "'Times New Roman', Times, serif".to_owned()
+    } else if name.starts_with("Courier") {
+        "'Courier New', Courier, monospace".to_owned()
+    } else if name.is_empty() {
+        "serif".to_owned()
+    } else {
+        // drop the style, e.g. Minion-Bold or Garamond,Italic
+        let family = name.split(['-', ',']).next().unwrap_or(name);
+        format!("\"{}\", serif", family)
+    };
+    let bold = name.contains("Bold") || name.contains("Black") || name.contains("Heavy");
+    let italic = name.contains("Italic") || name.contains("Oblique");
+    (family, bold, italic)
+}
+
+// the number of stops that axial and radial shadings are sampled with
+const GRADIENT_STOPS: usize = 16;
+
+impl<'a> OutputDev for SVGOutput<'a> {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        // the page is flipped so that PDF's y axis points down and the view box is in those coordinates
+        let (x, y, width, height) = match art_box {
+            Some(art_box) => {
+                let width = art_box.2 - art_box.0;
+                let height = art_box.3 - art_box.1;
+                (art_box.0, media_box.ury - art_box.1 - height, width, height)
+            }
+            None => (media_box.llx, 0., media_box.urx - media_box.llx, media_box.ury - media_box.lly),
+        };
+        writeln!(self.body, "<svg x='0' y='{}' width='{}' height='{}' viewBox='{} {} {} {}'>", self.height, width, height, x, y, width, height)?;
+        self.width = self.width.max(width);
+        self.height += height;
+        self.media_box = *media_box;
+        self.transparency = Transparency::new();
         type Mat = Transform;
 
         let ctm = Mat::create_scale(1., -1.).post_translate(vec2(0., media_box.ury));
-        write!(self.file, "<g transform='matrix({}, {}, {}, {}, {}, {})'>\n",
-               ctm.m11,
-               ctm.m12,
-               ctm.m21,
-               ctm.m22,
-               ctm.m31,
-               ctm.m32,
-        )?;
+        writeln!(self.body, "<g transform='{}'>", svg_matrix(&ctm))?;
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError>{
+        // close the clips of states that weren't restored
+        let open_groups = self.clip_groups + self.saved_clip_groups.drain(..).sum::<usize>();
+        self.clip_groups = 0;
+        write!(self.body, "{}", "</g>".repeat(open_groups))?;
+        writeln!(self.body, "</g>")?;
+        writeln!(self.body, "</svg>")?;
+        Ok(())
+    }
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        if let Some(file) = &mut self.file {
+            writeln!(file, "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>")?;
+            writeln!(file, r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">"#)?;
+            writeln!(file, "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"1.1\" viewBox='0 0 {} {}'>",
+                     self.width, self.height, self.width, self.height)?;
+            file.write_all(&self.body)?;
+            writeln!(file, "</svg>")?;
+        }
+        self.body.clear();
+        self.width = 0.;
+        self.height = 0.;
+        self.glyphs.clear();
+        Ok(())
+    }
+    fn output_character(&mut self, _trm: &Transform, _width: f64, _spacing: f64, _font_size: f64, _char: &str) -> Result<(), OutputError>{
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn fill(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError>{
+        self.fill_path(ctm, colorspace, color, path, FillRule::NonZero)
+    }
+    fn fill_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        writeln!(self.body, "<path transform='{}' d='{}' {} />", svg_matrix(ctm), svg_path_data(path), self.fill_attributes(colorspace, color, fill_rule))?;
+        Ok(())
+    }
+    fn stroke(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.stroke_path(ctm, colorspace, color, path, &StrokeStyle::new())
+    }
+    fn stroke_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, style: &StrokeStyle) -> Result<(), OutputError> {
+        writeln!(self.body, "<path transform='{}' d='{}' {} />", svg_matrix(ctm), svg_path_data(path), self.stroke_attributes(colorspace, color, style))?;
+        Ok(())
+    }
+    fn set_transparency(&mut self, transparency: &Transparency) -> Result<(), OutputError> {
+        self.transparency = transparency.clone();
+        Ok(())
+    }
+    fn save_state(&mut self) -> Result<(), OutputError> {
+        self.saved_clip_groups.push(self.clip_groups);
+        self.clip_groups = 0;
+        Ok(())
+    }
+    fn restore_state(&mut self) -> Result<(), OutputError> {
+        write!(self.body, "{}", "</g>".repeat(self.clip_groups))?;
+        self.clip_groups = self.saved_clip_groups.pop().unwrap_or(0);
+        Ok(())
+    }
+    fn clip(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        let id = self.new_id("clip");
+        writeln!(self.body, "<defs><clipPath id='{}'><path transform='{}' d='{}' clip-rule='{}' /></clipPath></defs><g clip-path='url(#{})'>",
+                 id, svg_matrix(ctm), svg_path_data(path), svg_fill_rule(fill_rule), id)?;
+        self.clip_groups += 1;
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        if self.glyph_outlines && !matches!(glyph.render_mode, TextRenderMode::Invisible | TextRenderMode::Clip) {
+            if let Some(id) = self.glyph_outline_id(glyph)? {
+                // the outline is in glyph space
+                let scale = glyph.font_size / 1000.;
+                let line_width = if scale != 0. { glyph.line_width / scale } else { glyph.line_width };
+                if let Some(paint) = self.glyph_paint(glyph, line_width) {
+                    writeln!(self.body, "<use xlink:href='#{}' transform='{} scale({})' {} />", id, svg_matrix(&glyph.trm), scale, paint)?;
+                }
+                return Ok(());
+            }
+        }
+        let text = xml_escape(glyph.text);
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        let paint = match self.glyph_paint(glyph, glyph.line_width) {
+            Some(paint) => paint,
+            None => return Ok(()),
+        };
+        // text is drawn upright in the flipped page
+        writeln!(self.body, "<text transform='{} scale(1, -1)' font-size='{}' {} xml:space='preserve' {}>{}</text>",
+                 svg_matrix(&glyph.trm), glyph.font_size, svg_font(glyph.base_font().as_deref()), paint, text)?;
+        Ok(())
+    }
+    fn image(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], image: &Image) -> Result<(), OutputError> {
+        let data_uri = match image_data_uri(colorspace, color, image) {
+            Some(data_uri) => data_uri,
+            None => return Ok(()),
+        };
+        let opacity = if self.transparency.fill_alpha < 1. { format!(" opacity='{}'", self.transparency.fill_alpha) } else { String::new() };
+        // images are drawn top down in the unit square
+        writeln!(self.body, "<image transform='{} matrix(1, 0, 0, -1, 0, 1)' width='1' height='1' preserveAspectRatio='none'{}{} xlink:href='{}' />",
+                 svg_matrix(ctm), opacity, self.blend_style(), data_uri)?;
+        Ok(())
+    }
+    fn shade(&mut self, ctm: &Transform, shading: &Shading, path: Option<(&Transform, &Path, FillRule)>) -> Result<(), OutputError> {
+        // `sh` paints the whole page, which is limited by the clip
+        let media_box = self.media_box;
+        let page = Path { ops: vec![PathOp::Rect(media_box.llx, media_box.lly, media_box.urx - media_box.llx, media_box.ury - media_box.lly)] };
+        let identity = Transform2D::identity();
+        let (path_ctm, path, fill_rule) = path.unwrap_or((&identity, &page, FillRule::NonZero));
+        let opacity = if self.transparency.fill_alpha < 1. { format!(" opacity='{}'", self.transparency.fill_alpha) } else { String::new() };
+        match &shading.geometry {
+            ShadingGeometry::Axial { domain, .. } | ShadingGeometry::Radial { domain, .. } => {
+                // gradients are in the user space of the path that they fill
+                let gradient_transform = match path_ctm.inverse() {
+                    Some(inverse) => ctm.post_transform(&inverse),
+                    None => return Ok(()),
+                };
+                let id = self.new_id("shading");
+                // SVG gradients always extend past their ends, like shadings with Extend [true true]
+                match shading.geometry {
+                    ShadingGeometry::Axial { coords: [x0, y0, x1, y1], .. } => {
+                        write!(self.body, "<defs><linearGradient id='{}' gradientUnits='userSpaceOnUse' gradientTransform='{}' x1='{}' y1='{}' x2='{}' y2='{}'>",
+                               id, svg_matrix(&gradient_transform), x0, y0, x1, y1)?;
+                    }
+                    ShadingGeometry::Radial { coords: [x0, y0, r0, x1, y1, r1], .. } => {
+                        write!(self.body, "<defs><radialGradient id='{}' gradientUnits='userSpaceOnUse' gradientTransform='{}' fx='{}' fy='{}' fr='{}' cx='{}' cy='{}' r='{}'>",
+                               id, svg_matrix(&gradient_transform), x0, y0, r0, x1, y1, r1)?;
+                    }
+                    _ => unreachable!()
+                }
+                for i in 0..=GRADIENT_STOPS {
+                    let offset = i as f64 / GRADIENT_STOPS as f64;
+                    let color = shading.color_at(domain[0] + offset * (domain[1] - domain[0]));
+                    write!(self.body, "<stop offset='{}' stop-color='{}'/>", offset, svg_color(&shading.colorspace, &color))?;
+                }
+                let element = if matches!(shading.geometry, ShadingGeometry::Axial { .. }) { "linearGradient" } else { "radialGradient" };
+                write!(self.body, "</{}></defs>", element)?;
+                writeln!(self.body, "<path transform='{}' d='{}' fill='url(#{})' fill-rule='{}'{}{} />",
+                       svg_matrix(path_ctm), svg_path_data(path), id, svg_fill_rule(fill_rule), opacity, self.blend_style())?;
+            }
+            ShadingGeometry::Polygons(polygons) => {
+                let id = self.new_id("clip");
+                write!(self.body, "<defs><clipPath id='{}'><path transform='{}' d='{}' clip-rule='{}' /></clipPath></defs>",
+                       id, svg_matrix(path_ctm), svg_path_data(path), svg_fill_rule(fill_rule))?;
+                write!(self.body, "<g clip-path='url(#{})'{}{}><g transform='{}'>", id, opacity, self.blend_style(), svg_matrix(ctm))?;
+                for polygon in polygons {
+                    let d: Vec<_> = polygon.points.iter().map(|(x, y)| format!("{} {}", x, y)).collect();
+                    write!(self.body, "<path d='M{} Z' fill='{}' />", d.join(" L"), svg_color(&shading.colorspace, &polygon.color))?;
+                }
+                writeln!(self.body, "</g></g>")?;
+            }
+        }
+        Ok(())
+    }
+    fn begin_pattern(&mut self, pattern: &TilingPattern) -> Result<(), OutputError> {
+        // the cell is drawn in device space so we undo the pattern matrix
+        let inverse = pattern.matrix.inverse().unwrap_or_else(Transform2D::identity);
+        write!(self.body, "<defs><pattern id='pattern{}' patternUnits='userSpaceOnUse' patternTransform='{}' x='{}' y='{}' width='{}' height='{}'><g transform='{}'>",
+               pattern.id, svg_matrix(&pattern.matrix), pattern.bbox[0], pattern.bbox[1], pattern.x_step.abs(), pattern.y_step.abs(), svg_matrix(&inverse))?;
+        Ok(())
+    }
+    fn end_pattern(&mut self) -> Result<(), OutputError> {
+        writeln!(self.body, "</g></pattern></defs>")?;
+        Ok(())
+    }
+    fn fill_path_with_pattern(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule, pattern: &TilingPattern) -> Result<(), OutputError> {
+        // patterns are in the user space of the elements that they fill and they're defined in
+        // device space, so the path is given in device space
+        let opacity = if self.transparency.fill_alpha < 1. { format!(" fill-opacity='{}'", self.transparency.fill_alpha) } else { String::new() };
+        writeln!(self.body, "<path d='{}' fill='url(#pattern{})' fill-rule='{}'{}{} />",
+                 svg_device_path_data(ctm, path), pattern.id, svg_fill_rule(fill_rule), opacity, self.blend_style())?;
+        Ok(())
+    }
+}
+
+/// Sends everything to two output devices, e.g. to extract the text and render the pages with
+/// one pass over the document
+pub struct TeeOutput<'a, 'b> {
+    first: &'a mut dyn OutputDev,
+    second: &'b mut dyn OutputDev,
+}
+
+impl<'a, 'b> TeeOutput<'a, 'b> {
+    pub fn new(first: &'a mut dyn OutputDev, second: &'b mut dyn OutputDev) -> TeeOutput<'a, 'b> {
+        TeeOutput { first, second }
+    }
+}
+
+impl<'a, 'b> OutputDev for TeeOutput<'a, 'b> {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.first.begin_page(page_num, media_box, art_box)?;
+        self.second.begin_page(page_num, media_box, art_box)
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.first.end_page()?;
+        self.second.end_page()
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.first.output_character(trm, width, spacing, font_size, char)?;
+        self.second.output_character(trm, width, spacing, font_size, char)
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.first.begin_word()?;
+        self.second.begin_word()
+    }
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.first.end_word()?;
+        self.second.end_word()
+    }
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.first.end_line()?;
+        self.second.end_line()
+    }
+    fn text_gap(&mut self, trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        self.first.text_gap(trm, gap, font_size, space_width)?;
+        self.second.text_gap(trm, gap, font_size, space_width)
+    }
+    fn stroke(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.first.stroke(ctm, colorspace, color, path)?;
+        self.second.stroke(ctm, colorspace, color, path)
+    }
+    fn fill(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.first.fill(ctm, colorspace, color, path)?;
+        self.second.fill(ctm, colorspace, color, path)
+    }
+    fn stroke_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, style: &StrokeStyle) -> Result<(), OutputError> {
+        self.first.stroke_path(ctm, colorspace, color, path, style)?;
+        self.second.stroke_path(ctm, colorspace, color, path, style)
+    }
+    fn fill_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        self.first.fill_path(ctm, colorspace, color, path, fill_rule)?;
+        self.second.fill_path(ctm, colorspace, color, path, fill_rule)
+    }
+    fn image(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], image: &Image) -> Result<(), OutputError> {
+        self.first.image(ctm, colorspace, color, image)?;
+        self.second.image(ctm, colorspace, color, image)
+    }
+    fn set_transparency(&mut self, transparency: &Transparency) -> Result<(), OutputError> {
+        self.first.set_transparency(transparency)?;
+        self.second.set_transparency(transparency)
+    }
+    fn shade(&mut self, ctm: &Transform, shading: &Shading, path: Option<(&Transform, &Path, FillRule)>) -> Result<(), OutputError> {
+        self.first.shade(ctm, shading, path)?;
+        self.second.shade(ctm, shading, path)
+    }
+    fn begin_pattern(&mut self, pattern: &TilingPattern) -> Result<(), OutputError> {
+        self.first.begin_pattern(pattern)?;
+        self.second.begin_pattern(pattern)
+    }
+    fn end_pattern(&mut self) -> Result<(), OutputError> {
+        self.first.end_pattern()?;
+        self.second.end_pattern()
+    }
+    fn fill_path_with_pattern(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule, pattern: &TilingPattern) -> Result<(), OutputError> {
+        self.first.fill_path_with_pattern(ctm, path, fill_rule, pattern)?;
+        self.second.fill_path_with_pattern(ctm, path, fill_rule, pattern)
+    }
+    fn save_state(&mut self) -> Result<(), OutputError> {
+        self.first.save_state()?;
+        self.second.save_state()
+    }
+    fn restore_state(&mut self) -> Result<(), OutputError> {
+        self.first.restore_state()?;
+        self.second.restore_state()
+    }
+    fn clip(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        self.first.clip(ctm, path, fill_rule)?;
+        self.second.clip(ctm, path, fill_rule)
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.first.draw_glyph(glyph)?;
+        self.second.draw_glyph(glyph)
+    }
+    fn link(&mut self, rect: &(f64, f64, f64, f64), uri: &str) -> Result<(), OutputError> {
+        self.first.link(rect, uri)?;
+        self.second.link(rect, uri)
+    }
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        self.first.end_document()?;
+        self.second.end_document()
+    }
+}
+
+/*
+File doesn't implement std::fmt::Write so we have
+to do some gymnastics to accept a File or String
+See https://github.com/rust-lang/rust/issues/51305
+*/
+
+pub trait ConvertToFmt {
+    type Writer: std::fmt::Write;
+    fn convert(self) -> Self::Writer;
+}
+
+impl<'a> ConvertToFmt for &'a mut String {
+    type Writer = &'a mut String;
+    fn convert(self) -> Self::Writer {
+        self
+    }
+}
+
+pub struct WriteAdapter<W> {
+    f: W,
+}
+
+impl<W: std::io::Write> std::fmt::Write for WriteAdapter<W> {
+    fn write_str(&mut self, s: &str) -> Result<(), std::fmt::Error> {
+        self.f.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl<'a> ConvertToFmt for &'a mut dyn std::io::Write {
+    type Writer = WriteAdapter<Self>;
+    fn convert(self) -> Self::Writer {
+        WriteAdapter { f: self }
+    }
+}
+
+impl<'a> ConvertToFmt for &'a mut File {
+    type Writer = WriteAdapter<Self>;
+    fn convert(self) -> Self::Writer {
+        WriteAdapter { f: self }
+    }
+}
+
+/// The order that `PlainTextOutput` writes the text in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadingOrder {
+    /// The order that the text is drawn in, with the text put together as it's drawn. The lines
+    /// and paragraphs break where `LineBuilder::line_break` finds them, with the thresholds of
+    /// the options.
+    #[default]
+    ContentStream,
+    /// The text of each page is grouped into blocks which are ordered by splitting the page into
+    /// columns and then into bands from the top recursively (an XY-cut). Multi-column layouts are
+    /// read a column at a time. The blocks are split into paragraphs as with `LineBuilder`. The
+    /// thresholds other than `blank_lines_between_paragraphs` aren't used.
+    Layout,
+}
+
+/// How `PlainTextOutput` puts the characters together into text. The thresholds are fractions
+/// of the font size.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractOptions {
+    /// A space is written when the gap between two characters is larger than this, when
+    /// `adaptive_spacing` is off or the font has no space character. Gaps in TJ arrays also have
+    /// to be wider than this.
+    pub space_threshold: f64,
+    /// Compare the gaps between characters with half a space of the font, taking the character
+    /// and word spacing into account, unless the gaps on the line fall into narrow ones within
+    /// words and wide ones between them. Lines whose gaps are all about as wide, wider than half
+    /// a space but narrower than three quarters of one, are taken to be letterspaced headings and
+    /// written without spaces. On by default.
+    pub adaptive_spacing: bool,
+    /// A newline is written when the text moves to the left and up or down by more than this
+    pub line_threshold: f64,
+    /// A paragraph break is written when the text moves up or down by more than this
+    pub paragraph_threshold: f64,
+    /// Written between pages, e.g. "\x0c" for a form feed
+    pub page_separator: String,
+    /// Whether paragraph breaks that move back to the left are written as a blank line instead
+    /// of a single newline
+    pub blank_lines_between_paragraphs: bool,
+    pub reading_order: ReadingOrder,
+    /// Join the words that are hyphenated at the end of a line when the next line continues
+    /// with a lowercase letter. Soft hyphens are removed, while hyphens are kept in the
+    /// `compound_words` and in words that are made of more than two parts or that start with a
+    /// prefix such as "self-" or "non-". The text of each page is written when the page ends.
+    pub dehyphenate: bool,
+    /// Lowercase hyphenated words, e.g. "well-known", that keep their hyphen when they're split
+    /// at it by `dehyphenate`
+    pub compound_words: HashSet<String>,
+    /// Replacement Unicode mappings for fonts with broken or missing `ToUnicode` maps
+    pub font_overrides: FontOverrides,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions {
+            space_threshold: 0.1,
+            adaptive_spacing: true,
+            line_threshold: 0.5,
+            paragraph_threshold: 1.5,
+            page_separator: String::new(),
+            blank_lines_between_paragraphs: true,
+            reading_order: ReadingOrder::ContentStream,
+            dehyphenate: false,
+            compound_words: HashSet::new(),
+            font_overrides: FontOverrides::new(),
+        }
+    }
+}
+
+// The prefixes that are joined to words with a hyphen
+const COMPOUND_PREFIXES: &[&str] = &["all", "cross", "ex", "half", "non", "quasi", "self", "well"];
+
+// Join the words that are hyphenated at the ends of the lines of `text`, see
+// `ExtractOptions::dehyphenate`
+fn dehyphenate(text: &str, compound_words: &HashSet<String>) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut joined = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        joined.push_str(line);
+        let next = match lines.get(i + 1) {
+            Some(next) => next,
+            None => break,
+        };
+        let hyphen = joined.chars().next_back().filter(|c| matches!(c, '-' | '\u{2010}' | '\u{ad}' | '\u{2027}'));
+        let hyphen_len = hyphen.map_or(0, char::len_utf8);
+        let before = &joined[..joined.len() - hyphen_len];
+        let prefix_start = before.trim_end_matches(char::is_alphabetic).len();
+        let prefix = &before[prefix_start..];
+        let next_word: String = next.chars().take_while(|c| c.is_alphabetic()).collect();
+        let hyphen = match hyphen {
+            Some(hyphen) if !prefix.is_empty() && next_word.starts_with(char::is_lowercase) => hyphen,
+            _ => {
+                joined.push('\n');
+                continue;
+            }
+        };
+        let keep_hyphen = matches!(hyphen, '-' | '\u{2010}') && {
+            let compound = format!("{}-{}", prefix, next_word).to_lowercase();
+            // a hyphen before the prefix makes the word a compound of more than two parts
+            compound_words.contains(&compound) || joined[..prefix_start].ends_with(['-', '\u{2010}'])
+                || COMPOUND_PREFIXES.contains(&prefix.to_lowercase().as_str())
+        };
+        if !keep_hyphen {
+            joined.truncate(joined.len() - hyphen_len);
+        }
+    }
+    joined
+}
+
+// The narrowest gap between words that the gaps of a line are split at, in ems
+const MIN_WORD_GAP: f64 = 0.1;
+// How much the gaps of a letterspaced line can differ, in ems
+const MAX_LETTERSPACING_SPREAD: f64 = 0.1;
+
+// A character of the line that `PlainTextOutput` is putting together
+struct LineChar {
+    text: String,
+    // the gap from the end of the previous character in ems, less the character spacing, when
+    // the character starts a string that continues the line
+    gap: Option<f64>,
+    // whether the gaps from TJ arrays before the character separate words
+    word_gap: Option<bool>,
+    // the gap that separates words in the character's font, in ems
+    threshold: f64,
+}
+
+// Where the narrow gaps within the words of a line end and the wide gaps between them start, in
+// ems, with the number of narrow gaps. The gaps between words have to be at least twice as wide.
+fn split_gaps(gaps: &mut [f64]) -> Option<(f64, usize)> {
+    gaps.sort_by(f64::total_cmp);
+    let i = gaps.windows(2).position(|pair| pair[1] >= MIN_WORD_GAP && pair[1] >= 2. * pair[0].max(0.))?;
+    Some(((gaps[i] + gaps[i + 1]) / 2., i + 1))
+}
+
+pub struct PlainTextOutput<W: ConvertToFmt>   {
+    writer: W::Writer,
+    options: ExtractOptions,
+    // where the last character ended, none until something has been written
+    last_end: Option<f64>,
+    first_char: bool,
+    flip_ctm: Transform,
+    pages: u32,
+    // finds the line breaks, or collects the pages' characters for ReadingOrder::Layout
+    lines: LineBuilder,
+    // the text of the page for dehyphenation
+    page: String,
+    // the gaps from TJ arrays since the last character, with the font size and space width
+    gap: Option<(f64, f64, Option<f64>)>,
+    // the characters of the line, which are written when it ends
+    line: Vec<LineChar>,
+    // half the advance of a space in the font of the glyph that was just drawn, in ems
+    word_space: Option<f64>,
+    // the character spacing after the last character, in ems
+    last_spacing: f64,
+}
+
+impl<W: ConvertToFmt> PlainTextOutput<W> {
+    pub fn new(writer: W) -> PlainTextOutput<W> {
+        PlainTextOutput::with_options(writer, ExtractOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: ExtractOptions) -> PlainTextOutput<W> {
+        PlainTextOutput{
+            writer: writer.convert(),
+            lines: LineBuilder::with_thresholds(options.line_threshold, options.paragraph_threshold),
+            options,
+            last_end: None,
+            first_char: false,
+            flip_ctm: Transform2D::identity(),
+            pages: 0,
+            page: String::new(),
+            gap: None,
+            line: Vec::new(),
+            word_space: None,
+            last_spacing: 0.,
+        }
+    }
+
+    // The text is written to the page until it's dehyphenated
+    fn out(&mut self) -> &mut dyn std::fmt::Write {
+        if self.options.dehyphenate {
+            &mut self.page
+        } else {
+            &mut self.writer
+        }
+    }
+
+    // Write the characters of the line with spaces at the gaps between words. The gaps of the
+    // line can lower the threshold of the fonts, and they can raise it when most of the
+    // characters are apart, as in letterspaced headings.
+    fn write_line(&mut self) -> Result<(), OutputError> {
+        let line = std::mem::take(&mut self.line);
+        let mut gaps: Vec<f64> = line.iter().filter(|c| c.word_gap.is_none()).filter_map(|c| c.gap).collect();
+        let split = if self.options.adaptive_spacing { split_gaps(&mut gaps) } else { None };
+        // the characters are evenly letterspaced when there's no split although every gap is wide,
+        // as long as the gaps are the same and narrower than the spaces between words
+        let letterspaced = self.options.adaptive_spacing && split.is_none() && gaps.len() >= 3
+            && gaps[gaps.len() - 1] - gaps[0] < MAX_LETTERSPACING_SPREAD
+            && line.iter().all(|c| c.word_gap.is_some() || c.gap.is_none_or(|gap| gap > c.threshold && gap < 1.5 * c.threshold));
+        for c in &line {
+            let space = match (c.gap, c.word_gap) {
+                (Some(_), Some(word_gap)) => word_gap,
+                (Some(_), None) if letterspaced => false,
+                (Some(gap), None) => gap > match split {
+                    Some((threshold, narrow)) if threshold <= c.threshold || 2 * narrow > line.len() => threshold,
+                    _ => c.threshold,
+                },
+                (None, _) => false,
+            };
+            if space {
+                self.out().write_str(" ")?;
+            }
+            self.out().write_str(&c.text)?;
+        }
+        Ok(())
+    }
+}
+
+/* There are some structural hints that PDFs can use to signal word and line endings:
+ * however relying on these is not likely to be sufficient. */
+impl<W: ConvertToFmt> OutputDev for PlainTextOutput<W> {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        if self.pages > 0 {
+            self.writer.write_str(&self.options.page_separator)?;
+        }
+        self.pages += 1;
+        self.flip_ctm = Transform2D::row_major(1., 0., 0., -1., 0., media_box.ury - media_box.lly);
+        if self.options.reading_order == ReadingOrder::Layout {
+            self.lines.begin_page(page_num, media_box);
+        }
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.write_line()?;
+        for blocks in self.lines.finish_reading_order() {
+            for (i, block) in blocks.iter().enumerate() {
+                if i > 0 && self.options.blank_lines_between_paragraphs {
+                    writeln!(self.out())?;
+                }
+                for line in block {
+                    let words: Vec<&str> = line.words.iter().map(|word| word.text.as_str()).collect();
+                    writeln!(self.out(), "{}", words.join(" "))?;
+                }
+            }
+        }
+        if self.options.dehyphenate {
+            let page = std::mem::take(&mut self.page);
+            self.writer.write_str(&dehyphenate(&page, &self.options.compound_words))?;
+        }
+        Ok(())
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        if self.options.reading_order == ReadingOrder::Layout {
+            self.lines.character(trm, width, spacing, font_size, char);
+            return Ok(());
+        }
+        let line_break = self.lines.line_break(trm, width, font_size);
+        let space_threshold = self.options.space_threshold;
+        let word_gap = self.gap.take().map(|(gap, font_size, space_width)| {
+            is_word_gap(gap, font_size, space_width) && gap > font_size * space_threshold
+        });
+        let word_space = self.word_space.take();
+        let mut gap = None;
+        let position = trm.post_transform(&self.flip_ctm);
+        let transformed_font_size_vec = trm.transform_vector(vec2(font_size, font_size));
+        // get the length of one sized of the square with the same area with a rectangle of size (x, y)
+        let transformed_font_size = (transformed_font_size_vec.x*transformed_font_size_vec.y).sqrt();
+        let x = position.m31;
+        //dlog!("last_end: {} x: {}, width: {}", self.last_end, x, width);
+        if self.first_char {
+            let (paragraph, line) = match line_break {
+                LineBreak::None => (false, false),
+                LineBreak::Line => (false, true),
+                LineBreak::Paragraph { moved_left } => (true, moved_left && self.options.blank_lines_between_paragraphs),
+            };
+            if paragraph || line {
+                self.write_line()?;
+            }
+            if paragraph {
+                write!(self.out(), "\n")?;
+            }
+            if line {
+                write!(self.out(), "\n")?;
+            }
+            if !paragraph && !line {
+                gap = self.last_end.map(|end| (x - end) / transformed_font_size - self.last_spacing);
+                dlog!("width: {}, gap: {:?}, spacing: {}", width, gap, self.last_spacing);
+            }
+        }
+        //let norm = unicode_normalization::UnicodeNormalization::nfkc(char);
+        let threshold = word_space.filter(|space| self.options.adaptive_spacing && space.is_finite() && *space > 0.)
+            .unwrap_or(self.options.space_threshold);
+        self.line.push(LineChar { text: char.to_owned(), gap, word_gap, threshold });
+        self.last_spacing = if font_size != 0. { spacing / font_size } else { 0. };
+        self.first_char = false;
+        self.last_end = Some(x + width * transformed_font_size);
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.first_char = true;
+        Ok(())
+    }
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError>{
+        //write!(self.file, "\n");
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.word_space = glyph.space_width()
+            .map(|width| 0.5 * (width + glyph.character_spacing + glyph.word_spacing) / glyph.font_size);
+        Ok(())
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        if self.options.reading_order == ReadingOrder::Layout {
+            self.lines.text_gap(gap, font_size, space_width);
+        } else {
+            let total = self.gap.map_or(0., |(total, ..)| total);
+            self.gap = Some((total + gap, font_size, space_width));
+        }
+        Ok(())
+    }
+}
+
+
+/// Options for `LayoutTextOutput`
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayoutTextOptions {
+    /// The width in points of a column of the character grid. By default it's the average
+    /// width of the characters on each page.
+    pub fixed_pitch: Option<f64>,
+    /// Start the lines at the leftmost text of each page instead of at the left edge of the page
+    pub trim_margins: bool,
+}
+
+// The furthest column of the grid that characters are placed in
+const MAX_GRID_COLUMN: usize = 1000;
+
+// A character on the page with the position of its baseline, y pointing up
+struct GridChar {
+    x: f64,
+    end: f64,
+    y: f64,
+    size: f64,
+    text: String,
+}
+
+/// Writes the text laid out on a monospaced grid of characters, like `pdftotext -layout`, so
+/// that columns and tables line up. The characters are placed in columns by dividing their x
+/// positions by the average character width and the lines are spaced by their distance divided
+/// by the most common distance between the lines. Pages are followed by a form feed.
+pub struct LayoutTextOutput<W: ConvertToFmt> {
+    writer: W::Writer,
+    options: LayoutTextOptions,
+    media_box: MediaBox,
+    chars: Vec<GridChar>,
+}
+
+impl<W: ConvertToFmt> LayoutTextOutput<W> {
+    pub fn new(writer: W) -> LayoutTextOutput<W> {
+        LayoutTextOutput::with_options(writer, LayoutTextOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: LayoutTextOptions) -> LayoutTextOutput<W> {
+        LayoutTextOutput {
+            writer: writer.convert(),
+            options,
+            media_box: MediaBox { llx: 0., lly: 0., urx: 0., ury: 0. },
+            chars: Vec::new(),
+        }
+    }
+
+    // The lines of the page with the characters placed in the columns of the grid
+    fn grid(&mut self) -> Vec<(f64, String)> {
+        let mut chars = std::mem::take(&mut self.chars);
+        // characters without a size can't be placed on the grid
+        chars.retain(|c| c.size > 0. && !c.text.chars().all(char::is_whitespace));
+        if chars.is_empty() {
+            return Vec::new();
+        }
+        let pitch = self.options.fixed_pitch.filter(|pitch| *pitch > 0.).unwrap_or_else(|| {
+            let (width, count) = chars.iter().fold((0., 0), |(width, count), c| {
+                (width + (c.end - c.x), count + c.text.chars().count())
+            });
+            if width > 0. { width / count as f64 } else { chars[0].size / 2. }
+        });
+        let pitch = if pitch.is_finite() && pitch > 0. { pitch } else { 1. };
+        // group the characters into lines from the top of the page, allowing for superscripts
+        chars.sort_by(|a, b| b.y.total_cmp(&a.y));
+        let mut lines: Vec<(f64, f64, Vec<GridChar>)> = Vec::new();
+        for c in chars {
+            match lines.last_mut() {
+                Some((y, size, line)) if *y - c.y < 0.5 * size.max(c.size) => {
+                    *size = size.max(c.size);
+                    line.push(c);
+                }
+                _ => lines.push((c.y, c.size, vec![c])),
+            }
+        }
+        let origin = if self.options.trim_margins {
+            lines.iter().flat_map(|(_, _, line)| line.iter().map(|c| c.x)).fold(f64::MAX, f64::min)
+        } else {
+            self.media_box.llx
+        };
+        lines.into_iter().map(|(y, _, mut line)| {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+            let mut text = String::new();
+            let mut column = 0;
+            let mut last: Option<&GridChar> = None;
+            for c in &line {
+                let mut start = ((c.x - origin) / pitch).round().clamp(0., MAX_GRID_COLUMN as f64) as usize;
+                // keep the characters from overlapping and the words apart
+                if let Some(last) = last {
+                    let gap = c.x - last.end > 0.1 * c.size.max(last.size);
+                    start = start.max(column + gap as usize);
+                }
+                text.push_str(&" ".repeat(start.saturating_sub(column)));
+                text.push_str(&c.text);
+                column = start.max(column) + c.text.chars().count();
+                last = Some(c);
+            }
+            (y, text)
+        }).collect()
+    }
+}
+
+impl<W: ConvertToFmt> OutputDev for LayoutTextOutput<W> {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        self.media_box = *media_box;
+        self.chars.clear();
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        let lines = self.grid();
+        // the most common distance between lines is one row of the grid
+        let mut distances: HashMap<i64, usize> = HashMap::new();
+        for pair in lines.windows(2) {
+            *distances.entry(((pair[0].0 - pair[1].0) * 2.).round() as i64).or_insert(0) += 1;
+        }
+        let pitch = distances.into_iter().max_by_key(|&(distance, count)| (count, -distance))
+            .map_or(0., |(distance, _)| distance as f64 / 2.);
+        let mut last_y: Option<f64> = None;
+        for (y, text) in &lines {
+            if let Some(last_y) = last_y {
+                let rows = if pitch > 0. { ((last_y - y) / pitch).round() as usize } else { 1 };
+                for _ in 1..rows {
+                    writeln!(self.writer)?;
+                }
+            }
+            writeln!(self.writer, "{}", text.trim_end())?;
+            last_y = Some(*y);
+        }
+        write!(self.writer, "\x0c")?;
+        Ok(())
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        let size = font_size * (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        let end = trm.pre_transform(&Transform::create_translation(width * font_size + spacing, 0.)).m31;
+        self.chars.push(GridChar { x: trm.m31, end: end.max(trm.m31), y: trm.m32, size, text: char.to_owned() });
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+}
+
+/// Writes the text as Markdown. The layout of the pages is analyzed to find headings, from
+/// their size and weight relative to the body text, paragraphs, lists, tables, which become pipe
+/// tables, and figures. Bold and italic text is emphasized and link annotations become links.
+/// Each page starts with a `<!-- page N -->` comment. The document is written by `end_document`.
+pub struct MarkdownOutput<W: ConvertToFmt> {
+    writer: W::Writer,
+    layout: LayoutBuilder,
+}
+
+impl<W: ConvertToFmt> MarkdownOutput<W> {
+    pub fn new(writer: W) -> MarkdownOutput<W> {
+        MarkdownOutput { writer: writer.convert(), layout: LayoutBuilder::default() }
+    }
+}
+
+impl<W: ConvertToFmt> OutputDev for MarkdownOutput<W> {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        self.layout.begin_page(page_num, media_box);
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.layout.glyph(glyph);
+        Ok(())
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.layout.character(trm, width, spacing, font_size, char);
+        Ok(())
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        self.layout.gap(gap, font_size, space_width);
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn image(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _image: &Image) -> Result<(), OutputError> {
+        // the images themselves aren't written
+        self.layout.image(ctm, None);
+        Ok(())
+    }
+    fn link(&mut self, rect: &(f64, f64, f64, f64), uri: &str) -> Result<(), OutputError> {
+        self.layout.link(rect, uri);
+        Ok(())
+    }
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        let mut first = true;
+        for page in self.layout.finish() {
+            let mut blocks = vec![format!("<!-- page {} -->", page.page_num)];
+            for block in page.blocks {
+                blocks.push(match block {
+                    Block::Heading { level, spans } => format!("{} {}", "#".repeat(level as usize), markdown_spans(&spans)),
+                    Block::Paragraph(spans) => markdown_block_start(markdown_spans(&spans)),
+                    Block::List { ordered, start, items } => {
+                        let items: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                            let marker = if ordered { format!("{}.", start as usize + i) } else { "-".to_owned() };
+                            format!("{} {}", marker, markdown_spans(item))
+                        }).collect();
+                        items.join("\n")
+                    }
+                    Block::Table(rows) => {
+                        let mut lines: Vec<String> = rows.iter().map(|row| {
+                            let cells: Vec<String> = row.iter().map(|cell| markdown_spans(cell).replace('|', "\\|")).collect();
+                            format!("| {} |", cells.join(" | "))
+                        }).collect();
+                        // the first row is the header
+                        let columns = rows.first().map_or(0, Vec::len);
+                        lines.insert(1.min(lines.len()), format!("|{}", " --- |".repeat(columns)));
+                        lines.join("\n")
+                    }
+                    Block::Figure(figure) => {
+                        let alt = figure.caption.as_deref().map_or("Figure".to_owned(), plain_text);
+                        format!("![{}]()", markdown_escape(&alt))
+                    }
+                });
+            }
+            if !first {
+                self.writer.write_str("\n")?;
+            }
+            first = false;
+            writeln!(self.writer, "{}", blocks.join("\n\n"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the text as hOCR, with an `ocr_page` per page, `ocr_carea` and `ocr_par` elements for
+/// the blocks of text found by the layout analysis, and their `ocr_line` and `ocrx_word`
+/// elements. Bounding boxes are in pixels at a resolution of 72 dpi unless set with `with_dpi`.
+pub struct HocrOutput<W: ConvertToFmt> {
+    writer: W::Writer,
+    dpi: f64,
+    layout: LayoutBuilder,
+    started: bool,
+}
+
+impl<W: ConvertToFmt> HocrOutput<W> {
+    pub fn new(writer: W) -> HocrOutput<W> {
+        HocrOutput::with_dpi(writer, 72.)
+    }
+
+    pub fn with_dpi(writer: W, dpi: f64) -> HocrOutput<W> {
+        HocrOutput { writer: writer.convert(), dpi, layout: LayoutBuilder::default(), started: false }
+    }
+
+    fn write_header(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        writeln!(self.writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(self.writer, "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">")?;
+        writeln!(self.writer, "<html xmlns=\"http://www.w3.org/1999/xhtml\">\n<head>\n<title></title>")?;
+        writeln!(self.writer, "<meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\" />")?;
+        writeln!(self.writer, "<meta name=\"ocr-system\" content=\"pdf-extract\" />")?;
+        writeln!(self.writer, "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word ocr_photo ocrp_font\" />")?;
+        writeln!(self.writer, "</head>\n<body>")?;
+        Ok(())
+    }
+}
+
+impl<W: ConvertToFmt> OutputDev for HocrOutput<W> {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        self.write_header()?;
+        self.layout.begin_page(page_num, media_box);
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        for page in self.layout.finish_regions() {
+            let scale = self.dpi / 72.;
+            let media_box = page.media_box;
+            // boxes are in pixels from the top left of the page
+            let bbox = |(x0, y0, x1, y1): (f64, f64, f64, f64)| {
+                format!("bbox {} {} {} {}", ((x0 - media_box.llx) * scale).round(), ((media_box.ury - y1) * scale).round(),
+                        ((x1 - media_box.llx) * scale).round(), ((media_box.ury - y0) * scale).round())
+            };
+            let round = |x: f64| (x * 100.).round() / 100.;
+            let p = page.page_num;
+            writeln!(self.writer, "<div class=\"ocr_page\" id=\"page_{}\" title=\"{}; ppageno {}; scan_res {} {}\">",
+                     p, bbox((media_box.llx, media_box.lly, media_box.urx, media_box.ury)), p - 1, self.dpi, self.dpi)?;
+            let (mut blocks, mut lines, mut words, mut photos) = (0, 0, 0, 0);
+            for region in &page.regions {
+                let text_lines = match region {
+                    Region::Text(lines) | Region::Table(lines) => lines,
+                    Region::Figure(rect) => {
+                        photos += 1;
+                        writeln!(self.writer, "<div class=\"ocr_photo\" id=\"photo_{}_{}\" title=\"{}\"></div>", p, photos, bbox(*rect))?;
+                        continue;
+                    }
+                };
+                blocks += 1;
+                let block_bbox = text_lines.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, l| {
+                    (b.0.min(l.bbox.0), b.1.min(l.bbox.1), b.2.max(l.bbox.2), b.3.max(l.bbox.3))
+                });
+                writeln!(self.writer, "<div class=\"ocr_carea\" id=\"block_{}_{}\" title=\"{}\">", p, blocks, bbox(block_bbox))?;
+                writeln!(self.writer, "<p class=\"ocr_par\" id=\"par_{}_{}\" title=\"{}\">", p, blocks, bbox(block_bbox))?;
+                for line in text_lines {
+                    lines += 1;
+                    // the baseline is relative to the bottom of the line and the ascenders are measured
+                    // from the x-height, which is taken to be half an em
+                    let offset = ((line.bbox.1 - line.baseline) * scale).round();
+                    writeln!(self.writer, "<span class=\"ocr_line\" id=\"line_{}_{}\" title=\"{}; baseline 0 {}; x_size {}; x_descenders {}; x_ascenders {}\">",
+                             p, lines, bbox(line.bbox), offset, round(line.size * scale),
+                             round(DESCENT * line.size * scale), round((ASCENT - 0.5) * line.size * scale))?;
+                    for word in &line.words {
+                        words += 1;
+                        let mut text = xml_escape(&word.text);
+                        if word.italic {
+                            text = format!("<em>{}</em>", text);
+                        }
+                        if word.bold {
+                            text = format!("<strong>{}</strong>", text);
+                        }
+                        writeln!(self.writer, "<span class=\"ocrx_word\" id=\"word_{}_{}\" title=\"{}; x_wconf 100; x_fsize {}\">{}</span>",
+                                 p, words, bbox(word.bbox), round(word.size), text)?;
+                    }
+                    writeln!(self.writer, "</span>")?;
+                }
+                writeln!(self.writer, "</p>\n</div>")?;
+            }
+            writeln!(self.writer, "</div>")?;
+        }
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.layout.glyph(glyph);
+        Ok(())
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.layout.character(trm, width, spacing, font_size, char);
+        Ok(())
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        self.layout.gap(gap, font_size, space_width);
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn image(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _image: &Image) -> Result<(), OutputError> {
+        self.layout.image(ctm, None);
+        Ok(())
+    }
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        self.write_header()?;
+        writeln!(self.writer, "</body>\n</html>")?;
+        self.started = false;
+        Ok(())
+    }
+}
+
+/// The measurement unit of the positions and sizes written by `AltoOutput`
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AltoUnits {
+    /// PDF points, written as pixels at 72 dpi
+    Points,
+    /// Tenths of a millimetre
+    Mm10,
+    /// Pixels at the given resolution in dpi
+    Pixels(f64),
+}
+
+impl AltoUnits {
+    fn scale(self) -> f64 {
+        match self {
+            AltoUnits::Points => 1.,
+            AltoUnits::Mm10 => 254. / 72.,
+            AltoUnits::Pixels(dpi) => dpi / 72.,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AltoUnits::Mm10 => "mm10",
+            AltoUnits::Points | AltoUnits::Pixels(_) => "pixel",
+        }
+    }
+}
+
+/// Writes the text as ALTO v4 XML, with a `Page` and `PrintSpace` per page, `TextBlock`
+/// elements for the blocks of text found by the layout analysis with their `TextLine`, `String`
+/// and `SP` elements, `Illustration` elements for the images and `GraphicalElement` elements for
+/// the filled and stroked paths. The fonts are listed as `TextStyle` definitions.
+pub struct AltoOutput<W: ConvertToFmt> {
+    writer: W::Writer,
+    units: AltoUnits,
+    layout: LayoutBuilder,
+    // the pages are buffered because the styles come before them
+    pages: String,
+    // the family, size in half points, bold and italic of each TextStyle
+    styles: Vec<(String, i64, bool, bool)>,
+    pattern_depth: u32,
+}
+
+impl<W: ConvertToFmt> AltoOutput<W> {
+    pub fn new(writer: W) -> AltoOutput<W> {
+        AltoOutput::with_units(writer, AltoUnits::Points)
+    }
+
+    pub fn with_units(writer: W, units: AltoUnits) -> AltoOutput<W> {
+        AltoOutput {
+            writer: writer.convert(), units, layout: LayoutBuilder::default(), pages: String::new(),
+            styles: Vec::new(), pattern_depth: 0,
+        }
+    }
+
+    fn style(&mut self, word: &layout::TextWord) -> usize {
+        // drop the style from the font name, e.g. Minion-Bold or Garamond,Italic
+        let family = word.font.split(['-', ',']).next().unwrap_or_default();
+        let key = (family.to_owned(), (word.size * 2.).round() as i64, word.bold, word.italic);
+        match self.styles.iter().position(|style| *style == key) {
+            Some(i) => i,
+            None => {
+                self.styles.push(key);
+                self.styles.len() - 1
+            }
+        }
+    }
+
+    fn write_page(&mut self, page: layout::PageRegions) -> Result<(), OutputError> {
+        let scale = self.units.scale();
+        let media_box = page.media_box;
+        let round = |x: f64| (x * scale * 100.).round() / 100.;
+        // positions are from the top left of the page
+        let position = |(x0, y0, x1, y1): (f64, f64, f64, f64)| {
+            format!("HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+                    round(x0 - media_box.llx), round(media_box.ury - y1), round(x1 - x0), round(y1 - y0))
+        };
+        let p = page.page_num;
+        let page_box = (media_box.llx, media_box.lly, media_box.urx, media_box.ury);
+        let mut out = String::new();
+        writeln!(out, "<Page ID=\"page_{}\" PHYSICAL_IMG_NR=\"{}\" WIDTH=\"{}\" HEIGHT=\"{}\">",
+                 p, p, round(media_box.urx - media_box.llx), round(media_box.ury - media_box.lly))?;
+        writeln!(out, "<PrintSpace ID=\"space_{}\" {}>", p, position(page_box))?;
+        let (mut blocks, mut lines, mut words, mut illustrations) = (0, 0, 0, 0);
+        for region in &page.regions {
+            let text_lines = match region {
+                Region::Text(lines) | Region::Table(lines) => lines,
+                Region::Figure(rect) => {
+                    illustrations += 1;
+                    writeln!(out, "<Illustration ID=\"illustration_{}_{}\" {}/>", p, illustrations, position(*rect))?;
+                    continue;
+                }
+            };
+            blocks += 1;
+            let block_bbox = text_lines.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, l| {
+                (b.0.min(l.bbox.0), b.1.min(l.bbox.1), b.2.max(l.bbox.2), b.3.max(l.bbox.3))
+            });
+            writeln!(out, "<TextBlock ID=\"block_{}_{}\" {}>", p, blocks, position(block_bbox))?;
+            for line in text_lines {
+                lines += 1;
+                writeln!(out, "<TextLine ID=\"line_{}_{}\" {} BASELINE=\"{}\">",
+                         p, lines, position(line.bbox), round(media_box.ury - line.baseline))?;
+                let mut last_end: Option<f64> = None;
+                for word in &line.words {
+                    words += 1;
+                    if let Some(end) = last_end {
+                        writeln!(out, "<SP HPOS=\"{}\" VPOS=\"{}\" WIDTH=\"{}\"/>",
+                                 round(end - media_box.llx), round(media_box.ury - line.bbox.3), round((word.bbox.0 - end).max(0.)))?;
+                    }
+                    let style = self.style(word);
+                    writeln!(out, "<String ID=\"string_{}_{}\" {} CONTENT=\"{}\" STYLEREFS=\"font{}\" WC=\"1\"/>",
+                             p, words, position(word.bbox), xml_escape(&word.text), style)?;
+                    last_end = Some(word.bbox.2);
+                }
+                writeln!(out, "</TextLine>")?;
+            }
+            writeln!(out, "</TextBlock>")?;
+        }
+        for (i, rect) in page.graphics.iter().enumerate() {
+            writeln!(out, "<GraphicalElement ID=\"graphic_{}_{}\" {}/>", p, i + 1, position(*rect))?;
+        }
+        writeln!(out, "</PrintSpace>\n</Page>")?;
+        self.pages.push_str(&out);
+        Ok(())
+    }
+}
+
+impl<W: ConvertToFmt> OutputDev for AltoOutput<W> {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        self.layout.begin_page(page_num, media_box);
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        for page in self.layout.finish_regions() {
+            self.write_page(page)?;
+        }
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.layout.glyph(glyph);
+        Ok(())
+    }
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.layout.character(trm, width, spacing, font_size, char);
+        Ok(())
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        self.layout.gap(gap, font_size, space_width);
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn stroke(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], path: &Path) -> Result<(), OutputError> {
+        if self.pattern_depth == 0 {
+            self.layout.graphic(ctm, path);
+        }
+        Ok(())
+    }
+    fn fill(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], path: &Path) -> Result<(), OutputError> {
+        if self.pattern_depth == 0 {
+            self.layout.graphic(ctm, path);
+        }
+        Ok(())
+    }
+    fn image(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _image: &Image) -> Result<(), OutputError> {
+        self.layout.image(ctm, None);
         Ok(())
     }
-    fn end_page(&mut self) -> Result<(), OutputError>{
-        write!(self.file, "</g>\n")?;
-        write!(self.file, "</svg>")?;
+    // the contents of pattern cells aren't on the page
+    fn begin_pattern(&mut self, _pattern: &TilingPattern) -> Result<(), OutputError> {
+        self.pattern_depth += 1;
         Ok(())
     }
-    fn output_character(&mut self, _trm: &Transform, _width: f64, _spacing: f64, _font_size: f64, _char: &str) -> Result<(), OutputError>{
+    fn end_pattern(&mut self) -> Result<(), OutputError> {
+        self.pattern_depth = self.pattern_depth.saturating_sub(1);
         Ok(())
     }
-    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
-    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
-    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
-    fn fill(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], path: &Path) -> Result<(), OutputError>{
-        write!(self.file, "<g transform='matrix({}, {}, {}, {}, {}, {})'>",
-               ctm.m11,
-               ctm.m12,
-               ctm.m21,
-               ctm.m22,
-               ctm.m31,
-               ctm.m32,
-        )?;
-
-        /*if path.ops.len() == 1 {
-            if let PathOp::Rect(x, y, width, height) = path.ops[0] {
-                write!(self.file, "<rect x={} y={} width={} height={} />\n", x, y, width, height);
-                write!(self.file, "</g>");
-                return;
-            }
-        }*/
-        let mut d = Vec::new();
-        for op in &path.ops {
-            match op {
-                &PathOp::MoveTo(x, y) => { d.push(format!("M{} {}", x, y))}
-                &PathOp::LineTo(x, y) => { d.push(format!("L{} {}", x, y))},
-                &PathOp::CurveTo(x1, y1, x2, y2, x, y) => { d.push(format!("C{} {} {} {} {} {}", x1, y1, x2, y2, x, y))},
-                &PathOp::Close => { d.push(format!("Z"))},
-                &PathOp::Rect(x, y, width, height) => {
-                    d.push(format!("M{} {}", x, y));
-                    d.push(format!("L{} {}", x + width, y));
-                    d.push(format!("L{} {}", x + width, y + height));
-                    d.push(format!("L{} {}", x, y + height));
-                    d.push(format!("Z"));
+    fn end_document(&mut self) -> Result<(), OutputError> {
+        use std::fmt::Write;
+        writeln!(self.writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(self.writer, "<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# http://www.loc.gov/standards/alto/v4/alto-4-2.xsd\">")?;
+        writeln!(self.writer, "<Description>\n<MeasurementUnit>{}</MeasurementUnit>", self.units.name())?;
+        writeln!(self.writer, "<OCRProcessing ID=\"processing_1\">\n<ocrProcessingStep>\n<processingSoftware>\n<softwareName>pdf-extract</softwareName>\n</processingSoftware>\n</ocrProcessingStep>\n</OCRProcessing>")?;
+        writeln!(self.writer, "</Description>")?;
+        if !self.styles.is_empty() {
+            writeln!(self.writer, "<Styles>")?;
+            for (i, (family, size, bold, italic)) in self.styles.iter().enumerate() {
+                let mut style = Vec::new();
+                if *bold {
+                    style.push("bold");
                 }
-
+                if *italic {
+                    style.push("italics");
+                }
+                write!(self.writer, "<TextStyle ID=\"font{}\" FONTFAMILY=\"{}\" FONTSIZE=\"{}\"", i, xml_escape(family), *size as f64 / 2.)?;
+                if !style.is_empty() {
+                    write!(self.writer, " FONTSTYLE=\"{}\"", style.join(" "))?;
+                }
+                writeln!(self.writer, "/>")?;
             }
+            writeln!(self.writer, "</Styles>")?;
         }
-        write!(self.file, "<path d='{}' />", d.join(" "))?;
-        write!(self.file, "</g>")?;
-        write!(self.file, "\n")?;
+        writeln!(self.writer, "<Layout>\n{}</Layout>\n</alto>", self.pages)?;
+        self.pages.clear();
+        self.styles.clear();
         Ok(())
     }
 }
 
-/*
-File doesn't implement std::fmt::Write so we have
-to do some gymnastics to accept a File or String
-See https://github.com/rust-lang/rust/issues/51305
-*/
-
-pub trait ConvertToFmt {
-    type Writer: std::fmt::Write;
-    fn convert(self) -> Self::Writer;
-}
-
-impl<'a> ConvertToFmt for &'a mut String {
-    type Writer = &'a mut String;
-    fn convert(self) -> Self::Writer {
-        self
-    }
-}
-
-pub struct WriteAdapter<W> {
-    f: W,
+/// Builds a `DocumentModel` of the text with the layout analysis, which groups the glyphs into
+/// words, lines and blocks. Images are blocks of the `Figure` kind.
+#[derive(Default)]
+pub struct StructuredOutput {
+    layout: LayoutBuilder,
+    model: DocumentModel,
 }
 
-impl<W: std::io::Write> std::fmt::Write for WriteAdapter<W> {
-    fn write_str(&mut self, s: &str) -> Result<(), std::fmt::Error> {
-        self.f.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+impl StructuredOutput {
+    pub fn new() -> StructuredOutput {
+        StructuredOutput::default()
     }
-}
 
-impl<'a> ConvertToFmt for &'a mut dyn std::io::Write {
-    type Writer = WriteAdapter<Self>;
-    fn convert(self) -> Self::Writer {
-        WriteAdapter { f: self }
+    /// The pages that have been output so far
+    pub fn model(&self) -> &DocumentModel {
+        &self.model
     }
-}
 
-impl<'a> ConvertToFmt for &'a mut File {
-    type Writer = WriteAdapter<Self>;
-    fn convert(self) -> Self::Writer {
-        WriteAdapter { f: self }
+    pub fn into_model(self) -> DocumentModel {
+        self.model
     }
 }
 
-pub struct PlainTextOutput<W: ConvertToFmt>   {
-    writer: W::Writer,
-    last_end: f64,
-    last_y: f64,
-    first_char: bool,
-    flip_ctm: Transform,
-}
-
-impl<W: ConvertToFmt> PlainTextOutput<W> {
-    pub fn new(writer: W) -> PlainTextOutput<W> {
-        PlainTextOutput{
-            writer: writer.convert(),
-            last_end: 100000.,
-            first_char: false,
-            last_y: 0.,
-            flip_ctm: Transform2D::identity(),
-        }
-    }
+fn line_model(line: &layout::TextLine) -> LineModel {
+    let words = line.words.iter().map(|word| WordModel {
+        text: word.text.clone(),
+        bbox: BoundingBox::new(word.bbox),
+        glyphs: word.chars.iter().map(|c| GlyphModel {
+            text: c.text.clone(),
+            bbox: BoundingBox::new(c.bbox()),
+            font: FontInfo {
+                name: if c.font.is_empty() { None } else { Some(c.font.to_string()) },
+                size: c.size,
+                bold: c.bold,
+                italic: c.italic,
+            },
+            color: c.color,
+        }).collect(),
+    }).collect();
+    LineModel { bbox: BoundingBox::new(line.bbox), baseline: line.baseline, words }
 }
 
-/* There are some structural hints that PDFs can use to signal word and line endings:
- * however relying on these is not likely to be sufficient. */
-impl<W: ConvertToFmt> OutputDev for PlainTextOutput<W> {
-    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
-        self.flip_ctm = Transform2D::row_major(1., 0., 0., -1., 0., media_box.ury - media_box.lly);
+impl OutputDev for StructuredOutput {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> {
+        self.layout.begin_page(page_num, media_box);
         Ok(())
     }
     fn end_page(&mut self) -> Result<(), OutputError> {
+        for page in self.layout.finish_regions() {
+            let blocks = page.regions.iter().enumerate().map(|(reading_order, region)| {
+                let (kind, lines): (_, Vec<LineModel>) = match region {
+                    Region::Text(lines) => (BlockKind::Text, lines.iter().map(line_model).collect()),
+                    Region::Table(lines) => (BlockKind::Table, lines.iter().map(line_model).collect()),
+                    Region::Figure(rect) => {
+                        return BlockModel { kind: BlockKind::Figure, bbox: BoundingBox::new(*rect), reading_order, lines: Vec::new() };
+                    }
+                };
+                let bbox = lines.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, l| {
+                    (b.0.min(l.bbox.llx), b.1.min(l.bbox.lly), b.2.max(l.bbox.urx), b.3.max(l.bbox.ury))
+                });
+                BlockModel { kind, bbox: BoundingBox::new(bbox), reading_order, lines }
+            }).collect();
+            self.model.pages.push(PageModel { page_num: page.page_num, media_box: page.media_box, blocks });
+        }
         Ok(())
     }
-    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
-        let position = trm.post_transform(&self.flip_ctm);
-        let transformed_font_size_vec = trm.transform_vector(vec2(font_size, font_size));
-        // get the length of one sized of the square with the same area with a rectangle of size (x, y)
-        let transformed_font_size = (transformed_font_size_vec.x*transformed_font_size_vec.y).sqrt();
-        let (x, y) = (position.m31, position.m32);
-        use std::fmt::Write;
-        //dlog!("last_end: {} x: {}, width: {}", self.last_end, x, width);
-        if self.first_char {
-            if (y - self.last_y).abs() > transformed_font_size * 1.5 {
-                write!(self.writer, "\n")?;
-            }
-
-            // we've moved to the left and down
-            if x < self.last_end && (y - self.last_y).abs() > transformed_font_size * 0.5 {
-                write!(self.writer, "\n")?;
-            }
-
-            if x > self.last_end + transformed_font_size * 0.1 {
-                dlog!("width: {}, space: {}, thresh: {}", width, x - self.last_end, transformed_font_size * 0.1);
-                write!(self.writer, " ")?;
-            }
-        }
-        //let norm = unicode_normalization::UnicodeNormalization::nfkc(char);
-        write!(self.writer, "{}", char)?;
-        self.first_char = false;
-        self.last_y = y;
-        self.last_end = x + width * transformed_font_size;
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        self.layout.glyph(glyph);
         Ok(())
     }
-    fn begin_word(&mut self) -> Result<(), OutputError> {
-        self.first_char = true;
+    fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        self.layout.character(trm, width, spacing, font_size, char);
+        Ok(())
+    }
+    fn text_gap(&mut self, _trm: &Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), OutputError> {
+        self.layout.gap(gap, font_size, space_width);
         Ok(())
     }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
     fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
-    fn end_line(&mut self) -> Result<(), OutputError>{
-        //write!(self.file, "\n");
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn image(&mut self, ctm: &Transform, _colorspace: &ColorSpace, _color: &[f64], _image: &Image) -> Result<(), OutputError> {
+        self.layout.image(ctm, None);
         Ok(())
     }
 }
 
+// Escape the characters that Markdown would take for formatting
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Escape the start of a paragraph that would make it a heading, list item or quote
+fn markdown_block_start(text: String) -> String {
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    if text.starts_with(['#', '-', '+', '>', '=']) {
+        format!("\\{}", text)
+    } else if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        format!("{}\\{}", &text[..digits], &text[digits..])
+    } else {
+        text
+    }
+}
+
+// Spans as Markdown with ** for bold, * for italic and links
+fn markdown_spans(spans: &[Span]) -> String {
+    let mut markdown = String::new();
+    let mut i = 0;
+    while i < spans.len() {
+        // the spans under the same link share it
+        let link = &spans[i].link;
+        let end = i + spans[i..].iter().take_while(|span| span.link == *link).count();
+        let mut text = String::new();
+        for span in &spans[i..end] {
+            let escaped = markdown_escape(&span.text);
+            let core = escaped.trim();
+            let marker = match (span.bold, span.italic) {
+                (true, true) => "***",
+                (true, false) => "**",
+                (false, true) => "*",
+                (false, false) => "",
+            };
+            if core.is_empty() || marker.is_empty() {
+                text += &escaped;
+                continue;
+            }
+            // emphasis can't start or end with a space
+            let leading = &escaped[..escaped.len() - escaped.trim_start().len()];
+            let trailing = &escaped[escaped.trim_end().len()..];
+            text += &format!("{}{}{}{}{}", leading, marker, core, marker, trailing);
+        }
+        match link {
+            Some(uri) => {
+                let uri = uri.replace(' ', "%20").replace('(', "%28").replace(')', "%29");
+                markdown += &format!("[{}]({})", text, uri);
+            }
+            None => markdown += &text,
+        }
+        i = end;
+    }
+    markdown
+}
+
 
 pub fn print_metadata(doc: &Document) {
     dlog!("Version: {}", doc.version);
@@ -2217,9 +5774,38 @@ pub fn print_metadata(doc: &Document) {
 
 /// Extract the text from a pdf at `path` and return a `String` with the results
 pub fn extract_text<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<String, OutputError> {
+    extract_text_with_options(path, &ExtractOptions::default())
+}
+
+/// Extract the text from a pdf at `path` with `options` to tune how it's put together
+pub fn extract_text_with_options<P: std::convert::AsRef<std::path::Path>>(path: P, options: &ExtractOptions) -> Result<String, OutputError> {
+    let mut s = String::new();
+    {
+        let mut output = PlainTextOutput::with_options(&mut s, options.clone());
+        let mut doc = Document::load(path)?;
+        maybe_decrypt(&mut doc)?;
+        output_doc_with_font_overrides(&doc, &mut output, &options.font_overrides)?;
+    }
+    Ok(s)
+}
+
+/// Extract the text from a pdf at `path` as Markdown, see `MarkdownOutput`
+pub fn extract_markdown<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<String, OutputError> {
+    let mut s = String::new();
+    {
+        let mut output = MarkdownOutput::new(&mut s);
+        let mut doc = Document::load(path)?;
+        maybe_decrypt(&mut doc)?;
+        output_doc(&doc, &mut output)?;
+    }
+    Ok(s)
+}
+
+/// Extract the text from a pdf at `path` laid out on a grid of characters, see `LayoutTextOutput`
+pub fn extract_text_layout<P: std::convert::AsRef<std::path::Path>>(path: P, options: LayoutTextOptions) -> Result<String, OutputError> {
     let mut s = String::new();
     {
-        let mut output = PlainTextOutput::new(&mut s);
+        let mut output = LayoutTextOutput::with_options(&mut s, options);
         let mut doc = Document::load(path)?;
         maybe_decrypt(&mut doc)?;
         output_doc(&doc, &mut output)?;
@@ -2227,6 +5813,15 @@ pub fn extract_text<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<
     Ok(s)
 }
 
+/// Extract the text from a pdf at `path` as a `DocumentModel`, see `StructuredOutput`
+pub fn extract_model<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<DocumentModel, OutputError> {
+    let mut output = StructuredOutput::new();
+    let mut doc = Document::load(path)?;
+    maybe_decrypt(&mut doc)?;
+    output_doc(&doc, &mut output)?;
+    Ok(output.into_model())
+}
+
 fn maybe_decrypt(doc: &mut Document) -> Result<(), OutputError> {
     if ! doc.is_encrypted() {
         return Ok(());
@@ -2246,23 +5841,35 @@ fn maybe_decrypt(doc: &mut Document) -> Result<(), OutputError> {
 pub fn extract_text_encrypted<P: std::convert::AsRef<std::path::Path>>(
     path: P,
     password: &str,
+) -> Result<String, OutputError> {
+    extract_text_encrypted_with_options(path, password, &ExtractOptions::default())
+}
+
+pub fn extract_text_encrypted_with_options<P: std::convert::AsRef<std::path::Path>>(
+    path: P,
+    password: &str,
+    options: &ExtractOptions,
 ) -> Result<String, OutputError> {
     let mut s = String::new();
     {
-        let mut output = PlainTextOutput::new(&mut s);
-        let mut doc = Document::load(path)?;
-        output_doc_encrypted(&mut doc, &mut output, password)?;
+        let mut output = PlainTextOutput::with_options(&mut s, options.clone());
+        let doc = Document::load_with_password(path, password)?;
+        output_doc_with_font_overrides(&doc, &mut output, &options.font_overrides)?;
     }
     Ok(s)
 }
 
 pub fn extract_text_from_mem(buffer: &[u8]) -> Result<String, OutputError> {
+    extract_text_from_mem_with_options(buffer, &ExtractOptions::default())
+}
+
+pub fn extract_text_from_mem_with_options(buffer: &[u8], options: &ExtractOptions) -> Result<String, OutputError> {
     let mut s = String::new();
     {
-        let mut output = PlainTextOutput::new(&mut s);
+        let mut output = PlainTextOutput::with_options(&mut s, options.clone());
         let mut doc = Document::load_mem(buffer)?;
         maybe_decrypt(&mut doc)?;
-        output_doc(&doc, &mut output)?;
+        output_doc_with_font_overrides(&doc, &mut output, &options.font_overrides)?;
     }
     Ok(s)
 }
@@ -2270,82 +5877,81 @@ pub fn extract_text_from_mem(buffer: &[u8]) -> Result<String, OutputError> {
 pub fn extract_text_from_mem_encrypted(
     buffer: &[u8],
     password: &str,
+) -> Result<String, OutputError> {
+    extract_text_from_mem_encrypted_with_options(buffer, password, &ExtractOptions::default())
+}
+
+pub fn extract_text_from_mem_encrypted_with_options(
+    buffer: &[u8],
+    password: &str,
+    options: &ExtractOptions,
 ) -> Result<String, OutputError> {
     let mut s = String::new();
     {
-        let mut output = PlainTextOutput::new(&mut s);
-        let mut doc = Document::load_mem(buffer)?;
-        output_doc_encrypted(&mut doc, &mut output, password)?;
+        let mut output = PlainTextOutput::with_options(&mut s, options.clone());
+        let doc = Document::load_mem_with_password(buffer, password)?;
+        output_doc_with_font_overrides(&doc, &mut output, &options.font_overrides)?;
     }
     Ok(s)
 }
 
-
-fn extract_text_by_page(doc: &Document, page_num: u32) -> Result<String, OutputError> {
+fn extract_text_by_page(doc: &Document, page_num: u32, options: &ExtractOptions) -> Result<String, OutputError> {
     let mut s = String::new();
     {
-        let mut output = PlainTextOutput::new(&mut s);
-        output_doc_page(doc, &mut output, page_num)?;
+        let mut output = PlainTextOutput::with_options(&mut s, options.clone());
+        output_doc_page_with_font_overrides(doc, &mut output, page_num, &options.font_overrides)?;
     }
     Ok(s)
 }
 
+fn extract_text_by_pages_inner(doc: &Document, options: &ExtractOptions) -> Vec<String> {
+    let mut v = Vec::new();
+    let mut page_num = 1;
+    while let Ok(content) = extract_text_by_page(doc, page_num, options) {
+        v.push(content);
+        page_num += 1;
+    }
+    v
+}
+
 /// Extract the text from a pdf at `path` and return a `Vec<String>` with the results separately by page
 
 pub fn extract_text_by_pages<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<Vec<String>, OutputError> {
-    let mut v = Vec::new();
-    {
-        let mut doc = Document::load(path)?;
-        maybe_decrypt(&mut doc)?;
-        let mut page_num = 1;
-        while let Ok(content) = extract_text_by_page(&doc, page_num) {
-            v.push(content);
-            page_num += 1;
-        }
-    }
-    Ok(v)
+    extract_text_by_pages_with_options(path, &ExtractOptions::default())
+}
+
+pub fn extract_text_by_pages_with_options<P: std::convert::AsRef<std::path::Path>>(path: P, options: &ExtractOptions) -> Result<Vec<String>, OutputError> {
+    let mut doc = Document::load(path)?;
+    maybe_decrypt(&mut doc)?;
+    Ok(extract_text_by_pages_inner(&doc, options))
 }
 
 pub fn extract_text_by_pages_encrypted<P: std::convert::AsRef<std::path::Path>>(path: P, password: &str) -> Result<Vec<String>, OutputError> {
-    let mut v = Vec::new();
-    {
-        let mut doc = Document::load(path)?;
-        doc.decrypt(password)?;
-        let mut page_num = 1;
-        while let Ok(content) = extract_text_by_page(&mut doc, page_num) {
-            v.push(content);
-            page_num += 1;
-        }
-    }
-    Ok(v)
+    extract_text_by_pages_encrypted_with_options(path, password, &ExtractOptions::default())
+}
+
+pub fn extract_text_by_pages_encrypted_with_options<P: std::convert::AsRef<std::path::Path>>(path: P, password: &str, options: &ExtractOptions) -> Result<Vec<String>, OutputError> {
+    let doc = Document::load_with_password(path, password)?;
+    Ok(extract_text_by_pages_inner(&doc, options))
 }
 
 pub fn extract_text_from_mem_by_pages(buffer: &[u8]) -> Result<Vec<String>, OutputError> {
-    let mut v = Vec::new();
-    {
-        let mut doc = Document::load_mem(buffer)?;
-        maybe_decrypt(&mut doc)?;
-        let mut page_num = 1;
-        while let Ok(content) = extract_text_by_page(&doc, page_num) {
-            v.push(content);
-            page_num += 1;
-        }
-    }
-    Ok(v)
+    extract_text_from_mem_by_pages_with_options(buffer, &ExtractOptions::default())
+}
+
+pub fn extract_text_from_mem_by_pages_with_options(buffer: &[u8], options: &ExtractOptions) -> Result<Vec<String>, OutputError> {
+    let mut doc = Document::load_mem(buffer)?;
+    maybe_decrypt(&mut doc)?;
+    Ok(extract_text_by_pages_inner(&doc, options))
 }
 
 pub fn extract_text_from_mem_by_pages_encrypted(buffer: &[u8], password: &str) -> Result<Vec<String>, OutputError> {
-    let mut v = Vec::new();
-    {
-        let mut doc = Document::load_mem(buffer)?;
-        doc.decrypt(password)?;
-        let mut page_num = 1;
-        while let Ok(content) = extract_text_by_page(&doc, page_num) {
-            v.push(content);
-            page_num += 1;
-        }
-    }
-    Ok(v)
+    extract_text_from_mem_by_pages_encrypted_with_options(buffer, password, &ExtractOptions::default())
+}
+
+pub fn extract_text_from_mem_by_pages_encrypted_with_options(buffer: &[u8], password: &str, options: &ExtractOptions) -> Result<Vec<String>, OutputError> {
+    let doc = Document::load_mem_with_password(buffer, password)?;
+    Ok(extract_text_by_pages_inner(&doc, options))
 }
 
 
@@ -2365,36 +5971,56 @@ pub fn output_doc_encrypted(
     doc: &mut Document,
     output: &mut dyn OutputDev,
     password: &str,
+) -> Result<(), OutputError> {
+    output_doc_encrypted_with_font_overrides(doc, output, password, &NO_FONT_OVERRIDES)
+}
+
+pub fn output_doc_encrypted_with_font_overrides(
+    doc: &mut Document,
+    output: &mut dyn OutputDev,
+    password: &str,
+    font_overrides: &FontOverrides,
 ) -> Result<(), OutputError> {
     doc.decrypt(password)?;
-    output_doc(doc, output)
+    output_doc_with_font_overrides(doc, output, font_overrides)
 }
 
 /// Parse a given document and output it to `output`
 pub fn output_doc(doc: &Document, output: &mut dyn OutputDev) -> Result<(), OutputError> {
+    output_doc_with_font_overrides(doc, output, &NO_FONT_OVERRIDES)
+}
+
+/// Like `output_doc` but with `font_overrides` taking precedence over the Unicode mappings in the fonts
+pub fn output_doc_with_font_overrides(doc: &Document, output: &mut dyn OutputDev, font_overrides: &FontOverrides) -> Result<(), OutputError> {
     if doc.is_encrypted() {
         error!("Encrypted documents must be decrypted with a password using {{extract_text|extract_text_from_mem|output_doc}}_encrypted");
     }
     let empty_resources = Dictionary::new();
     let pages = doc.get_pages();
-    let mut p = Processor::new();
+    let mut p = Processor::new(font_overrides);
     for dict in pages {
         let page_num = dict.0;
         let object_id = dict.1;
         output_doc_inner(page_num, object_id, doc, &mut p, output, &empty_resources)?;
     }
+    output.end_document()?;
     Ok(())
 }
 
 pub fn output_doc_page(doc: &Document, output: &mut dyn OutputDev, page_num: u32) -> Result<(), OutputError> {
+    output_doc_page_with_font_overrides(doc, output, page_num, &NO_FONT_OVERRIDES)
+}
+
+pub fn output_doc_page_with_font_overrides(doc: &Document, output: &mut dyn OutputDev, page_num: u32, font_overrides: &FontOverrides) -> Result<(), OutputError> {
     if doc.is_encrypted() {
         error!("Encrypted documents must be decrypted with a password using {{extract_text|extract_text_from_mem|output_doc}}_encrypted");
     }
     let empty_resources = Dictionary::new();
     let pages = doc.get_pages();
     let object_id = pages.get(&page_num).ok_or(lopdf::Error::PageNumberNotFound(page_num))?;
-    let mut p = Processor::new();
+    let mut p = Processor::new(font_overrides);
     output_doc_inner(page_num, *object_id, doc, &mut p, output, &empty_resources)?;
+    output.end_document()?;
     Ok(())
 }
 
@@ -2410,7 +6036,254 @@ fn output_doc_inner<'a>(page_num: u32, object_id: ObjectId, doc: &'a Document, p
     let art_box = get::<Option<Vec<f64>>>(&doc, page_dict, b"ArtBox")
         .map(|x| (x[0], x[1], x[2], x[3]));
     output.begin_page(page_num, &media_box, art_box)?;
-    p.process_stream(&doc, doc.get_page_content(object_id).unwrap(), resources, &media_box, output, page_num)?;
+    // output devices can start over with their pattern definitions on each page
+    p.tiling_patterns.clear();
+    p.process_stream(&doc, doc.get_page_content(object_id).unwrap(), resources, &media_box, output, GraphicsState::new())?;
+    for (rect, uri) in page_links(doc, page_dict) {
+        output.link(&rect, &uri)?;
+    }
     output.end_page()?;
     Ok(())
 }
+
+// The rectangles and URIs of the URI link annotations of a page
+fn page_links(doc: &Document, page_dict: &Dictionary) -> Vec<((f64, f64, f64, f64), String)> {
+    let mut links = Vec::new();
+    for annot in maybe_get_array(doc, page_dict, b"Annots").into_iter().flatten() {
+        let annot = match maybe_deref(doc, annot).as_dict() {
+            Ok(annot) => annot,
+            Err(_) => continue,
+        };
+        if maybe_get_name_string(doc, annot, b"Subtype").as_deref() != Some("Link") {
+            continue;
+        }
+        let action = match maybe_get::<&Dictionary>(doc, annot, b"A") {
+            Some(action) if maybe_get_name_string(doc, action, b"S").as_deref() == Some("URI") => action,
+            _ => continue,
+        };
+        let uri = match maybe_get_obj(doc, action, b"URI") {
+            Some(Object::String(uri, _)) => String::from_utf8_lossy(uri).into_owned(),
+            _ => continue,
+        };
+        if let Some([x0, y0, x1, y1]) = maybe_get::<Vec<f64>>(doc, annot, b"Rect").as_deref() {
+            links.push(((x0.min(*x1), y0.min(*y1), x0.max(*x1), y0.max(*y1)), uri));
+        }
+    }
+    links
+}
+
+// Find the fonts used by the pages of `doc`, including those used by form XObjects, along with
+// the pages that use them
+fn document_fonts(doc: &Document) -> Vec<(&Dictionary, Vec<u32>)> {
+    fn collect<'a>(doc: &'a Document, resources: &'a Dictionary, page_num: u32, depth: u32,
+                   fonts: &mut Vec<(&'a Dictionary, Vec<u32>)>) {
+        if let Some(font_dict) = maybe_get::<&Dictionary>(doc, resources, b"Font") {
+            for (_, font) in font_dict.iter() {
+                let font = match maybe_deref(doc, font).as_dict() {
+                    Ok(font) => font,
+                    Err(_) => continue,
+                };
+                match fonts.iter_mut().find(|(f, _)| std::ptr::eq(*f, font)) {
+                    Some((_, pages)) => if !pages.contains(&page_num) { pages.push(page_num) },
+                    None => fonts.push((font, vec![page_num])),
+                }
+            }
+        }
+        // guard against forms that draw themselves
+        if depth > 10 {
+            return;
+        }
+        // the glyphs of Type3 fonts can use fonts of their own
+        if let Some(font_dict) = maybe_get::<&Dictionary>(doc, resources, b"Font") {
+            for (_, font) in font_dict.iter() {
+                if let Some(resources) = maybe_deref(doc, font).as_dict().ok().and_then(|font| maybe_get::<&Dictionary>(doc, font, b"Resources")) {
+                    collect(doc, resources, page_num, depth + 1, fonts);
+                }
+            }
+        }
+        if let Some(xobjects) = maybe_get::<&Dictionary>(doc, resources, b"XObject") {
+            for (_, xobject) in xobjects.iter() {
+                if let Ok(stream) = maybe_deref(doc, xobject).as_stream() {
+                    if let Some(resources) = maybe_get::<&Dictionary>(doc, &stream.dict, b"Resources") {
+                        collect(doc, resources, page_num, depth + 1, fonts);
+                    }
+                }
+            }
+        }
+    }
+    let mut fonts = Vec::new();
+    for (page_num, object_id) in doc.get_pages() {
+        let page_dict = match doc.get_dictionary(object_id) {
+            Ok(page_dict) => page_dict,
+            Err(_) => continue,
+        };
+        if let Some(resources) = get_inherited::<&Dictionary>(doc, page_dict, b"Resources") {
+            collect(doc, resources, page_num, 0, &mut fonts);
+        }
+    }
+    fonts
+}
+
+/// List the character code to glyph name, glyph id and Unicode tables of all the fonts used in `doc`.
+/// This is useful for writing `FontOverrides`.
+pub fn font_glyph_tables(doc: &Document) -> Vec<FontGlyphTable> {
+    document_fonts(doc).into_iter().map(|(font, _)| {
+        let base_font = maybe_get_name_string(doc, font, b"BaseFont").unwrap_or_default();
+        let subtype = maybe_get_name_string(doc, font, b"Subtype").unwrap_or_default();
+        let glyphs = make_font(doc, font, &NO_FONT_OVERRIDES).glyph_table();
+        FontGlyphTable { base_font, subtype, glyphs }
+    }).collect()
+}
+
+fn subset_tag(base_font: &str) -> Option<String> {
+    // "the tag shall consist of exactly six uppercase letters"
+    let (tag, _) = base_font.split_once('+')?;
+    if tag.len() == 6 && tag.bytes().all(|b| b.is_ascii_uppercase()) {
+        Some(tag.to_owned())
+    } else {
+        None
+    }
+}
+
+fn encoding_description(doc: &Document, font: &Dictionary) -> Option<String> {
+    match maybe_get_obj(doc, font, b"Encoding")? {
+        Object::Name(name) => Some(pdf_to_utf8(name)),
+        Object::Dictionary(dict) => {
+            if dict.has(b"Differences") {
+                Some("Custom".to_owned())
+            } else {
+                maybe_get_name_string(doc, dict, b"BaseEncoding")
+            }
+        }
+        Object::Stream(stream) => Some(maybe_get_name_string(doc, &stream.dict, b"CMapName").unwrap_or_else(|| "Custom".to_owned())),
+        _ => None,
+    }
+}
+
+// Discards everything. Used for collecting the statistics of a `Processor`
+struct NullOutput;
+
+impl OutputDev for NullOutput {
+    fn begin_page(&mut self, _page_num: u32, _media_box: &MediaBox, _: Option<ArtBox>) -> Result<(), OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), OutputError> { Ok(()) }
+    fn output_character(&mut self, _trm: &Transform, _width: f64, _spacing: f64, _font_size: f64, _char: &str) -> Result<(), OutputError> { Ok(()) }
+    fn begin_word(&mut self) -> Result<(), OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), OutputError> { Ok(()) }
+}
+
+/// List the fonts used in `doc` similar to `pdffonts`. The document is run through
+/// extraction to find the char codes that can't be mapped to Unicode.
+pub fn list_fonts(doc: &Document) -> Result<Vec<FontReport>, OutputError> {
+    let empty_resources = Dictionary::new();
+    let mut p = Processor::new(&NO_FONT_OVERRIDES);
+    for (page_num, object_id) in doc.get_pages() {
+        output_doc_inner(page_num, object_id, doc, &mut p, &mut NullOutput, &empty_resources)?;
+    }
+
+    Ok(document_fonts(doc).into_iter().map(|(font, pages)| {
+        let base_font = maybe_get_name_string(doc, font, b"BaseFont").unwrap_or_default();
+        let subtype = maybe_get_name_string(doc, font, b"Subtype").unwrap_or_default();
+        let descendant = maybe_get_array(doc, font, b"DescendantFonts")
+            .and_then(|descendants| descendants.first())
+            .and_then(|descendant| maybe_deref(doc, descendant).as_dict().ok());
+        let cid_subtype = descendant.and_then(|descendant| maybe_get_name_string(doc, descendant, b"Subtype"));
+        let embedded = maybe_get::<&Dictionary>(doc, descendant.unwrap_or(font), b"FontDescriptor")
+            .and_then(|descriptor| font_program::embedded_stream(doc, descriptor))
+            .map(|(kind, _)| kind);
+        let mut unmapped: Vec<_> = p.unmapped_codes.get(&(font as *const Dictionary))
+            .map(|codes| codes.iter().map(|(code, count)| (*code, *count)).collect())
+            .unwrap_or_default();
+        unmapped.sort();
+        FontReport {
+            pages,
+            subset_tag: subset_tag(&base_font),
+            base_font,
+            subtype,
+            cid_subtype,
+            embedded,
+            encoding: encoding_description(doc, font),
+            has_to_unicode: font.has(b"ToUnicode"),
+            unmapped_codes: unmapped.iter().map(|(code, _)| *code).collect(),
+            unmapped_count: unmapped.iter().map(|(_, count)| count).sum(),
+        }
+    }).collect())
+}
+
+/// The font programs embedded in `doc`. Programs that are shared between fonts are only listed once.
+pub fn embedded_fonts(doc: &Document) -> Vec<EmbeddedFont> {
+    let mut seen: Vec<*const Stream> = Vec::new();
+    let mut result = Vec::new();
+    for (font, _) in document_fonts(doc) {
+        let descendant = maybe_get_array(doc, font, b"DescendantFonts")
+            .and_then(|descendants| descendants.first())
+            .and_then(|descendant| maybe_deref(doc, descendant).as_dict().ok());
+        let descriptor = match maybe_get::<&Dictionary>(doc, descendant.unwrap_or(font), b"FontDescriptor") {
+            Some(descriptor) => descriptor,
+            None => continue,
+        };
+        let (kind, stream) = match font_program::embedded_stream(doc, descriptor) {
+            Some(program) => program,
+            None => continue,
+        };
+        if seen.contains(&(stream as *const Stream)) {
+            continue;
+        }
+        seen.push(stream);
+
+        let pdf_font = make_font(doc, font, &NO_FONT_OVERRIDES);
+        let glyphs = used_glyphs(pdf_font.as_ref());
+        let type1_lengths = match (maybe_get::<i64>(doc, &stream.dict, b"Length1"), maybe_get::<i64>(doc, &stream.dict, b"Length2")) {
+            (Some(clear_text), Some(binary)) if kind == FontProgramKind::Type1 => Some((clear_text as usize, binary as usize)),
+            _ => None,
+        };
+        result.push(EmbeddedFont {
+            base_font: maybe_get_name_string(doc, font, b"BaseFont").unwrap_or_default(),
+            kind,
+            data: get_contents(stream),
+            type1_lengths,
+            glyphs,
+            ascent: maybe_get::<f64>(doc, descriptor, b"Ascent").unwrap_or(0.),
+            descent: maybe_get::<f64>(doc, descriptor, b"Descent").unwrap_or(0.),
+        });
+    }
+    result
+}
+
+// The glyph id, width and Unicode of the glyphs of `font` as `EmbeddedFont` keeps them
+fn used_glyphs(font: &dyn PdfFont) -> Vec<(u16, f64, Option<char>)> {
+    font.glyph_table().into_iter().filter_map(|glyph| {
+        let gid = u16::try_from(glyph.glyph_id?).ok()?;
+        let mut chars = glyph.unicode.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        };
+        Some((gid, font.get_width(glyph.code), c))
+    }).collect()
+}
+
+/// Write the font programs embedded in `doc` to `dir` using the names from `EmbeddedFont::file_name`,
+/// or `EmbeddedFont::opentype_file_name` for bare CFF programs when `wrap_cff` is set.
+/// Returns the paths of the written files.
+pub fn write_embedded_fonts(doc: &Document, dir: &std::path::Path, wrap_cff: bool) -> Result<Vec<std::path::PathBuf>, OutputError> {
+    let mut paths: Vec<std::path::PathBuf> = Vec::new();
+    for font in embedded_fonts(doc) {
+        let wrapped = if wrap_cff { font.to_opentype() } else { None };
+        let (file_name, contents) = match wrapped {
+            Some(otf) => (font.opentype_file_name(), otf),
+            None => (font.file_name(), font.file_contents()),
+        };
+        // different fonts can have the same base font
+        let mut path = dir.join(&file_name);
+        let mut i = 1;
+        while paths.contains(&path) {
+            i += 1;
+            let (stem, extension) = file_name.rsplit_once('.').unwrap_or((&file_name, ""));
+            path = dir.join(format!("{}-{}.{}", stem, i, extension));
+        }
+        std::fs::write(&path, contents)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
@@ -0,0 +1,96 @@
+// The structured document model built by `StructuredOutput`. Everything is in the default user
+// space of the page, i.e. in points with the origin at the bottom left of the page.
+
+use crate::MediaBox;
+
+/// A rectangle in the default user space of the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoundingBox {
+    pub llx: f64,
+    pub lly: f64,
+    pub urx: f64,
+    pub ury: f64,
+}
+
+impl BoundingBox {
+    pub(crate) fn new((llx, lly, urx, ury): (f64, f64, f64, f64)) -> BoundingBox {
+        BoundingBox { llx, lly, urx, ury }
+    }
+}
+
+/// The font that a glyph is drawn with
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontInfo {
+    /// The base font without the subset tag, if the font has one
+    pub name: Option<String>,
+    /// The size of the text on the page, taking the transformations into account
+    pub size: f64,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlyphModel {
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub font: FontInfo,
+    /// The fill color, or the stroke color of glyphs that are only stroked, as RGB components
+    /// from 0 to 1. Invisible glyphs and glyphs in color spaces that can't be converted have none.
+    pub color: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WordModel {
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub glyphs: Vec<GlyphModel>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineModel {
+    pub bbox: BoundingBox,
+    /// The y coordinate of the baseline
+    pub baseline: f64,
+    pub words: Vec<WordModel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockKind {
+    Text,
+    /// The lines are the rows of the table
+    Table,
+    /// An image, which has no lines
+    Figure,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockModel {
+    pub kind: BlockKind,
+    pub bbox: BoundingBox,
+    /// The position of the block in the reading order of the page, starting at 0
+    pub reading_order: usize,
+    pub lines: Vec<LineModel>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageModel {
+    pub page_num: u32,
+    pub media_box: MediaBox,
+    /// The blocks in reading order
+    pub blocks: Vec<BlockModel>,
+}
+
+/// The text of a document grouped into pages, blocks, lines, words and glyphs
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocumentModel {
+    pub pages: Vec<PageModel>,
+}
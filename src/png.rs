@@ -0,0 +1,49 @@
+// A minimal PNG encoder for the RGBA images that the output devices produce
+
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encode 8-bit RGBA pixels, `width` * 4 bytes per row, as a PNG file
+pub(crate) fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, no filtering, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let stride = width as usize * 4;
+    if stride > 0 {
+        for row in rgba.chunks(stride).take(height as usize) {
+            // each row starts with its filter type, which is always None
+            encoder.write_all(&[0]).unwrap();
+            encoder.write_all(row).unwrap();
+        }
+    }
+    write_chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
@@ -0,0 +1,921 @@
+// A software rasterizer that renders pages to RGBA pixels
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use euclid::point2;
+
+use crate::{png, BlendMode, ColorSpace, FillRule, Image, ImageFormat, LineCap, LineJoin, MediaBox, OutputDev,
+            OutputError, Path, PathOp, Shading, ShadingGeometry, StrokeStyle, TextGlyph, TextRenderMode, TilingPattern,
+            Transform, Transparency};
+
+/// An RGBA image of a page, with 8 bits per component and alpha that isn't premultiplied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixmap {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels row by row from the top
+    pub data: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+    }
+
+    pub fn write_png(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        w.write_all(&png::encode_rgba(self.width, self.height, &self.data))
+    }
+
+    /// Write a binary PPM. It doesn't have alpha so the pixels are composited on white.
+    pub fn write_ppm(&self, w: &mut dyn std::io::Write) -> std::io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let rgb: Vec<u8> = self.data.chunks(4).flat_map(|p| {
+            let a = p[3] as u32;
+            [0, 1, 2].map(|c| ((p[c] as u32 * a + 255 * (255 - a)) / 255) as u8)
+        }).collect();
+        w.write_all(&rgb)
+    }
+}
+
+// the number of sub-scanlines per row of pixels used for antialiasing
+const SUBSAMPLES: usize = 4;
+// how far flattened curves may be from the real ones, in pixels
+const TOLERANCE: f64 = 0.2;
+
+// Coverage of the pixels in a rectangle of the canvas, from 0 to 1
+struct Mask {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Mask {
+    fn get(&self, x: usize, y: usize) -> f32 {
+        if x < self.x || y < self.y || x >= self.x + self.width || y >= self.y + self.height {
+            return 0.;
+        }
+        self.data[(y - self.y) * self.width + x - self.x]
+    }
+}
+
+struct Edge {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+    // +1 for edges that go down, -1 for edges that go up
+    winding: i32,
+}
+
+fn transform_point(m: &Transform, x: f64, y: f64) -> (f64, f64) {
+    let p = m.transform_point(point2(x, y));
+    (p.x, p.y)
+}
+
+// how much `m` scales lengths on average
+fn scale_of(m: &Transform) -> f64 {
+    (m.m11 * m.m22 - m.m12 * m.m21).abs().sqrt()
+}
+
+// Flatten `path` into polylines after transforming it by `m`. `tolerance` is in the units after
+// the transform. The second value of each polyline says whether it was closed.
+fn flatten(path: &Path, m: &Transform, tolerance: f64) -> Vec<(Vec<(f64, f64)>, bool)> {
+    let mut polylines = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+    let mut start = (0., 0.);
+    let mut last = (0., 0.);
+    let finish = |current: &mut Vec<(f64, f64)>, polylines: &mut Vec<(Vec<(f64, f64)>, bool)>, closed: bool| {
+        if !current.is_empty() {
+            polylines.push((std::mem::take(current), closed));
+        }
+    };
+    for op in &path.ops {
+        match *op {
+            PathOp::MoveTo(x, y) => {
+                finish(&mut current, &mut polylines, false);
+                last = (x, y);
+                start = last;
+                current.push(transform_point(m, x, y));
+            }
+            PathOp::LineTo(x, y) => {
+                if current.is_empty() {
+                    current.push(transform_point(m, last.0, last.1));
+                }
+                last = (x, y);
+                current.push(transform_point(m, x, y));
+            }
+            PathOp::CurveTo(x1, y1, x2, y2, x, y) => {
+                if current.is_empty() {
+                    current.push(transform_point(m, last.0, last.1));
+                }
+                let p0 = *current.last().unwrap();
+                let p1 = transform_point(m, x1, y1);
+                let p2 = transform_point(m, x2, y2);
+                let p3 = transform_point(m, x, y);
+                let distance = |a: (f64, f64), b: (f64, f64)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+                let length = distance(p0, p1) + distance(p1, p2) + distance(p2, p3);
+                let n = (length / tolerance).sqrt().ceil().clamp(1., 500.) as usize;
+                for i in 1..=n {
+                    let t = i as f64 / n as f64;
+                    let u = 1. - t;
+                    let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+                    current.push((a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1));
+                }
+                last = (x, y);
+            }
+            PathOp::Close => {
+                finish(&mut current, &mut polylines, true);
+                last = start;
+            }
+            PathOp::Rect(x, y, width, height) => {
+                finish(&mut current, &mut polylines, false);
+                let rect = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+                polylines.push((rect.iter().map(|&(x, y)| transform_point(m, x, y)).collect(), true));
+                last = (x, y);
+                start = last;
+            }
+        }
+    }
+    finish(&mut current, &mut polylines, false);
+    polylines
+}
+
+// The antialiased coverage of the polygons, which are implicitly closed, on a canvas of the given size
+fn rasterize(polygons: &[Vec<(f64, f64)>], fill_rule: FillRule, canvas_width: usize, canvas_height: usize) -> Option<Mask> {
+    let mut edges = Vec::new();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for polygon in polygons {
+        for (i, &(x0, y0)) in polygon.iter().enumerate() {
+            let (x1, y1) = polygon[(i + 1) % polygon.len()];
+            if !(x0.is_finite() && y0.is_finite() && x1.is_finite() && y1.is_finite()) {
+                return None;
+            }
+            min_x = min_x.min(x0);
+            max_x = max_x.max(x0);
+            min_y = min_y.min(y0);
+            max_y = max_y.max(y0);
+            if y0 == y1 {
+                continue;
+            }
+            edges.push(if y0 < y1 {
+                Edge { x0, y0, x1, y1, winding: 1 }
+            } else {
+                Edge { x0: x1, y0: y1, x1: x0, y1: y0, winding: -1 }
+            });
+        }
+    }
+    if edges.is_empty() {
+        return None;
+    }
+    let x = min_x.floor().max(0.) as usize;
+    let y = min_y.floor().max(0.) as usize;
+    let right = (max_x.ceil().max(0.) as usize).min(canvas_width);
+    let bottom = (max_y.ceil().max(0.) as usize).min(canvas_height);
+    if right <= x || bottom <= y {
+        return None;
+    }
+    let (width, height) = (right - x, bottom - y);
+    let mut data = vec![0f32; width * height];
+
+    edges.sort_by(|a, b| a.y0.total_cmp(&b.y0));
+    let mut next_edge = 0;
+    let mut active: Vec<&Edge> = Vec::new();
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    // coverage of the partially covered pixels and the change in coverage of fully covered runs
+    let mut cover = vec![0f32; width + 1];
+    let mut delta = vec![0f32; width + 1];
+    let weight = 1. / SUBSAMPLES as f32;
+    for row in 0..height {
+        cover.iter_mut().for_each(|c| *c = 0.);
+        delta.iter_mut().for_each(|c| *c = 0.);
+        for sub in 0..SUBSAMPLES {
+            let sy = (y + row) as f64 + (sub as f64 + 0.5) / SUBSAMPLES as f64;
+            while next_edge < edges.len() && edges[next_edge].y0 <= sy {
+                active.push(&edges[next_edge]);
+                next_edge += 1;
+            }
+            active.retain(|e| e.y1 > sy);
+            crossings.clear();
+            for e in &active {
+                if e.y0 <= sy {
+                    crossings.push((e.x0 + (sy - e.y0) * (e.x1 - e.x0) / (e.y1 - e.y0), e.winding));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for i in 0..crossings.len() {
+                winding += crossings[i].1;
+                let inside = match fill_rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if !inside || i + 1 == crossings.len() {
+                    continue;
+                }
+                let x0 = (crossings[i].0 - x as f64).clamp(0., width as f64);
+                let x1 = (crossings[i + 1].0 - x as f64).clamp(0., width as f64);
+                if x1 <= x0 {
+                    continue;
+                }
+                let (i0, i1) = (x0 as usize, x1 as usize);
+                if i0 == i1 {
+                    cover[i0] += (x1 - x0) as f32 * weight;
+                } else {
+                    cover[i0] += (i0 as f64 + 1. - x0) as f32 * weight;
+                    delta[i0 + 1] += weight;
+                    delta[i1] -= weight;
+                    cover[i1] += (x1 - i1 as f64) as f32 * weight;
+                }
+            }
+        }
+        let mut run = 0.;
+        let out = &mut data[row * width..(row + 1) * width];
+        for (i, coverage) in out.iter_mut().enumerate() {
+            run += delta[i];
+            *coverage = (run + cover[i]).min(1.);
+        }
+    }
+    Some(Mask { x, y, width, height, data })
+}
+
+fn signed_area(polygon: &[(f64, f64)]) -> f64 {
+    let mut area = 0.;
+    for (i, &(x0, y0)) in polygon.iter().enumerate() {
+        let (x1, y1) = polygon[(i + 1) % polygon.len()];
+        area += x0 * y1 - x1 * y0;
+    }
+    area / 2.
+}
+
+// Split polylines into dashes
+fn dash(polylines: Vec<(Vec<(f64, f64)>, bool)>, dash_array: &[f64], dash_phase: f64) -> Vec<(Vec<(f64, f64)>, bool)> {
+    let total: f64 = dash_array.iter().sum();
+    if dash_array.iter().any(|d| *d < 0.) || total <= 0. {
+        return polylines;
+    }
+    let mut dashes = Vec::new();
+    for (mut points, closed) in polylines {
+        if closed {
+            points.push(points[0]);
+        }
+        // find where the phase puts us in the dash array
+        let mut index = 0;
+        let mut remaining = dash_array[0];
+        let mut phase = dash_phase.rem_euclid(total * if dash_array.len() % 2 == 1 { 2. } else { 1. });
+        let mut on = true;
+        while phase > 0. {
+            if phase < remaining {
+                remaining -= phase;
+                break;
+            }
+            phase -= remaining;
+            index = (index + 1) % dash_array.len();
+            remaining = dash_array[index];
+            on = !on;
+        }
+        let mut current = if on { vec![points[0]] } else { Vec::new() };
+        for pair in points.windows(2) {
+            let (mut a, b) = (pair[0], pair[1]);
+            let mut length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            while length > remaining {
+                let t = remaining / length;
+                let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                if on {
+                    current.push(p);
+                    dashes.push((std::mem::take(&mut current), false));
+                } else {
+                    current.push(p);
+                }
+                on = !on;
+                length -= remaining;
+                a = p;
+                index = (index + 1) % dash_array.len();
+                remaining = dash_array[index];
+                // guard against dash arrays of zeros
+                if remaining <= 0. && dash_array.iter().all(|d| *d <= 0.) {
+                    break;
+                }
+            }
+            remaining -= length;
+            if on {
+                current.push(b);
+            }
+        }
+        if on && current.len() > 1 {
+            dashes.push((current, false));
+        }
+    }
+    dashes
+}
+
+fn circle(center: (f64, f64), radius: f64, segments: usize) -> Vec<(f64, f64)> {
+    (0..segments).map(|i| {
+        let angle = i as f64 * std::f64::consts::TAU / segments as f64;
+        (center.0 + radius * angle.cos(), center.1 + radius * angle.sin())
+    }).collect()
+}
+
+// The polygons that cover the stroke of `path`, in the user space of the path. They all have the
+// same orientation so they can be filled together with the nonzero rule.
+fn stroke_polygons(path: &Path, style: &StrokeStyle, device_scale: f64) -> Vec<Vec<(f64, f64)>> {
+    let identity = Transform::identity();
+    let mut polylines = flatten(path, &identity, TOLERANCE / device_scale);
+    if !style.dash_array.is_empty() {
+        polylines = dash(polylines, &style.dash_array, style.dash_phase);
+    }
+    // a width of 0 means the thinnest line that can be drawn
+    let half_width = if style.line_width > 0. { style.line_width / 2. } else { 0.5 / device_scale };
+    let circle_segments = ((half_width * device_scale * 2.).ceil() as usize).clamp(8, 64);
+    let mut polygons = Vec::new();
+    for (mut points, closed) in polylines {
+        points.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        if closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() == 1 {
+            // zero length subpaths only show up with round caps
+            if style.line_cap == LineCap::Round {
+                polygons.push(circle(points[0], half_width, circle_segments));
+            }
+            continue;
+        }
+        let segment_count = if closed { points.len() } else { points.len() - 1 };
+        let direction = |i: usize| {
+            let (a, b) = (points[i % points.len()], points[(i + 1) % points.len()]);
+            let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            ((b.0 - a.0) / length, (b.1 - a.1) / length)
+        };
+        for i in 0..segment_count {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            let d = direction(i);
+            let n = (-d.1 * half_width, d.0 * half_width);
+            polygons.push(vec![(a.0 + n.0, a.1 + n.1), (b.0 + n.0, b.1 + n.1), (b.0 - n.0, b.1 - n.1), (a.0 - n.0, a.1 - n.1)]);
+        }
+        // joins between segments
+        let joins = if closed { 0..points.len() } else { 1..points.len() - 1 };
+        for i in joins {
+            let v = points[i];
+            let d0 = direction((i + points.len() - 1) % points.len());
+            let d1 = direction(i);
+            let cross = d0.0 * d1.1 - d0.1 * d1.0;
+            let dot = d0.0 * d1.0 + d0.1 * d1.1;
+            if cross.abs() < 1e-9 && dot > 0. {
+                continue;
+            }
+            // the outside of the turn
+            let side = if cross > 0. { -1. } else { 1. };
+            let o0 = (-d0.1 * half_width * side, d0.0 * half_width * side);
+            let o1 = (-d1.1 * half_width * side, d1.0 * half_width * side);
+            let bevel = vec![v, (v.0 + o0.0, v.1 + o0.1), (v.0 + o1.0, v.1 + o1.1)];
+            match style.line_join {
+                LineJoin::Round => polygons.push(circle(v, half_width, circle_segments)),
+                LineJoin::Bevel => polygons.push(bevel),
+                LineJoin::Miter => {
+                    let sin_half_angle = ((1. + dot) / 2.).max(0.).sqrt();
+                    let ratio = if sin_half_angle > 0. { 1. / sin_half_angle } else { f64::INFINITY };
+                    let bisector = (o0.0 + o1.0, o0.1 + o1.1);
+                    let length = (bisector.0.powi(2) + bisector.1.powi(2)).sqrt();
+                    if ratio <= style.miter_limit && length > 0. {
+                        let tip = (v.0 + bisector.0 / length * half_width * ratio, v.1 + bisector.1 / length * half_width * ratio);
+                        polygons.push(vec![v, (v.0 + o0.0, v.1 + o0.1), tip, (v.0 + o1.0, v.1 + o1.1)]);
+                    } else {
+                        polygons.push(bevel);
+                    }
+                }
+            }
+        }
+        if !closed {
+            let ends = [(points[0], direction(0), -1.), (points[points.len() - 1], direction(points.len() - 2), 1.)];
+            for (p, d, forward) in ends {
+                match style.line_cap {
+                    LineCap::Butt => {}
+                    LineCap::Round => polygons.push(circle(p, half_width, circle_segments)),
+                    LineCap::ProjectingSquare => {
+                        let n = (-d.1 * half_width, d.0 * half_width);
+                        let e = (d.0 * half_width * forward, d.1 * half_width * forward);
+                        polygons.push(vec![(p.0 + n.0, p.1 + n.1), (p.0 + n.0 + e.0, p.1 + n.1 + e.1),
+                                           (p.0 - n.0 + e.0, p.1 - n.1 + e.1), (p.0 - n.0, p.1 - n.1)]);
+                    }
+                }
+            }
+        }
+    }
+    for polygon in &mut polygons {
+        if signed_area(polygon) < 0. {
+            polygon.reverse();
+        }
+    }
+    polygons
+}
+
+// separable blend modes (11.3.5 "Blend mode") of a backdrop and a source component
+fn blend(mode: BlendMode, b: f32, s: f32) -> f32 {
+    match mode {
+        BlendMode::Multiply => b * s,
+        BlendMode::Screen => b + s - b * s,
+        BlendMode::Overlay => blend(BlendMode::HardLight, s, b),
+        BlendMode::Darken => b.min(s),
+        BlendMode::Lighten => b.max(s),
+        BlendMode::ColorDodge => if b == 0. { 0. } else if s >= 1. { 1. } else { (b / (1. - s)).min(1.) },
+        BlendMode::ColorBurn => if b >= 1. { 1. } else if s <= 0. { 0. } else { 1. - ((1. - b) / s).min(1.) },
+        BlendMode::HardLight => if s <= 0.5 { b * 2. * s } else { blend(BlendMode::Screen, b, 2. * s - 1.) },
+        BlendMode::SoftLight => {
+            if s <= 0.5 {
+                b - (1. - 2. * s) * b * (1. - b)
+            } else {
+                let d = if b <= 0.25 { ((16. * b - 12.) * b + 4.) * b } else { b.sqrt() };
+                b + (2. * s - 1.) * (d - b)
+            }
+        }
+        BlendMode::Difference => (b - s).abs(),
+        BlendMode::Exclusion => b + s - 2. * b * s,
+        // the non-separable modes are drawn like Normal
+        _ => s,
+    }
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    // premultiplied RGBA
+    pixels: Vec<u8>,
+    // maps the user space of the page to pixels
+    device: Transform,
+    clip: Option<Rc<Mask>>,
+    saved_clips: Vec<Option<Rc<Mask>>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, device: Transform, background: [u8; 4]) -> Canvas {
+        Canvas { width, height, pixels: background.repeat(width * height), device, clip: None, saved_clips: Vec::new() }
+    }
+
+    fn clip_at(&self, x: usize, y: usize) -> f32 {
+        self.clip.as_ref().map_or(1., |clip| clip.get(x, y))
+    }
+
+    // composite the premultiplied color `source` scaled by `coverage` onto the pixel at (x, y)
+    fn composite(&mut self, x: usize, y: usize, source: [f32; 4], coverage: f32, blend_mode: BlendMode) {
+        let coverage = coverage * self.clip_at(x, y);
+        if coverage <= 0. {
+            return;
+        }
+        let i = (y * self.width + x) * 4;
+        let dst = &mut self.pixels[i..i + 4];
+        let backdrop = [0, 1, 2, 3].map(|c| dst[c] as f32 / 255.);
+        let mut source = source.map(|c| c * coverage);
+        if blend_mode != BlendMode::Normal && backdrop[3] > 0. && source[3] > 0. {
+            for c in 0..3 {
+                let (b, s) = (backdrop[c] / backdrop[3], source[c] / source[3]);
+                let blended = (1. - backdrop[3]) * s + backdrop[3] * blend(blend_mode, b, s);
+                source[c] = blended * source[3];
+            }
+        }
+        for c in 0..4 {
+            let value = source[c] + backdrop[c] * (1. - source[3]);
+            dst[c] = (value * 255.).round().clamp(0., 255.) as u8;
+        }
+    }
+
+    fn fill_mask(&mut self, mask: &Mask, rgb: [f64; 3], alpha: f64, blend_mode: BlendMode) {
+        let a = alpha as f32;
+        let source = [rgb[0] as f32 * a, rgb[1] as f32 * a, rgb[2] as f32 * a, a];
+        for row in 0..mask.height {
+            for column in 0..mask.width {
+                let coverage = mask.data[row * mask.width + column];
+                if coverage > 0. {
+                    self.composite(mask.x + column, mask.y + row, source, coverage, blend_mode);
+                }
+            }
+        }
+    }
+
+    fn path_mask(&self, ctm: &Transform, path: &Path, fill_rule: FillRule) -> Option<Mask> {
+        let m = ctm.post_transform(&self.device);
+        let polygons: Vec<_> = flatten(path, &m, TOLERANCE).into_iter().map(|(points, _)| points).collect();
+        rasterize(&polygons, fill_rule, self.width, self.height)
+    }
+
+    fn stroke_mask(&self, ctm: &Transform, path: &Path, style: &StrokeStyle) -> Option<Mask> {
+        let m = ctm.post_transform(&self.device);
+        let polygons: Vec<_> = stroke_polygons(path, style, scale_of(&m)).into_iter()
+            .map(|polygon| polygon.into_iter().map(|(x, y)| transform_point(&m, x, y)).collect())
+            .collect();
+        rasterize(&polygons, FillRule::NonZero, self.width, self.height)
+    }
+
+    fn full_mask(&self) -> Mask {
+        Mask { x: 0, y: 0, width: self.width, height: self.height, data: vec![1.; self.width * self.height] }
+    }
+}
+
+// A rendered tiling pattern cell
+struct PatternCell {
+    canvas: Canvas,
+    pattern: TilingPattern,
+    // pixels per unit of pattern space
+    scale: (f64, f64),
+}
+
+fn color_rgb(colorspace: &ColorSpace, color: &[f64]) -> [f64; 3] {
+    colorspace.to_rgb(color).unwrap_or([0., 0., 0.])
+}
+
+/// Renders pages to RGBA pixels. Glyphs are drawn with the outlines from the embedded font
+/// programs, so text in fonts that aren't embedded isn't drawn. JPEG and the other encoded image
+/// formats aren't decoded and are drawn as gray boxes. Soft masks are ignored.
+pub struct RasterOutput {
+    dpi: f64,
+    pages: Vec<Pixmap>,
+    // the page and the pattern cells that are being drawn, in that order
+    canvases: Vec<Canvas>,
+    patterns: HashMap<usize, PatternCell>,
+    // the patterns of the cells that are being drawn, with their pixels per unit of pattern space
+    pattern_stack: Vec<(TilingPattern, (f64, f64))>,
+    transparency: Transparency,
+}
+
+impl RasterOutput {
+    pub fn new(dpi: f64) -> RasterOutput {
+        RasterOutput { dpi, pages: Vec::new(), canvases: Vec::new(), patterns: HashMap::new(), pattern_stack: Vec::new(), transparency: Transparency::new() }
+    }
+
+    /// The pages that have been rendered so far
+    pub fn pages(&self) -> &[Pixmap] {
+        &self.pages
+    }
+
+    pub fn into_pages(self) -> Vec<Pixmap> {
+        self.pages
+    }
+
+    fn canvas(&mut self) -> Option<&mut Canvas> {
+        self.canvases.last_mut()
+    }
+
+    fn fill_with(&mut self, mask: Option<Mask>, colorspace: &ColorSpace, color: &[f64], alpha: f64) {
+        let blend_mode = self.transparency.blend_mode;
+        if let (Some(canvas), Some(mask)) = (self.canvas(), mask) {
+            canvas.fill_mask(&mask, color_rgb(colorspace, color), alpha, blend_mode);
+        }
+    }
+
+    fn draw_image_pixels(&mut self, ctm: &Transform, width: usize, height: usize, rgba: &[u8]) {
+        let alpha = self.transparency.fill_alpha as f32;
+        let blend_mode = self.transparency.blend_mode;
+        let canvas = match self.canvases.last_mut() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let m = ctm.post_transform(&canvas.device);
+        let inverse = match m.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let corners = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)].map(|(x, y)| transform_point(&m, x, y));
+        let min_x = corners.iter().map(|p| p.0).fold(f64::MAX, f64::min).floor().max(0.) as usize;
+        let max_x = (corners.iter().map(|p| p.0).fold(f64::MIN, f64::max).ceil().max(0.) as usize).min(canvas.width);
+        let min_y = corners.iter().map(|p| p.1).fold(f64::MAX, f64::min).floor().max(0.) as usize;
+        let max_y = (corners.iter().map(|p| p.1).fold(f64::MIN, f64::max).ceil().max(0.) as usize).min(canvas.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (u, v) = transform_point(&inverse, x as f64 + 0.5, y as f64 + 0.5);
+                if !(0. ..1.).contains(&u) || !(0. ..1.).contains(&v) {
+                    continue;
+                }
+                // the first row of the image is at the top of the unit square
+                let column = ((u * width as f64) as usize).min(width - 1);
+                let row = (((1. - v) * height as f64) as usize).min(height - 1);
+                let i = (row * width + column) * 4;
+                let a = rgba[i + 3] as f32 / 255. * alpha;
+                let source = [0, 1, 2].map(|c| rgba[i + c] as f32 / 255. * a);
+                canvas.composite(x, y, [source[0], source[1], source[2], a], 1., blend_mode);
+            }
+        }
+    }
+}
+
+impl OutputDev for RasterOutput {
+    fn begin_page(&mut self, _page_num: u32, media_box: &MediaBox, art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        let (llx, lly, urx, ury) = art_box.unwrap_or((media_box.llx, media_box.lly, media_box.urx, media_box.ury));
+        let scale = self.dpi / 72.;
+        let width = ((urx - llx).abs() * scale).ceil().max(1.) as usize;
+        let height = ((ury - lly).abs() * scale).ceil().max(1.) as usize;
+        // pixels go down from the top left corner
+        let device = Transform::create_translation(-llx, -ury).post_scale(scale, -scale);
+        self.canvases = vec![Canvas::new(width, height, device, [255; 4])];
+        self.patterns.clear();
+        self.pattern_stack.clear();
+        self.transparency = Transparency::new();
+        Ok(())
+    }
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        if let Some(canvas) = self.canvases.drain(..).next() {
+            let mut data = canvas.pixels;
+            for pixel in data.chunks_mut(4) {
+                let a = pixel[3] as u32;
+                if a > 0 && a < 255 {
+                    for c in &mut pixel[..3] {
+                        *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+                    }
+                }
+            }
+            self.pages.push(Pixmap { width: canvas.width as u32, height: canvas.height as u32, data });
+        }
+        Ok(())
+    }
+    fn output_character(&mut self, _trm: &Transform, _width: f64, _spacing: f64, _font_size: f64, _char: &str) -> Result<(), OutputError> {
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_word(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn end_line(&mut self) -> Result<(), OutputError> {Ok(())}
+    fn fill(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.fill_path(ctm, colorspace, color, path, FillRule::NonZero)
+    }
+    fn fill_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        let mask = self.canvas().and_then(|canvas| canvas.path_mask(ctm, path, fill_rule));
+        self.fill_with(mask, colorspace, color, self.transparency.fill_alpha);
+        Ok(())
+    }
+    fn stroke(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path) -> Result<(), OutputError> {
+        self.stroke_path(ctm, colorspace, color, path, &StrokeStyle::new())
+    }
+    fn stroke_path(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], path: &Path, style: &StrokeStyle) -> Result<(), OutputError> {
+        let mask = self.canvas().and_then(|canvas| canvas.stroke_mask(ctm, path, style));
+        self.fill_with(mask, colorspace, color, self.transparency.stroke_alpha);
+        Ok(())
+    }
+    fn image(&mut self, ctm: &Transform, colorspace: &ColorSpace, color: &[f64], image: &Image) -> Result<(), OutputError> {
+        match image.to_rgba(color_rgb(colorspace, color)) {
+            Some(rgba) if image.width > 0 && image.height > 0 => {
+                self.draw_image_pixels(ctm, image.width as usize, image.height as usize, &rgba);
+            }
+            Some(_) => {}
+            None => {
+                if image.format == ImageFormat::Raw {
+                    dlog!("can't convert image");
+                    return Ok(());
+                }
+                let square = Path { ops: vec![PathOp::Rect(0., 0., 1., 1.)] };
+                self.fill_path(ctm, &ColorSpace::DeviceGray, &[0.75], &square, FillRule::NonZero)?;
+            }
+        }
+        Ok(())
+    }
+    fn set_transparency(&mut self, transparency: &Transparency) -> Result<(), OutputError> {
+        self.transparency = transparency.clone();
+        Ok(())
+    }
+    fn save_state(&mut self) -> Result<(), OutputError> {
+        if let Some(canvas) = self.canvas() {
+            canvas.saved_clips.push(canvas.clip.clone());
+        }
+        Ok(())
+    }
+    fn restore_state(&mut self) -> Result<(), OutputError> {
+        if let Some(canvas) = self.canvas() {
+            if let Some(clip) = canvas.saved_clips.pop() {
+                canvas.clip = clip;
+            }
+        }
+        Ok(())
+    }
+    fn clip(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule) -> Result<(), OutputError> {
+        let canvas = match self.canvas() {
+            Some(canvas) => canvas,
+            None => return Ok(()),
+        };
+        // an empty clip is a mask without any coverage
+        let mut mask = canvas.path_mask(ctm, path, fill_rule)
+            .unwrap_or(Mask { x: 0, y: 0, width: 0, height: 0, data: Vec::new() });
+        if let Some(clip) = &canvas.clip {
+            for row in 0..mask.height {
+                for column in 0..mask.width {
+                    mask.data[row * mask.width + column] *= clip.get(mask.x + column, mask.y + row);
+                }
+            }
+        }
+        canvas.clip = Some(Rc::new(mask));
+        Ok(())
+    }
+    fn draw_glyph(&mut self, glyph: &TextGlyph) -> Result<(), OutputError> {
+        let (fill, stroke) = match glyph.render_mode {
+            TextRenderMode::Fill | TextRenderMode::FillClip => (true, false),
+            TextRenderMode::Stroke | TextRenderMode::StrokeClip => (false, true),
+            TextRenderMode::FillStroke | TextRenderMode::FillStrokeClip => (true, true),
+            TextRenderMode::Invisible | TextRenderMode::Clip => return Ok(()),
+        };
+        let outline = match glyph.outline() {
+            Some(outline) => outline,
+            None => return Ok(()),
+        };
+        // the outline is in glyph space
+        let scale = glyph.font_size / 1000.;
+        let ctm = Transform::create_scale(scale, scale).post_transform(&glyph.trm);
+        if fill {
+            self.fill_path(&ctm, glyph.fill_colorspace, glyph.fill_color, &outline, FillRule::NonZero)?;
+        }
+        if stroke && scale != 0. {
+            let style = StrokeStyle { line_width: glyph.line_width / scale, ..StrokeStyle::new() };
+            self.stroke_path(&ctm, glyph.stroke_colorspace, glyph.stroke_color, &outline, &style)?;
+        }
+        Ok(())
+    }
+    fn shade(&mut self, ctm: &Transform, shading: &Shading, path: Option<(&Transform, &Path, FillRule)>) -> Result<(), OutputError> {
+        let alpha = self.transparency.fill_alpha;
+        let blend_mode = self.transparency.blend_mode;
+        let canvas = match self.canvases.last_mut() {
+            Some(canvas) => canvas,
+            None => return Ok(()),
+        };
+        let mask = match path {
+            Some((path_ctm, path, fill_rule)) => canvas.path_mask(path_ctm, path, fill_rule),
+            None => Some(canvas.full_mask()),
+        };
+        let mask = match mask {
+            Some(mask) => mask,
+            None => return Ok(()),
+        };
+        let m = ctm.post_transform(&canvas.device);
+        let background = match (path, &shading.background) {
+            (Some(_), Some(background)) => Some(color_rgb(&shading.colorspace, background)),
+            _ => None,
+        };
+        match &shading.geometry {
+            ShadingGeometry::Axial { domain, extend, .. } | ShadingGeometry::Radial { domain, extend, .. } => {
+                let inverse = match m.inverse() {
+                    Some(inverse) => inverse,
+                    None => return Ok(()),
+                };
+                // sample the colors along the shading instead of evaluating the functions per pixel
+                const SAMPLES: usize = 256;
+                let colors: Vec<[f64; 3]> = (0..=SAMPLES).map(|i| {
+                    let t = domain[0] + (domain[1] - domain[0]) * i as f64 / SAMPLES as f64;
+                    color_rgb(&shading.colorspace, &shading.color_at(t))
+                }).collect();
+                // the position of a point between the start and end of the shading
+                let position = |x: f64, y: f64| -> Option<f64> {
+                    match shading.geometry {
+                        ShadingGeometry::Axial { coords: [x0, y0, x1, y1], .. } => {
+                            let length = (x1 - x0).powi(2) + (y1 - y0).powi(2);
+                            if length == 0. {
+                                return None;
+                            }
+                            Some(((x - x0) * (x1 - x0) + (y - y0) * (y1 - y0)) / length)
+                        }
+                        ShadingGeometry::Radial { coords: [x0, y0, r0, x1, y1, r1], .. } => {
+                            // solve |p - c(s)| = r(s) for the largest s with r(s) >= 0
+                            let (cdx, cdy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+                            let (pdx, pdy) = (x - x0, y - y0);
+                            let a = cdx * cdx + cdy * cdy - dr * dr;
+                            let b = pdx * cdx + pdy * cdy + r0 * dr;
+                            let c = pdx * pdx + pdy * pdy - r0 * r0;
+                            let candidates = if a.abs() < 1e-12 {
+                                if b == 0. { return None; }
+                                [c / (2. * b), f64::NAN]
+                            } else {
+                                let discriminant = b * b - a * c;
+                                if discriminant < 0. {
+                                    return None;
+                                }
+                                let root = discriminant.sqrt();
+                                let (s1, s2) = ((b + root) / a, (b - root) / a);
+                                [s1.max(s2), s1.min(s2)]
+                            };
+                            candidates.iter().copied().find(|s| {
+                                s.is_finite() && r0 + s * dr >= 0. &&
+                                    (*s >= 0. || extend[0]) && (*s <= 1. || extend[1])
+                            })
+                        }
+                        _ => None,
+                    }
+                };
+                for row in 0..mask.height {
+                    for column in 0..mask.width {
+                        let coverage = mask.data[row * mask.width + column];
+                        if coverage <= 0. {
+                            continue;
+                        }
+                        let (x, y) = (mask.x + column, mask.y + row);
+                        let (sx, sy) = transform_point(&inverse, x as f64 + 0.5, y as f64 + 0.5);
+                        let rgb = match position(sx, sy) {
+                            Some(s) if s < 0. && !extend[0] => background,
+                            Some(s) if s > 1. && !extend[1] => background,
+                            Some(s) => Some(colors[(s.clamp(0., 1.) * SAMPLES as f64).round() as usize]),
+                            None => background,
+                        };
+                        if let Some(rgb) = rgb {
+                            let a = alpha as f32;
+                            let source = [rgb[0] as f32 * a, rgb[1] as f32 * a, rgb[2] as f32 * a, a];
+                            canvas.composite(x, y, source, coverage, blend_mode);
+                        }
+                    }
+                }
+            }
+            ShadingGeometry::Polygons(polygons) => {
+                if let Some(background) = background {
+                    canvas.fill_mask(&mask, background, alpha, blend_mode);
+                }
+                for polygon in polygons {
+                    let points: Vec<_> = polygon.points.iter().map(|&(x, y)| transform_point(&m, x, y)).collect();
+                    let polygon_mask = match rasterize(&[points], FillRule::NonZero, canvas.width, canvas.height) {
+                        Some(polygon_mask) => polygon_mask,
+                        None => continue,
+                    };
+                    let rgb = color_rgb(&shading.colorspace, &polygon.color);
+                    let a = alpha as f32;
+                    let source = [rgb[0] as f32 * a, rgb[1] as f32 * a, rgb[2] as f32 * a, a];
+                    for row in 0..polygon_mask.height {
+                        for column in 0..polygon_mask.width {
+                            let (x, y) = (polygon_mask.x + column, polygon_mask.y + row);
+                            let coverage = polygon_mask.data[row * polygon_mask.width + column] * mask.get(x, y);
+                            if coverage > 0. {
+                                canvas.composite(x, y, source, coverage, blend_mode);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn begin_pattern(&mut self, pattern: &TilingPattern) -> Result<(), OutputError> {
+        let page_device = match self.canvases.first() {
+            Some(canvas) => canvas.device,
+            None => return Ok(()),
+        };
+        let [x0, y0, x1, y1] = pattern.bbox;
+        let (cell_width, cell_height) = ((x1 - x0).abs().max(1e-6), (y1 - y0).abs().max(1e-6));
+        // the cell is rendered at about the resolution that it's drawn at on the page
+        let pixels_per_unit = scale_of(&pattern.matrix.post_transform(&page_device)).max(1e-3);
+        let width = (cell_width * pixels_per_unit).ceil().clamp(1., 2048.);
+        let height = (cell_height * pixels_per_unit).ceil().clamp(1., 2048.);
+        let scale = (width / cell_width, height / cell_height);
+        let to_cell = Transform::create_translation(-x0.min(x1), -y0.min(y1)).post_scale(scale.0, scale.1);
+        let device = match pattern.matrix.inverse() {
+            Some(inverse) => inverse.post_transform(&to_cell),
+            None => to_cell,
+        };
+        self.canvases.push(Canvas::new(width as usize, height as usize, device, [0; 4]));
+        self.pattern_stack.push((pattern.clone(), scale));
+        Ok(())
+    }
+    fn end_pattern(&mut self) -> Result<(), OutputError> {
+        if let Some((pattern, scale)) = self.pattern_stack.pop() {
+            if let Some(canvas) = self.canvases.pop() {
+                self.patterns.insert(pattern.id, PatternCell { canvas, pattern, scale });
+            }
+        }
+        Ok(())
+    }
+    fn fill_path_with_pattern(&mut self, ctm: &Transform, path: &Path, fill_rule: FillRule, pattern: &TilingPattern) -> Result<(), OutputError> {
+        let alpha = self.transparency.fill_alpha as f32;
+        let blend_mode = self.transparency.blend_mode;
+        let cell = match self.patterns.get(&pattern.id) {
+            Some(cell) => cell,
+            _ => return Ok(()),
+        };
+        let canvas = match self.canvases.last_mut() {
+            Some(canvas) => canvas,
+            None => return Ok(()),
+        };
+        let mask = match canvas.path_mask(ctm, path, fill_rule) {
+            Some(mask) => mask,
+            None => return Ok(()),
+        };
+        let to_pattern = match cell.pattern.matrix.post_transform(&canvas.device).inverse() {
+            Some(inverse) => inverse,
+            None => return Ok(()),
+        };
+        let [x0, y0, x1, y1] = cell.pattern.bbox;
+        let (x0, x1, y0, y1) = (x0.min(x1), x0.max(x1), y0.min(y1), y0.max(y1));
+        let (x_step, y_step) = (cell.pattern.x_step.abs(), cell.pattern.y_step.abs());
+        for row in 0..mask.height {
+            for column in 0..mask.width {
+                let coverage = mask.data[row * mask.width + column];
+                if coverage <= 0. {
+                    continue;
+                }
+                let (x, y) = (mask.x + column, mask.y + row);
+                let (px, py) = transform_point(&to_pattern, x as f64 + 0.5, y as f64 + 0.5);
+                // the tile that the point is in, relative to the one at the origin
+                let u = if x_step > 0. { x0 + (px - x0).rem_euclid(x_step) } else { px };
+                let v = if y_step > 0. { y0 + (py - y0).rem_euclid(y_step) } else { py };
+                if u < x0 || u >= x1 || v < y0 || v >= y1 {
+                    continue;
+                }
+                let cx = (((u - x0) * cell.scale.0) as usize).min(cell.canvas.width - 1);
+                let cy = (((v - y0) * cell.scale.1) as usize).min(cell.canvas.height - 1);
+                let i = (cy * cell.canvas.width + cx) * 4;
+                let source = [0, 1, 2, 3].map(|c| cell.canvas.pixels[i + c] as f32 / 255. * alpha);
+                canvas.composite(x, y, source, coverage, blend_mode);
+            }
+        }
+        Ok(())
+    }
+}
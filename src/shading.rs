@@ -0,0 +1,375 @@
+// Shadings (8.7.4.5 "Shading types"). Axial and radial shadings are kept as their geometry
+// and color function; the other types are approximated by polygons of a single color.
+
+use lopdf::{Dictionary, Document, Object};
+use log::warn;
+
+use crate::{get, get_contents, make_colorspace, make_colorspace_from_obj, maybe_deref, maybe_get, maybe_get_obj, ColorSpace, Function, Transform};
+
+/// A polygon of a single color that's part of an approximated shading
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadedPolygon {
+    pub points: Vec<(f64, f64)>,
+    pub color: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ShadingGeometry {
+    /// Type 2 shadings: colors vary along the axis from (x0, y0) to (x1, y1)
+    Axial { coords: [f64; 4], domain: [f64; 2], extend: [bool; 2] },
+    /// Type 3 shadings: colors vary between the circle centered on (x0, y0) with radius r0
+    /// and the circle centered on (x1, y1) with radius r1
+    Radial { coords: [f64; 6], domain: [f64; 2], extend: [bool; 2] },
+    /// Function-based and mesh shadings, approximated by polygons
+    Polygons(Vec<ShadedPolygon>),
+}
+
+/// The colors that are painted by the `sh` operator or by a shading pattern. Coordinates are
+/// in the shading's target coordinate space and colors are in `colorspace`.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct Shading {
+    pub shading_type: i64,
+    pub colorspace: ColorSpace,
+    /// The color used outside of the shading by shading patterns
+    pub background: Option<Vec<f64>>,
+    pub bbox: Option<[f64; 4]>,
+    pub geometry: ShadingGeometry,
+    // either a single function or one function per color component
+    functions: Vec<Function>,
+}
+
+impl Shading {
+    /// The color at `t` of the domain of an axial or radial shading
+    pub fn color_at(&self, t: f64) -> Vec<f64> {
+        eval_functions(&self.functions, &[t])
+    }
+}
+
+fn eval_functions(functions: &[Function], input: &[f64]) -> Vec<f64> {
+    if functions.len() == 1 {
+        functions[0].call(input)
+    } else {
+        functions.iter().map(|f| f.call(input).first().copied().unwrap_or(0.)).collect()
+    }
+}
+
+fn make_functions(doc: &Document, dict: &Dictionary) -> Vec<Function> {
+    match maybe_get_obj(doc, dict, b"Function") {
+        Some(Object::Array(functions)) => functions.iter().map(|f| Function::new(doc, maybe_deref(doc, f))).collect(),
+        Some(f) => vec![Function::new(doc, f)],
+        None => Vec::new(),
+    }
+}
+
+fn get_extend(doc: &Document, dict: &Dictionary) -> [bool; 2] {
+    let extend = maybe_get::<Vec<&Object>>(doc, dict, b"Extend").unwrap_or_default();
+    let e = |i: usize| extend.get(i).and_then(|e| e.as_bool().ok()).unwrap_or(false);
+    [e(0), e(1)]
+}
+
+pub(crate) fn make_shading(doc: &Document, obj: &Object, resources: &Dictionary) -> Option<Shading> {
+    let (dict, stream) = match obj {
+        Object::Dictionary(dict) => (dict, None),
+        Object::Stream(stream) => (&stream.dict, Some(stream)),
+        _ => {
+            warn!("unexpected shading {:?}", obj);
+            return None;
+        }
+    };
+    let shading_type: i64 = get(doc, dict, b"ShadingType");
+    let colorspace = match maybe_get_obj(doc, dict, b"ColorSpace") {
+        Some(Object::Name(name)) => make_colorspace(doc, name, resources),
+        Some(cs) => make_colorspace_from_obj(doc, cs, resources),
+        None => {
+            warn!("shading without a colorspace");
+            return None;
+        }
+    };
+    let functions = make_functions(doc, dict);
+    let domain = |doc: &Document| -> [f64; 2] {
+        match maybe_get::<Vec<f64>>(doc, dict, b"Domain").as_deref() {
+            Some([t0, t1]) => [*t0, *t1],
+            _ => [0., 1.],
+        }
+    };
+    let geometry = match shading_type {
+        1 => ShadingGeometry::Polygons(function_based(doc, dict, &functions)),
+        2 => {
+            let coords: Vec<f64> = get(doc, dict, b"Coords");
+            if coords.len() != 4 {
+                warn!("bad axial shading coords {:?}", coords);
+                return None;
+            }
+            ShadingGeometry::Axial { coords: [coords[0], coords[1], coords[2], coords[3]], domain: domain(doc), extend: get_extend(doc, dict) }
+        }
+        3 => {
+            let c: Vec<f64> = get(doc, dict, b"Coords");
+            if c.len() != 6 {
+                warn!("bad radial shading coords {:?}", c);
+                return None;
+            }
+            ShadingGeometry::Radial { coords: [c[0], c[1], c[2], c[3], c[4], c[5]], domain: domain(doc), extend: get_extend(doc, dict) }
+        }
+        4..=7 => {
+            let stream = match stream {
+                Some(stream) => stream,
+                None => {
+                    warn!("mesh shading should be a stream");
+                    return None;
+                }
+            };
+            let n = if functions.is_empty() { colorspace.components().unwrap_or(1) } else { 1 };
+            let mut mesh = MeshReader::new(doc, dict, get_contents(stream), n);
+            let mut polygons = match shading_type {
+                4 => mesh.free_form_triangles(),
+                5 => {
+                    let vertices_per_row: i64 = get(doc, dict, b"VerticesPerRow");
+                    mesh.lattice_triangles(vertices_per_row.max(2) as usize)
+                }
+                _ => mesh.patches(shading_type == 7),
+            };
+            if !functions.is_empty() {
+                for polygon in &mut polygons {
+                    polygon.color = eval_functions(&functions, &polygon.color);
+                }
+            }
+            ShadingGeometry::Polygons(polygons)
+        }
+        _ => {
+            warn!("unknown shading type {}", shading_type);
+            return None;
+        }
+    };
+    Some(Shading {
+        shading_type,
+        colorspace,
+        background: maybe_get(doc, dict, b"Background"),
+        bbox: maybe_get(doc, dict, b"BBox"),
+        geometry,
+        functions,
+    })
+}
+
+// how many cells function-based shadings are sampled with in each direction
+const FUNCTION_SAMPLES: usize = 16;
+
+fn function_based(doc: &Document, dict: &Dictionary, functions: &[Function]) -> Vec<ShadedPolygon> {
+    let domain = match maybe_get::<Vec<f64>>(doc, dict, b"Domain").as_deref() {
+        Some([x0, x1, y0, y1]) => [*x0, *x1, *y0, *y1],
+        _ => [0., 1., 0., 1.],
+    };
+    let matrix = match maybe_get::<Vec<f64>>(doc, dict, b"Matrix").as_deref() {
+        Some([a, b, c, d, e, f]) => Transform::row_major(*a, *b, *c, *d, *e, *f),
+        _ => Transform::identity(),
+    };
+    let n = FUNCTION_SAMPLES as f64;
+    let x = |i: f64| domain[0] + (domain[1] - domain[0]) * i / n;
+    let y = |j: f64| domain[2] + (domain[3] - domain[2]) * j / n;
+    let point = |x: f64, y: f64| {
+        let p = matrix.transform_point(euclid::point2(x, y));
+        (p.x, p.y)
+    };
+    let mut polygons = Vec::new();
+    for j in 0..FUNCTION_SAMPLES {
+        for i in 0..FUNCTION_SAMPLES {
+            let (i, j) = (i as f64, j as f64);
+            let color = eval_functions(functions, &[x(i + 0.5), y(j + 0.5)]);
+            let points = vec![point(x(i), y(j)), point(x(i + 1.), y(j)), point(x(i + 1.), y(j + 1.)), point(x(i), y(j + 1.))];
+            polygons.push(ShadedPolygon { points, color });
+        }
+    }
+    polygons
+}
+
+#[derive(Clone)]
+struct Vertex {
+    point: (f64, f64),
+    color: Vec<f64>,
+}
+
+fn average_color(colors: &[&[f64]]) -> Vec<f64> {
+    let n = colors.len() as f64;
+    let mut color = vec![0.; colors.first().map_or(0, |c| c.len())];
+    for c in colors {
+        for (sum, x) in color.iter_mut().zip(c.iter()) {
+            *sum += x / n;
+        }
+    }
+    color
+}
+
+fn triangle(a: &Vertex, b: &Vertex, c: &Vertex) -> ShadedPolygon {
+    ShadedPolygon { points: vec![a.point, b.point, c.point], color: average_color(&[&a.color, &b.color, &c.color]) }
+}
+
+// the 12 boundary points and 4 corner colors of a patch
+type Patch = (Vec<(f64, f64)>, Vec<Vec<f64>>);
+
+fn cubic(p: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let u = 1. - t;
+    let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+    (a * p[0].0 + b * p[1].0 + c * p[2].0 + d * p[3].0, a * p[0].1 + b * p[1].1 + c * p[2].1 + d * p[3].1)
+}
+
+// Reads the packed vertex data of mesh shadings
+struct MeshReader {
+    data: Vec<u8>,
+    bit: usize,
+    bits_per_coordinate: u32,
+    bits_per_component: u32,
+    bits_per_flag: u32,
+    decode: Vec<f64>,
+    components: usize,
+}
+
+impl MeshReader {
+    fn new(doc: &Document, dict: &Dictionary, data: Vec<u8>, components: usize) -> MeshReader {
+        let bits = |key: &[u8]| maybe_get::<i64>(doc, dict, key).unwrap_or(8).clamp(1, 32) as u32;
+        MeshReader {
+            data,
+            bit: 0,
+            bits_per_coordinate: bits(b"BitsPerCoordinate"),
+            bits_per_component: bits(b"BitsPerComponent"),
+            bits_per_flag: bits(b"BitsPerFlag"),
+            decode: maybe_get(doc, dict, b"Decode").unwrap_or_default(),
+            components,
+        }
+    }
+
+    fn read_bits(&mut self, bits: u32) -> Option<u64> {
+        if self.bit + bits as usize > self.data.len() * 8 {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.data[self.bit / 8];
+            value = (value << 1) | ((byte >> (7 - self.bit % 8)) & 1) as u64;
+            self.bit += 1;
+        }
+        Some(value)
+    }
+
+    fn align(&mut self) {
+        self.bit = self.bit.div_ceil(8) * 8;
+    }
+
+    fn read_decoded(&mut self, bits: u32, decode_index: usize) -> Option<f64> {
+        let raw = self.read_bits(bits)? as f64;
+        let max = ((1u64 << bits) - 1) as f64;
+        Some(match self.decode.get(decode_index..decode_index + 2) {
+            Some(d) => d[0] + raw * (d[1] - d[0]) / max,
+            None => raw / max,
+        })
+    }
+
+    fn read_point(&mut self) -> Option<(f64, f64)> {
+        let x = self.read_decoded(self.bits_per_coordinate, 0)?;
+        let y = self.read_decoded(self.bits_per_coordinate, 2)?;
+        Some((x, y))
+    }
+
+    fn read_color(&mut self) -> Option<Vec<f64>> {
+        (0..self.components).map(|i| self.read_decoded(self.bits_per_component, 4 + 2 * i)).collect()
+    }
+
+    fn read_vertex(&mut self) -> Option<Vertex> {
+        let point = self.read_point()?;
+        let color = self.read_color()?;
+        Some(Vertex { point, color })
+    }
+
+    fn free_form_triangles(&mut self) -> Vec<ShadedPolygon> {
+        let mut polygons = Vec::new();
+        // the last two vertices of the previous triangle
+        let mut previous: Option<(Vertex, Vertex, Vertex)> = None;
+        while let Some(flag) = self.read_bits(self.bits_per_flag) {
+            let vertex = match self.read_vertex() {
+                Some(v) => v,
+                None => break,
+            };
+            self.align();
+            let t = match (flag, previous.take()) {
+                (1, Some((_, b, c))) => (b, c, vertex),
+                (2, Some((a, _, c))) => (a, c, vertex),
+                _ => {
+                    // a new triangle takes two more vertices, whose flags are ignored
+                    let mut next = || { self.read_bits(self.bits_per_flag)?; let v = self.read_vertex(); self.align(); v };
+                    match (next(), next()) {
+                        (Some(b), Some(c)) => (vertex, b, c),
+                        _ => break,
+                    }
+                }
+            };
+            polygons.push(triangle(&t.0, &t.1, &t.2));
+            previous = Some(t);
+        }
+        polygons
+    }
+
+    fn lattice_triangles(&mut self, vertices_per_row: usize) -> Vec<ShadedPolygon> {
+        let mut vertices = Vec::new();
+        while let Some(v) = self.read_vertex() {
+            vertices.push(v);
+            self.align();
+        }
+        let mut polygons = Vec::new();
+        let rows: Vec<_> = vertices.chunks_exact(vertices_per_row).collect();
+        for pair in rows.windows(2) {
+            for i in 0..vertices_per_row - 1 {
+                polygons.push(triangle(&pair[0][i], &pair[0][i + 1], &pair[1][i]));
+                polygons.push(triangle(&pair[0][i + 1], &pair[1][i + 1], &pair[1][i]));
+            }
+        }
+        polygons
+    }
+
+    // Coons (type 6) and tensor-product (type 7) patches, approximated by polygons that follow
+    // their boundary curves and have the average color of their corners
+    fn patches(&mut self, tensor: bool) -> Vec<ShadedPolygon> {
+        let mut polygons = Vec::new();
+        let mut previous: Option<Patch> = None;
+        while let Some(flag) = self.read_bits(self.bits_per_flag) {
+            let shared = match (flag, &previous) {
+                (0, _) | (_, None) => None,
+                (flag, Some((points, colors))) => {
+                    // the edge that this patch shares with the previous one
+                    let (p, c) = match flag {
+                        1 => ([points[3], points[4], points[5], points[6]], [colors[1].clone(), colors[2].clone()]),
+                        2 => ([points[6], points[7], points[8], points[9]], [colors[2].clone(), colors[3].clone()]),
+                        _ => ([points[9], points[10], points[11], points[0]], [colors[3].clone(), colors[0].clone()]),
+                    };
+                    Some((p, c))
+                }
+            };
+            let new_points = if shared.is_some() { 8 } else { 12 } + if tensor { 4 } else { 0 };
+            let points: Option<Vec<_>> = (0..new_points).map(|_| self.read_point()).collect();
+            let new_colors = if shared.is_some() { 2 } else { 4 };
+            let colors: Option<Vec<_>> = (0..new_colors).map(|_| self.read_color()).collect();
+            self.align();
+            let (points, colors) = match (points, colors) {
+                (Some(p), Some(c)) => (p, c),
+                _ => break,
+            };
+            let (mut boundary, mut corner_colors) = match shared {
+                Some((p, c)) => (p.to_vec(), c.to_vec()),
+                None => (Vec::new(), Vec::new()),
+            };
+            boundary.extend_from_slice(&points[..points.len() - if tensor { 4 } else { 0 }]);
+            boundary.truncate(12);
+            corner_colors.extend(colors);
+            let mut outline = Vec::new();
+            for edge in 0..4 {
+                let curve = [boundary[edge * 3], boundary[edge * 3 + 1], boundary[edge * 3 + 2], boundary[(edge * 3 + 3) % 12]];
+                for t in 0..3 {
+                    outline.push(cubic(&curve, t as f64 / 3.));
+                }
+            }
+            let color = average_color(&corner_colors.iter().map(|c| c.as_slice()).collect::<Vec<_>>());
+            polygons.push(ShadedPolygon { points: outline, color });
+            previous = Some((boundary, corner_colors));
+        }
+        polygons
+    }
+}
@@ -0,0 +1,403 @@
+// Glyph outlines from Type 1 font programs (Adobe Type 1 Font Format). The private part of the
+// program is decrypted and the charstrings of the glyphs are interpreted into paths.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use log::warn;
+
+use crate::encodings::STANDARD_ENCODING;
+use crate::PathOp;
+
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+
+#[derive(Debug)]
+pub(crate) struct Type1Program {
+    // the FontMatrix, which maps glyph space to text space
+    matrix: [f64; 6],
+    subrs: Vec<Vec<u8>>,
+    charstrings: HashMap<String, Vec<u8>>,
+}
+
+fn decrypt(data: &[u8], key: u16, skip: usize) -> Vec<u8> {
+    let mut r = key;
+    let mut plain = Vec::with_capacity(data.len());
+    for &c in data {
+        plain.push(c ^ (r >> 8) as u8);
+        r = (c as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+    }
+    plain.split_off(skip.min(plain.len()))
+}
+
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || b"()<>[]{}/%".contains(&c)
+}
+
+// The values of the array after `/key` in the clear text, e.g. the FontMatrix
+fn find_array(text: &[u8], key: &[u8]) -> Option<Vec<f64>> {
+    let start = text.windows(key.len()).position(|w| w == key)? + key.len();
+    let text = &text[start..];
+    let open = text.iter().position(|&c| c == b'[' || c == b'{')?;
+    let close = text[open..].iter().position(|&c| c == b']' || c == b'}')? + open;
+    std::str::from_utf8(&text[open + 1..close]).ok()?
+        .split_whitespace()
+        .map(|v| v.parse().ok())
+        .collect()
+}
+
+impl Type1Program {
+    pub(crate) fn parse(data: &[u8]) -> Option<Type1Program> {
+        let eexec = data.windows(5).position(|w| w == b"eexec")?;
+        let clear_text = &data[..eexec];
+        let mut start = eexec + 5;
+        while data.get(start).is_some_and(|c| c.is_ascii_whitespace()) {
+            start += 1;
+        }
+        let encrypted = &data[start..];
+        // the encrypted portion is either binary or hex, which is recognized by its first bytes
+        let encrypted = if encrypted.len() >= 4 && encrypted[..4].iter().all(|c| c.is_ascii_hexdigit()) {
+            let digits: Vec<u8> = encrypted.iter()
+                .take_while(|c| c.is_ascii_hexdigit() || c.is_ascii_whitespace())
+                .filter_map(|c| (*c as char).to_digit(16).map(|d| d as u8))
+                .collect();
+            digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect()
+        } else {
+            encrypted.to_vec()
+        };
+        let private = decrypt(&encrypted, EEXEC_KEY, 4);
+
+        let matrix = match find_array(clear_text, b"/FontMatrix") {
+            Some(m) if m.len() == 6 => [m[0], m[1], m[2], m[3], m[4], m[5]],
+            _ => [0.001, 0., 0., 0.001, 0., 0.],
+        };
+        let len_iv = private.windows(6).position(|w| w == b"/lenIV")
+            .and_then(|i| {
+                let rest = std::str::from_utf8(&private[i + 6..(i + 16).min(private.len())]).ok()?;
+                rest.split_whitespace().next()?.parse::<i32>().ok()
+            })
+            .unwrap_or(4);
+
+        // Subrs are written as `dup index length RD <binary> NP` and charstrings as
+        // `/name length RD <binary> ND`, where RD is sometimes spelled -|
+        let mut subrs = Vec::new();
+        let mut charstrings = HashMap::new();
+        let mut tokens: Vec<&[u8]> = Vec::new();
+        let mut i = 0;
+        while i < private.len() {
+            if private[i].is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+            let token_start = i;
+            i += 1;
+            while i < private.len() && !is_delimiter(private[i]) {
+                i += 1;
+            }
+            let token = &private[token_start..i];
+            if token != b"RD" && token != b"-|" {
+                tokens.push(token);
+                continue;
+            }
+            let length = tokens.last().and_then(|t| std::str::from_utf8(t).ok()).and_then(|t| t.parse::<usize>().ok());
+            let owner = tokens.len().checked_sub(2).map(|i| tokens[i]);
+            tokens.clear();
+            // a single space separates RD from the binary data
+            let binary_start = i + 1;
+            let (length, owner) = match (length, owner) {
+                (Some(length), Some(owner)) if binary_start + length <= private.len() => (length, owner),
+                _ => break,
+            };
+            let binary = &private[binary_start..binary_start + length];
+            i = binary_start + length;
+            let charstring = if len_iv < 0 { binary.to_vec() } else { decrypt(binary, CHARSTRING_KEY, len_iv as usize) };
+            if let Some(name) = owner.strip_prefix(b"/") {
+                charstrings.insert(String::from_utf8_lossy(name).into_owned(), charstring);
+            } else if let Some(index) = std::str::from_utf8(owner).ok().and_then(|t| t.parse::<usize>().ok()) {
+                if index >= subrs.len() {
+                    subrs.resize(index + 1, Vec::new());
+                }
+                subrs[index] = charstring;
+            }
+        }
+        if charstrings.is_empty() {
+            warn!("no charstrings found in Type 1 font");
+            return None;
+        }
+        Some(Type1Program { matrix, subrs, charstrings })
+    }
+
+    /// The outline of the glyph named `name` in glyph space, i.e. 1000 units per em
+    pub(crate) fn glyph_outline(&self, name: &str) -> Option<Vec<PathOp>> {
+        let charstring = self.charstrings.get(name)?;
+        let mut interpreter = Interpreter::new(self);
+        interpreter.run(charstring, 0)?;
+        let m = self.matrix.map(|x| x * 1000.);
+        let transform = |x: f64, y: f64| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]);
+        Some(interpreter.ops.into_iter().map(|op| match op {
+            PathOp::MoveTo(x, y) => {
+                let (x, y) = transform(x, y);
+                PathOp::MoveTo(x, y)
+            }
+            PathOp::LineTo(x, y) => {
+                let (x, y) = transform(x, y);
+                PathOp::LineTo(x, y)
+            }
+            PathOp::CurveTo(x1, y1, x2, y2, x, y) => {
+                let (x1, y1) = transform(x1, y1);
+                let (x2, y2) = transform(x2, y2);
+                let (x, y) = transform(x, y);
+                PathOp::CurveTo(x1, y1, x2, y2, x, y)
+            }
+            op => op,
+        }).collect())
+    }
+}
+
+struct Interpreter<'a> {
+    program: &'a Type1Program,
+    stack: Vec<f64>,
+    // the results of othersubrs that are moved to the stack by `pop`
+    ps_stack: Vec<f64>,
+    current: (f64, f64),
+    // where the origin of the glyph is placed, which is moved for the accent of seac
+    origin: (f64, f64),
+    // the points collected by the flex othersubrs
+    flex: Option<Vec<(f64, f64)>>,
+    open: bool,
+    ops: Vec<PathOp>,
+}
+
+// charstrings that nest subroutines deeper than this are broken
+const MAX_DEPTH: usize = 10;
+
+impl<'a> Interpreter<'a> {
+    fn new(program: &'a Type1Program) -> Interpreter<'a> {
+        Interpreter { program, stack: Vec::new(), ps_stack: Vec::new(), current: (0., 0.), origin: (0., 0.), flex: None, open: false, ops: Vec::new() }
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        self.current = (self.current.0 + dx, self.current.1 + dy);
+        if self.flex.is_some() {
+            return;
+        }
+        self.close_path();
+        self.ops.push(PathOp::MoveTo(self.current.0, self.current.1));
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.current = (self.current.0 + dx, self.current.1 + dy);
+        self.ops.push(PathOp::LineTo(self.current.0, self.current.1));
+        self.open = true;
+    }
+
+    fn curve_to(&mut self, d: [f64; 6]) {
+        let (x0, y0) = self.current;
+        let (x1, y1) = (x0 + d[0], y0 + d[1]);
+        let (x2, y2) = (x1 + d[2], y1 + d[3]);
+        let (x3, y3) = (x2 + d[4], y2 + d[5]);
+        self.ops.push(PathOp::CurveTo(x1, y1, x2, y2, x3, y3));
+        self.current = (x3, y3);
+        self.open = true;
+    }
+
+    fn close_path(&mut self) {
+        if self.open {
+            self.ops.push(PathOp::Close);
+            self.open = false;
+        }
+    }
+
+    // the operands of an operator, from the bottom of the stack
+    fn args<const N: usize>(&mut self) -> Option<[f64; N]> {
+        if self.stack.len() < N {
+            warn!("Type 1 charstring stack underflow");
+            return None;
+        }
+        let args = self.stack.split_off(self.stack.len() - N);
+        self.stack.clear();
+        args.try_into().ok()
+    }
+
+    // Returns Some(true) when the glyph is finished by endchar or seac
+    fn run(&mut self, charstring: &[u8], depth: usize) -> Option<bool> {
+        if depth > MAX_DEPTH {
+            warn!("Type 1 subroutines nested too deeply");
+            return None;
+        }
+        let mut i = 0;
+        while i < charstring.len() {
+            let v = charstring[i];
+            i += 1;
+            match v {
+                32..=246 => self.stack.push(v as f64 - 139.),
+                247..=250 => {
+                    let w = *charstring.get(i)?;
+                    i += 1;
+                    self.stack.push(((v as f64 - 247.) * 256.) + w as f64 + 108.);
+                }
+                251..=254 => {
+                    let w = *charstring.get(i)?;
+                    i += 1;
+                    self.stack.push(-((v as f64 - 251.) * 256.) - w as f64 - 108.);
+                }
+                255 => {
+                    let bytes = charstring.get(i..i + 4)?;
+                    i += 4;
+                    self.stack.push(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64);
+                }
+                // hstem, vstem: hints don't change the outline
+                1 | 3 => self.stack.clear(),
+                4 => {
+                    let [dy] = self.args()?;
+                    self.move_to(0., dy);
+                }
+                5 => {
+                    let [dx, dy] = self.args()?;
+                    self.line_to(dx, dy);
+                }
+                6 => {
+                    let [dx] = self.args()?;
+                    self.line_to(dx, 0.);
+                }
+                7 => {
+                    let [dy] = self.args()?;
+                    self.line_to(0., dy);
+                }
+                8 => {
+                    let d = self.args::<6>()?;
+                    self.curve_to(d);
+                }
+                9 => self.close_path(),
+                10 => {
+                    let index = self.stack.pop()? as usize;
+                    let subr = self.program.subrs.get(index)?;
+                    if self.run(subr, depth + 1)? {
+                        return Some(true);
+                    }
+                }
+                11 => return Some(false),
+                12 => {
+                    let escape = *charstring.get(i)?;
+                    i += 1;
+                    match escape {
+                        // dotsection, vstem3, hstem3
+                        0..=2 => self.stack.clear(),
+                        6 => {
+                            let [asb, adx, ady, bchar, achar] = self.args()?;
+                            return self.seac(asb, adx, ady, bchar, achar, depth);
+                        }
+                        7 => {
+                            let [sbx, sby, _wx, _wy] = self.args()?;
+                            self.current = (self.origin.0 + sbx, self.origin.1 + sby);
+                        }
+                        12 => {
+                            let b = self.stack.pop()?;
+                            let a = self.stack.pop()?;
+                            self.stack.push(if b == 0. { 0. } else { a / b });
+                        }
+                        16 => self.call_other_subr()?,
+                        17 => {
+                            let v = self.ps_stack.pop().unwrap_or(0.);
+                            self.stack.push(v);
+                        }
+                        33 => {
+                            let [x, y] = self.args()?;
+                            self.current = (x, y);
+                        }
+                        _ => {
+                            dlog!("unknown Type 1 escape {}", escape);
+                            self.stack.clear();
+                        }
+                    }
+                }
+                13 => {
+                    let [sbx, _wx] = self.args()?;
+                    self.current = (self.origin.0 + sbx, self.origin.1);
+                }
+                14 => {
+                    self.close_path();
+                    return Some(true);
+                }
+                21 => {
+                    let [dx, dy] = self.args()?;
+                    self.move_to(dx, dy);
+                }
+                22 => {
+                    let [dx] = self.args()?;
+                    self.move_to(dx, 0.);
+                }
+                30 => {
+                    let [dy1, dx2, dy2, dx3] = self.args()?;
+                    self.curve_to([0., dy1, dx2, dy2, dx3, 0.]);
+                }
+                31 => {
+                    let [dx1, dx2, dy2, dy3] = self.args()?;
+                    self.curve_to([dx1, 0., dx2, dy2, 0., dy3]);
+                }
+                _ => {
+                    dlog!("unknown Type 1 operator {}", v);
+                    self.stack.clear();
+                }
+            }
+        }
+        Some(false)
+    }
+
+    fn call_other_subr(&mut self) -> Option<()> {
+        let other_subr = self.stack.pop()? as i32;
+        let n = self.stack.pop()? as usize;
+        if n > self.stack.len() {
+            return None;
+        }
+        let args = self.stack.split_off(self.stack.len() - n);
+        match other_subr {
+            // the end of a flex, which draws the two curves through the collected points
+            0 => {
+                let points = self.flex.take().unwrap_or_default();
+                if points.len() >= 7 {
+                    let p = &points[1..7];
+                    self.ops.push(PathOp::CurveTo(p[0].0, p[0].1, p[1].0, p[1].1, p[2].0, p[2].1));
+                    self.ops.push(PathOp::CurveTo(p[3].0, p[3].1, p[4].0, p[4].1, p[5].0, p[5].1));
+                    self.open = true;
+                }
+                if let Some(&end) = points.last() {
+                    self.current = end;
+                }
+                // the end point is popped and passed to setcurrentpoint
+                self.ps_stack = vec![self.current.1, self.current.0];
+            }
+            1 => {
+                self.flex = Some(Vec::new());
+                self.ps_stack.clear();
+            }
+            2 => {
+                let current = self.current;
+                if let Some(points) = self.flex.as_mut() {
+                    points.push(current);
+                }
+                self.ps_stack.clear();
+            }
+            // hint replacement and unknown othersubrs give back their arguments
+            _ => self.ps_stack = args.into_iter().collect(),
+        }
+        Some(())
+    }
+
+    // seac composes a glyph from two glyphs of the standard encoding, e.g. for accented letters
+    fn seac(&mut self, asb: f64, adx: f64, ady: f64, bchar: f64, achar: f64, depth: usize) -> Option<bool> {
+        let glyph = |code: f64| STANDARD_ENCODING.get(code as usize).copied().flatten()
+            .and_then(|name| self.program.charstrings.get(name));
+        let (base, accent) = (glyph(bchar)?, glyph(achar)?);
+        self.stack.clear();
+        self.run(base, depth + 1)?;
+        self.close_path();
+        // the accent's side bearing point is placed at (adx, ady) from the base's origin
+        self.origin = (adx - asb, ady);
+        self.current = self.origin;
+        self.stack.clear();
+        self.run(accent, depth + 1)?;
+        self.close_path();
+        Some(true)
+    }
+}
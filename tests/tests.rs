@@ -81,3 +81,1843 @@ impl ExpectedText<'_> {
         );
     }
 }
+
+// Builds a single page document with `content` drawn using `resources`
+fn single_page_doc(resources: lopdf::Dictionary, content: &str) -> lopdf::Document {
+    use lopdf::{dictionary, Document, Object, Stream};
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "Resources" => resources,
+        "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc
+}
+
+#[derive(Default)]
+struct RecordingOutput {
+    chars: Vec<(f64, String)>,
+    fills: Vec<pdf_extract::Transform>,
+    gaps: Vec<(f64, f64, Option<f64>)>,
+}
+
+impl pdf_extract::OutputDev for RecordingOutput {
+    fn begin_page(&mut self, _: u32, _: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn output_character(&mut self, trm: &pdf_extract::Transform, _: f64, _: f64, _: f64, char: &str) -> Result<(), pdf_extract::OutputError> {
+        self.chars.push((trm.m31, char.to_owned()));
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn text_gap(&mut self, _: &pdf_extract::Transform, gap: f64, font_size: f64, space_width: Option<f64>) -> Result<(), pdf_extract::OutputError> {
+        self.gaps.push((gap, font_size, space_width));
+        Ok(())
+    }
+    fn fill(&mut self, ctm: &pdf_extract::Transform, _: &pdf_extract::ColorSpace, _: &[f64], _: &pdf_extract::Path) -> Result<(), pdf_extract::OutputError> {
+        self.fills.push(*ctm);
+        Ok(())
+    }
+}
+
+#[test]
+fn type3_glyphs_without_widths() {
+    use lopdf::{dictionary, Object, Stream};
+    let mut doc = single_page_doc(dictionary! {}, "");
+    let glyph = doc.add_object(Stream::new(dictionary! {}, b"500 0 0 0 500 700 d1 0 0 500 700 re f".to_vec()));
+    // blank glyphs whose only operator is d0 or d1
+    let blank = doc.add_object(Stream::new(dictionary! {}, b"300 0 d0".to_vec()));
+    let empty = doc.add_object(Stream::new(dictionary! {}, b"500 0 0 0 500 700 d1".to_vec()));
+    let font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type3",
+        "FontBBox" => vec![0.into(), 0.into(), 500.into(), 700.into()],
+        "FontMatrix" => vec![0.001.into(), 0.into(), 0.into(), 0.001.into(), 0.into(), 0.into()],
+        "CharProcs" => dictionary! { "a" => glyph, "b" => blank, "c" => empty },
+        "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(b"a".to_vec()), Object::Name(b"b".to_vec()), Object::Name(b"c".to_vec())] },
+    };
+    let resources = dictionary! { "Font" => dictionary! { "T3" => font } };
+    let content = doc.add_object(Stream::new(dictionary! {}, b"BT /T3 10 Tf 20 30 Td (aabca) Tj ET".to_vec()));
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", resources);
+    page.set("Contents", content);
+
+    let mut output = RecordingOutput::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let chars: Vec<_> = output.chars.iter().map(|c| c.1.as_str()).collect();
+    assert_eq!(chars, ["a", "a", "b", "c", "a"]);
+    // the second glyph is advanced by the d1 width: 500 glyph units * 0.001 * 10pt
+    assert!((output.chars[1].0 - 25.).abs() < 1e-6, "{:?}", output.chars);
+    assert!((output.chars[3].0 - 33.).abs() < 1e-6, "{:?}", output.chars);
+    assert!((output.chars[4].0 - 38.).abs() < 1e-6, "{:?}", output.chars);
+    assert_eq!(output.fills.len(), 3);
+    assert!((output.fills[1].m11 - 0.01).abs() < 1e-9);
+    assert!((output.fills[1].m31 - 25.).abs() < 1e-6);
+}
+
+#[test]
+fn font_overrides() {
+    use lopdf::dictionary;
+    use pdf_extract::{FontOverrides, GlyphKey};
+    let font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "ABCDEF+Broken",
+        "Encoding" => "WinAnsiEncoding",
+        "FirstChar" => 97,
+        "LastChar" => 99,
+        "Widths" => vec![500.into(), 500.into(), 500.into()],
+    };
+    let doc = single_page_doc(dictionary! { "Font" => dictionary! { "F1" => font } }, "BT /F1 10 Tf (abc) Tj ET");
+
+    let tables = pdf_extract::font_glyph_tables(&doc);
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].base_font, "ABCDEF+Broken");
+    let a = tables[0].glyphs.iter().find(|g| g.code == 97).unwrap();
+    assert_eq!(a.glyph_name.as_deref(), Some("a"));
+    assert_eq!(a.unicode, "a");
+
+    let mut overrides = FontOverrides::new();
+    overrides
+        .add("Broken", GlyphKey::GlyphName("a".to_owned()), "ä")
+        .add("Bro*", GlyphKey::CharCode(98), "ß")
+        .add("Other", GlyphKey::CharCode(99), "!");
+    let mut s = String::new();
+    pdf_extract::output_doc_with_font_overrides(&doc, &mut pdf_extract::PlainTextOutput::new(&mut s), &overrides).unwrap();
+    assert_eq!(s.trim(), "äßc");
+
+    // the per page entry points take the overrides too
+    let mut s = String::new();
+    pdf_extract::output_doc_page_with_font_overrides(&doc, &mut pdf_extract::PlainTextOutput::new(&mut s), 1, &overrides).unwrap();
+    assert_eq!(s.trim(), "äßc");
+    let mut pdf = Vec::new();
+    doc.clone().save_to(&mut pdf).unwrap();
+    // and so do the text entry points through the options
+    let mut options = pdf_extract::ExtractOptions::default();
+    options.font_overrides = overrides;
+    let pages = pdf_extract::extract_text_from_mem_by_pages_with_options(&pdf, &options).unwrap();
+    assert_eq!(pages.iter().map(|page| page.trim()).collect::<Vec<_>>(), ["äßc"]);
+    let text = pdf_extract::extract_text_from_mem_with_options(&pdf, &options).unwrap();
+    assert_eq!(text.trim(), "äßc");
+}
+
+// A minimal TrueType font with one glyph per contour in `glyphs`. Glyph i + 1 is mapped from
+// `unicode[i]` in a Unicode cmap and from `codes[i]` in a Macintosh cmap.
+fn tiny_truetype_font(glyphs: &[&[(i16, i16)]], unicode: &[u8], codes: &[u8]) -> Vec<u8> {
+    fn be16(v: &mut Vec<u8>, x: i32) { v.extend_from_slice(&(x as u16).to_be_bytes()) }
+
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    // the empty .notdef glyph
+    be16(&mut loca, 0);
+    be16(&mut loca, 0);
+    for points in glyphs {
+        be16(&mut glyf, 1);
+        glyf.extend_from_slice(&[0; 8]); // bounding box
+        be16(&mut glyf, points.len() as i32 - 1);
+        be16(&mut glyf, 0);
+        glyf.extend(points.iter().map(|_| 1u8)); // on curve with 16 bit deltas
+        let mut last = (0, 0);
+        for &(x, _) in points.iter() { be16(&mut glyf, (x - last.0) as i32); last.0 = x }
+        for &(_, y) in points.iter() { be16(&mut glyf, (y - last.1) as i32); last.1 = y }
+        if glyf.len() % 2 == 1 { glyf.push(0) }
+        be16(&mut loca, glyf.len() as i32 / 2);
+    }
+
+    let mut head = Vec::new();
+    head.extend_from_slice(&[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x5f, 0x0f, 0x3c, 0xf5]);
+    be16(&mut head, 0); // flags
+    be16(&mut head, 1000); // unitsPerEm
+    head.extend_from_slice(&[0; 16 + 8 + 6]); // dates, bounding box, macStyle, lowestRecPPEM, fontDirectionHint
+    be16(&mut head, 0); // short loca
+    be16(&mut head, 0);
+    let mut hhea = vec![0, 1, 0, 0];
+    hhea.extend_from_slice(&[0; 30]);
+    be16(&mut hhea, 0);
+    let mut maxp = vec![0, 0, 0x50, 0];
+    be16(&mut maxp, glyphs.len() as i32 + 1);
+    let mut cmap = Vec::new();
+    be16(&mut cmap, 0);
+    be16(&mut cmap, 2);
+    for (i, (platform, encoding)) in [(0, 3), (1, 0)].iter().enumerate() {
+        be16(&mut cmap, *platform);
+        be16(&mut cmap, *encoding);
+        cmap.extend_from_slice(&(20 + 262 * i as u32).to_be_bytes());
+    }
+    for map in [unicode, codes] {
+        be16(&mut cmap, 0);
+        be16(&mut cmap, 262);
+        be16(&mut cmap, 0);
+        let mut ids = [0u8; 256];
+        for (i, c) in map.iter().enumerate() { ids[*c as usize] = i as u8 + 1 }
+        cmap.extend_from_slice(&ids);
+    }
+
+    let tables = [(b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea), (b"loca", loca), (b"maxp", maxp)];
+    let mut font = vec![0, 1, 0, 0];
+    be16(&mut font, tables.len() as i32);
+    font.extend_from_slice(&[0; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        font.extend_from_slice(*tag);
+        font.extend_from_slice(&[0; 4]);
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in &tables {
+        font.extend_from_slice(data);
+        font.resize((font.len() + 3) & !3, 0);
+    }
+    font
+}
+
+#[test]
+fn glyph_fingerprints() {
+    use lopdf::{dictionary, Object, Stream};
+    use pdf_extract::{FontOverrides, GlyphFingerprintDb};
+    let triangle: &[(i16, i16)] = &[(0, 0), (500, 0), (250, 700)];
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let font_file = tiny_truetype_font(&[triangle, square], b"xy", b"ab");
+
+    // the same font program once with glyph names that say what the glyphs are and once without
+    let doc_with_names = |names: [&str; 2]| {
+        let mut doc = single_page_doc(dictionary! {}, "BT /F1 10 Tf (ab) Tj ET");
+        let font_file = doc.add_object(Stream::new(dictionary! {}, font_file.clone()));
+        let font = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "ABCDEF+Tiny",
+            "FirstChar" => 97,
+            "LastChar" => 98,
+            "Widths" => vec![500.into(), 500.into()],
+            "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(names[0].into()), Object::Name(names[1].into())] },
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "ABCDEF+Tiny", "FontFile2" => font_file },
+        };
+        let page_id = doc.page_iter().next().unwrap();
+        let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+        page.set("Resources", dictionary! { "Font" => dictionary! { "F1" => font } });
+        doc
+    };
+    let extract = |doc: &lopdf::Document, overrides: &FontOverrides| {
+        let mut s = String::new();
+        pdf_extract::output_doc_with_font_overrides(doc, &mut pdf_extract::PlainTextOutput::new(&mut s), overrides).unwrap();
+        s.trim().to_owned()
+    };
+
+    let unmapped = doc_with_names(["g1", "g2"]);
+    assert_eq!(extract(&unmapped, &FontOverrides::new()), "ab");
+    // the bundled fingerprints are DejaVu glyphs, which these aren't
+    assert_eq!(extract(&unmapped, FontOverrides::new().recover_unmapped_glyphs()), "ab");
+
+    let mut db = GlyphFingerprintDb::new();
+    assert_eq!(db.learn_from_document(&doc_with_names(["x", "y"])), 2);
+    let mut overrides = FontOverrides::new();
+    overrides.set_glyph_fingerprints(db.clone());
+    assert_eq!(extract(&unmapped, &overrides), "xy");
+
+    // the cmap of the font file gives the same fingerprints
+    let mut from_file = GlyphFingerprintDb::new();
+    assert_eq!(from_file.learn_from_font_file(&font_file), 2);
+    let mut saved = Vec::new();
+    from_file.write(&mut saved).unwrap();
+    let loaded = GlyphFingerprintDb::read(&mut &saved[..]).unwrap();
+    let mut learned: Vec<_> = db.iter().collect();
+    let mut read: Vec<_> = loaded.iter().collect();
+    learned.sort();
+    read.sort();
+    assert_eq!(learned, read);
+}
+
+#[test]
+fn list_fonts() {
+    use lopdf::{dictionary, Object, Stream};
+    use pdf_extract::FontProgramKind;
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let mut doc = single_page_doc(dictionary! {}, "BT /F1 10 Tf (aab) Tj /F2 10 Tf (a) Tj ET");
+    let font_file = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square], b"x", b"a")));
+    let fonts = dictionary! {
+        "F1" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "ABCDEF+Tiny",
+            "FirstChar" => 97,
+            "LastChar" => 98,
+            "Widths" => vec![500.into(), 500.into()],
+            "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(b"g1".to_vec()), Object::Name(b"b".to_vec())] },
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "ABCDEF+Tiny", "FontFile2" => font_file },
+        },
+        "F2" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        },
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", dictionary! { "Font" => fonts });
+
+    let mut fonts = pdf_extract::list_fonts(&doc).unwrap();
+    fonts.sort_by(|a, b| a.base_font.cmp(&b.base_font));
+    assert_eq!(fonts.len(), 2);
+    let tiny = &fonts[0];
+    assert_eq!(tiny.base_font, "ABCDEF+Tiny");
+    assert_eq!(tiny.subset_tag.as_deref(), Some("ABCDEF"));
+    assert_eq!(tiny.subtype, "TrueType");
+    assert_eq!(tiny.embedded, Some(FontProgramKind::TrueType));
+    assert_eq!(tiny.encoding.as_deref(), Some("Custom"));
+    assert_eq!(tiny.pages, [1]);
+    assert!(!tiny.has_to_unicode);
+    assert_eq!(tiny.unmapped_codes, [97]);
+    assert_eq!(tiny.unmapped_count, 2);
+    let helvetica = &fonts[1];
+    assert_eq!(helvetica.subset_tag, None);
+    assert_eq!(helvetica.embedded, None);
+    assert_eq!(helvetica.encoding.as_deref(), Some("WinAnsiEncoding"));
+    assert!(helvetica.unmapped_codes.is_empty());
+}
+
+// A bare CFF font whose glyph 1 is a square named "a"
+fn tiny_cff_font() -> Vec<u8> {
+    fn index(items: &[&[u8]]) -> Vec<u8> {
+        let mut v = (items.len() as u16).to_be_bytes().to_vec();
+        if items.is_empty() {
+            return v;
+        }
+        v.push(1);
+        let mut offset = 1;
+        v.push(offset);
+        for item in items {
+            offset += item.len() as u8;
+            v.push(offset);
+        }
+        items.iter().for_each(|item| v.extend_from_slice(item));
+        v
+    }
+    fn int(v: i32) -> Vec<u8> {
+        let mut b = vec![29];
+        b.extend_from_slice(&v.to_be_bytes());
+        b
+    }
+    let notdef: &[u8] = &[14];
+    // 100 100 rmoveto 400 0 0 400 -400 0 rlineto endchar
+    let square: &[u8] = &[239, 239, 21, 248, 36, 139, 139, 248, 36, 252, 36, 139, 5, 14];
+    let charset: &[u8] = &[0, 0, 66];
+    let private: &[u8] = &[139, 20];
+
+    let header: &[u8] = &[1, 0, 4, 1];
+    let name = index(&[b"Tiny"]);
+    let top_dict_len = 6 + 6 + 11;
+    let charset_offset = header.len() + name.len() + (5 + top_dict_len) + 2 + 2;
+    let char_strings_offset = charset_offset + charset.len();
+    let char_strings = index(&[notdef, square]);
+    let private_offset = char_strings_offset + char_strings.len();
+    let mut top_dict = int(charset_offset as i32);
+    top_dict.push(15);
+    top_dict.extend(int(char_strings_offset as i32));
+    top_dict.push(17);
+    top_dict.extend(int(private.len() as i32));
+    top_dict.extend(int(private_offset as i32));
+    top_dict.push(18);
+    assert_eq!(top_dict.len(), top_dict_len);
+
+    let mut cff = header.to_vec();
+    cff.extend(name);
+    cff.extend(index(&[&top_dict]));
+    cff.extend(index(&[]));
+    cff.extend(index(&[]));
+    cff.extend_from_slice(charset);
+    cff.extend(char_strings);
+    cff.extend_from_slice(private);
+    cff
+}
+
+#[test]
+fn embedded_fonts() {
+    use lopdf::{dictionary, Stream};
+    use pdf_extract::FontProgramKind;
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let mut doc = single_page_doc(dictionary! {}, "BT /F1 10 Tf (a) Tj /F2 10 Tf (a) Tj ET");
+    let ttf = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square], b"a", b"a")));
+    let cff = doc.add_object(Stream::new(dictionary! { "Subtype" => "Type1C" }, tiny_cff_font()));
+    let fonts = dictionary! {
+        "F1" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "ABCDEF+Tiny Sans",
+            "FirstChar" => 97,
+            "LastChar" => 97,
+            "Widths" => vec![500.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontFile2" => ttf },
+        },
+        "F2" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "GHIJKL+TinyCFF",
+            "FirstChar" => 97,
+            "LastChar" => 97,
+            "Widths" => vec![600.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "Ascent" => 800, "Descent" => -200, "FontFile3" => cff },
+        },
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", dictionary! { "Font" => fonts });
+
+    let mut fonts = pdf_extract::embedded_fonts(&doc);
+    fonts.sort_by(|a, b| a.base_font.cmp(&b.base_font));
+    assert_eq!(fonts.len(), 2);
+    assert_eq!(fonts[0].kind, FontProgramKind::TrueType);
+    assert_eq!(fonts[0].file_name(), "ABCDEF+Tiny_Sans.ttf");
+    assert_eq!(fonts[0].to_opentype(), None);
+    assert_eq!(fonts[1].kind, FontProgramKind::Type1C);
+    assert_eq!(fonts[1].file_name(), "GHIJKL+TinyCFF.cff");
+    assert_eq!(fonts[1].file_contents(), tiny_cff_font());
+
+    let otf = fonts[1].to_opentype().unwrap();
+    let face = ttf_parser::Face::parse(&otf, 0).unwrap();
+    let gid = face.glyph_index('a').unwrap();
+    assert_eq!(gid.0, 1);
+    assert_eq!(face.glyph_hor_advance(gid), Some(600));
+    assert_eq!(face.ascender(), 800);
+    assert_eq!(face.descender(), -200);
+    assert!(face.tables().os2.is_some() && face.tables().post.is_some());
+    let family = face.names().into_iter().find(|n| n.name_id == ttf_parser::name_id::FAMILY).and_then(|n| n.to_string());
+    assert_eq!(family.as_deref(), Some("TinyCFF"));
+    let bbox = face.outline_glyph(gid, &mut NoOutline).unwrap();
+    assert_eq!((bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max), (100, 100, 500, 500));
+
+    let dir = std::env::temp_dir().join(format!("pdf-extract-fonts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut paths = pdf_extract::write_embedded_fonts(&doc, &dir, true).unwrap();
+    paths.sort();
+    let names: Vec<_> = paths.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect();
+    assert_eq!(names, ["ABCDEF+Tiny_Sans.ttf", "GHIJKL+TinyCFF.otf"]);
+    assert_eq!(std::fs::read(&paths[1]).unwrap(), otf);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+struct NoOutline;
+
+impl ttf_parser::OutlineBuilder for NoOutline {
+    fn move_to(&mut self, _: f32, _: f32) {}
+    fn line_to(&mut self, _: f32, _: f32) {}
+    fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+    fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+    fn close(&mut self) {}
+}
+
+#[derive(Default)]
+struct PaintRecorder {
+    paints: Vec<(&'static str, usize, Option<pdf_extract::FillRule>, Option<pdf_extract::StrokeStyle>)>,
+}
+
+impl pdf_extract::OutputDev for PaintRecorder {
+    fn begin_page(&mut self, _: u32, _: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn output_character(&mut self, _: &pdf_extract::Transform, _: f64, _: f64, _: f64, _: &str) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn fill_path(&mut self, _: &pdf_extract::Transform, _: &pdf_extract::ColorSpace, _: &[f64], path: &pdf_extract::Path, fill_rule: pdf_extract::FillRule) -> Result<(), pdf_extract::OutputError> {
+        self.paints.push(("fill", path.ops.len(), Some(fill_rule), None));
+        Ok(())
+    }
+    fn stroke_path(&mut self, _: &pdf_extract::Transform, _: &pdf_extract::ColorSpace, _: &[f64], path: &pdf_extract::Path, style: &pdf_extract::StrokeStyle) -> Result<(), pdf_extract::OutputError> {
+        self.paints.push(("stroke", path.ops.len(), None, Some(style.clone())));
+        Ok(())
+    }
+}
+
+#[test]
+fn path_painting_operators() {
+    use lopdf::dictionary;
+    use pdf_extract::{FillRule, LineCap, LineJoin};
+    let doc = single_page_doc(dictionary! {},
+        "0 0 10 10 re B 2 w 1 J 2 j 5 M [3 1] 2 d 0 0 m 5 5 l s 0 0 5 5 re f* 0 0 m 1 1 l b* 0 0 m 1 1 l n");
+    let mut output = PaintRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let ops: Vec<_> = output.paints.iter().map(|p| (p.0, p.1, p.2)).collect();
+    assert_eq!(ops, [
+        ("fill", 1, Some(FillRule::NonZero)),
+        ("stroke", 1, None),
+        ("stroke", 3, None),
+        ("fill", 1, Some(FillRule::EvenOdd)),
+        ("fill", 3, Some(FillRule::EvenOdd)),
+        ("stroke", 3, None),
+    ]);
+    let default_style = output.paints[1].3.as_ref().unwrap();
+    assert_eq!(default_style.line_width, 1.);
+    assert_eq!(default_style.line_cap, LineCap::Butt);
+    assert!(default_style.dash_array.is_empty());
+    let style = output.paints[2].3.as_ref().unwrap();
+    assert_eq!(style.line_width, 2.);
+    assert_eq!(style.line_cap, LineCap::Round);
+    assert_eq!(style.line_join, LineJoin::Bevel);
+    assert_eq!(style.miter_limit, 5.);
+    assert_eq!(style.dash_array, [3., 1.]);
+    assert_eq!(style.dash_phase, 2.);
+}
+
+#[test]
+fn malformed_line_style_operators() {
+    use lopdf::dictionary;
+    use pdf_extract::LineCap;
+    let doc = single_page_doc(dictionary! {},
+        "2 w 1 J [3 1] 2 d w /Bogus w J /Round j () M [5] d d 0 0 m 5 5 l S");
+    let mut output = PaintRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let style = output.paints[0].3.as_ref().unwrap();
+    assert_eq!(style.line_width, 2.);
+    assert_eq!(style.line_cap, LineCap::Round);
+    assert_eq!(style.dash_array, [3., 1.]);
+    assert_eq!(style.dash_phase, 2.);
+}
+
+#[test]
+fn malformed_ext_g_state() {
+    use lopdf::dictionary;
+    use pdf_extract::LineCap;
+    let resources = dictionary! {
+        "ExtGState" => dictionary! {
+            "GS1" => dictionary! {
+                "LW" => "Bogus",
+                "LC" => "Bogus",
+                "LJ" => "Bogus",
+                "ML" => "Bogus",
+                "D" => vec![vec![2.into()].into(), "Bogus".into()],
+                "CA" => "Bogus",
+            },
+        },
+    };
+    let doc = single_page_doc(resources, "2 w 1 J [3 1] 2 d /GS1 gs 0 0 m 5 5 l S");
+    let mut output = PaintRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let style = output.paints[0].3.as_ref().unwrap();
+    assert_eq!(style.line_width, 2.);
+    assert_eq!(style.line_cap, LineCap::Round);
+    assert_eq!(style.dash_array, [3., 1.]);
+}
+
+#[derive(Default)]
+struct TransparencyRecorder {
+    events: Vec<String>,
+    transparency: Vec<pdf_extract::Transparency>,
+    line_widths: Vec<f64>,
+}
+
+impl pdf_extract::OutputDev for TransparencyRecorder {
+    fn begin_page(&mut self, _: u32, _: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn output_character(&mut self, _: &pdf_extract::Transform, _: f64, _: f64, font_size: f64, char: &str) -> Result<(), pdf_extract::OutputError> {
+        self.events.push(format!("{}@{}", char, font_size));
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn stroke_path(&mut self, _: &pdf_extract::Transform, _: &pdf_extract::ColorSpace, _: &[f64], _: &pdf_extract::Path, style: &pdf_extract::StrokeStyle) -> Result<(), pdf_extract::OutputError> {
+        self.events.push("stroke".to_owned());
+        self.line_widths.push(style.line_width);
+        Ok(())
+    }
+    fn set_transparency(&mut self, transparency: &pdf_extract::Transparency) -> Result<(), pdf_extract::OutputError> {
+        self.events.push("transparency".to_owned());
+        self.transparency.push(transparency.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn ext_g_state() {
+    use lopdf::dictionary;
+    use pdf_extract::{BlendMode, SoftMaskType};
+    let helvetica = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    };
+    let resources = dictionary! {
+        "ExtGState" => dictionary! {
+            "GS1" => dictionary! {
+                "Type" => "ExtGState",
+                "CA" => 0.5,
+                "ca" => 0.25,
+                "BM" => vec!["Bogus".into(), "Multiply".into()],
+                "LW" => 3,
+                "D" => vec![vec![2.into()].into(), 0.into()],
+                "Font" => vec![helvetica.into(), 12.into()],
+                // not a valid value but seen in the wild
+                "SMask" => "Bogus",
+            },
+            "GS2" => dictionary! {
+                "SMask" => dictionary! {
+                    "Type" => "Mask",
+                    "S" => "Luminosity",
+                    "BC" => vec![0.into(), 0.into(), 0.into()],
+                },
+            },
+        },
+    };
+    let doc = single_page_doc(resources,
+        "q /GS1 gs 0 0 m 1 1 l S BT (A) Tj ET /GS2 gs Q 0 0 m 1 1 l S");
+    let mut output = TransparencyRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    assert_eq!(output.events, ["transparency", "stroke", "A@12", "transparency", "transparency", "stroke"]);
+    assert_eq!(output.line_widths, [3., 1.]);
+
+    let t = &output.transparency[0];
+    assert_eq!((t.stroke_alpha, t.fill_alpha), (0.5, 0.25));
+    assert_eq!(t.blend_mode, BlendMode::Multiply);
+    assert!(t.soft_mask.is_none());
+    let mask = output.transparency[1].soft_mask.as_ref().unwrap();
+    assert_eq!(mask.subtype, SoftMaskType::Luminosity);
+    assert_eq!(mask.backdrop.as_deref(), Some(&[0., 0., 0.][..]));
+    let t = &output.transparency[2];
+    assert_eq!((t.stroke_alpha, t.fill_alpha, t.blend_mode), (1., 1., BlendMode::Normal));
+    assert!(t.soft_mask.is_none());
+}
+
+#[derive(Default)]
+struct ShadingRecorder {
+    shadings: Vec<(pdf_extract::Shading, bool)>,
+}
+
+impl pdf_extract::OutputDev for ShadingRecorder {
+    fn begin_page(&mut self, _: u32, _: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn output_character(&mut self, _: &pdf_extract::Transform, _: f64, _: f64, _: f64, _: &str) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn shade(&mut self, _: &pdf_extract::Transform, shading: &pdf_extract::Shading, path: Option<(&pdf_extract::Transform, &pdf_extract::Path, pdf_extract::FillRule)>) -> Result<(), pdf_extract::OutputError> {
+        self.shadings.push((shading.clone(), path.is_some()));
+        Ok(())
+    }
+}
+
+#[test]
+fn shadings() {
+    use lopdf::{dictionary, Stream};
+    use pdf_extract::ShadingGeometry;
+    let mut doc = single_page_doc(dictionary! {}, "");
+    let red_to_blue = dictionary! {
+        "FunctionType" => 2,
+        "Domain" => vec![0.into(), 1.into()],
+        "C0" => vec![1.into(), 0.into(), 0.into()],
+        "C1" => vec![0.into(), 0.into(), 1.into()],
+        "N" => 1,
+    };
+    // black to white and back again
+    let stitched = dictionary! {
+        "FunctionType" => 3,
+        "Domain" => vec![0.into(), 1.into()],
+        "Functions" => vec![
+            dictionary! { "FunctionType" => 2, "Domain" => vec![0.into(), 1.into()], "N" => 1 }.into(),
+            dictionary! { "FunctionType" => 2, "Domain" => vec![0.into(), 1.into()], "C0" => vec![1.into()], "C1" => vec![0.into()], "N" => 1 }.into(),
+        ],
+        "Bounds" => vec![0.5.into()],
+        "Encode" => vec![0.into(), 1.into(), 0.into(), 1.into()],
+    };
+    // { x y add 2 div } gives a gray that increases diagonally
+    let calculator = doc.add_object(Stream::new(dictionary! {
+        "FunctionType" => 4,
+        "Domain" => vec![0.into(), 1.into(), 0.into(), 1.into()],
+        "Range" => vec![0.into(), 1.into()],
+    }, b"{ add 2 div }".to_vec()));
+    let resources = dictionary! {
+        "Shading" => dictionary! {
+            "Sh1" => dictionary! {
+                "ShadingType" => 2,
+                "ColorSpace" => "DeviceRGB",
+                "Coords" => vec![0.into(), 0.into(), 100.into(), 0.into()],
+                "Function" => red_to_blue,
+                "Extend" => vec![true.into(), false.into()],
+            },
+            "Sh2" => dictionary! {
+                "ShadingType" => 1,
+                "ColorSpace" => "DeviceGray",
+                "Function" => calculator,
+            },
+        },
+        "Pattern" => dictionary! {
+            "P1" => dictionary! {
+                "PatternType" => 2,
+                "Matrix" => vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()],
+                "Shading" => dictionary! {
+                    "ShadingType" => 3,
+                    "ColorSpace" => "DeviceGray",
+                    "Coords" => vec![50.into(), 50.into(), 0.into(), 50.into(), 50.into(), 40.into()],
+                    "Function" => stitched,
+                },
+            },
+        },
+    };
+    let page = doc.page_iter().next().unwrap();
+    doc.get_object_mut(page).unwrap().as_dict_mut().unwrap().set("Resources", resources);
+    let content = doc.get_page_contents(page)[0];
+    doc.get_object_mut(content).unwrap().as_stream_mut().unwrap()
+        .set_plain_content(b"/Sh1 sh /Pattern cs /P1 scn 0 0 100 100 re f /Sh2 sh".to_vec());
+
+    let mut output = ShadingRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    assert_eq!(output.shadings.len(), 3);
+    let (axial, _) = &output.shadings[0];
+    assert_eq!(axial.geometry, ShadingGeometry::Axial { coords: [0., 0., 100., 0.], domain: [0., 1.], extend: [true, false] });
+    assert_eq!(axial.color_at(0.25), [0.75, 0., 0.25]);
+    let (radial, filled) = &output.shadings[1];
+    assert!(filled);
+    assert!(matches!(radial.geometry, ShadingGeometry::Radial { .. }));
+    assert_eq!(radial.color_at(0.25), [0.5]);
+    assert_eq!(radial.color_at(0.75), [0.5]);
+    assert_eq!(radial.color_at(0.5), [1.]);
+    match &output.shadings[2].0.geometry {
+        ShadingGeometry::Polygons(polygons) => {
+            assert_eq!(polygons.len(), 256);
+            // the first cell is sampled at its center
+            assert_eq!(polygons[0].points[2], (1. / 16., 1. / 16.));
+            assert_eq!(polygons[0].color, [1. / 32.]);
+        }
+        g => panic!("unexpected geometry {:?}", g),
+    }
+
+    let mut svg = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::new(&mut svg)).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.contains("<linearGradient"));
+    assert!(svg.contains("<stop offset='0' stop-color='#ff0000'/>"));
+    assert!(svg.contains("<stop offset='1' stop-color='#0000ff'/>"));
+    assert!(svg.contains("<radialGradient"));
+    assert!(svg.contains("gradientTransform='matrix(2, 0, 0, 2, 0, 0)'"));
+    assert!(svg.contains("<clipPath"));
+
+    let mut raster = pdf_extract::RasterOutput::new(72.);
+    pdf_extract::output_doc(&doc, &mut raster).unwrap();
+    assert!(raster.pages()[0].data.chunks(4).any(|p| p[0] != p[2]));
+}
+
+#[derive(Default)]
+struct PatternRecorder {
+    events: Vec<String>,
+    patterns: Vec<pdf_extract::TilingPattern>,
+}
+
+impl pdf_extract::OutputDev for PatternRecorder {
+    fn begin_page(&mut self, _: u32, _: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn output_character(&mut self, trm: &pdf_extract::Transform, _: f64, _: f64, _: f64, char: &str) -> Result<(), pdf_extract::OutputError> {
+        self.events.push(format!("char {} at {},{}", char, trm.m31, trm.m32));
+        Ok(())
+    }
+    fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+    fn fill_path(&mut self, ctm: &pdf_extract::Transform, _: &pdf_extract::ColorSpace, color: &[f64], _: &pdf_extract::Path, _: pdf_extract::FillRule) -> Result<(), pdf_extract::OutputError> {
+        self.events.push(format!("fill {:?} scaled {}", color, ctm.m11));
+        Ok(())
+    }
+    fn begin_pattern(&mut self, pattern: &pdf_extract::TilingPattern) -> Result<(), pdf_extract::OutputError> {
+        self.events.push("begin pattern".to_owned());
+        self.patterns.push(pattern.clone());
+        Ok(())
+    }
+    fn end_pattern(&mut self) -> Result<(), pdf_extract::OutputError> {
+        self.events.push("end pattern".to_owned());
+        Ok(())
+    }
+    fn fill_path_with_pattern(&mut self, _: &pdf_extract::Transform, _: &pdf_extract::Path, _: pdf_extract::FillRule, pattern: &pdf_extract::TilingPattern) -> Result<(), pdf_extract::OutputError> {
+        let index = self.patterns.iter().position(|p| p.id == pattern.id).unwrap();
+        self.events.push(format!("fill with pattern {}", index));
+        Ok(())
+    }
+}
+
+#[test]
+fn tiling_patterns() {
+    use lopdf::{dictionary, Stream};
+    let mut doc = single_page_doc(dictionary! {}, "");
+    let helvetica = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    };
+    let colored = doc.add_object(Stream::new(dictionary! {
+        "PatternType" => 1,
+        "PaintType" => 1,
+        "TilingType" => 1,
+        "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+        "XStep" => 10,
+        "YStep" => 10,
+        "Matrix" => vec![2.into(), 0.into(), 0.into(), 2.into(), 0.into(), 0.into()],
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => helvetica } },
+    }, b"0.5 g 0 0 5 5 re f BT /F1 5 Tf 1 2 Td (X) Tj ET".to_vec()));
+    let uncolored = doc.add_object(Stream::new(dictionary! {
+        "PatternType" => 1,
+        "PaintType" => 2,
+        "TilingType" => 1,
+        "BBox" => vec![0.into(), 0.into(), 4.into(), 4.into()],
+        "XStep" => 4,
+        "YStep" => 4,
+    }, b"0 0 2 2 re f".to_vec()));
+    let resources = dictionary! {
+        "Pattern" => dictionary! { "P1" => colored, "P2" => uncolored },
+        "ColorSpace" => dictionary! { "UP" => vec!["Pattern".into(), "DeviceRGB".into()] },
+    };
+    let page = doc.page_iter().next().unwrap();
+    doc.get_object_mut(page).unwrap().as_dict_mut().unwrap().set("Resources", resources);
+    let content = doc.get_page_contents(page)[0];
+    doc.get_object_mut(content).unwrap().as_stream_mut().unwrap()
+        .set_plain_content(b"/Pattern cs /P1 scn 0 0 50 50 re f 50 50 50 50 re f /UP cs 1 0 0 /P2 scn 0 0 8 8 re f 0 1 0 /P2 scn 0 0 8 8 re f".to_vec());
+
+    let mut output = PatternRecorder::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    assert_eq!(output.events, [
+        "begin pattern",
+        "fill [0.5] scaled 2",
+        "char X at 2,4",
+        "end pattern",
+        "fill with pattern 0",
+        "fill with pattern 0",
+        // uncolored patterns are defined for each color
+        "begin pattern",
+        "fill [1.0, 0.0, 0.0] scaled 1",
+        "end pattern",
+        "fill with pattern 1",
+        "begin pattern",
+        "fill [0.0, 1.0, 0.0] scaled 1",
+        "end pattern",
+        "fill with pattern 2",
+    ]);
+    let pattern = &output.patterns[0];
+    assert_eq!((pattern.paint_type, pattern.bbox, pattern.x_step, pattern.y_step), (1, [0., 0., 10., 10.], 10., 10.));
+    assert_eq!(pattern.matrix.m11, 2.);
+    assert_eq!(output.patterns[1].paint_type, 2);
+    // the ids are numbered from 0 in each document
+    let ids: Vec<usize> = output.patterns.iter().map(|p| p.id).collect();
+    assert_eq!(ids, [0, 1, 2]);
+
+    let svg = || {
+        let mut svg = Vec::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::new(&mut svg)).unwrap();
+        String::from_utf8(svg).unwrap()
+    };
+    assert_eq!(svg(), svg());
+    let svg = svg();
+    assert_eq!(svg.matches("<pattern ").count(), 3);
+    assert!(svg.contains("patternTransform='matrix(2, 0, 0, 2, 0, 0)' x='0' y='0' width='10' height='10'"));
+    assert_eq!(svg.matches("fill='url(#pattern").count(), 4);
+
+    let mut raster = pdf_extract::RasterOutput::new(72.);
+    pdf_extract::output_doc(&doc, &mut raster).unwrap();
+    assert!(raster.pages()[0].data.chunks(4).any(|p| p != [255; 4]));
+}
+
+#[test]
+fn separation_without_function_domain() {
+    use lopdf::{dictionary, Object};
+    // the tint transform has no Domain, which should be [0 1]
+    let doc = single_page_doc(dictionary! {
+        "ColorSpace" => dictionary! {
+            "CS0" => vec![
+                Object::Name(b"Separation".to_vec()),
+                Object::Name(b"Spot".to_vec()),
+                Object::Name(b"DeviceRGB".to_vec()),
+                Object::Dictionary(dictionary! {
+                    "FunctionType" => 2,
+                    "C0" => vec![1.into(), 1.into(), 1.into()],
+                    "C1" => vec![1.into(), 0.into(), 0.into()],
+                    "N" => 1,
+                }),
+            ],
+        },
+    }, "/CS0 cs 1 scn 0 0 200 200 re f");
+    let mut text = String::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::new(&mut text)).unwrap();
+    let mut raster = pdf_extract::RasterOutput::new(72.);
+    pdf_extract::output_doc(&doc, &mut raster).unwrap();
+    assert!(raster.pages()[0].data.chunks(4).all(|p| p == [255, 0, 0, 255]));
+}
+
+#[test]
+fn malformed_images() {
+    use lopdf::{dictionary, Object, Stream};
+    let image = |width: i64, height: i64, data: Vec<u8>| Object::Stream(Stream::new(dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width,
+        "Height" => height,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+    }, data));
+    // a negative size, a size that's far larger than the data and data that's a row short
+    let doc = single_page_doc(dictionary! {
+        "XObject" => dictionary! {
+            "Im1" => image(-1, 2, vec![0, 255]),
+            "Im2" => image(1 << 20, 1 << 20, vec![0, 255]),
+            "Im3" => image(2, 2, vec![0, 255]),
+        },
+    }, "/Im1 Do /Im2 Do /Im3 Do");
+    let mut svg = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::new(&mut svg)).unwrap();
+    assert!(!String::from_utf8(svg).unwrap().contains("image/png"));
+    let mut raster = pdf_extract::RasterOutput::new(1.);
+    pdf_extract::output_doc(&doc, &mut raster).unwrap();
+}
+
+#[test]
+fn svg_output() {
+    use lopdf::{dictionary, Object, Stream};
+    let image = Stream::new(dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => 2,
+        "Height" => 1,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+    }, vec![0, 255]);
+    let mut doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "Helvetica-Bold",
+            },
+        },
+        "XObject" => dictionary! {
+            "Im1" => Object::Stream(image),
+        },
+    }, "1 0 0 rg 10 10 50 50 re f \
+        0 0 1 RG 3 w 1 J 1 j [4 2] 1 d 10 10 m 100 100 l S \
+        q 0 0 100 100 re W n 0.5 g 0 0 200 200 re f Q \
+        BT /F1 12 Tf 20 150 Td (A<&) Tj ET \
+        q 10 0 0 10 50 50 cm /Im1 Do Q");
+
+    // add a second, taller page
+    let pages_id = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+    let content_id = doc.add_object(Stream::new(dictionary! {}, b"0 1 0 rg 0 0 10 10 re f".to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), 100.into(), 300.into()],
+    });
+    let pages = doc.get_object_mut(pages_id).unwrap().as_dict_mut().unwrap();
+    pages.get_mut(b"Kids").unwrap().as_array_mut().unwrap().push(page_id.into());
+    pages.set("Count", 2);
+
+    let mut svg = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::new(&mut svg)).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    // one document with the pages stacked vertically
+    assert_eq!(svg.matches("<?xml").count(), 1);
+    assert!(svg.contains("viewBox='0 0 200 500'>"));
+    assert!(svg.contains("<svg x='0' y='0' width='200' height='200' viewBox='0 0 200 200'>"));
+    assert!(svg.contains("<svg x='0' y='200' width='100' height='300' viewBox='0 0 100 300'>"));
+    assert!(svg.trim_end().ends_with("</svg>\n</svg>"));
+
+    assert!(svg.contains("fill='#ff0000'"));
+    assert!(svg.contains("fill='#00ff00'"));
+    assert!(svg.contains("fill='none' stroke='#0000ff' stroke-width='3' stroke-linecap='round' stroke-linejoin='round' stroke-miterlimit='10' stroke-dasharray='4 2' stroke-dashoffset='1'"));
+    // the fill inside q/Q is clipped and the clip ends with Q
+    assert!(svg.contains("<clipPath id='clip1'><path transform='matrix(1, 0, 0, 1, 0, 0)' d='M0 0 L100 0 L100 100 L0 100 Z' clip-rule='nonzero' /></clipPath>"));
+    let clipped = svg.find("<g clip-path='url(#clip1)'>").unwrap();
+    let gray = svg.find("fill='#808080'").unwrap();
+    let text = svg.find("<text").unwrap();
+    assert!(clipped < gray && gray < svg[clipped..].find("</g>").unwrap() + clipped);
+    assert!(svg[clipped..text].contains("</g>"));
+
+    assert!(svg.contains("<text transform='matrix(1, 0, 0, 1, 20, 150) scale(1, -1)' font-size='12' font-family='Helvetica, Arial, sans-serif' font-weight='bold' xml:space='preserve' fill='#ff0000'>A</text>"));
+    assert!(svg.contains(">&lt;</text>"));
+    assert!(svg.contains(">&amp;</text>"));
+    assert!(svg.contains("<image transform='matrix(10, 0, 0, 10, 50, 50) matrix(1, 0, 0, -1, 0, 1)' width='1' height='1' preserveAspectRatio='none' xlink:href='data:image/png;base64,iVBORw0KGgo"));
+}
+
+// A minimal Type 1 font with a glyph named A, which calls a subroutine
+fn tiny_type1_font() -> Vec<u8> {
+    fn encrypt(data: &[u8], key: u16) -> Vec<u8> {
+        let mut r = key;
+        // the first four bytes are random
+        [0u8; 4].iter().chain(data).map(|&p| {
+            let c = p ^ (r >> 8) as u8;
+            r = (c as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+            c
+        }).collect()
+    }
+    // 0 500 hsbw 100 0 rmoveto 0 callsubr 100 0 rlineto closepath endchar
+    let glyph = encrypt(&[139, 248, 136, 13, 239, 139, 21, 139, 10, 239, 139, 5, 9, 14], 4330);
+    // 0 100 rlineto return
+    let subr = encrypt(&[139, 239, 5, 11], 4330);
+    let mut private = b"dup /Private 8 dict dup begin /lenIV 4 def\n/Subrs 1 array\n".to_vec();
+    private.extend_from_slice(format!("dup 0 {} RD ", subr.len()).as_bytes());
+    private.extend_from_slice(&subr);
+    private.extend_from_slice(b" NP\nND\n2 index /CharStrings 1 dict dup begin\n");
+    private.extend_from_slice(format!("/A {} RD ", glyph.len()).as_bytes());
+    private.extend_from_slice(&glyph);
+    private.extend_from_slice(b" ND\nend\nend\nmark currentfile closefile\n");
+    let mut font = b"%!PS-AdobeFont-1.0: Tiny\n/FontMatrix [0.001 0 0 0.001 0 0] readonly def\n\
+                     /Encoding 256 array\ndup 65 /A put\nreadonly def\ncurrentfile eexec\n".to_vec();
+    font.extend_from_slice(&encrypt(&private, 55665));
+    font
+}
+
+#[test]
+fn svg_glyph_outlines() {
+    use lopdf::{dictionary, Stream};
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let triangle: &[(i16, i16)] = &[(0, 0), (500, 0), (250, 700)];
+    let mut doc = single_page_doc(dictionary! {}, "BT /F1 10 Tf (aab) Tj /F2 20 Tf (A) Tj /F3 12 Tf (x) Tj ET");
+    let truetype = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square, triangle], b"xy", b"ab")));
+    let type1 = doc.add_object(Stream::new(dictionary! {}, tiny_type1_font()));
+    let fonts = dictionary! {
+        "F1" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "ABCDEF+Tiny",
+            "FirstChar" => 97,
+            "LastChar" => 98,
+            "Widths" => vec![500.into(), 500.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "ABCDEF+Tiny", "FontFile2" => truetype },
+        },
+        "F2" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Tiny1",
+            "FirstChar" => 65,
+            "LastChar" => 65,
+            "Widths" => vec![500.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "Tiny1", "FontFile" => type1 },
+        },
+        "F3" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Times-Roman",
+        },
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", dictionary! { "Font" => fonts });
+
+    let mut svg = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::with_glyph_outlines(&mut svg)).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    // each glyph is defined once
+    assert_eq!(svg.matches("<path id='glyph").count(), 3);
+    assert_eq!(svg.matches("<use xlink:href='#glyph").count(), 4);
+    assert!(svg.contains("d='M0 0 L400 0 L400 400 L0 400 L0 0 Z'"));
+    assert!(svg.contains("<use xlink:href='#glyph1' transform='matrix(1, 0, 0, 1, 0, 0) scale(0.01)' fill='#000000' />"));
+    assert!(svg.contains("<use xlink:href='#glyph1' transform='matrix(1, 0, 0, 1, 5, 0) scale(0.01)' fill='#000000' />"));
+    // the Type 1 outline
+    assert!(svg.contains("d='M100 0 L100 100 L200 100 Z'"));
+    // the font that isn't embedded is drawn as text
+    assert!(svg.contains("font-family='&apos;Times New Roman&apos;, Times, serif' xml:space='preserve' fill='#000000'>x</text>"));
+
+    // without the option all the text is text
+    let mut svg = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::SVGOutput::new(&mut svg)).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert_eq!(svg.matches("<text").count(), 5);
+    assert!(!svg.contains("<use"));
+}
+
+#[test]
+fn raster_output() {
+    use lopdf::{dictionary, Object, Stream};
+    let image = Stream::new(dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => 2,
+        "Height" => 1,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+    }, vec![0, 255]);
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let mut doc = single_page_doc(dictionary! {}, "1 0 0 rg 0 0 100 100 re f \
+        0 0 1 rg 100 100 80 80 re 120 120 40 40 re f* \
+        q 100 0 50 50 re W n 0 1 0 rg 100 0 100 100 re f Q \
+        0 g 10 w 0 190 m 90 190 l S \
+        q 20 0 0 20 0 140 cm /Im1 Do Q \
+        BT /F1 50 Tf 0 100 Td (a) Tj ET");
+    let font_file = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square], b"x", b"a")));
+    let resources = dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! {
+                "Type" => "Font",
+                "Subtype" => "TrueType",
+                "BaseFont" => "Tiny",
+                "FirstChar" => 97,
+                "LastChar" => 97,
+                "Widths" => vec![500.into()],
+                "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "Tiny", "FontFile2" => font_file },
+            },
+        },
+        "XObject" => dictionary! { "Im1" => Object::Stream(image) },
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap().set("Resources", resources);
+
+    // the text and the pixels come from one pass
+    let mut text = String::new();
+    let mut raster = pdf_extract::RasterOutput::new(36.);
+    {
+        let mut plain = pdf_extract::PlainTextOutput::new(&mut text);
+        pdf_extract::output_doc(&doc, &mut pdf_extract::TeeOutput::new(&mut plain, &mut raster)).unwrap();
+    }
+    assert_eq!(text.trim(), "a");
+    let pages = raster.into_pages();
+    assert_eq!(pages.len(), 1);
+    let page = &pages[0];
+    assert_eq!((page.width, page.height), (100, 100));
+    let (red, green, blue, black, white) = ([255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [0, 0, 0, 255], [255; 4]);
+    assert_eq!(page.pixel(25, 75), red);
+    assert_eq!(page.pixel(75, 25), white);
+    // even-odd leaves a hole
+    assert_eq!(page.pixel(55, 45), blue);
+    assert_eq!(page.pixel(70, 30), white);
+    // the clip
+    assert_eq!(page.pixel(60, 90), green);
+    assert_eq!(page.pixel(90, 90), white);
+    assert_eq!(page.pixel(60, 60), white);
+    // the stroke, with antialiased edges
+    assert_eq!(page.pixel(20, 5), black);
+    assert_eq!(page.pixel(20, 1), white);
+    let edge = page.pixel(20, 2);
+    assert!(edge[0] > 0 && edge[0] < 255);
+    // the image
+    assert_eq!(page.pixel(2, 25), black);
+    assert_eq!(page.pixel(7, 25), white);
+    // the glyph outline
+    assert_eq!(page.pixel(5, 45), black);
+    assert_eq!(page.pixel(15, 45), white);
+
+    let mut png = Vec::new();
+    page.write_png(&mut png).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    let mut ppm = Vec::new();
+    page.write_ppm(&mut ppm).unwrap();
+    assert!(ppm.starts_with(b"P6\n100 100\n255\n"));
+    assert_eq!(ppm.len(), 15 + 100 * 100 * 3);
+}
+
+#[test]
+fn html_output() {
+    use lopdf::{dictionary, Stream};
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    let mut doc = single_page_doc(dictionary! {}, "0 0 1 rg 10 10 50 50 re f \
+        BT /F1 12 Tf 1 0 0 rg 20 150 Td (A<&) Tj /F2 10 Tf 0 g (a) Tj ET \
+        BT /F1 10 Tf 0 1 -1 0 100 20 Tm (up) Tj ET \
+        BT 3 Tr /F1 10 Tf 20 100 Td (hidden) Tj ET");
+    let ttf = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square], b"a", b"a")));
+    let fonts = dictionary! {
+        "F1" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica-Bold",
+        },
+        "F2" => dictionary! {
+            "Type" => "Font",
+            "Subtype" => "TrueType",
+            "BaseFont" => "ABCDEF+Tiny Sans",
+            "FirstChar" => 97,
+            "LastChar" => 97,
+            "Widths" => vec![500.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontFile2" => ttf },
+        },
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", dictionary! { "Font" => fonts });
+
+    let mut html = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::HTMLOutput::new(&mut html)).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Document</title>"));
+    assert_eq!(html.matches("<meta").count(), 1);
+    assert!(html.trim_end().ends_with("</body>\n</html>"));
+    assert!(html.contains("<div class=\"page\" id=\"page1\" style=\"width: 200px; height: 200px\">"));
+    // a run per style with the text escaped and the baseline at 200 - 150
+    assert!(html.contains("<span style=\"left: 20px; top: 39.8px; font-size: 12px; font-family: Helvetica, Arial, sans-serif; \
+                           font-weight: bold; color: #ff0000\">A&lt;&amp;</span>"));
+    assert!(html.contains("font-family: &apos;f1&apos;, &quot;Tiny Sans&quot;, serif; color: #000000\">a</span>"));
+    assert!(html.contains("@font-face { font-family: 'f1'; src: url(data:font/ttf;base64,"));
+    // rotated text turns around its baseline
+    assert!(html.contains("transform: matrix(0, -1, 1, 0, 0, 0); transform-origin: 0 0.85em"));
+    assert!(html.contains("color: transparent\">hidden</span>"));
+    // no debug output or graphics by default
+    assert!(!html.contains("debug") && !html.contains("<svg"));
+
+    let mut options = pdf_extract::HTMLOptions::default();
+    options.background = true;
+    options.debug_overlay = true;
+    options.embed_fonts = false;
+    options.title = Some("A & B".to_owned());
+    let mut html = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::HTMLOutput::with_options(&mut html, options)).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<title>A &amp; B</title>"));
+    assert!(!html.contains("@font-face"));
+    assert!(html.contains("<div class=\"page debug\" id=\"page1\""));
+    assert!(html.contains("<span class=\"debug\" style=\"left: 20px; top: 39.8px; font-size: 12px\">A</span>"));
+    // the graphics go behind the text
+    let svg = html.find("<svg x='0' y='0' width='200' height='200' viewBox='0 0 200 200'>").unwrap();
+    assert!(html[svg..].contains("fill='#0000ff'"));
+    assert!(svg < html.find("<span").unwrap());
+
+    // TrueType programs get a cmap for the text
+    let font = pdf_extract::embedded_fonts(&doc).pop().unwrap();
+    let web_font = font.to_web_font().unwrap();
+    let face = ttf_parser::Face::parse(&web_font, 0).unwrap();
+    assert_eq!(face.glyph_index('a').map(|gid| gid.0), Some(1));
+    assert!(face.tables().os2.is_some() && face.tables().name.is_some() && face.tables().post.is_some());
+}
+
+#[test]
+fn web_font_with_many_characters() {
+    use lopdf::{dictionary, Stream};
+    let square: &[(i16, i16)] = &[(0, 0), (400, 0), (400, 400), (0, 400)];
+    // CIDs mapped to every other character, which needs more segments than a format 4 cmap has
+    let to_unicode: String = (1..=10000u32).map(|cid| format!("<{:04x}> <{:04x}>\n", cid, 0x1000 + 2 * cid)).collect();
+    let to_unicode = format!("begincmap\n10000 beginbfchar\n{}endbfchar\nendcmap\n", to_unicode);
+    let mut doc = single_page_doc(dictionary! {}, "BT /F1 10 Tf <0001> Tj ET");
+    let ttf = doc.add_object(Stream::new(dictionary! {}, tiny_truetype_font(&[square], b"a", b"a")));
+    let to_unicode = doc.add_object(Stream::new(dictionary! {}, to_unicode.into_bytes()));
+    let font = dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "Tiny",
+        "Encoding" => "Identity-H",
+        "ToUnicode" => to_unicode,
+        "DescendantFonts" => vec![dictionary! {
+            "Type" => "Font",
+            "Subtype" => "CIDFontType2",
+            "BaseFont" => "Tiny",
+            "CIDSystemInfo" => dictionary! { "Registry" => lopdf::Object::string_literal("Adobe"), "Ordering" => lopdf::Object::string_literal("Identity"), "Supplement" => 0 },
+            "CIDToGIDMap" => "Identity",
+            "W" => vec![1.into(), 10000.into(), 500.into()],
+            "FontDescriptor" => dictionary! { "Type" => "FontDescriptor", "FontName" => "Tiny", "FontFile2" => ttf },
+        }.into()],
+    };
+    let page_id = doc.page_iter().next().unwrap();
+    doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap().set("Resources", dictionary! { "Font" => dictionary! { "F1" => font } });
+
+    let font = pdf_extract::embedded_fonts(&doc).pop().unwrap();
+    let web_font = font.to_web_font().unwrap();
+    let face = ttf_parser::Face::parse(&web_font, 0).unwrap();
+    assert_eq!(face.glyph_index('\u{1002}').map(|gid| gid.0), Some(1));
+    assert_eq!(face.glyph_index('\u{5e20}').map(|gid| gid.0), Some(10000));
+    assert_eq!(face.glyph_index('\u{1003}'), None);
+}
+
+// A page with a heading, a paragraph with a link, lists, a table and a figure
+fn layout_doc() -> lopdf::Document {
+    use lopdf::{dictionary, Object, Stream};
+    let image = Stream::new(dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => 2,
+        "Height" => 1,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+    }, vec![0, 255]);
+    let mut doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Roman" },
+            "F2" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Bold" },
+        },
+    }, "BT /F1 24 Tf 72 700 Td (Title) Tj ET \
+        BT /F1 12 Tf 72 670 Td (First line with ) Tj /F2 12 Tf (bold) Tj /F1 12 Tf 0 -14 Td (the paragraph with a link.) Tj ET \
+        BT /F1 12 Tf 72 630 Td (- first item) Tj 0 -14 Td (- second item) Tj ET \
+        BT /F2 12 Tf 72 590 Td (Name) Tj 100 0 Td (Value) Tj /F1 12 Tf -100 -14 Td (a < b) Tj 100 0 Td (1) Tj ET \
+        q 100 0 0 50 72 500 cm /Im1 Do Q \
+        BT /F1 12 Tf 72 485 Td (Figure 1: A box) Tj ET \
+        BT /F1 12 Tf 72 460 Td (1. one) Tj 0 -14 Td (2. two) Tj ET");
+    // the image is an indirect object so that the document can be saved
+    let image_id = doc.add_object(image);
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    let resources = page.get_mut(b"Resources").unwrap().as_dict_mut().unwrap();
+    resources.set("XObject", dictionary! { "Im1" => image_id });
+    page.set("Annots", vec![Object::Dictionary(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Link",
+        "Rect" => vec![70.into(), 650.into(), 400.into(), 668.into()],
+        "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com/?a=1&b=2") },
+    })]);
+    doc
+}
+
+#[test]
+fn reflowed_html() {
+    let doc = layout_doc();
+    let mut options = pdf_extract::HTMLOptions::default();
+    options.reflow = true;
+    let mut html = Vec::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::HTMLOutput::with_options(&mut html, options)).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    let body = html.split("<body>\n").nth(1).unwrap();
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some("<!-- page 1 -->"));
+    assert_eq!(lines.next(), Some("<h1>Title</h1>"));
+    assert_eq!(lines.next(), Some("<p>First line with <strong>bold </strong><a href=\"https://example.com/?a=1&amp;b=2\">the paragraph with a link.</a></p>"));
+    assert_eq!(lines.next(), Some("<ul>"));
+    assert_eq!(lines.next(), Some("<li>first item</li>"));
+    assert_eq!(lines.next(), Some("<li>second item</li>"));
+    assert_eq!(lines.next(), Some("</ul>"));
+    assert_eq!(lines.next(), Some("<table>"));
+    assert_eq!(lines.next(), Some("<tr><th>Name</th><th>Value</th></tr>"));
+    assert_eq!(lines.next(), Some("<tr><td>a &lt; b</td><td>1</td></tr>"));
+    assert_eq!(lines.next(), Some("</table>"));
+    let figure = lines.next().unwrap();
+    assert!(figure.starts_with("<figure><img src=\"data:image/png;base64,"));
+    assert!(figure.ends_with("width=\"100\" height=\"50\" alt=\"Figure 1: A box\"><figcaption>Figure 1: A box</figcaption></figure>"));
+    assert_eq!(lines.next(), Some("<ol>"));
+    assert_eq!(lines.next(), Some("<li>one</li>"));
+    assert_eq!(lines.next(), Some("<li>two</li>"));
+    assert_eq!(lines.next(), Some("</ol>"));
+    assert_eq!(lines.next(), Some("</body>"));
+}
+
+#[test]
+fn markdown_output() {
+    let mut doc = layout_doc();
+    let path = std::env::temp_dir().join(format!("pdf-extract-markdown-{}.pdf", std::process::id()));
+    doc.save(&path).unwrap();
+    let markdown = pdf_extract::extract_markdown(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(markdown, "<!-- page 1 -->\n\n\
+                          # Title\n\n\
+                          First line with **bold** [the paragraph with a link.](https://example.com/?a=1&b=2)\n\n\
+                          - first item\n\
+                          - second item\n\n\
+                          | **Name** | **Value** |\n\
+                          | --- | --- |\n\
+                          | a \\< b | 1 |\n\n\
+                          ![Figure 1: A box]()\n\n\
+                          1. one\n\
+                          2. two\n");
+}
+
+#[test]
+fn hocr_output() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Roman" },
+            "F2" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Italic" },
+        },
+    }, "BT /F1 20 Tf 10 150 Td (Hello ) Tj /F2 20 Tf (world) Tj ET \
+        BT /F1 10 Tf 10 100 Td (A & B) Tj ET");
+    let mut hocr = String::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::HocrOutput::with_dpi(&mut hocr, 144.)).unwrap();
+    assert!(hocr.starts_with("<?xml"));
+    assert!(hocr.contains("<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_carea ocr_par ocr_line ocrx_word ocr_photo ocrp_font\" />"));
+    assert!(hocr.contains("<div class=\"ocr_page\" id=\"page_1\" title=\"bbox 0 0 400 400; ppageno 0; scan_res 144 144\">"));
+    assert_eq!(hocr.matches("class=\"ocr_carea\"").count(), 2);
+    assert_eq!(hocr.matches("class=\"ocr_line\"").count(), 2);
+    // Hello is 2222 thousandths of an em wide in Times-Roman
+    assert!(hocr.contains("<span class=\"ocr_line\" id=\"line_1_1\" title=\"bbox 20 66 "));
+    assert!(hocr.contains("; baseline 0 -6; x_size 40; x_descenders 6; x_ascenders 14\">"));
+    assert!(hocr.contains("<span class=\"ocrx_word\" id=\"word_1_1\" title=\"bbox 20 66 109 106; x_wconf 100; x_fsize 20\">Hello</span>"));
+    assert!(hocr.contains("x_fsize 20\"><em>world</em></span>"));
+    assert!(hocr.contains("x_fsize 10\">&amp;</span>"));
+    assert!(hocr.trim_end().ends_with("</div>\n</body>\n</html>"));
+}
+
+#[test]
+fn alto_output() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Roman" },
+            "F2" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Italic" },
+        },
+    }, "BT /F1 20 Tf 10 150 Td (Hello ) Tj /F2 20 Tf (world) Tj ET \
+        BT /F1 10 Tf 10 100 Td (A & B) Tj ET \
+        10 10 100 20 re f");
+    let mut alto = String::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::AltoOutput::new(&mut alto)).unwrap();
+    assert!(alto.starts_with("<?xml"));
+    assert!(alto.contains("<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\""));
+    assert!(alto.contains("<MeasurementUnit>pixel</MeasurementUnit>"));
+    assert!(alto.contains("<TextStyle ID=\"font0\" FONTFAMILY=\"Times\" FONTSIZE=\"20\"/>"));
+    assert!(alto.contains("<TextStyle ID=\"font1\" FONTFAMILY=\"Times\" FONTSIZE=\"20\" FONTSTYLE=\"italics\"/>"));
+    assert!(alto.contains("<TextStyle ID=\"font2\" FONTFAMILY=\"Times\" FONTSIZE=\"10\"/>"));
+    assert!(alto.contains("<Page ID=\"page_1\" PHYSICAL_IMG_NR=\"1\" WIDTH=\"200\" HEIGHT=\"200\">"));
+    assert_eq!(alto.matches("<TextBlock ").count(), 2);
+    assert_eq!(alto.matches("<TextLine ").count(), 2);
+    // Hello is 2222 thousandths of an em wide in Times-Roman
+    assert!(alto.contains("<String ID=\"string_1_1\" HPOS=\"10\" VPOS=\"33\" WIDTH=\"44.44\" HEIGHT=\"20\" CONTENT=\"Hello\" STYLEREFS=\"font0\" WC=\"1\"/>"));
+    assert!(alto.contains("<SP HPOS=\"54.44\" VPOS=\"33\" WIDTH=\"5\"/>"));
+    assert!(alto.contains("CONTENT=\"world\" STYLEREFS=\"font1\""));
+    assert!(alto.contains("CONTENT=\"&amp;\" STYLEREFS=\"font2\""));
+    assert!(alto.contains("<GraphicalElement ID=\"graphic_1_1\" HPOS=\"10\" VPOS=\"170\" WIDTH=\"100\" HEIGHT=\"20\"/>"));
+    assert!(alto.trim_end().ends_with("</Layout>\n</alto>"));
+
+    let mut alto = String::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::AltoOutput::with_units(&mut alto, pdf_extract::AltoUnits::Mm10)).unwrap();
+    assert!(alto.contains("<MeasurementUnit>mm10</MeasurementUnit>"));
+    assert!(alto.contains("WIDTH=\"705.56\" HEIGHT=\"705.56\">"));
+}
+
+#[test]
+fn structured_output() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Roman" },
+            "F2" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Times-Italic" },
+        },
+    }, "BT /F1 20 Tf 10 150 Td (Hello ) Tj 1 0 0 rg /F2 20 Tf (world) Tj ET \
+        BT /F1 10 Tf 10 100 Td 3 Tr (hidden) Tj ET");
+    let mut output = pdf_extract::StructuredOutput::new();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let model = output.into_model();
+    assert_eq!(model.pages.len(), 1);
+    let page = &model.pages[0];
+    assert_eq!(page.page_num, 1);
+    assert_eq!(page.blocks.len(), 2);
+    assert_eq!(page.blocks.iter().map(|b| b.reading_order).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(page.blocks[0].kind, pdf_extract::BlockKind::Text);
+    let line = &page.blocks[0].lines[0];
+    assert_eq!(line.baseline, 150.);
+    assert_eq!(line.words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>(), vec!["Hello", "world"]);
+    let hello = &line.words[0];
+    // Hello is 2222 thousandths of an em wide in Times-Roman
+    assert!((hello.bbox.urx - 54.44).abs() < 0.01);
+    assert_eq!(hello.bbox.lly, 147.);
+    assert_eq!(hello.bbox.ury, 167.);
+    assert_eq!(hello.glyphs.len(), 5);
+    let h = &hello.glyphs[0];
+    assert_eq!(h.text, "H");
+    assert_eq!(h.font, pdf_extract::FontInfo { name: Some("Times-Roman".into()), size: 20., bold: false, italic: false });
+    assert_eq!(h.color, Some([0., 0., 0.]));
+    let w = &line.words[1].glyphs[0];
+    assert!(w.font.italic);
+    assert_eq!(w.color, Some([1., 0., 0.]));
+    assert_eq!(page.blocks[1].lines[0].words[0].glyphs[0].color, None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn structured_output_json() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 12 Tf 10 150 Td (Hi there) Tj ET");
+    let mut output = pdf_extract::StructuredOutput::new();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let model = output.into_model();
+    let json = serde_json::to_value(&model).unwrap();
+    assert_eq!(json["pages"][0]["media_box"]["urx"], 200.);
+    assert_eq!(json["pages"][0]["blocks"][0]["kind"], "Text");
+    assert_eq!(json["pages"][0]["blocks"][0]["lines"][0]["words"][1]["text"], "there");
+    assert_eq!(json["pages"][0]["blocks"][0]["lines"][0]["words"][0]["glyphs"][0]["font"]["name"], "Helvetica");
+    let round_trip: pdf_extract::DocumentModel = serde_json::from_value(json).unwrap();
+    assert_eq!(round_trip, model);
+
+    let path = pdf_extract::Path { ops: vec![pdf_extract::PathOp::MoveTo(1., 2.), pdf_extract::PathOp::Close] };
+    assert_eq!(serde_json::to_string(&path).unwrap(), r#"{"ops":[{"MoveTo":[1.0,2.0]},"Close"]}"#);
+    let colorspace: pdf_extract::ColorSpace = serde_json::from_str(r#""DeviceRGB""#).unwrap();
+    assert_eq!(colorspace.to_rgb(&[0., 1., 0.]), Some([0., 1., 0.]));
+}
+
+#[test]
+fn layout_text_output() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" },
+        },
+    }, "BT /F1 10 Tf 20 150 Td (Name) Tj 60 0 Td (Value) Tj ET \
+        BT /F1 10 Tf 20 138 Td (a) Tj 60 0 Td (12345) Tj ET \
+        BT /F1 10 Tf 20 114 Td (end) Tj ET");
+    let layout = |options: pdf_extract::LayoutTextOptions| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::LayoutTextOutput::with_options(&mut text, options)).unwrap();
+        text
+    };
+    // Courier is 6 points wide at 10 points
+    assert_eq!(layout(Default::default()), "   Name      Value\n   a         12345\n\n   end\n\x0c");
+    let mut options = pdf_extract::LayoutTextOptions::default();
+    options.trim_margins = true;
+    assert_eq!(layout(options.clone()), "Name      Value\na         12345\n\nend\n\x0c");
+    options.fixed_pitch = Some(12.);
+    assert_eq!(layout(options), "Name Value\na    12345\n\nend\n\x0c");
+}
+
+#[test]
+fn layout_text_output_degenerate_sizes() {
+    use lopdf::dictionary;
+    let layout = |content: &str, fixed_pitch: Option<f64>| {
+        let doc = single_page_doc(dictionary! {
+            "Font" => dictionary! {
+                "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" },
+            },
+        }, content);
+        let mut options = pdf_extract::LayoutTextOptions::default();
+        options.fixed_pitch = fixed_pitch;
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::LayoutTextOutput::with_options(&mut text, options)).unwrap();
+        text
+    };
+    // text without a size isn't placed, and text far to the right is kept to a sane column
+    assert_eq!(layout("BT /F1 0 Tf 50 50 Td (ab) Tj ET", None), "\x0c");
+    assert!(layout("BT /F1 10 Tf 50 50 Td (ab) Tj ET", Some(1e-300)).len() < 2000);
+    assert!(layout("BT /F1 10 Tf 1000000000000 50 Td (ab) Tj ET", None).contains("ab"));
+    assert!(layout("BT /F1 10 Tf 1000000000000 50 Td (ab) Tj ET", None).len() < 2000);
+}
+
+#[test]
+fn extract_options() {
+    use lopdf::{dictionary, Object};
+    let mut doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 10 Tf 10 150 Td [(ab) -300 (cd)] TJ 0 -12 Td (ef) Tj 0 -30 Td (gh) Tj ET");
+    // a second page with the same contents
+    let page_id = doc.page_iter().next().unwrap();
+    let second_page = doc.add_object(doc.get_object(page_id).unwrap().clone());
+    let pages = doc.catalog().unwrap().get(b"Pages").unwrap().as_reference().unwrap();
+    let pages = doc.get_object_mut(pages).unwrap().as_dict_mut().unwrap();
+    pages.set("Kids", vec![Object::Reference(page_id), Object::Reference(second_page)]);
+    pages.set("Count", 2);
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf).unwrap();
+
+    let text = pdf_extract::extract_text_from_mem(&pdf).unwrap();
+    assert_eq!(text, "\n\nab cd\nef\n\ngh\n\nab cd\nef\n\ngh");
+    let mut options = pdf_extract::ExtractOptions::default();
+    options.space_threshold = 0.5;
+    options.page_separator = "\x0c".into();
+    options.blank_lines_between_paragraphs = false;
+    let text = pdf_extract::extract_text_from_mem_with_options(&pdf, &options).unwrap();
+    assert_eq!(text, "\nabcd\nef\ngh\x0c\nabcd\nef\ngh");
+    options.line_threshold = 2.;
+    let text = pdf_extract::extract_text_from_mem_with_options(&pdf, &options).unwrap();
+    assert_eq!(text, "\nabcdef\ngh\x0c\nabcdef\ngh");
+    options.line_threshold = 4.;
+    options.paragraph_threshold = 5.;
+    let pages = pdf_extract::extract_text_from_mem_by_pages_with_options(&pdf, &options).unwrap();
+    assert_eq!(pages, vec!["\nabcdefgh", "\nabcdefgh"]);
+}
+
+#[test]
+fn extract_options_encrypted() {
+    use lopdf::dictionary;
+    let mut doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 10 Tf 10 150 Td [(ab) -300 (cd)] TJ 0 -12 Td (ef) Tj ET");
+    use std::convert::TryFrom;
+    let id = lopdf::Object::string_literal("0123456789abcdef");
+    doc.trailer.set("ID", vec![id.clone(), id]);
+    let state = lopdf::EncryptionState::try_from(lopdf::EncryptionVersion::V2 {
+        document: &doc,
+        owner_password: "owner",
+        user_password: "user",
+        key_length: 128,
+        permissions: lopdf::Permissions::all(),
+    }).unwrap();
+    doc.encrypt(&state).unwrap();
+    let mut pdf = Vec::new();
+    doc.save_to(&mut pdf).unwrap();
+
+    let text = pdf_extract::extract_text_from_mem_encrypted(&pdf, "user").unwrap();
+    assert_eq!(text, "\n\nab cd\nef");
+    let mut options = pdf_extract::ExtractOptions::default();
+    options.space_threshold = 0.5;
+    let text = pdf_extract::extract_text_from_mem_encrypted_with_options(&pdf, "user", &options).unwrap();
+    assert_eq!(text, "\n\nabcd\nef");
+    let pages = pdf_extract::extract_text_from_mem_by_pages_encrypted_with_options(&pdf, "user", &options).unwrap();
+    assert_eq!(pages, vec!["\n\nabcd\nef"]);
+    // the font overrides are passed along with the options
+    options.font_overrides.add("Helvetica", pdf_extract::GlyphKey::CharCode(97), "A");
+    let text = pdf_extract::extract_text_from_mem_encrypted_with_options(&pdf, "user", &options).unwrap();
+    assert_eq!(text, "\n\nAbcd\nef");
+}
+
+#[test]
+fn layout_reading_order() {
+    use lopdf::dictionary;
+    // the columns are drawn a line at a time
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 12 Tf 10 180 Td (A heading across the page) Tj ET \
+        BT /F1 10 Tf 10 150 Td (left column one) Tj 100 0 Td (right column one) Tj \
+        -100 -12 Td (left column two) Tj 100 0 Td (right column two) Tj ET \
+        BT /F1 10 Tf 10 60 Td (Name) Tj 60 0 Td (Value) Tj -60 -12 Td (a) Tj 60 0 Td (1) Tj ET");
+    let text = |options: &pdf_extract::ExtractOptions| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::with_options(&mut text, options.clone())).unwrap();
+        text
+    };
+    let mut options = pdf_extract::ExtractOptions::default();
+    assert!(text(&options).contains("left column one right column one"));
+    options.reading_order = pdf_extract::ReadingOrder::Layout;
+    assert_eq!(text(&options), "A heading across the page\n\nleft column one\nleft column two\n\n\
+        right column one\nright column two\n\nName Value\na 1\n");
+    options.blank_lines_between_paragraphs = false;
+    assert_eq!(text(&options), "A heading across the page\nleft column one\nleft column two\n\
+        right column one\nright column two\nName Value\na 1\n");
+}
+
+#[test]
+fn line_builder() {
+    use lopdf::dictionary;
+    // each word is positioned with Td and the start of the second paragraph is drawn after the
+    // rest of its line and its superscript
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" },
+        },
+    }, "BT /F1 10 Tf 30 180 Td (First) Tj 36 0 Td (line) Tj -36 -12 Td (of) Tj 18 0 Td (it) Tj \
+        -18 -12 Td (and the last) Tj ET \
+        BT /F1 10 Tf 72 140 Td (line of text) Tj ET BT /F1 6 Tf 3 Ts 144 140 Td (1) Tj ET \
+        BT /F1 10 Tf 30 140 Td (A long) Tj 0 -12 Td (and a short one) Tj ET \
+        BT /F1 10 Tf 40 80 Td (Centered) Tj -15 -12 Td (more text here) Tj ET");
+    struct Lines(pdf_extract::LineBuilder, Vec<pdf_extract::Paragraph>, usize, Vec<pdf_extract::LineBreak>);
+    impl pdf_extract::OutputDev for Lines {
+        fn begin_page(&mut self, page_num: u32, media_box: &pdf_extract::MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), pdf_extract::OutputError> {
+            self.0.begin_page(page_num, media_box);
+            Ok(())
+        }
+        fn end_page(&mut self) -> Result<(), pdf_extract::OutputError> {
+            self.1.extend(self.0.finish());
+            Ok(())
+        }
+        fn output_character(&mut self, trm: &pdf_extract::Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), pdf_extract::OutputError> {
+            self.0.character(trm, width, spacing, font_size, char);
+            match self.0.line_break(trm, width, font_size) {
+                pdf_extract::LineBreak::None => {}
+                line_break => self.3.push(line_break),
+            }
+            Ok(())
+        }
+        fn begin_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+        fn end_word(&mut self) -> Result<(), pdf_extract::OutputError> { Ok(()) }
+        fn end_line(&mut self) -> Result<(), pdf_extract::OutputError> {
+            self.2 += 1;
+            Ok(())
+        }
+    }
+    let mut output = Lines(pdf_extract::LineBuilder::new(), Vec::new(), 0, Vec::new());
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    let paragraphs = output.1;
+    let text: Vec<Vec<&str>> = paragraphs.iter().map(|p| p.lines.iter().map(|l| l.text.as_str()).collect()).collect();
+    assert_eq!(text, vec![
+        vec!["First line", "of it", "and the last"],
+        vec!["A long line of text1", "and a short one"],
+        vec!["Centered", "more text here"],
+    ]);
+    assert!(output.2 > 6);
+    // the breaks as the text is drawn, where the out of order start of the line doesn't break it
+    use pdf_extract::LineBreak::{Line, Paragraph};
+    assert_eq!(output.3, [Paragraph { moved_left: true }, Line, Line, Paragraph { moved_left: true }, Line,
+        Paragraph { moved_left: true }, Line]);
+    assert_eq!(paragraphs[0].lines[0].baseline, 180.);
+    assert_eq!(paragraphs[0].alignment, pdf_extract::Alignment::Left);
+    assert_eq!(paragraphs[0].indent, 0.);
+    assert_eq!(paragraphs[1].lines[0].size, 10.);
+    assert_eq!(paragraphs[2].alignment, pdf_extract::Alignment::Centered);
+}
+
+#[test]
+fn dehyphenation() {
+    use lopdf::dictionary;
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 10 Tf 10 180 Td (the extrac-) Tj 0 -12 Td (tion of well-) Tj 0 -12 Td (known text, self-) Tj \
+        0 -12 Td (made and state-of-the-) Tj 0 -12 Td (art or Anglo-) Tj 0 -12 Td (Saxon, a pre-) Tj \
+        0 -12 Td (set and a co-) Tj 0 -12 Td (worker) Tj ET");
+    let text = |options: &pdf_extract::ExtractOptions| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::with_options(&mut text, options.clone())).unwrap();
+        text
+    };
+    let mut options = pdf_extract::ExtractOptions::default();
+    assert!(text(&options).contains("the extrac-\ntion of well-\n"));
+    options.dehyphenate = true;
+    options.compound_words.insert("co-worker".into());
+    assert_eq!(text(&options).trim(), "the extraction of well-known text, self-made and state-of-the-art or Anglo-\nSaxon, a preset and a co-worker");
+    options.reading_order = pdf_extract::ReadingOrder::Layout;
+    options.blank_lines_between_paragraphs = false;
+    assert_eq!(text(&options).trim(), "the extraction of well-known text, self-made and state-of-the-art or Anglo-\nSaxon, a preset and a co-worker");
+}
+
+#[test]
+fn tj_gaps() {
+    use lopdf::dictionary;
+    // the heading is letterspaced with Tc and kerned, and the words of the body are moved apart
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    }, "BT /F1 10 Tf 10 180 Td 3 Tc [(L) 20 (E) -20 (T)] TJ 0 Tc 0 -20 Td [(Hello) -250 (world) -120 (again)] TJ ET");
+    let mut recording = RecordingOutput::default();
+    pdf_extract::output_doc(&doc, &mut recording).unwrap();
+    assert_eq!(recording.gaps.len(), 4);
+    let (gap, font_size, space_width) = recording.gaps[2];
+    assert_eq!((gap, font_size), (2.5, 10.));
+    // the space is 278 thousandths of an em wide in Helvetica
+    assert!((space_width.unwrap() - 2.78).abs() < 1e-9);
+    assert!(recording.gaps[0].0 < 0.);
+
+    let text = |options: &pdf_extract::ExtractOptions| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::with_options(&mut text, options.clone())).unwrap();
+        text
+    };
+    let mut options = pdf_extract::ExtractOptions::default();
+    assert_eq!(text(&options).trim(), "LET\n\nHello worldagain");
+    options.reading_order = pdf_extract::ReadingOrder::Layout;
+    assert_eq!(text(&options).trim(), "LET\n\nHello worldagain");
+}
+
+#[test]
+fn tj_gaps_between_strings() {
+    use lopdf::dictionary;
+    let fonts = dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Helvetica" },
+        },
+    };
+    // the kerning at the end of the array doesn't apply to the text moved by Td
+    let doc = single_page_doc(fonts.clone(), "BT /F1 12 Tf 50 700 Td [(Hello) -20] TJ 40 0 Td (World) Tj ET");
+    let mut recording = RecordingOutput::default();
+    pdf_extract::output_doc(&doc, &mut recording).unwrap();
+    assert!(recording.gaps.is_empty());
+    for reading_order in [pdf_extract::ReadingOrder::ContentStream, pdf_extract::ReadingOrder::Layout] {
+        let mut options = pdf_extract::ExtractOptions::default();
+        options.reading_order = reading_order;
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::with_options(&mut text, options)).unwrap();
+        assert_eq!(text.trim(), "Hello World");
+    }
+
+    // the gaps are horizontally scaled like the text
+    let doc = single_page_doc(fonts, "BT /F1 10 Tf 50 Tz [(ab) -500 (cd)] TJ ET");
+    let mut recording = RecordingOutput::default();
+    pdf_extract::output_doc(&doc, &mut recording).unwrap();
+    assert_eq!(recording.gaps.len(), 1);
+    let (gap, font_size, space_width) = recording.gaps[0];
+    assert_eq!((gap, font_size), (2.5, 10.));
+    assert!((space_width.unwrap() - 1.39).abs() < 1e-9);
+}
+
+#[test]
+fn adaptive_word_spacing() {
+    use lopdf::dictionary;
+    // each string is placed on its own, and Courier's glyphs and space are 6 wide at 10 points
+    let strings = |y: f64, strings: &[(f64, &str)]| -> String {
+        strings.iter().map(|(x, s)| format!("1 0 0 1 {} {} Tm ({}) Tj ", x, y, s)).collect()
+    };
+    let content = [
+        // tightly set words, whose gaps are narrower than half a space
+        strings(180., &[(10., "ab"), (22., "cd"), (36., "ef"), (48., "gh"), (62., "ij")]),
+        // one gap that's narrower than half a space
+        strings(160., &[(10., "ab"), (24., "cd")]),
+        // a letterspaced heading
+        strings(140., &[(10., "H"), (20., "E"), (30., "Y"), (52., "Y"), (62., "O"), (72., "U")]),
+        // character spacing between the strings
+        "1 0 0 1 10 120 Tm 2 Tc (AB) Tj (CD) Tj 0 Tc ".to_owned(),
+        // word spacing narrows the space
+        format!("-3 Tw {}0 Tw ", strings(100., &[(10., "ab"), (24., "cd")])),
+        // an evenly letterspaced heading drawn a letter at a time
+        strings(80., &[(10., "H"), (20., "E"), (30., "A"), (40., "D")]),
+        // a gap of 0.9 em
+        strings(60., &[(10., "ab"), (31., "cd")]),
+        // a word at a time with spaces between them
+        strings(40., &[(10., "the"), (34., "quick"), (70., "brown"), (106., "fox")]),
+    ].concat();
+    let doc = single_page_doc(dictionary! {
+        "Font" => dictionary! {
+            "F1" => dictionary! { "Type" => "Font", "Subtype" => "Type1", "BaseFont" => "Courier" },
+        },
+    }, &format!("BT /F1 10 Tf {}ET", content));
+    let text = |options: &pdf_extract::ExtractOptions| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::with_options(&mut text, options.clone())).unwrap();
+        text.lines().filter(|line| !line.is_empty()).map(str::to_owned).collect::<Vec<_>>()
+    };
+    let mut options = pdf_extract::ExtractOptions::default();
+    assert_eq!(text(&options), ["abcd efgh ij", "abcd", "HEY YOU", "ABCD", "ab cd", "HEAD", "ab cd", "the quick brown fox"]);
+    // the fixed threshold is used instead
+    options.adaptive_spacing = false;
+    options.space_threshold = 2.;
+    assert_eq!(text(&options), ["abcdefghij", "abcd", "HEYYOU", "ABCD", "abcd", "HEAD", "abcd", "thequickbrownfox"]);
+}
+
+#[test]
+fn type3_glyphs_showing_text() {
+    use lopdf::{dictionary, Object, Stream};
+    let mut doc = single_page_doc(dictionary! {}, "BT /T3 10 Tf (ab) Tj ET");
+    let font_id = doc.new_object_id();
+    // the glyph "a" shows text in a font without Unicode mappings and "b" shows itself
+    let a = doc.add_object(Stream::new(dictionary! {}, b"500 0 d0 BT /F1 1 Tf (a) Tj ET".to_vec()));
+    let b = doc.add_object(Stream::new(dictionary! {}, b"500 0 d0 BT /T3 1 Tf (b) Tj ET 0 0 10 10 re f".to_vec()));
+    doc.objects.insert(font_id, Object::Dictionary(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type3",
+        "FontBBox" => vec![0.into(), 0.into(), 500.into(), 700.into()],
+        "FontMatrix" => vec![0.001.into(), 0.into(), 0.into(), 0.001.into(), 0.into(), 0.into()],
+        "CharProcs" => dictionary! { "a" => a, "b" => b },
+        "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(b"a".to_vec()), Object::Name(b"b".to_vec())] },
+        "Resources" => dictionary! {
+            "Font" => dictionary! {
+                "T3" => font_id,
+                "F1" => dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Unknown",
+                    "FirstChar" => 97,
+                    "LastChar" => 97,
+                    "Widths" => vec![500.into()],
+                    "Encoding" => dictionary! { "Differences" => vec![97.into(), Object::Name(b"g1".to_vec())] },
+                },
+            },
+        },
+    }));
+    let page_id = doc.page_iter().next().unwrap();
+    let page = doc.get_object_mut(page_id).unwrap().as_dict_mut().unwrap();
+    page.set("Resources", dictionary! { "Font" => dictionary! { "T3" => font_id } });
+
+    let mut output = RecordingOutput::default();
+    pdf_extract::output_doc(&doc, &mut output).unwrap();
+    // only the Type3 characters are output, not the text that their glyphs show
+    let chars: Vec<_> = output.chars.iter().map(|c| c.1.as_str()).collect();
+    assert_eq!(chars, ["a", "b"]);
+    assert_eq!(output.fills.len(), 1);
+    let mut text = String::new();
+    pdf_extract::output_doc(&doc, &mut pdf_extract::PlainTextOutput::new(&mut text)).unwrap();
+    assert_eq!(text.trim(), "ab");
+
+    let fonts = pdf_extract::list_fonts(&doc).unwrap();
+    let unknown = fonts.iter().find(|font| font.base_font == "Unknown").unwrap();
+    assert_eq!(unknown.unmapped_codes, [97]);
+    assert_eq!(unknown.unmapped_count, 1);
+}
+